@@ -0,0 +1,1141 @@
+//! OpenASIO aggregate driver: runs several OpenASIO drivers as one multi-device stream.
+//!
+//! The device name lists sub-driver libraries separated by `;`, optionally followed by
+//! `tolerance=<frames>` (at most 8192), e.g.
+//! `libopenasio_driver_umc202hd.so;libopenasio_driver_alsa17h.so`.
+//! The first driver is the clock master and runs the host's `process`; the others exchange audio
+//! with it through ring buffers. Their clocks drift apart over time, so each ring is padded with
+//! silence when it runs dry and trimmed when it holds more than one block plus the tolerance.
+#![allow(clippy::missing_safety_doc)]
+mod ring;
+
+use openasio_sys as sys;
+use ring::Ring;
+use std::cell::UnsafeCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const CAPS: sys::OaCaps = sys::OaCaps::OUTPUT
+    .union(sys::OaCaps::INPUT)
+    .union(sys::OaCaps::FULL_DUPLEX)
+    .union(sys::OaCaps::SET_SAMPLERATE)
+    .union(sys::OaCaps::SET_BUFFRAMES)
+    .union(sys::OaCaps::SAMPLE_POSITION);
+
+/// Frames a slave ring may hold beyond one block before the oldest are dropped.
+const DEFAULT_TOLERANCE: u32 = 32;
+
+/// Largest callback the master may deliver; the mix buffers are sized for it up front.
+const MAX_FRAMES: usize = 8192;
+
+/// Largest tolerance a device name may ask for: one maximum-size block.
+const MAX_TOLERANCE: u32 = MAX_FRAMES as u32;
+
+/// Log through the host's `log_cb` when it installed one, otherwise through the `log` crate.
+macro_rules! log {
+    ($drv:expr, $lvl:ident, $($arg:tt)+) => {
+        $drv.state.log.emit(log::Level::$lvl, format_args!($($arg)+))
+    };
+}
+
+/// Host callbacks handed to every sub-driver; `user` is the sub's `SubCtx`.
+static SUB_HOST: sys::oa_host_callbacks = sys::oa_host_callbacks {
+    process: Some(sub_process),
+    ..sys::oa_host_callbacks::empty()
+};
+
+/// What a sub-driver's callback needs to find its place in the aggregate.
+struct SubCtx {
+    state: *const DriverState,
+    index: usize,
+}
+
+/// One sub-driver instance and its share of the aggregate channels.
+struct Sub {
+    path: String,
+    drv: *mut sys::oa_driver,
+    _ctx: Box<SubCtx>, // the sub's host_user; must live as long as `drv`
+    default_cfg: sys::oa_stream_config,
+    cfg: sys::oa_stream_config, // what it was last started with
+    in_first: usize,            // first aggregate input channel it provides
+    out_first: usize,           // first aggregate output channel it receives
+    inputs: Ring,               // slave -> master
+    outputs: Ring,              // master -> slave
+    padded: AtomicU32,          // drift corrections and sub-driver xruns since start
+    dropped: AtomicU32,
+    seen_underruns: AtomicU32, // the sub-driver's own totals at its last callback
+    seen_overruns: AtomicU32,
+    running: bool,
+    opened: bool,
+    lib: sys::loader::DriverLib, // declared last: dropped after the driver is destroyed
+}
+
+impl Sub {
+    fn vt(&self) -> &sys::oa_driver_vtable {
+        unsafe { &*(*self.drv).vt }
+    }
+
+    /// Drift corrections from one ring operation, reported to the host as xruns.
+    fn count(&self, (padded, dropped): (usize, usize)) {
+        if padded > 0 {
+            self.padded.fetch_add(1, Ordering::Relaxed);
+        }
+        if dropped > 0 {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn stop(&mut self) -> i32 {
+        if !self.running {
+            return sys::OA_OK;
+        }
+        self.running = false;
+        match self.vt().stop {
+            Some(stop) => unsafe { stop(self.drv) },
+            None => sys::OA_OK,
+        }
+    }
+}
+
+impl Drop for Sub {
+    fn drop(&mut self) {
+        self.stop();
+        if self.opened {
+            if let Some(close) = self.vt().close_device {
+                unsafe { close(self.drv) };
+            }
+        }
+        unsafe { (self.lib.destroy)(self.drv) };
+    }
+}
+
+/// Host-facing buffers, touched only from the master's callback while running.
+struct Mix {
+    in_buf: Vec<f32>,
+    out_buf: Vec<f32>,
+    in_planes: Vec<*const f32>,
+    out_planes: Vec<*mut f32>,
+    scratch: Vec<f32>, // one sub's interleaved block
+    position: u64,     // frames passed to the host since start
+}
+
+struct DriverState {
+    host: sys::oa_host_callbacks,
+    host_user: *mut c_void,
+    log: sys::LogSink,
+    hints: sys::ThreadHints, // passed on to every sub-driver
+    phase: sys::DriverPhase,
+    cfg_size: usize, // the host's sizeof(oa_stream_config)
+    cfg: sys::oa_stream_config,
+    tolerance: u32,
+    slack_frames: usize, // frames a slave ring may keep after a pop: one block plus the tolerance
+    subs: Vec<Sub>,
+    mix: UnsafeCell<Mix>,
+    in_gains: sys::ChannelGains, // per aggregate channel
+    out_gains: sys::ChannelGains,
+}
+
+#[repr(C)]
+struct Driver {
+    vt: *const sys::oa_driver_vtable,
+    state: DriverState,
+}
+
+/// Sub-driver paths and drift tolerance from a device name such as `a.so;b.so;tolerance=64`.
+fn parse_spec(spec: &str) -> Result<(Vec<String>, u32), String> {
+    let mut paths = Vec::new();
+    let mut tolerance = DEFAULT_TOLERANCE;
+    for part in spec.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.strip_prefix("tolerance=") {
+            Some(v) => {
+                tolerance = v.parse().map_err(|_| format!("bad tolerance {v:?}"))?;
+                if tolerance > MAX_TOLERANCE {
+                    return Err(format!("tolerance {v} is above {MAX_TOLERANCE} frames"));
+                }
+            }
+            None => paths.push(part.to_string()),
+        }
+    }
+    if paths.is_empty() {
+        return Err("no sub-drivers listed".into());
+    }
+    Ok((paths, tolerance))
+}
+
+/// Sample `frame` of channel `ch` in a host buffer of `channels` channels.
+fn host_index(interleaved: bool, channels: usize, ch: usize, frame: usize) -> usize {
+    if interleaved {
+        frame * channels + ch
+    } else {
+        ch * MAX_FRAMES + frame
+    }
+}
+
+/// Copy an interleaved `src` block of `ch` channels into host channels `first..first + ch`,
+/// scaled by their input gains.
+fn scatter(
+    dst: &mut [f32],
+    cfg: &sys::oa_stream_config,
+    gains: &sys::ChannelGains,
+    first: usize,
+    ch: usize,
+    src: &[f32],
+) {
+    let interleaved = cfg.layout == sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    let total = cfg.in_channels as usize;
+    for (f, frame) in src.chunks_exact(ch).enumerate() {
+        for (c, &s) in frame.iter().enumerate() {
+            dst[host_index(interleaved, total, first + c, f)] = s * gains.get(first + c);
+        }
+    }
+}
+
+/// Copy host output channels `first..first + ch`, scaled by their output gains, into an
+/// interleaved `dst` block.
+fn gather(
+    dst: &mut [f32],
+    cfg: &sys::oa_stream_config,
+    gains: &sys::ChannelGains,
+    first: usize,
+    ch: usize,
+    src: &[f32],
+) {
+    let interleaved = cfg.layout == sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    let total = cfg.out_channels as usize;
+    for (f, frame) in dst.chunks_exact_mut(ch).enumerate() {
+        for (c, s) in frame.iter_mut().enumerate() {
+            *s = src[host_index(interleaved, total, first + c, f)] * gains.get(first + c);
+        }
+    }
+}
+
+unsafe extern "C" fn sub_process(
+    user: *mut c_void,
+    in_ptr: *const c_void,
+    out_ptr: *mut c_void,
+    frames: u32,
+    time: *const sys::oa_time_info,
+    _cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    let ctx = &*(user as *const SubCtx);
+    let state = &*ctx.state;
+    if ctx.index == 0 {
+        return master_process(state, in_ptr, out_ptr, frames as usize, time);
+    }
+    let sub = &state.subs[ctx.index];
+    let (in_ch, out_ch) = (sub.cfg.in_channels as usize, sub.cfg.out_channels as usize);
+    let frames = frames as usize;
+    if !time.is_null() {
+        // The sub reports totals since its start; count only what is new since the last block.
+        let under = sub
+            .seen_underruns
+            .swap((*time).underruns, Ordering::Relaxed);
+        let over = sub.seen_overruns.swap((*time).overruns, Ordering::Relaxed);
+        sub.padded
+            .fetch_add((*time).underruns.wrapping_sub(under), Ordering::Relaxed);
+        sub.dropped
+            .fetch_add((*time).overruns.wrapping_sub(over), Ordering::Relaxed);
+    }
+    if in_ch > 0 && !in_ptr.is_null() {
+        let src = std::slice::from_raw_parts(in_ptr as *const f32, frames * in_ch);
+        sub.count((0, sub.inputs.push(src)));
+    }
+    if out_ch > 0 && !out_ptr.is_null() {
+        let dst = std::slice::from_raw_parts_mut(out_ptr as *mut f32, frames * out_ch);
+        sub.count(sub.outputs.pop(dst, state.slack_frames * out_ch));
+    }
+    sys::OA_TRUE
+}
+
+/// The master's callback: assemble the aggregate input, run the host, distribute the output.
+unsafe fn master_process(
+    state: &DriverState,
+    in_ptr: *const c_void,
+    out_ptr: *mut c_void,
+    frames: usize,
+    time: *const sys::oa_time_info,
+) -> sys::oa_bool {
+    let master = &state.subs[0];
+    let m_out = master.cfg.out_channels as usize;
+    if frames > MAX_FRAMES {
+        if m_out > 0 && !out_ptr.is_null() {
+            std::slice::from_raw_parts_mut(out_ptr as *mut f32, frames * m_out).fill(0.0);
+        }
+        return sys::OA_TRUE;
+    }
+    let mix = &mut *state.mix.get();
+    let cfg = &state.cfg;
+    let mut info = sys::oa_time_info {
+        sample_position: mix.position,
+        ..Default::default()
+    };
+    if !time.is_null() {
+        // A 1.0 master passes the shorter struct; copy only the fields it wrote.
+        info.host_time_ns = (*time).host_time_ns;
+        info.device_time_ns = (*time).device_time_ns;
+        info.underruns = (*time).underruns;
+        info.overruns = (*time).overruns;
+        if sys::time_info_has_near_xrun(&*(*master.drv).vt) {
+            info.near_xrun = (*time).near_xrun;
+        }
+    }
+
+    for sub in &state.subs {
+        let ch = sub.cfg.in_channels as usize;
+        if ch == 0 {
+            continue;
+        }
+        let block = &mut mix.scratch[..frames * ch];
+        if ptr::eq(sub, master) {
+            if in_ptr.is_null() {
+                block.fill(0.0);
+            } else {
+                block.copy_from_slice(std::slice::from_raw_parts(
+                    in_ptr as *const f32,
+                    frames * ch,
+                ));
+            }
+        } else {
+            sub.count(sub.inputs.pop(block, state.slack_frames * ch));
+        }
+        scatter(
+            &mut mix.in_buf,
+            cfg,
+            &state.in_gains,
+            sub.in_first,
+            ch,
+            block,
+        );
+    }
+    for sub in &state.subs[1..] {
+        info.underruns = info
+            .underruns
+            .wrapping_add(sub.padded.load(Ordering::Relaxed));
+        info.overruns = info
+            .overruns
+            .wrapping_add(sub.dropped.load(Ordering::Relaxed));
+    }
+
+    let interleaved = cfg.layout == sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    let (host_in, host_out) = if interleaved {
+        (
+            mix.in_buf.as_ptr() as *const c_void,
+            mix.out_buf.as_mut_ptr() as *mut c_void,
+        )
+    } else {
+        (
+            mix.in_planes.as_ptr() as *const c_void,
+            mix.out_planes.as_mut_ptr() as *mut c_void,
+        )
+    };
+    let keep_going = match state.host.process {
+        Some(process) => process(
+            state.host_user,
+            host_in,
+            host_out,
+            frames as u32,
+            &info,
+            cfg,
+        ),
+        None => sys::OA_FALSE,
+    };
+    mix.position += frames as u64;
+
+    for sub in &state.subs {
+        let ch = sub.cfg.out_channels as usize;
+        if ch == 0 {
+            continue;
+        }
+        if ptr::eq(sub, master) {
+            if !out_ptr.is_null() {
+                let dst = std::slice::from_raw_parts_mut(out_ptr as *mut f32, frames * m_out);
+                gather(dst, cfg, &state.out_gains, sub.out_first, ch, &mix.out_buf);
+            }
+        } else {
+            let block = &mut mix.scratch[..frames * ch];
+            gather(
+                block,
+                cfg,
+                &state.out_gains,
+                sub.out_first,
+                ch,
+                &mix.out_buf,
+            );
+            sub.count((0, sub.outputs.push(block)));
+        }
+    }
+    keep_going
+}
+
+unsafe extern "C" fn get_caps(selfp: *mut sys::oa_driver) -> u32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_caps");
+    CAPS.bits()
+}
+
+unsafe extern "C" fn query_devices(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_devices(len={len})");
+    // Aggregates are described by their name; there is nothing to enumerate.
+    sys::write_device_list("", buf, len)
+}
+
+/// Load, create and open one sub-driver on its default device.
+unsafe fn open_sub(state: &DriverState, path: &str, index: usize) -> Result<Sub, (i32, String)> {
+    let lib = sys::loader::DriverLib::load(path)
+        .map_err(|e| (sys::OA_ERR_DEVICE, format!("failed to load {path}: {e}")))?;
+    let ctx = Box::new(SubCtx {
+        state: state as *const DriverState,
+        index,
+    });
+    let (log_cb, log_user) = state.log.raw();
+    let params = sys::oa_create_params {
+        log_cb,
+        log_user,
+        rt_priority: state.hints.rt_priority,
+        cpu_affinity_mask: state.hints.cpu_affinity_mask,
+        ..sys::oa_create_params::new(&SUB_HOST, &*ctx as *const SubCtx as *mut c_void)
+    };
+    let mut drv = ptr::null_mut();
+    let rc = (lib.create)(&params, &mut drv);
+    if rc < 0 || drv.is_null() {
+        return Err((
+            rc.min(sys::OA_ERR_GENERIC),
+            format!("{path}: create returned {}", sys::OaResult(rc)),
+        ));
+    }
+    let mut sub = Sub {
+        path: path.to_string(),
+        drv,
+        _ctx: ctx,
+        default_cfg: sys::oa_stream_config::default(),
+        cfg: sys::oa_stream_config::default(),
+        in_first: 0,
+        out_first: 0,
+        inputs: Ring::new(0),
+        outputs: Ring::new(0),
+        padded: AtomicU32::new(0),
+        dropped: AtomicU32::new(0),
+        seen_underruns: AtomicU32::new(0),
+        seen_overruns: AtomicU32::new(0),
+        running: false,
+        opened: false,
+        lib,
+    };
+    let vt = sub.vt();
+    if !sys::struct_has(vt.struct_size, 0, sys::OA_VTABLE_V1_SIZE) {
+        return Err((
+            sys::OA_ERR_UNSUPPORTED,
+            format!(
+                "{path}: vtable is {} bytes, shorter than 1.0",
+                vt.struct_size
+            ),
+        ));
+    }
+    let (Some(open), Some(get_default)) = (vt.open_device, vt.get_default_config) else {
+        return Err((
+            sys::OA_ERR_UNSUPPORTED,
+            format!("{path}: missing open_device/get_default_config"),
+        ));
+    };
+    let rc = open(sub.drv, ptr::null());
+    if rc < 0 {
+        return Err((
+            rc,
+            format!("{path}: open_device returned {}", sys::OaResult(rc)),
+        ));
+    }
+    sub.opened = true;
+    let rc = get_default(sub.drv, &mut sub.default_cfg);
+    if rc < 0 {
+        return Err((
+            rc,
+            format!("{path}: get_default_config returned {}", sys::OaResult(rc)),
+        ));
+    }
+    Ok(sub)
+}
+
+unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    if s.state.phase != sys::DriverPhase::Created {
+        log!(s, Error, "open_device called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    let spec = if name.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    log!(s, Debug, "open_device(name={spec:?})");
+    let (paths, tolerance) = match parse_spec(&spec) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log!(s, Error, "invalid aggregate device {spec:?}: {e}");
+            return sys::OA_ERR_INVALID_ARG;
+        }
+    };
+    let mut subs = Vec::with_capacity(paths.len());
+    for (index, path) in paths.iter().enumerate() {
+        match open_sub(&s.state, path, index) {
+            Ok(sub) => subs.push(sub),
+            Err((rc, msg)) => {
+                log!(s, Error, "{msg}");
+                return rc;
+            }
+        }
+    }
+    for sub in &subs {
+        log!(s, Info, "sub-driver {}: {:?}", sub.path, sub.default_cfg);
+    }
+    s.state.subs = subs;
+    s.state.tolerance = tolerance;
+    s.state.phase = sys::DriverPhase::Opened;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn close_device(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "close_device");
+    if s.state.phase == sys::DriverPhase::Running {
+        log!(s, Error, "close_device called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    s.state.subs.clear();
+    s.state.phase = sys::DriverPhase::Created;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_default_config(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_stream_config,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_default_config");
+    if out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let Some(master) = s.state.subs.first() else {
+        return sys::OA_ERR_STATE;
+    };
+    let sum = |f: fn(&sys::oa_stream_config) -> u16| {
+        s.state
+            .subs
+            .iter()
+            .map(|sub| f(&sub.default_cfg))
+            .sum::<u16>()
+    };
+    let cfg = sys::oa_stream_config {
+        sample_rate: master.default_cfg.sample_rate,
+        buffer_frames: master.default_cfg.buffer_frames,
+        in_channels: sum(|c| c.in_channels),
+        out_channels: sum(|c| c.out_channels),
+        ..Default::default()
+    };
+    sys::write_stream_config(&cfg, out, s.state.cfg_size);
+    sys::OA_OK
+}
+
+/// Split the aggregate channel counts across the subs in order, each up to its default count.
+fn assign_channels(subs: &mut [Sub], cfg: &sys::oa_stream_config) {
+    let (mut ins, mut outs) = (cfg.in_channels, cfg.out_channels);
+    let (mut in_first, mut out_first) = (0, 0);
+    for sub in subs {
+        let in_ch = ins.min(sub.default_cfg.in_channels);
+        let out_ch = outs.min(sub.default_cfg.out_channels);
+        sub.cfg = sys::oa_stream_config {
+            sample_rate: cfg.sample_rate,
+            buffer_frames: cfg.buffer_frames,
+            in_channels: in_ch,
+            out_channels: out_ch,
+            exclusive: cfg.exclusive,
+            ..Default::default()
+        };
+        sub.in_first = in_first;
+        sub.out_first = out_first;
+        (ins, outs) = (ins - in_ch, outs - out_ch);
+        (in_first, out_first) = (in_first + in_ch as usize, out_first + out_ch as usize);
+    }
+}
+
+/// Check `cfg` against the sub-drivers and size the mix buffers and rings for it. Nothing is
+/// started.
+unsafe fn configure(s: &mut Driver, cfg: sys::oa_stream_config) -> i32 {
+    // Whether an input-only stream works is up to the sub-drivers, which check their own floor.
+    let rc = sys::oa_stream_config_check(&cfg);
+    if rc != sys::OA_OK {
+        log!(s, Error, "invalid stream config {cfg:?}");
+        return rc;
+    }
+    if cfg.format != sys::oa_sample_format::OA_SAMPLE_F32 || cfg.buffer_frames as usize > MAX_FRAMES
+    {
+        log!(
+            s,
+            Error,
+            "aggregate streams are f32 with at most {MAX_FRAMES} frames"
+        );
+        return sys::OA_ERR_UNSUPPORTED;
+    }
+    let (max_in, max_out) = s.state.subs.iter().fold((0u32, 0u32), |(i, o), sub| {
+        (
+            i + sub.default_cfg.in_channels as u32,
+            o + sub.default_cfg.out_channels as u32,
+        )
+    });
+    if cfg.in_channels as u32 > max_in || cfg.out_channels as u32 > max_out {
+        log!(
+            s,
+            Error,
+            "requested {}in/{}out, sub-drivers provide {max_in}in/{max_out}out",
+            cfg.in_channels,
+            cfg.out_channels
+        );
+        return sys::OA_ERR_INVALID_ARG;
+    }
+
+    let tolerance = s.state.tolerance;
+    let (Some(slack_frames), Some(ring_frames)) = (
+        cfg.buffer_frames.checked_add(tolerance),
+        cfg.buffer_frames
+            .checked_mul(4)
+            .and_then(|f| f.checked_add(tolerance)),
+    ) else {
+        log!(
+            s,
+            Error,
+            "{} frames plus tolerance {tolerance} overflow",
+            cfg.buffer_frames
+        );
+        return sys::OA_ERR_INVALID_ARG;
+    };
+
+    s.state.cfg = cfg;
+    s.state.slack_frames = slack_frames as usize;
+    assign_channels(&mut s.state.subs, &cfg);
+    let (tin, tout) = (cfg.in_channels as usize, cfg.out_channels as usize);
+    let widest = s
+        .state
+        .subs
+        .iter()
+        .map(|sub| sub.cfg.in_channels.max(sub.cfg.out_channels) as usize)
+        .max()
+        .unwrap_or(0);
+    let mix = s.state.mix.get_mut();
+    mix.in_buf = vec![0.0; MAX_FRAMES * tin];
+    mix.out_buf = vec![0.0; MAX_FRAMES * tout];
+    mix.in_planes = (0..tin)
+        .map(|c| mix.in_buf[c * MAX_FRAMES..].as_ptr())
+        .collect();
+    mix.out_planes = (0..tout)
+        .map(|c| mix.out_buf[c * MAX_FRAMES..].as_mut_ptr())
+        .collect();
+    mix.scratch = vec![0.0; MAX_FRAMES * widest];
+    mix.position = 0;
+    let ring_frames = ring_frames as usize;
+    for sub in &mut s.state.subs[1..] {
+        sub.inputs = Ring::new(ring_frames * sub.cfg.in_channels as usize);
+        sub.outputs = Ring::new(ring_frames * sub.cfg.out_channels as usize);
+        sub.padded.store(0, Ordering::Relaxed);
+        sub.dropped.store(0, Ordering::Relaxed);
+        sub.seen_underruns.store(0, Ordering::Relaxed);
+        sub.seen_overruns.store(0, Ordering::Relaxed);
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_stream_config) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    if cfg.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let cfg = sys::read_stream_config(cfg, s.state.cfg_size);
+    log!(s, Debug, "start(cfg={cfg:?})");
+    if !s.state.phase.can_start() {
+        log!(s, Error, "start called while {:?}", s.state.phase);
+        return s.state.phase.start_error();
+    }
+    let rc = configure(s, cfg);
+    if rc != sys::OA_OK {
+        return rc;
+    }
+
+    // Slaves first so their rings are filling by the time the master asks the host for audio.
+    let order: Vec<usize> = (1..s.state.subs.len()).chain([0]).collect();
+    for i in order {
+        let sub = &mut s.state.subs[i];
+        if i > 0 && sub.cfg.in_channels == 0 && sub.cfg.out_channels == 0 {
+            continue;
+        }
+        // Flag it before the sub's thread starts reading the aggregate state.
+        sub.running = true;
+        let rc = match sub.vt().start {
+            Some(start) => start(sub.drv, &sub.cfg),
+            None => sys::OA_ERR_UNSUPPORTED,
+        };
+        if rc != sys::OA_OK {
+            sub.running = false;
+            let path = sub.path.clone();
+            for sub in &mut s.state.subs {
+                sub.stop();
+            }
+            log!(s, Error, "{path}: start returned {}", sys::OaResult(rc));
+            return rc;
+        }
+    }
+    s.state.phase = sys::DriverPhase::Running;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn stop(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "stop");
+    if s.state.phase != sys::DriverPhase::Running {
+        log!(s, Error, "stop called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    // Master first: once it is quiet nothing feeds the slaves' output rings.
+    let mut result = sys::OA_OK;
+    for sub in &mut s.state.subs {
+        let rc = sub.stop();
+        if rc != sys::OA_OK {
+            log!(
+                s,
+                Error,
+                "{}: stop returned {}",
+                sub.path,
+                sys::OaResult(rc)
+            );
+            result = rc;
+        }
+    }
+    s.state.phase = sys::DriverPhase::Configured;
+    result
+}
+
+unsafe extern "C" fn get_latency(
+    selfp: *mut sys::oa_driver,
+    in_lat: *mut u32,
+    out_lat: *mut u32,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_latency");
+    if in_lat.is_null() || out_lat.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let (mut max_in, mut max_out) = (0u32, 0u32);
+    for (i, sub) in s.state.subs.iter().enumerate() {
+        let (mut li, mut lo) = (0u32, 0u32);
+        if let Some(get) = sub.vt().get_latency {
+            get(sub.drv, &mut li, &mut lo);
+        }
+        // Slaves add the block buffered in their rings.
+        let ring = if i > 0 { s.state.cfg.buffer_frames } else { 0 };
+        max_in = max_in.max(li + ring);
+        max_out = max_out.max(lo + ring);
+    }
+    *in_lat = max_in;
+    *out_lat = max_out;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn set_sr(selfp: *mut sys::oa_driver, sr: u32) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_sample_rate({sr})");
+    sys::OA_ERR_UNSUPPORTED
+}
+
+unsafe extern "C" fn set_buf(selfp: *mut sys::oa_driver, frames: u32) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_buffer_frames({frames})");
+    sys::OA_ERR_UNSUPPORTED
+}
+
+unsafe extern "C" fn get_channel_name(
+    selfp: *mut sys::oa_driver,
+    direction: sys::oa_direction,
+    index: u32,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    sys::default_channel_name_for(&s.state.cfg, direction, index, buf, len)
+}
+
+unsafe extern "C" fn set_channel_gain(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    channel: u32,
+    gain: f32,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(
+        s,
+        Debug,
+        "set_channel_gain(direction={direction}, channel={channel}, gain={gain})"
+    );
+    sys::set_channel_gain(
+        &s.state.in_gains,
+        &s.state.out_gains,
+        direction,
+        channel,
+        gain,
+    )
+}
+
+static VTABLE: sys::oa_driver_vtable = sys::oa_driver_vtable {
+    get_caps: Some(get_caps),
+    query_devices: Some(query_devices),
+    open_device: Some(open_device),
+    close_device: Some(close_device),
+    get_default_config: Some(get_default_config),
+    start: Some(start),
+    stop: Some(stop),
+    get_latency: Some(get_latency),
+    set_sample_rate: Some(set_sr),
+    set_buffer_frames: Some(set_buf),
+    query_clock_sources: Some(sys::internal_clock_sources),
+    set_clock_source: Some(sys::internal_set_clock_source),
+    get_channel_name: Some(get_channel_name),
+    set_channel_gain: Some(set_channel_gain),
+    ..sys::oa_driver_vtable::empty()
+};
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_create(
+    params: *const sys::oa_create_params,
+    out: *mut *mut sys::oa_driver,
+) -> i32 {
+    if params.is_null() || out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let p = &*params;
+    let rc = sys::create_params_validate(p);
+    if rc != sys::OA_OK {
+        return rc;
+    }
+    let drv = Box::new(Driver {
+        vt: &VTABLE,
+        state: DriverState {
+            host: sys::host_callbacks(p),
+            host_user: p.host_user,
+            log: sys::LogSink::from_params(p),
+            hints: sys::ThreadHints::from_params(p),
+            phase: sys::DriverPhase::Created,
+            cfg_size: sys::stream_config_size(p),
+            cfg: sys::oa_stream_config::default(),
+            tolerance: DEFAULT_TOLERANCE,
+            slack_frames: 0,
+            subs: Vec::new(),
+            mix: UnsafeCell::new(Mix {
+                in_buf: Vec::new(),
+                out_buf: Vec::new(),
+                in_planes: Vec::new(),
+                out_planes: Vec::new(),
+                scratch: Vec::new(),
+                position: 0,
+            }),
+            in_gains: sys::ChannelGains::default(),
+            out_gains: sys::ChannelGains::default(),
+        },
+    });
+    *out = Box::into_raw(drv) as *mut sys::oa_driver;
+    sys::OA_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_destroy(driver: *mut sys::oa_driver) {
+    if !driver.is_null() {
+        let _ = Box::from_raw(driver as *mut Driver);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_get_info(out: *mut sys::oa_driver_info) -> i32 {
+    let info = sys::oa_driver_info::new(
+        "OpenASIO Aggregate",
+        "OpenASIO project",
+        env!("CARGO_PKG_VERSION"),
+    );
+    sys::write_driver_info(&info, out)
+}
+
+sys::oa_export_abi_version!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// Both sub-drivers open the loopback's `default` ring, so only one test may hold it.
+    static RING: Mutex<()> = Mutex::new(());
+
+    /// The loopback cdylib cargo built for the dev-dependency.
+    fn loopback_path() -> String {
+        let exe = std::env::current_exe().unwrap();
+        let name = format!(
+            "{}openasio_driver_loopback{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        );
+        let path = exe.parent().unwrap().join(name);
+        assert!(path.exists(), "loopback driver library not built");
+        path.to_str().unwrap().to_string()
+    }
+
+    /// Host state behind `host_user`: the last time info it was given and the first sample of
+    /// aggregate input channel 2, which the second loopback feeds from output channel 0.
+    #[derive(Default)]
+    struct Host {
+        calls: AtomicUsize,
+        last: Mutex<sys::oa_time_info>,
+        looped: Mutex<f32>,
+    }
+
+    unsafe extern "C" fn host_process(
+        user: *mut c_void,
+        in_ptr: *const c_void,
+        out_ptr: *mut c_void,
+        frames: u32,
+        time: *const sys::oa_time_info,
+        cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        let host = &*(user as *const Host);
+        let (cfg, frames) = (&*cfg, frames as usize);
+        let (ins, outs) = (cfg.in_channels as usize, cfg.out_channels as usize);
+        if ins > 2 {
+            *host.looped.lock().unwrap() = *(in_ptr as *const f32).add(2);
+        }
+        std::slice::from_raw_parts_mut(out_ptr as *mut f32, frames * outs).fill(0.5);
+        *host.last.lock().unwrap() = *time;
+        host.calls.fetch_add(1, Ordering::Relaxed);
+        sys::OA_TRUE
+    }
+
+    static HOST: sys::oa_host_callbacks = sys::oa_host_callbacks {
+        process: Some(host_process),
+        ..sys::oa_host_callbacks::empty()
+    };
+
+    unsafe fn create(host: &Host) -> *mut sys::oa_driver {
+        let params = sys::oa_create_params::new(&HOST, host as *const Host as *mut c_void);
+        let mut drv = ptr::null_mut();
+        assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+        drv
+    }
+
+    /// Open `drv` on two loopback instances: the master writes the ring, the slave reads it.
+    unsafe fn open_loopbacks(drv: *mut sys::oa_driver, extra: &str) {
+        let lib = loopback_path();
+        let spec = std::ffi::CString::new(format!("{lib};{lib}{extra}")).unwrap();
+        assert_eq!(open_device(drv, spec.as_ptr()), sys::OA_OK);
+    }
+
+    /// `host_user` of sub-driver `index`, for calling `sub_process` as that sub would.
+    unsafe fn sub_user(drv: *mut sys::oa_driver, index: usize) -> *mut c_void {
+        let s = &*(drv as *const Driver);
+        &*s.state.subs[index]._ctx as *const SubCtx as *mut c_void
+    }
+
+    /// 4 in (2 from each loopback) and 2 out (all to the master), so the slave only feeds the
+    /// master and has no output ring of its own to pad.
+    fn input_heavy() -> sys::oa_stream_config {
+        sys::oa_stream_config {
+            in_channels: 4,
+            out_channels: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spec_lists_paths_and_an_optional_tolerance() {
+        assert_eq!(
+            parse_spec(" a.so ;b.so; tolerance=64 ;"),
+            Ok((vec!["a.so".to_string(), "b.so".to_string()], 64))
+        );
+        assert_eq!(
+            parse_spec("a.so"),
+            Ok((vec!["a.so".to_string()], DEFAULT_TOLERANCE))
+        );
+        assert_eq!(
+            parse_spec("a.so;tolerance=8192"),
+            Ok((vec!["a.so".to_string()], MAX_TOLERANCE))
+        );
+        for bad in [
+            "",
+            " ; ",
+            "tolerance=8",
+            "a.so;tolerance=",
+            "a.so;tolerance=-1",
+            "a.so;tolerance=8193",
+            "a.so;tolerance=4294967296",
+        ] {
+            assert!(parse_spec(bad).is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn bad_spec_or_sub_driver_fails_open() {
+        let host = Host::default();
+        unsafe {
+            let drv = create(&host);
+            let lib = loopback_path();
+            let too_tolerant = std::ffi::CString::new(format!("{lib};tolerance=100000")).unwrap();
+            assert_eq!(
+                open_device(drv, too_tolerant.as_ptr()),
+                sys::OA_ERR_INVALID_ARG
+            );
+            assert_eq!(open_device(drv, ptr::null()), sys::OA_ERR_INVALID_ARG);
+            assert_eq!(
+                open_device(drv, c"/nonexistent/libdriver.so".as_ptr()),
+                sys::OA_ERR_DEVICE
+            );
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv, &mut cfg), sys::OA_ERR_STATE);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn two_loopbacks_stream_as_one_device() {
+        let _ring = RING.lock().unwrap_or_else(|e| e.into_inner());
+        let host = Host::default();
+        unsafe {
+            let drv = create(&host);
+            open_loopbacks(drv, "");
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv, &mut cfg), sys::OA_OK);
+            assert_eq!((cfg.in_channels, cfg.out_channels), (4, 4));
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            // The master's output 0 reaches the slave's input 0, aggregate input 2.
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while *host.looped.lock().unwrap() != 0.5 {
+                assert!(Instant::now() < deadline, "output never came back");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            let calls = host.calls.load(Ordering::Relaxed);
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(host.calls.load(Ordering::Relaxed), calls);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn drift_pads_a_dry_ring_and_trims_a_full_one() {
+        let _ring = RING.lock().unwrap_or_else(|e| e.into_inner());
+        let host = Host::default();
+        unsafe {
+            let drv = create(&host);
+            open_loopbacks(drv, ";tolerance=32");
+            let cfg = input_heavy();
+            assert_eq!(configure(&mut *(drv as *mut Driver), cfg), sys::OA_OK);
+            let frames = cfg.buffer_frames;
+            let (master, slave) = (sub_user(drv, 0), sub_user(drv, 1));
+            let block = vec![0.25f32; frames as usize * 2];
+            let mut out = vec![0.0f32; frames as usize * 2];
+            let time = sys::oa_time_info::default();
+            let mut run_master = || {
+                let rc = sub_process(
+                    master,
+                    block.as_ptr() as *const c_void,
+                    out.as_mut_ptr() as *mut c_void,
+                    frames,
+                    &time,
+                    &cfg,
+                );
+                assert_eq!(rc, sys::OA_TRUE);
+                *host.last.lock().unwrap()
+            };
+            let run_slave = || {
+                sub_process(
+                    slave,
+                    block.as_ptr() as *const c_void,
+                    ptr::null_mut(),
+                    frames,
+                    &time,
+                    &cfg,
+                )
+            };
+
+            // Nothing from the slave yet: its inputs are padded with silence.
+            let info = run_master();
+            assert_eq!((info.underruns, info.overruns), (1, 0));
+            // One block in, one block out: no correction.
+            run_slave();
+            let info = run_master();
+            assert_eq!((info.underruns, info.overruns), (1, 0));
+            // Four blocks in leave three after the pop, more than one block plus the tolerance.
+            for _ in 0..4 {
+                run_slave();
+            }
+            let info = run_master();
+            assert_eq!((info.underruns, info.overruns), (1, 1));
+            // The trim kept one block and the tolerance, so the next pop is a full block again.
+            let info = run_master();
+            assert_eq!((info.underruns, info.overruns), (1, 1));
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn sub_driver_xruns_are_counted_once() {
+        let _ring = RING.lock().unwrap_or_else(|e| e.into_inner());
+        let host = Host::default();
+        unsafe {
+            let drv = create(&host);
+            open_loopbacks(drv, "");
+            let cfg = input_heavy();
+            assert_eq!(configure(&mut *(drv as *mut Driver), cfg), sys::OA_OK);
+            let frames = cfg.buffer_frames;
+            let (master, slave) = (sub_user(drv, 0), sub_user(drv, 1));
+            let block = vec![0.0f32; frames as usize * 2];
+            let mut out = vec![0.0f32; frames as usize * 2];
+            // Each pair: the sub-driver's totals so far, as its time info carries them.
+            let mut block_pair = |master_total: (u32, u32), slave_total: (u32, u32)| {
+                let time = |(underruns, overruns)| sys::oa_time_info {
+                    underruns,
+                    overruns,
+                    ..Default::default()
+                };
+                sub_process(
+                    slave,
+                    block.as_ptr() as *const c_void,
+                    ptr::null_mut(),
+                    frames,
+                    &time(slave_total),
+                    &cfg,
+                );
+                sub_process(
+                    master,
+                    block.as_ptr() as *const c_void,
+                    out.as_mut_ptr() as *mut c_void,
+                    frames,
+                    &time(master_total),
+                    &cfg,
+                );
+                let info = *host.last.lock().unwrap();
+                (info.underruns, info.overruns)
+            };
+            assert_eq!(block_pair((0, 0), (3, 1)), (3, 1));
+            assert_eq!(block_pair((0, 0), (3, 1)), (3, 1));
+            assert_eq!(block_pair((1, 0), (3, 1)), (4, 1));
+            assert_eq!(block_pair((1, 2), (5, 1)), (6, 3));
+
+            // A restart resets the totals on both sides.
+            assert_eq!(configure(&mut *(drv as *mut Driver), cfg), sys::OA_OK);
+            assert_eq!(block_pair((0, 0), (1, 0)), (1, 0));
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Single-producer/single-consumer sample ring between a sub-driver's thread and the master's.
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Interleaved f32 ring. All lengths passed in are whole frames, so the fill level stays
+/// frame-aligned as long as the capacity is a multiple of the channel count.
+pub struct Ring {
+    buf: Box<[UnsafeCell<f32>]>,
+    head: AtomicUsize, // read position, owned by the consumer
+    tail: AtomicUsize, // write position, owned by the producer
+}
+
+// SAFETY: the producer only writes slots in [tail, head + cap) and the consumer only reads
+// slots in [head, tail); the Release/Acquire pairs on head and tail order those accesses.
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: (0..capacity).map(|_| UnsafeCell::new(0.0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    /// Producer side. Appends as much of `src` as fits; returns the number of samples that had
+    /// to be discarded because the consumer fell behind.
+    pub fn push(&self, src: &[f32]) -> usize {
+        let cap = self.buf.len();
+        let tail = self.tail.load(Ordering::Relaxed);
+        let n = src.len().min(cap - self.len());
+        for (i, &s) in src[..n].iter().enumerate() {
+            unsafe { *self.buf[(tail + i) % cap].get() = s };
+        }
+        self.tail.store(tail.wrapping_add(n), Ordering::Release);
+        src.len() - n
+    }
+
+    /// Consumer side. Fills `out`, padding with silence when the ring runs short, then drops the
+    /// oldest samples if more than `slack` are left over. Returns `(padded, dropped)` samples.
+    pub fn pop(&self, out: &mut [f32], slack: usize) -> (usize, usize) {
+        let cap = self.buf.len();
+        let head = self.head.load(Ordering::Relaxed);
+        let n = out.len().min(self.len());
+        for (i, o) in out[..n].iter_mut().enumerate() {
+            *o = unsafe { *self.buf[(head + i) % cap].get() };
+        }
+        out[n..].fill(0.0);
+        let head = head.wrapping_add(n);
+        let left = self.tail.load(Ordering::Acquire).wrapping_sub(head);
+        let dropped = left.saturating_sub(slack);
+        self.head
+            .store(head.wrapping_add(dropped), Ordering::Release);
+        (out.len() - n, dropped)
+    }
+}
@@ -0,0 +1,202 @@
+//! Clock selection through the card's "Clock Source" mixer control, behind both drivers'
+//! `enumerate_clock_sources`/`select_clock_source` and `query_clock_sources`/`set_clock_source`
+//! entries. The drivers check their phase and pick the card; the rest is here.
+use alsa::mixer::{Mixer, SelemChannelId, SelemId};
+use openasio_sys as sys;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// Enumerated mixer control that switches the sample clock on interfaces with external sync.
+const CLOCK_SOURCE_CONTROL: &str = "Clock Source";
+
+/// Control device for a PCM name: `hw:1,0` -> `hw:1`; anything else uses `default`.
+pub fn ctl_name(pcm: &str) -> String {
+    match pcm
+        .strip_prefix("hw:")
+        .or_else(|| pcm.strip_prefix("plughw:"))
+    {
+        Some(rest) => format!("hw:{}", rest.split(',').next().unwrap_or(rest)),
+        None => "default".to_string(),
+    }
+}
+
+/// Items of the card's "Clock Source" control; empty when it has none.
+pub fn clock_sources(card: &str) -> alsa::Result<Vec<String>> {
+    let mixer = Mixer::new(card, false)?;
+    let Some(selem) = mixer.find_selem(&SelemId::new(CLOCK_SOURCE_CONTROL, 0)) else {
+        return Ok(Vec::new());
+    };
+    if !selem.is_enumerated() {
+        return Ok(Vec::new());
+    }
+    (0..selem.get_enum_items()?)
+        .map(|i| selem.get_enum_item_name(i))
+        .collect()
+}
+
+/// Writes the "Clock Source" control. `Ok(false)` when the control or item does not exist.
+pub fn set_clock_source(card: &str, name: &str) -> alsa::Result<bool> {
+    let mixer = Mixer::new(card, false)?;
+    let Some(selem) = mixer.find_selem(&SelemId::new(CLOCK_SOURCE_CONTROL, 0)) else {
+        return Ok(false);
+    };
+    for i in 0..selem.get_enum_items()? {
+        if selem.get_enum_item_name(i)? == name {
+            selem.set_enum_item(SelemChannelId::mono(), i)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Index of the item the "Clock Source" control is set to; `None` when there is no control.
+pub fn current_clock_source(card: &str) -> alsa::Result<Option<u32>> {
+    let mixer = Mixer::new(card, false)?;
+    let Some(selem) = mixer.find_selem(&SelemId::new(CLOCK_SOURCE_CONTROL, 0)) else {
+        return Ok(None);
+    };
+    if !selem.is_enumerated() {
+        return Ok(None);
+    }
+    selem.get_enum_item(SelemChannelId::mono()).map(Some)
+}
+
+/// `enumerate_clock_sources` for `card`: the item names, newline-separated, into `buf[..len]`
+/// and their count, or `OA_ERR_UNSUPPORTED` without a clock control.
+///
+/// # Safety
+/// `buf`, if non-null, must point to `len` writable bytes.
+pub unsafe fn enumerate_clock_sources(
+    log: &sys::LogSink,
+    card: &str,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let sources = match clock_sources(card) {
+        Ok(sources) if !sources.is_empty() => sources,
+        Ok(_) => return sys::OA_ERR_UNSUPPORTED,
+        Err(e) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("failed to read clock sources on {card}: {e}"),
+            );
+            return sys::OA_ERR_DEVICE;
+        }
+    };
+    if !buf.is_null() && len > 0 {
+        let list = sources.join("\n");
+        let n = list.len().min(len - 1);
+        ptr::copy_nonoverlapping(list.as_ptr(), buf as *mut u8, n);
+        *buf.add(n) = 0;
+    }
+    sources.len() as i32
+}
+
+/// `select_clock_source` for `card`: `OA_ERR_UNSUPPORTED` without a clock control and
+/// `OA_ERR_INVALID_ARG` for a name it does not list.
+pub fn select_clock_source(log: &sys::LogSink, card: &str, name: &str) -> i32 {
+    match set_clock_source(card, name) {
+        Ok(true) => sys::OA_OK,
+        Ok(false) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("no clock source {name:?} on {card}"),
+            );
+            if clock_sources(card).is_ok_and(|s| s.is_empty()) {
+                sys::OA_ERR_UNSUPPORTED
+            } else {
+                sys::OA_ERR_INVALID_ARG
+            }
+        }
+        Err(e) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("failed to set clock source on {card}: {e}"),
+            );
+            sys::OA_ERR_DEVICE
+        }
+    }
+}
+
+/// `query_clock_sources` for `card`: one entry per item of its clock control, or the internal
+/// clock alone when it has none.
+///
+/// # Safety
+/// As [`sys::write_clock_sources`].
+pub unsafe fn query_clock_sources(
+    log: &sys::LogSink,
+    card: &str,
+    buf: *mut sys::oa_clock_source,
+    len: usize,
+) -> i32 {
+    let names = match clock_sources(card) {
+        Ok(names) => names,
+        Err(e) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("failed to read clock sources on {card}: {e}"),
+            );
+            return sys::OA_ERR_DEVICE;
+        }
+    };
+    if names.is_empty() {
+        return sys::write_clock_sources(&[sys::oa_clock_source::internal()], buf, len);
+    }
+    let current = current_clock_source(card).ok().flatten();
+    let sources: Vec<sys::oa_clock_source> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| sys::oa_clock_source::new(i as u32, name, current == Some(i as u32)))
+        .collect();
+    sys::write_clock_sources(&sources, buf, len)
+}
+
+/// `set_clock_source` for `card`: select item `index` of its clock control, or accept only the
+/// internal clock (index 0) when it has none.
+pub fn set_clock_source_index(log: &sys::LogSink, card: &str, index: u32) -> i32 {
+    let names = match clock_sources(card) {
+        Ok(names) => names,
+        Err(e) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("failed to read clock sources on {card}: {e}"),
+            );
+            return sys::OA_ERR_DEVICE;
+        }
+    };
+    if names.is_empty() {
+        // Only the internal clock, which is always in use.
+        return unsafe { sys::internal_set_clock_source(ptr::null_mut(), index) };
+    }
+    let Some(name) = names.get(index as usize) else {
+        log.emit(
+            log::Level::Error,
+            format_args!("no clock source #{index} on {card}"),
+        );
+        return sys::OA_ERR_INVALID_ARG;
+    };
+    match set_clock_source(card, name) {
+        Ok(true) => sys::OA_OK,
+        Ok(false) => sys::OA_ERR_INVALID_ARG,
+        Err(e) => {
+            log.emit(
+                log::Level::Error,
+                format_args!("failed to set clock source on {card}: {e}"),
+            );
+            sys::OA_ERR_DEVICE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctl_name_keeps_the_card_of_hw_devices() {
+        assert_eq!(ctl_name("hw:1,0"), "hw:1");
+        assert_eq!(ctl_name("plughw:UMC202HD,0"), "hw:UMC202HD");
+        assert_eq!(ctl_name("hw:2"), "hw:2");
+        assert_eq!(ctl_name("null"), "default");
+    }
+}
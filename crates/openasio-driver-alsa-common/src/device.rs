@@ -0,0 +1,98 @@
+//! Device probing and naming both drivers share: the limits `query_device_info` reports, and
+//! which PCM a stream opens for a device name.
+use alsa::pcm::{HwParams, PCM};
+use alsa::Direction as PcmDir;
+use openasio_sys as sys;
+
+use crate::ctl_name;
+
+/// Largest channel count and period range `name` accepts in `dir`, from a brief non-blocking open.
+fn pcm_limits(name: &str, dir: PcmDir) -> alsa::Result<(u32, u32, u32)> {
+    let pcm = PCM::new(name, dir, true)?;
+    let hwp = HwParams::any(&pcm)?;
+    let clamp = |v: alsa::pcm::Frames| v.clamp(0, u32::MAX as alsa::pcm::Frames) as u32;
+    Ok((
+        hwp.get_channels_max()?,
+        clamp(hwp.get_period_size_min()?),
+        clamp(hwp.get_period_size_max()?),
+    ))
+}
+
+/// Limits for `query_device_info`. A direction that fails to open reports zero channels; the
+/// device is unknown only if neither opens.
+pub fn probe_device_info(name: &str) -> alsa::Result<sys::oa_device_info> {
+    let (pb, cap) = (
+        pcm_limits(name, PcmDir::Playback),
+        pcm_limits(name, PcmDir::Capture),
+    );
+    let (min_period, max_period) = match (&pb, &cap) {
+        (Err(e), Err(_)) => return Err(*e),
+        (Ok((_, pmin, pmax)), Ok((_, cmin, cmax))) => ((*pmin).max(*cmin), (*pmax).min(*cmax)),
+        (Ok((_, min, max)), _) | (_, Ok((_, min, max))) => (*min, *max),
+    };
+    let description = alsa::Ctl::new(&ctl_name(name), false)
+        .and_then(|ctl| ctl.card_info()?.get_longname().map(str::to_owned))
+        .unwrap_or_else(|_| name.to_string());
+    let mut info = sys::oa_device_info {
+        max_in_channels: cap.map_or(0, |l| l.0),
+        max_out_channels: pb.map_or(0, |l| l.0),
+        min_buffer_frames: min_period,
+        max_buffer_frames: max_period,
+        ..Default::default()
+    };
+    info.set_strings(name, &description);
+    Ok(info)
+}
+
+/// PCM to open for a stream on `name`. An exclusive stream needs a `hw:` device, which ALSA gives
+/// to one client at a time with no conversion in between; `default`, `plughw:`, dmix and other
+/// plugins (a sound server's PCM among them) are refused with the reason. A shared stream on a
+/// `hw:` device goes through `plughw:` instead, so ALSA converts what the card cannot take.
+pub fn stream_pcm_name(name: &str, exclusive: bool) -> Result<String, String> {
+    let hw = name == "hw" || name.starts_with("hw:");
+    match (exclusive, hw) {
+        (true, true) | (false, false) => Ok(name.to_string()),
+        (true, false) => Err(format!(
+            "{name} is not a hw: device and may be shared or converted"
+        )),
+        (false, true) => Ok(format!("plug{name}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_streams_open_hw_only() {
+        assert_eq!(stream_pcm_name("hw:1,0", true).as_deref(), Ok("hw:1,0"));
+        assert_eq!(stream_pcm_name("hw", true).as_deref(), Ok("hw"));
+        let dmix = "plug:'dmix:CARD=1,DEV=0,RATE=48000,CHANNELS=2'";
+        for shared in ["default", "plughw:1,0", "pipewire", dmix] {
+            assert!(stream_pcm_name(shared, true).is_err(), "{shared}");
+        }
+        let err = stream_pcm_name("plughw:UMC202HD", true).unwrap_err();
+        assert!(err.contains("plughw:UMC202HD"), "{err}");
+    }
+
+    #[test]
+    fn shared_streams_prefer_plughw() {
+        assert_eq!(
+            stream_pcm_name("hw:1,0", false).as_deref(),
+            Ok("plughw:1,0")
+        );
+        assert_eq!(stream_pcm_name("hw", false).as_deref(), Ok("plughw"));
+        assert_eq!(stream_pcm_name("default", false).as_deref(), Ok("default"));
+        assert_eq!(
+            stream_pcm_name("plughw:0", false).as_deref(),
+            Ok("plughw:0")
+        );
+    }
+
+    #[test]
+    fn probe_reports_both_directions() {
+        let info = probe_device_info("null").unwrap();
+        assert!(info.max_out_channels > 0 && info.max_in_channels > 0);
+        assert!(info.min_buffer_frames <= info.max_buffer_frames);
+    }
+}
@@ -0,0 +1,343 @@
+//! The conversion stage the ALSA drivers run between the device's sample format and the `f32`
+//! blocks they trim, filter and hand to `process`. After `hw_params` a driver asks which format
+//! the PCM settled on ([`converter_of`]) and runs every period through that [`FormatConverter`],
+//! unless the host's own format is the device's ([`native_format`]) and it takes the samples as
+//! they are. [`recover_pcm`], [`start_devices`] and the timestamp helpers are the rest of the
+//! stream plumbing the two drivers share, [`probe_device_info`] and [`stream_pcm_name`] their
+//! device probing and naming, [`clock_sources`] and its neighbours the card's clock
+//! selection, and [`DriverOptions`] with [`apply_create_options`] the option keys they share.
+use alsa::pcm::{Format, HwParams, PCM};
+use openasio_sys as sys;
+
+mod clock;
+mod device;
+mod options;
+mod pcm;
+
+pub use clock::{
+    clock_sources, ctl_name, current_clock_source, enumerate_clock_sources, query_clock_sources,
+    select_clock_source, set_clock_source, set_clock_source_index,
+};
+pub use device::{probe_device_info, stream_pcm_name};
+pub use options::{
+    apply_create_options, common_option, device_option, parse_access, set_common_option,
+    DriverOptions, DEFAULT_PERIOD_COUNT, MAX_PERIOD_COUNT,
+};
+pub use pcm::{device_time_ns, monotonic_ns, pause_pcms, recover_pcm, start_devices};
+
+const I16_SCALE: f32 = 32768.0;
+const I32_SCALE: f32 = 2147483648.0;
+
+/// Converts interleaved blocks between one device format and `f32`. `hw` holds
+/// [`sample_bytes`](Self::sample_bytes) bytes per sample in the device's byte order; both sides
+/// are converted as far as the shorter one reaches. Float input outside [-1.0, +1.0] is clipped
+/// to the integer range and integer input is divided by its full-scale value, as in
+/// [`sys::convert`].
+pub trait FormatConverter: Sync {
+    /// The ALSA format on the device side.
+    fn format(&self) -> Format;
+    fn sample_bytes(&self) -> usize;
+    fn convert_in(&self, hw: &[u8], f32_out: &mut [f32]);
+    fn convert_out(&self, f32_in: &[f32], hw: &mut [u8]);
+}
+
+/// Native-endian float32; a copy.
+pub struct F32Converter;
+
+/// Native-endian S32, the format of most USB interfaces. 24-bit converters fill the top three
+/// bytes.
+pub struct I32Converter;
+
+/// Native-endian S16.
+pub struct I16Converter;
+
+/// S24_3LE: 24-bit samples packed in three little-endian bytes.
+pub struct I24Converter;
+
+impl FormatConverter for F32Converter {
+    fn format(&self) -> Format {
+        Format::float()
+    }
+    fn sample_bytes(&self) -> usize {
+        4
+    }
+    fn convert_in(&self, hw: &[u8], f32_out: &mut [f32]) {
+        let n = (hw.len() / 4).min(f32_out.len());
+        // Every bit pattern is an f32, so the bytes can be copied as they are.
+        unsafe {
+            std::ptr::copy_nonoverlapping(hw.as_ptr(), f32_out.as_mut_ptr() as *mut u8, n * 4)
+        };
+    }
+    fn convert_out(&self, f32_in: &[f32], hw: &mut [u8]) {
+        let n = f32_in.len().min(hw.len() / 4);
+        unsafe {
+            std::ptr::copy_nonoverlapping(f32_in.as_ptr() as *const u8, hw.as_mut_ptr(), n * 4)
+        };
+    }
+}
+
+impl FormatConverter for I32Converter {
+    fn format(&self) -> Format {
+        Format::s32()
+    }
+    fn sample_bytes(&self) -> usize {
+        4
+    }
+    fn convert_in(&self, hw: &[u8], f32_out: &mut [f32]) {
+        for (s, d) in hw.chunks_exact(4).zip(f32_out.iter_mut()) {
+            *d = i32::from_ne_bytes([s[0], s[1], s[2], s[3]]) as f32 / I32_SCALE;
+        }
+    }
+    fn convert_out(&self, f32_in: &[f32], hw: &mut [u8]) {
+        for (s, d) in f32_in.iter().zip(hw.chunks_exact_mut(4)) {
+            // `as` saturates, so +1.0 lands on i32::MAX rather than wrapping.
+            let v = (s.clamp(-1.0, 1.0) * I32_SCALE).round() as i32;
+            d.copy_from_slice(&v.to_ne_bytes());
+        }
+    }
+}
+
+impl FormatConverter for I16Converter {
+    fn format(&self) -> Format {
+        Format::s16()
+    }
+    fn sample_bytes(&self) -> usize {
+        2
+    }
+    fn convert_in(&self, hw: &[u8], f32_out: &mut [f32]) {
+        for (s, d) in hw.chunks_exact(2).zip(f32_out.iter_mut()) {
+            *d = i16::from_ne_bytes([s[0], s[1]]) as f32 / I16_SCALE;
+        }
+    }
+    fn convert_out(&self, f32_in: &[f32], hw: &mut [u8]) {
+        for (s, d) in f32_in.iter().zip(hw.chunks_exact_mut(2)) {
+            let v = (s.clamp(-1.0, 1.0) * I16_SCALE).round() as i16;
+            d.copy_from_slice(&v.to_ne_bytes());
+        }
+    }
+}
+
+impl FormatConverter for I24Converter {
+    fn format(&self) -> Format {
+        Format::S243LE
+    }
+    fn sample_bytes(&self) -> usize {
+        3
+    }
+    fn convert_in(&self, hw: &[u8], f32_out: &mut [f32]) {
+        sys::convert::i24_to_f32(hw, f32_out);
+    }
+    fn convert_out(&self, f32_in: &[f32], hw: &mut [u8]) {
+        sys::convert::f32_to_i24(f32_in, hw);
+    }
+}
+
+/// The converter for a device format, or `None` for one the drivers do not handle.
+pub fn converter_for(format: Format) -> Option<&'static dyn FormatConverter> {
+    let all: [&'static dyn FormatConverter; 4] =
+        [&F32Converter, &I32Converter, &I16Converter, &I24Converter];
+    all.into_iter().find(|c| c.format() == format)
+}
+
+/// The converter for the format `pcm`'s `hw_params` settled on, or why there is none.
+pub fn converter_of(pcm: &PCM) -> Result<&'static dyn FormatConverter, String> {
+    let format = pcm
+        .hw_params_current()
+        .and_then(|p| p.get_format())
+        .map_err(|e| format!("cannot read the negotiated format: {e}"))?;
+    converter_for(format).ok_or_else(|| format!("no conversion for the device format {format}"))
+}
+
+/// The host format whose samples are exactly a device format's, if any. A host asking for it
+/// can be given the device's buffers without a pass through `f32`.
+pub fn native_format(format: Format) -> Option<sys::oa_sample_format> {
+    use sys::oa_sample_format as F;
+    match format {
+        f if f == Format::float() => Some(F::OA_SAMPLE_F32),
+        f if f == Format::s32() => Some(F::OA_SAMPLE_I32),
+        f if f == Format::s16() => Some(F::OA_SAMPLE_I16),
+        Format::S243LE => Some(F::OA_SAMPLE_I24),
+        _ => None,
+    }
+}
+
+/// The device format a driver sets up for host format `host`. Integer hosts get the matching
+/// device format, so they take the device's samples as they are; float hosts run in `usual`, the
+/// format the driver converts from.
+pub fn device_format(host: sys::oa_sample_format, usual: Format) -> Format {
+    match host {
+        sys::oa_sample_format::OA_SAMPLE_I16 => Format::s16(),
+        sys::oa_sample_format::OA_SAMPLE_I24 => Format::S243LE,
+        sys::oa_sample_format::OA_SAMPLE_I32 => Format::s32(),
+        _ => usual,
+    }
+}
+
+/// The host format to suggest for the device behind `pcm`: `OA_SAMPLE_I16` for a device that
+/// takes S16 but not `usual`, as many HDMI outputs do, and `OA_SAMPLE_F32` otherwise.
+pub fn default_host_format(pcm: &PCM, usual: Format) -> sys::oa_sample_format {
+    let Ok(hwp) = HwParams::any(pcm) else {
+        return sys::oa_sample_format::OA_SAMPLE_F32;
+    };
+    if hwp.test_format(usual).is_err() && hwp.test_format(Format::s16()).is_ok() {
+        sys::oa_sample_format::OA_SAMPLE_I16
+    } else {
+        sys::oa_sample_format::OA_SAMPLE_F32
+    }
+}
+
+/// One direction's samples in the device format. The bytes are backed by `u64`s, so they are
+/// aligned for any sample type and can be handed to a host that takes the device's format.
+#[derive(Default)]
+pub struct HwBuffer {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl HwBuffer {
+    /// Hold `bytes` bytes, keeping what fits of the current contents.
+    pub fn resize(&mut self, bytes: usize) {
+        self.words.resize(bytes.div_ceil(8), 0);
+        self.len = bytes;
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        // Any u64 storage is valid as bytes, and `len` never exceeds it.
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.len) }
+    }
+
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.words.as_mut_ptr() as *mut u8, self.len) }
+    }
+
+    /// [`sys::compact_interleaved`] over samples of `width` bytes, so masked-off channels are
+    /// dropped before anything converts them.
+    pub fn compact(&mut self, width: usize, frames: usize, channels: usize, mask: u64) -> usize {
+        let bytes = self.bytes_mut();
+        match width {
+            2 => sys::compact_interleaved(bytes.as_chunks_mut::<2>().0, frames, channels, mask),
+            3 => sys::compact_interleaved(bytes.as_chunks_mut::<3>().0, frames, channels, mask),
+            4 => sys::compact_interleaved(bytes.as_chunks_mut::<4>().0, frames, channels, mask),
+            _ => unreachable!("no converter has {width}-byte samples"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(conv: &dyn FormatConverter, samples: &[f32]) -> (Vec<u8>, Vec<f32>) {
+        let mut hw = vec![0u8; samples.len() * conv.sample_bytes()];
+        conv.convert_out(samples, &mut hw);
+        let mut back = vec![0.0; samples.len()];
+        conv.convert_in(&hw, &mut back);
+        (hw, back)
+    }
+
+    #[test]
+    fn every_format_round_trips_within_its_resolution() {
+        let samples = [0.0, 0.5, -0.5, 0.999, -1.0, 0.123456];
+        for (conv, step) in [
+            (&F32Converter as &dyn FormatConverter, 0.0),
+            (&I32Converter, 1.0 / I32_SCALE),
+            (&I24Converter, 1.0 / 8388608.0),
+            (&I16Converter, 1.0 / I16_SCALE),
+        ] {
+            let (_, back) = round_trip(conv, &samples);
+            for (s, b) in samples.iter().zip(&back) {
+                assert!((s - b).abs() <= step, "{}: {s} -> {b}", conv.format());
+            }
+        }
+    }
+
+    #[test]
+    fn integer_formats_clip_at_full_scale() {
+        let (hw, back) = round_trip(&I16Converter, &[1.0, 7.0, -7.0]);
+        assert_eq!(
+            hw,
+            [
+                i16::MAX.to_ne_bytes(),
+                i16::MAX.to_ne_bytes(),
+                i16::MIN.to_ne_bytes()
+            ]
+            .concat()
+        );
+        assert_eq!(back[2], -1.0);
+        let (hw, _) = round_trip(&I32Converter, &[1.5]);
+        assert_eq!(hw, i32::MAX.to_ne_bytes());
+    }
+
+    #[test]
+    fn i16_scales_by_32768() {
+        let hw: Vec<u8> = [i16::MIN, -16384, 0, 16384, i16::MAX]
+            .iter()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect();
+        let mut back = [0.0; 5];
+        I16Converter.convert_in(&hw, &mut back);
+        assert_eq!(back, [-1.0, -0.5, 0.0, 0.5, 32767.0 / 32768.0]);
+        let mut out = [0u8; 10];
+        I16Converter.convert_out(&[-1.0, -0.5, 0.0, 0.5, 1.0], &mut out);
+        let ints: Vec<i16> = out
+            .chunks_exact(2)
+            .map(|b| i16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(ints, [i16::MIN, -16384, 0, 16384, i16::MAX]);
+    }
+
+    #[test]
+    fn i32_matches_the_sys_conversion() {
+        let samples = [1.0, -1.0, 0.25, 1e-9, -0.7];
+        let mut ints = [0i32; 5];
+        sys::convert::f32_to_i32(&samples, &mut ints);
+        let (hw, _) = round_trip(&I32Converter, &samples);
+        let expected: Vec<u8> = ints.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        assert_eq!(hw, expected);
+    }
+
+    #[test]
+    fn formats_map_to_converters_and_host_formats() {
+        for f in [
+            Format::float(),
+            Format::s32(),
+            Format::s16(),
+            Format::S243LE,
+        ] {
+            assert_eq!(converter_for(f).map(|c| c.format()), Some(f));
+            assert!(native_format(f).is_some());
+        }
+        assert!(converter_for(Format::U8).is_none());
+        assert_eq!(
+            native_format(Format::s32()),
+            Some(sys::oa_sample_format::OA_SAMPLE_I32)
+        );
+    }
+
+    #[test]
+    fn integer_hosts_pick_their_own_device_format() {
+        use sys::oa_sample_format as F;
+        for host in [F::OA_SAMPLE_I16, F::OA_SAMPLE_I24, F::OA_SAMPLE_I32] {
+            let format = device_format(host, Format::float());
+            assert_eq!(native_format(format), Some(host));
+        }
+        for host in [F::OA_SAMPLE_F32, F::OA_SAMPLE_F64] {
+            assert_eq!(device_format(host, Format::s32()), Format::s32());
+        }
+        // The null PCM takes every format, so the usual one wins.
+        let pcm = PCM::new("null", alsa::Direction::Playback, true).unwrap();
+        assert_eq!(default_host_format(&pcm, Format::float()), F::OA_SAMPLE_F32);
+    }
+
+    #[test]
+    fn hw_buffer_compacts_whole_samples() {
+        let mut buf = HwBuffer::default();
+        buf.resize(12);
+        buf.bytes_mut()
+            .copy_from_slice(&[1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4]);
+        // Two frames of two 3-byte channels; keep the second channel.
+        assert_eq!(buf.compact(3, 2, 2, 0b10), 1);
+        assert_eq!(buf.bytes()[..6], [2, 2, 2, 4, 4, 4]);
+        assert_eq!(buf.bytes().as_ptr() as usize % 8, 0);
+    }
+}
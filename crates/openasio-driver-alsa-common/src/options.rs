@@ -0,0 +1,172 @@
+//! The ALSA side of the drivers' options: the `set_option` keys both take the same way and the
+//! creation options they apply at `openasio_driver_create`. Parsing the options text itself is
+//! [`sys::CreateOptions`]'s job.
+use openasio_sys as sys;
+
+/// Periods in the device buffer unless the host sets `period_count`.
+pub const DEFAULT_PERIOD_COUNT: u32 = 2;
+
+/// Most periods `set_option("period_count", ..)` accepts.
+pub const MAX_PERIOD_COUNT: u32 = 16;
+
+/// A driver's `set_option`/`get_option` keys. Errors carry the `OA_ERR_*` code and the reason.
+pub trait DriverOptions {
+    /// Apply one `set_option` setting.
+    fn set_option(&mut self, key: &str, value: &str) -> Result<(), (i32, String)>;
+    /// The current value of a `set_option` key, or `None` for a key the driver does not take.
+    fn option(&self, key: &str) -> Option<String>;
+}
+
+/// Apply `period_count` or `rt_priority`, the keys every ALSA driver takes; `None` for any other
+/// key, which the driver handles itself.
+pub fn set_common_option(
+    period_count: &mut u32,
+    hints: &mut sys::ThreadHints,
+    key: &str,
+    value: &str,
+) -> Option<Result<(), (i32, String)>> {
+    let result = match key {
+        "period_count" => match value.parse::<u32>() {
+            Err(_) => Err((
+                sys::OA_ERR_INVALID_ARG,
+                format!("period_count {value:?} is not a number"),
+            )),
+            Ok(n) if !(2..=MAX_PERIOD_COUNT).contains(&n) => Err((
+                sys::OA_ERR_UNSUPPORTED,
+                format!("period_count {n} is outside 2..={MAX_PERIOD_COUNT}"),
+            )),
+            Ok(n) => {
+                *period_count = n;
+                Ok(())
+            }
+        },
+        "rt_priority" => {
+            if hints.set_priority_option(value) {
+                Ok(())
+            } else {
+                Err((
+                    sys::OA_ERR_INVALID_ARG,
+                    format!("rt_priority {value:?} is neither default nor 0..=99"),
+                ))
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Whether an `access` value asks for mmap (`true`) or read/write (`false`) transfers.
+pub fn parse_access(value: &str) -> Result<bool, (i32, String)> {
+    match value {
+        "rw" => Ok(false),
+        "mmap" => Ok(true),
+        _ => Err((
+            sys::OA_ERR_INVALID_ARG,
+            format!("access {value:?} is neither rw nor mmap"),
+        )),
+    }
+}
+
+/// The value of `period_count` or `rt_priority`; `None` for any other key.
+pub fn common_option(period_count: u32, hints: &sys::ThreadHints, key: &str) -> Option<String> {
+    match key {
+        "period_count" => Some(period_count.to_string()),
+        "rt_priority" => Some(hints.priority_option()),
+        _ => None,
+    }
+}
+
+/// The device `open_device(NULL)` opens: the `device` creation option, if there is one.
+pub fn device_option(options: &sys::CreateOptions) -> Option<String> {
+    options.get("device").map(str::to_string)
+}
+
+/// Apply the creation options through the driver's `set_option`, except `device`, which is
+/// [`device_option`]'s. Keys the driver does not know are logged and ignored.
+pub fn apply_create_options(
+    driver: &mut impl DriverOptions,
+    options: &sys::CreateOptions,
+    log: &sys::LogSink,
+) -> Result<(), (i32, String)> {
+    for (key, value) in options.iter() {
+        if key == "device" {
+            continue;
+        }
+        if driver.option(key).is_none() {
+            log.emit(
+                log::Level::Debug,
+                format_args!("ignoring create option {key:?}"),
+            );
+            continue;
+        }
+        driver.set_option(key, value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A driver with only the shared keys.
+    struct Common {
+        period_count: u32,
+        hints: sys::ThreadHints,
+    }
+
+    impl DriverOptions for Common {
+        fn set_option(&mut self, key: &str, value: &str) -> Result<(), (i32, String)> {
+            set_common_option(&mut self.period_count, &mut self.hints, key, value).unwrap_or_else(
+                || Err((sys::OA_ERR_UNSUPPORTED, format!("unknown option {key:?}"))),
+            )
+        }
+        fn option(&self, key: &str) -> Option<String> {
+            common_option(self.period_count, &self.hints, key)
+        }
+    }
+
+    #[test]
+    fn common_options_are_checked() {
+        let mut d = Common {
+            period_count: DEFAULT_PERIOD_COUNT,
+            hints: sys::ThreadHints::default(),
+        };
+        assert_eq!(d.set_option("period_count", "3"), Ok(()));
+        assert_eq!(d.option("period_count").as_deref(), Some("3"));
+        assert_eq!(
+            d.set_option("period_count", "x").unwrap_err().0,
+            sys::OA_ERR_INVALID_ARG
+        );
+        assert_eq!(
+            d.set_option("period_count", "17").unwrap_err().0,
+            sys::OA_ERR_UNSUPPORTED
+        );
+        assert_eq!(d.set_option("rt_priority", "50"), Ok(()));
+        assert_eq!(d.option("rt_priority").as_deref(), Some("50"));
+        assert!(d.set_option("rt_priority", "high").is_err());
+        assert_eq!(parse_access("mmap"), Ok(true));
+        assert_eq!(parse_access("rw"), Ok(false));
+        assert_eq!(parse_access("dma").unwrap_err().0, sys::OA_ERR_INVALID_ARG);
+        assert_eq!(d.option("access"), None);
+    }
+
+    #[test]
+    fn create_options_skip_device_and_unknown_keys() {
+        let mut d = Common {
+            period_count: DEFAULT_PERIOD_COUNT,
+            hints: sys::ThreadHints::default(),
+        };
+        let log = sys::LogSink::from_params(&sys::oa_create_params::new(
+            std::ptr::null(),
+            std::ptr::null_mut(),
+        ));
+        let options =
+            sys::CreateOptions::parse("device=\"hw:1,0\", mixer=a, period_count=4").unwrap();
+        assert_eq!(apply_create_options(&mut d, &options, &log), Ok(()));
+        assert_eq!(d.period_count, 4);
+        assert_eq!(device_option(&options).as_deref(), Some("hw:1,0"));
+        let bad = sys::CreateOptions::parse("period_count=1").unwrap();
+        assert!(apply_create_options(&mut d, &bad, &log).is_err());
+        assert_eq!(d.period_count, 4);
+    }
+}
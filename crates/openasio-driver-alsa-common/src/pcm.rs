@@ -0,0 +1,113 @@
+//! Stream plumbing both drivers' worker threads share: timestamps, xrun and suspend recovery, the
+//! synchronized start and pausing.
+use alsa::pcm::PCM;
+
+/// CLOCK_MONOTONIC in nanoseconds; the clock ALSA stamps status with once `TstampType::Monotonic` is set.
+pub fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Hardware timestamp of the last status update relative to `origin_ns`, or 0 if unavailable.
+pub fn device_time_ns(pcm: &PCM, origin_ns: u64) -> u64 {
+    match pcm.status() {
+        Ok(status) => {
+            let ts = status.get_htstamp();
+            let ns = ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64;
+            ns.saturating_sub(origin_ns)
+        }
+        Err(_) => 0,
+    }
+}
+
+/// Bring `pcm` back after a read or write failed with `errno`, returning what was done, or `None`
+/// if `errno` is not one this handles. Compare against `nix::errno::Errno` values as `i32`: alsa
+/// reports them positive.
+/// - `EPIPE`: an xrun; `recover` re-prepares the stream.
+/// - `ESTRPIPE`: the device was suspended (e.g. system sleep); `resume` restores it, and hardware
+///   that cannot resume (`ENOSYS`) is re-prepared through `recover` instead. Without this the
+///   stream stays silent after a suspend/resume cycle.
+pub fn recover_pcm(pcm: &PCM, errno: i32) -> Option<&'static str> {
+    use nix::errno::Errno;
+    if errno == Errno::EPIPE as i32 {
+        let _ = pcm.recover(Errno::EPIPE as i32, true);
+        Some("re-preparing PCM")
+    } else if errno == Errno::ESTRPIPE as i32 {
+        match pcm.resume() {
+            Err(e) if e.errno() == Errno::ENOSYS as i32 => {
+                let _ = pcm.recover(Errno::ESTRPIPE as i32, true);
+                Some("device suspended, re-preparing PCM")
+            }
+            _ => Some("device suspended, resuming PCM"),
+        }
+    } else {
+        None
+    }
+}
+
+/// Synchronized start: capture explicitly, playback by queueing a silent period through
+/// `queue_silence`, which crosses its start threshold. How that period is written depends on the
+/// PCM's access type, so the driver supplies it.
+pub fn start_devices(cap: Option<&PCM>, pb: Option<&PCM>, queue_silence: impl FnOnce(&PCM)) {
+    if let Some(cap) = cap {
+        let _ = cap.start();
+    }
+    if let Some(pb) = pb {
+        queue_silence(pb);
+    }
+}
+
+/// Pause or release the open PCMs with `snd_pcm_pause`. Pausing is all or nothing: it returns
+/// false, leaving the PCMs running, if one of them cannot pause (the hardware lacks it, or an
+/// armed stream has not started yet).
+pub fn pause_pcms(cap: Option<&PCM>, pb: Option<&PCM>, enable: bool) -> bool {
+    let pcms: Vec<&PCM> = cap.into_iter().chain(pb).collect();
+    if !enable {
+        for pcm in pcms {
+            let _ = pcm.pause(false);
+        }
+        return true;
+    }
+    if !pcms
+        .iter()
+        .all(|pcm| pcm.hw_params_current().is_ok_and(|hw| hw.can_pause()))
+    {
+        return false;
+    }
+    for (i, pcm) in pcms.iter().enumerate() {
+        if pcm.pause(true).is_err() {
+            for done in &pcms[..i] {
+                let _ = done.pause(false);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alsa::Direction;
+    use nix::errno::Errno;
+
+    #[test]
+    fn recover_leaves_errors_it_does_not_handle() {
+        let pcm = PCM::new("null", Direction::Playback, false).unwrap();
+        assert_eq!(recover_pcm(&pcm, Errno::EINVAL as i32), None);
+        assert_eq!(recover_pcm(&pcm, Errno::EIO as i32), None);
+    }
+
+    #[test]
+    fn device_time_is_relative_and_never_negative() {
+        let pcm = PCM::new("null", Direction::Playback, false).unwrap();
+        let a = monotonic_ns();
+        assert!(monotonic_ns() >= a);
+        // An origin past any timestamp the PCM could report clamps to 0.
+        assert_eq!(device_time_ns(&pcm, u64::MAX), 0);
+    }
+}
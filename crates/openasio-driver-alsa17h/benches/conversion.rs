@@ -0,0 +1,141 @@
+//! The per-period work of the alsa17h worker. The device runs float32, so there is no sample
+//! conversion; what remains is the trim gains, the planar/interleaved copies and a whole period
+//! with a pass-through host. Only scalar paths exist so far; vectorised ones belong next to them
+//! under the same group names.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::ptr;
+
+const SAMPLES: usize = 1024;
+const FRAMES: usize = 512;
+const CHANNELS: usize = 2;
+/// Period of the simulated stream, as `get_default_config` reports it.
+const PERIOD: usize = 128;
+const RATES: [u32; 3] = [44100, 48000, 192000];
+
+/// A full-scale ramp.
+fn test_signal(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| (i as f32 / len as f32) * 2.0 - 1.0)
+        .collect()
+}
+
+/// Gains with one channel off unity, so the loop is not skipped.
+fn trimmed() -> sys::ChannelGains {
+    let gains = sys::ChannelGains::default();
+    gains.set(1, 0.5);
+    gains
+}
+
+fn gains(c: &mut Criterion) {
+    let mut buf = test_signal(SAMPLES);
+    let gains = trimmed();
+    let mut group = c.benchmark_group("gains");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function(BenchmarkId::new("apply_interleaved", "scalar"), |b| {
+        b.iter(|| gains.apply_interleaved(black_box(&mut buf), CHANNELS))
+    });
+    group.finish();
+}
+
+fn layout(c: &mut Criterion) {
+    let planar = test_signal(FRAMES * CHANNELS);
+    let mut interleaved = vec![0f32; FRAMES * CHANNELS];
+    let mut back = vec![0f32; FRAMES * CHANNELS];
+
+    let mut group = c.benchmark_group("layout");
+    group.throughput(Throughput::Elements((FRAMES * CHANNELS) as u64));
+    group.bench_function(BenchmarkId::new("interleave", "scalar"), |b| {
+        b.iter(|| sys::layout::interleave(black_box(&planar), FRAMES, CHANNELS, &mut interleaved))
+    });
+    group.bench_function(BenchmarkId::new("deinterleave", "scalar"), |b| {
+        b.iter(|| sys::layout::deinterleave(black_box(&interleaved), CHANNELS, &mut back, FRAMES))
+    });
+    group.finish();
+}
+
+/// Copies input to output, like a monitoring host.
+unsafe extern "C" fn pass_through(
+    _user: *mut c_void,
+    in_ptr: *const c_void,
+    out_ptr: *mut c_void,
+    frames: u32,
+    _time: *const sys::oa_time_info,
+    cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    let n = frames as usize * (*cfg).out_channels as usize;
+    ptr::copy_nonoverlapping(in_ptr as *const f32, out_ptr as *mut f32, n);
+    sys::OA_TRUE
+}
+
+/// The worker's buffers, with the PCMs replaced by a captured block and a playback sink.
+struct Period {
+    cfg: sys::oa_stream_config,
+    captured: Vec<f32>,
+    in_buf: Vec<f32>,
+    out_buf: Vec<f32>,
+    played: Vec<f32>,
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
+}
+
+impl Period {
+    fn new(sample_rate: u32) -> Self {
+        let n = PERIOD * CHANNELS;
+        Self {
+            cfg: sys::oa_stream_config {
+                sample_rate,
+                buffer_frames: PERIOD as u32,
+                in_channels: CHANNELS as u16,
+                out_channels: CHANNELS as u16,
+                ..Default::default()
+            },
+            captured: test_signal(n),
+            in_buf: vec![0.0; n],
+            out_buf: vec![0.0; n],
+            played: vec![0.0; n],
+            in_gains: trimmed(),
+            out_gains: sys::ChannelGains::default(),
+        }
+    }
+
+    /// readi, trim, process, trim, writei.
+    fn run(&mut self) {
+        self.in_buf.copy_from_slice(&self.captured);
+        self.in_gains.apply_interleaved(&mut self.in_buf, CHANNELS);
+        let ti = sys::oa_time_info::default();
+        unsafe {
+            pass_through(
+                ptr::null_mut(),
+                self.in_buf.as_ptr() as *const c_void,
+                self.out_buf.as_mut_ptr() as *mut c_void,
+                PERIOD as u32,
+                &ti,
+                &self.cfg,
+            );
+        }
+        self.out_gains
+            .apply_interleaved(&mut self.out_buf, CHANNELS);
+        self.played.copy_from_slice(&self.out_buf);
+        black_box(&self.played);
+    }
+}
+
+/// One second of stereo audio per iteration at each rate; a time well under a second is the
+/// headroom the worker leaves for the host.
+fn driver_thread(c: &mut Criterion) {
+    let mut group = c.benchmark_group("driver_thread");
+    for rate in RATES {
+        let mut period = Period::new(rate);
+        let periods = (rate as usize).div_ceil(PERIOD);
+        group.throughput(Throughput::Elements(rate as u64));
+        group.bench_with_input(BenchmarkId::new("scalar", rate), &periods, |b, &periods| {
+            b.iter(|| (0..periods).for_each(|_| period.run()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, gains, layout, driver_thread);
+criterion_main!(benches);
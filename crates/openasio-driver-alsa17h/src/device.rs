@@ -0,0 +1,168 @@
+//! ALSA PCM names: the HDA cards `query_devices` lists, and sharing a card with other clients
+//! through the dmix/dsnoop plugins.
+
+/// Environment variable that makes `query_devices` list shared (dmix/dsnoop) devices.
+pub const USE_DMIX_ENV: &str = "OA_ALSA_USE_DMIX";
+
+/// Where the kernel lists the sound cards, one `N [id]: driver - name` line per card.
+pub const CARDS_PATH: &str = "/proc/asound/cards";
+
+/// `hw:card,device` pairs listed when the card list cannot be read or parsed.
+pub const FALLBACK_HW: &[(u32, u32)] = &[(0, 0), (1, 0)];
+
+/// Index and name of each card in `/proc/asound/cards` text driven by the HDA driver, or `None`
+/// when a card line does not parse. The continuation lines under each card are skipped.
+pub fn parse_hda_cards(text: &str) -> Option<Vec<(u32, String)>> {
+    let mut cards = Vec::new();
+    for line in text.lines().map(str::trim_start) {
+        if !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let (index, rest) = line.split_once(' ')?;
+        let index = index.parse().ok()?;
+        let (_, rest) = rest.split_once("]:")?;
+        let (driver, name) = rest.split_once(" - ")?;
+        let driver = driver.trim();
+        if driver.contains("HDA-Intel") || driver.contains("snd_hda_intel") {
+            cards.push((index, name.trim().to_string()));
+        }
+    }
+    Some(cards)
+}
+
+/// `hw:card,0` for every HDA card in `/proc/asound/cards`, or [`FALLBACK_HW`] when it cannot be
+/// read or parsed.
+pub fn discover_hda_pcms() -> Vec<(u32, u32)> {
+    match std::fs::read_to_string(CARDS_PATH)
+        .ok()
+        .as_deref()
+        .and_then(parse_hda_cards)
+    {
+        Some(cards) => cards.into_iter().map(|(card, _)| (card, 0)).collect(),
+        None => FALLBACK_HW.to_vec(),
+    }
+}
+
+/// The `hw:N,0` names of [`discover_hda_pcms`].
+pub fn discover_hda_devices() -> Vec<String> {
+    discover_hda_pcms()
+        .into_iter()
+        .map(|(card, device)| format!("hw:{card},{device}"))
+        .collect()
+}
+
+/// Rate and channel count the listed shared devices run the hardware at.
+const SHARED_RATE: u32 = 48_000;
+const SHARED_CHANNELS: u16 = 2;
+
+/// Playback PCM that mixes into `hw:card,device` alongside other clients.
+///
+/// The `plug` layer converts whatever the stream is configured for to the slave's fixed rate and
+/// channel count.
+pub fn make_dmix_name(card: u32, device: u32, rate: u32, channels: u16) -> String {
+    shared_name("dmix", card, device, rate, channels)
+}
+
+/// Capture counterpart of [`make_dmix_name`].
+pub fn make_dsnoop_name(card: u32, device: u32, rate: u32, channels: u16) -> String {
+    shared_name("dsnoop", card, device, rate, channels)
+}
+
+fn shared_name(plugin: &str, card: u32, device: u32, rate: u32, channels: u16) -> String {
+    format!("plug:'{plugin}:CARD={card},DEV={device},RATE={rate},CHANNELS={channels}'")
+}
+
+/// Whether `OA_ALSA_USE_DMIX=1` is set.
+pub fn use_dmix() -> bool {
+    std::env::var(USE_DMIX_ENV).is_ok_and(|v| v == "1")
+}
+
+/// Shared playback names for the given `hw:card,device` pairs.
+pub fn shared_devices(hw: &[(u32, u32)]) -> Vec<String> {
+    hw.iter()
+        .map(|&(card, device)| make_dmix_name(card, device, SHARED_RATE, SHARED_CHANNELS))
+        .collect()
+}
+
+/// PCM to capture from when the stream was opened on `name`: a dmix device captures through the
+/// matching dsnoop, anything else is full-duplex already.
+pub fn capture_name(name: &str) -> String {
+    match name.strip_prefix("plug:'dmix:") {
+        Some(rest) => format!("plug:'dsnoop:{rest}"),
+        None => name.to_string(),
+    }
+}
+
+/// The `plug:` PCM to retry a shared stream through when `name` rejected its configuration
+/// (`err` from `hw_setup` says `Invalid argument`): ALSA's plug layer then converts the rate and
+/// format in software. `None` for exclusive streams, which must not be converted, for names
+/// that already go through a plug layer, and for other failures.
+pub fn plug_fallback(name: &str, exclusive: bool, err: &str) -> Option<String> {
+    let plugged = name.starts_with("plug");
+    (!exclusive && !plugged && err.contains("Invalid argument")).then(|| format!("plug:'{name}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hda_cards_come_from_the_card_list() {
+        let cards = " 0 [NVidia         ]: HDA-Intel - HDA NVidia
+                      HDA NVidia at 0xfc080000 irq 88
+ 1 [Generic        ]: HDA-Intel - HD-Audio Generic
+                      HD-Audio Generic at 0xfc400000 irq 90
+ 2 [U192k          ]: USB-Audio - UMC202HD 192k
+                      BEHRINGER UMC202HD 192k at usb-0000:0b:00.3-4, high speed
+10 [Audio          ]: snd_hda_intel - Family 17h HD Audio
+                      Family 17h HD Audio at 0xfc300000 irq 91
+";
+        assert_eq!(
+            parse_hda_cards(cards),
+            Some(vec![
+                (0, "HDA NVidia".into()),
+                (1, "HD-Audio Generic".into()),
+                (10, "Family 17h HD Audio".into()),
+            ])
+        );
+        assert_eq!(parse_hda_cards("--- no soundcards ---\n"), Some(vec![]));
+        assert_eq!(parse_hda_cards(" 0 [PCH]: HDA-Intel\n"), None);
+    }
+
+    #[test]
+    fn dmix_name_format() {
+        assert_eq!(
+            make_dmix_name(1, 0, 48_000, 2),
+            "plug:'dmix:CARD=1,DEV=0,RATE=48000,CHANNELS=2'"
+        );
+        assert_eq!(
+            make_dsnoop_name(0, 3, 44_100, 4),
+            "plug:'dsnoop:CARD=0,DEV=3,RATE=44100,CHANNELS=4'"
+        );
+    }
+
+    #[test]
+    fn dmix_captures_through_dsnoop() {
+        assert_eq!(
+            capture_name(&make_dmix_name(1, 0, 48_000, 2)),
+            make_dsnoop_name(1, 0, 48_000, 2)
+        );
+        assert_eq!(capture_name("hw:1,0"), "hw:1,0");
+    }
+    #[test]
+    fn rejected_shared_configs_retry_through_plug() {
+        let einval = "sample rate 11025 not supported: ALSA function 'snd_pcm_hw_params_set_rate' \
+                      failed with error 'Invalid argument (22)'";
+        assert_eq!(
+            plug_fallback("sysdefault:CARD=1", false, einval).as_deref(),
+            Some("plug:'sysdefault:CARD=1'")
+        );
+        assert_eq!(plug_fallback("hw:0,0", true, einval), None);
+        assert_eq!(plug_fallback("plughw:0,0", false, einval), None);
+        assert_eq!(plug_fallback("plug:'hw:0,0'", false, einval), None);
+        assert_eq!(
+            plug_fallback("hw:0,0", false, "Device or resource busy"),
+            None
+        );
+    }
+}
@@ -1,121 +1,453 @@
 //! OpenASIO driver for AMD Family 17h HDA controllers (ALSA backend, full-duplex)
+//!
+//! Call order: `openasio_driver_create`, then `open_device`, then any number of `start` (or
+//! `arm_start` and `trigger_start`) / `stop` pairs, then `close_device`, then
+//! `openasio_driver_destroy`. `close_device` may be called with no device open, and `destroy`
+//! at any point, stopping a running stream itself. Calls out of this order fail with
+//! `OA_ERR_STATE` (see `sys::DriverPhase`). Calling into the driver while `destroy` runs, from
+//! any thread, or after it has returned is undefined behaviour; debug builds assert on calls
+//! that reach a driver already being destroyed, but cannot catch them all.
 #![allow(clippy::missing_safety_doc)]
-use alsa::pcm::{Access, Format, HwParams, PCM};
+use alsa::direct::pcm::{MmapCapture, MmapPlayback};
+use alsa::pcm::{Access, ChmapPosition, Format, Frames, HwParams, State, TstampType, PCM};
 use alsa::{Direction as PcmDir, ValueOr};
+use openasio_driver_alsa_common::{self as alsa_common, DriverOptions, FormatConverter, HwBuffer};
 use openasio_sys as sys;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::{ffi::CStr, os::raw::c_void, ptr, time::Instant};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
-const CAP_OUTPUT: u32 = 1 << 0;
-const CAP_INPUT: u32 = 1 << 1;
-const CAP_FULL_DUPLEX: u32 = 1 << 2;
-const CAP_SET_SR: u32 = 1 << 3;
-const CAP_SET_BF: u32 = 1 << 4;
-const CAPS: u32 = CAP_OUTPUT | CAP_INPUT | CAP_FULL_DUPLEX | CAP_SET_SR | CAP_SET_BF;
+pub mod device;
+mod mmap;
+#[cfg(test)]
+mod testing;
 
+const CAPS: sys::OaCaps = sys::OaCaps::OUTPUT
+    .union(sys::OaCaps::INPUT)
+    .union(sys::OaCaps::FULL_DUPLEX)
+    .union(sys::OaCaps::SET_SAMPLERATE)
+    .union(sys::OaCaps::SET_BUFFRAMES)
+    .union(sys::OaCaps::SAMPLE_POSITION)
+    .union(sys::OaCaps::EXCLUSIVE)
+    .union(sys::OaCaps::MMAP);
+
+/// How long stop/close wait for the worker thread before giving up with `OA_ERR_TIMEOUT`.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A playback block counts as a near-xrun when the device had less than this fraction of a
+/// period left to play as it arrived.
+const NEAR_XRUN_FRACTION: u32 = 4;
+
+/// Formats a host may ask for: the device runs f32 and f64 is converted per block, except for
+/// i16 streams, which run the device in S16.
+const FORMATS: u32 = sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_F32)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_F64)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_I16);
+
+/// Rates offered to hosts, filtered against what the hardware accepts.
+const CANDIDATE_RATES: &[u32] = &[
+    8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 176400, 192000,
+];
+
+/// Log through the host's `log_cb` when it installed one, otherwise through the `log` crate.
+macro_rules! log {
+    ($drv:expr, $lvl:ident, $($arg:tt)+) => {
+        $drv.state.log.emit(log::Level::$lvl, format_args!($($arg)+))
+    };
+}
+
+/// The stream's PCMs, and their mapped rings when `use_mmap` got them. The rings come first so
+/// they are unmapped before the PCMs close.
+#[derive(Default)]
 struct Io {
+    cap_ring: Option<MmapCapture<f32>>,
+    pb_ring: Option<MmapPlayback<f32>>,
     cap: Option<PCM>,
     pb: Option<PCM>,
 }
 
 struct DriverState {
-    host: *const sys::oa_host_callbacks,
+    host: sys::oa_host_callbacks,
     host_user: *mut c_void,
+    log: sys::LogSink,
+    hints: sys::ThreadHints, // applied to the worker thread
+    phase: sys::DriverPhase,
+    cfg_size: usize,             // the host's sizeof(oa_stream_config)
+    period_count: u32,           // periods in the device buffer, from set_option
+    use_mmap: bool,              // access=mmap: try the mapped DMA ring before read/write
+    options: sys::CreateOptions, // from oa_create_params.options
     dev_name: Option<String>,
+    plumbed_name: Option<String>, // the plug: PCM start fell back to, reused until the device changes
     io: Io,
     cfg: sys::oa_stream_config,
     time0: Instant,
+    dev_time0_ns: u64,
     underruns: AtomicU32,
     overruns: AtomicU32,
+    frames_processed: AtomicU64, // since the last start
+    near_xruns: AtomicU32,       // blocks that reached playback with under NEAR_XRUN_FRACTION left
+    max_callback_ns: AtomicU64,  // longest process call since the last start
+    callback_threshold_ns: u64,  // from set_option; process calls over it count as slow, 0 = off
+    slow_callbacks: AtomicU32,   // since the last start, reported as OA_XRUN_CALLBACK
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
+    converter: &'static dyn FormatConverter, // for the format hw_setup negotiated
+    passthrough: bool, // the host format is the device's, so process gets in_hw/out_hw
+    in_hw: HwBuffer,   // device format, unless the capture ring is mapped
     in_buf: Vec<f32>,  // interleaved
     out_buf: Vec<f32>, // interleaved
+    out_hw: HwBuffer,  // device format, unless the playback ring is mapped
+    f64_block: Option<sys::F64Block>, // what process sees when the host asked for f64
     running: AtomicBool,
+    paused: AtomicBool, // the worker plays silence instead of calling process
+    hw_paused: bool,    // the PCMs themselves are paused (snd_pcm_pause)
+    gate: sys::StartGate,
     worker: Option<std::thread::JoinHandle<()>>,
+    supported_rates: Vec<u32>, // backing storage for query_supported_configs
+    channel_names: Vec<CString>, // backing storage for get_channel_names
+    last_error: sys::LastError,
 }
 
 #[repr(C)]
 struct Driver {
-    vt: sys::oa_driver_vtable,
+    vt: *const sys::oa_driver_vtable,
     state: DriverState,
 }
 
 impl DriverState {
-    fn stop_worker(&mut self) {
+    /// Signal the worker to exit and wait up to `STOP_TIMEOUT` for it. Returns false, keeping the
+    /// handle, if it is still running; the PCMs must not be touched in that case.
+    fn stop_worker(&mut self) -> bool {
         self.running.store(false, Ordering::Release);
-        if let Some(handle) = self.worker.take() {
-            let _ = handle.join();
+        // A worker blocked on a paused PCM would never see the request.
+        if std::mem::take(&mut self.hw_paused) {
+            alsa_common::pause_pcms(self.io.cap.as_ref(), self.io.pb.as_ref(), false);
+        }
+        self.paused.store(false, Ordering::Relaxed);
+        let Some(handle) = self.worker.take() else {
+            return true;
+        };
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                self.worker = Some(handle);
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
         }
+        let _ = handle.join();
+        true
+    }
+}
+
+impl DriverOptions for DriverState {
+    fn set_option(&mut self, key: &str, value: &str) -> Result<(), (i32, String)> {
+        if let Some(r) =
+            alsa_common::set_common_option(&mut self.period_count, &mut self.hints, key, value)
+        {
+            return r;
+        }
+        match key {
+            "access" => self.use_mmap = alsa_common::parse_access(value)?,
+            "callback_threshold_ns" => {
+                self.callback_threshold_ns = value.parse().map_err(|_| {
+                    (
+                        sys::OA_ERR_INVALID_ARG,
+                        format!("callback_threshold_ns {value:?} is not a number"),
+                    )
+                })?;
+            }
+            _ => return Err((sys::OA_ERR_UNSUPPORTED, format!("unknown option {key:?}"))),
+        }
+        Ok(())
+    }
+
+    fn option(&self, key: &str) -> Option<String> {
+        match key {
+            "access" => Some(if self.use_mmap { "mmap" } else { "rw" }.into()),
+            "callback_threshold_ns" => Some(self.callback_threshold_ns.to_string()),
+            _ => alsa_common::common_option(self.period_count, &self.hints, key),
+        }
+    }
+}
+
+impl Driver {
+    /// Log `msg` and keep it for `get_last_error`, then return `code`.
+    fn fail(&mut self, code: i32, msg: String) -> i32 {
+        log!(self, Error, "{msg}");
+        self.state.last_error.set(msg);
+        code
     }
 }
 
 impl Drop for DriverState {
     fn drop(&mut self) {
-        self.stop_worker();
+        if !self.stop_worker() {
+            if let Some(handle) = self.worker.take() {
+                let _ = handle.join();
+            }
+        }
     }
 }
 
-unsafe extern "C" fn get_caps(_: *mut sys::oa_driver) -> u32 {
-    CAPS
+/// Debug builds catch a vtable call made once `openasio_driver_destroy` has begun, e.g. from a
+/// host callback run during teardown. This is no fence: such calls, and any racing or following
+/// destroy, are undefined behaviour.
+unsafe fn debug_assert_alive(selfp: *mut sys::oa_driver) {
+    debug_assert!(
+        (*(selfp as *const Driver)).state.phase != sys::DriverPhase::Destroyed,
+        "driver called after openasio_driver_destroy"
+    );
 }
 
-unsafe extern "C" fn query_devices(_selfp: *mut sys::oa_driver, buf: *mut i8, len: usize) -> i32 {
-    // Minimal enumeration: typical HDA device nodes; host may pass exact ALSA "hw:X,Y"
-    let list = "default\nhw:0,0\nhw:1,0\n";
-    let bytes = list.as_bytes();
-    let n = bytes.len().min(len.saturating_sub(1));
-    if n > 0 {
-        std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, n);
+unsafe extern "C" fn get_caps(selfp: *mut sys::oa_driver) -> u32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_caps");
+    let card = alsa_common::ctl_name(s.state.dev_name.as_deref().unwrap_or("default"));
+    match alsa_common::clock_sources(&card) {
+        Ok(sources) if !sources.is_empty() => (CAPS | sys::OaCaps::CLOCK_SELECT).bits(),
+        _ => CAPS.bits(),
     }
-    if len > 0 {
-        *buf.add(n) = 0;
+}
+
+unsafe extern "C" fn query_devices(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "query_devices(len={len})");
+    if buf.is_null() && len > 0 {
+        return s.fail(
+            sys::OA_ERR_INVALID_ARG,
+            "query_devices with a null buffer".into(),
+        );
     }
-    sys::OA_OK
+    // The HDA cards the kernel lists; hosts may still pass any exact ALSA "hw:X,Y".
+    let hw = device::discover_hda_pcms();
+    let mut list = String::from("default\n");
+    for &(card, dev) in &hw {
+        list.push_str(&format!("hw:{card},{dev}\n"));
+    }
+    if device::use_dmix() {
+        for name in device::shared_devices(&hw) {
+            list.push_str(&name);
+            list.push('\n');
+        }
+    }
+    sys::write_device_list(&list, buf, len)
 }
 
-unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const i8) -> i32 {
+unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
+    debug_assert_alive(selfp);
     let s = &mut *(selfp as *mut Driver);
+    s.state.last_error.clear();
+    if s.state.phase != sys::DriverPhase::Created {
+        let msg = format!("open_device called while {:?}", s.state.phase);
+        return s.fail(sys::OA_ERR_STATE, msg);
+    }
     s.state.dev_name = if name.is_null() {
-        None
+        alsa_common::device_option(&s.state.options)
     } else {
         Some(CStr::from_ptr(name).to_string_lossy().to_string())
     };
+    log!(s, Debug, "open_device(name={:?})", s.state.dev_name);
+    s.state.plumbed_name = None;
+    s.state.phase = sys::DriverPhase::Opened;
     sys::OA_OK
 }
 
 unsafe extern "C" fn close_device(selfp: *mut sys::oa_driver) -> i32 {
+    debug_assert_alive(selfp);
     let s = &mut *(selfp as *mut Driver);
-    s.state.stop_worker();
-    s.state.io.cap = None;
-    s.state.io.pb = None;
+    log!(s, Debug, "close_device");
+    if s.state.phase == sys::DriverPhase::Running {
+        log!(s, Error, "close_device called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    s.state.io = Io::default();
+    s.state.plumbed_name = None;
+    s.state.phase = sys::DriverPhase::Created;
     sys::OA_OK
 }
 
-fn hw_setup(pcm: &PCM, dir: PcmDir, cfg: &sys::oa_stream_config) -> Result<(), String> {
-    let hwp = HwParams::any(pcm).map_err(|e| e.to_string())?;
-    hwp.set_access(Access::RWInterleaved)
-        .map_err(|e| e.to_string())?;
-    hwp.set_channels(match dir {
+/// Smallest power of two >= `n` (1 for 0), saturating at 2^31.
+fn round_up_pow2(n: u32) -> u32 {
+    n.checked_next_power_of_two().unwrap_or(1 << 31)
+}
+
+/// The parts of a PCM `hw_setup` drives, so it can be tested against `testing::MockPcm`.
+trait PcmLike {
+    type HwParams<'a>: HwParamsLike
+    where
+        Self: 'a;
+    fn hw_params_any(&self) -> alsa::Result<Self::HwParams<'_>>;
+    fn hw_params(&self, hwp: &Self::HwParams<'_>) -> alsa::Result<()>;
+    /// Period the device settled on in the installed hardware parameters.
+    fn current_period_size(&self) -> alsa::Result<Frames>;
+    /// Start threshold and wakeup point, plus hardware timestamps where the plugin has them.
+    fn sw_setup(&self, period: Frames) -> alsa::Result<()>;
+}
+
+/// The `HwParams` setters `hw_setup` uses.
+trait HwParamsLike {
+    fn set_access(&self, v: Access) -> alsa::Result<()>;
+    fn set_channels(&self, v: u32) -> alsa::Result<()>;
+    fn set_rate(&self, v: u32, dir: ValueOr) -> alsa::Result<()>;
+    fn set_format(&self, v: Format) -> alsa::Result<()>;
+    fn set_period_size(&self, v: Frames, dir: ValueOr) -> alsa::Result<()>;
+    fn set_buffer_size(&self, v: Frames) -> alsa::Result<()>;
+}
+
+impl PcmLike for PCM {
+    type HwParams<'a> = HwParams<'a>;
+    fn hw_params_any(&self) -> alsa::Result<HwParams<'_>> {
+        HwParams::any(self)
+    }
+    fn hw_params(&self, hwp: &HwParams<'_>) -> alsa::Result<()> {
+        PCM::hw_params(self, hwp)
+    }
+    fn current_period_size(&self) -> alsa::Result<Frames> {
+        self.hw_params_current()?.get_period_size()
+    }
+    fn sw_setup(&self, period: Frames) -> alsa::Result<()> {
+        let swp = self.sw_params_current()?;
+        swp.set_start_threshold(period)?;
+        swp.set_avail_min(period)?;
+        // Hardware timestamps feed oa_time_info::device_time_ns; not every plugin supports them.
+        let _ = swp.set_tstamp_mode(true);
+        let _ = swp.set_tstamp_type(TstampType::Monotonic);
+        self.sw_params(&swp)
+    }
+}
+
+impl HwParamsLike for HwParams<'_> {
+    fn set_access(&self, v: Access) -> alsa::Result<()> {
+        HwParams::set_access(self, v)
+    }
+    fn set_channels(&self, v: u32) -> alsa::Result<()> {
+        HwParams::set_channels(self, v)
+    }
+    fn set_rate(&self, v: u32, dir: ValueOr) -> alsa::Result<()> {
+        HwParams::set_rate(self, v, dir)
+    }
+    fn set_format(&self, v: Format) -> alsa::Result<()> {
+        HwParams::set_format(self, v)
+    }
+    fn set_period_size(&self, v: Frames, dir: ValueOr) -> alsa::Result<()> {
+        HwParams::set_period_size(self, v, dir)
+    }
+    fn set_buffer_size(&self, v: Frames) -> alsa::Result<()> {
+        HwParams::set_buffer_size(self, v)
+    }
+}
+
+/// Configure `pcm` for `cfg` with `access` and a buffer of `periods` periods, then store the
+/// period the device actually accepted back into `cfg.buffer_frames`. Many HDA codecs only take
+/// power-of-two periods, so others are rounded up.
+fn hw_setup(
+    pcm: &impl PcmLike,
+    dir: PcmDir,
+    access: Access,
+    cfg: &mut sys::oa_stream_config,
+    periods: u32,
+    log: &sys::LogSink,
+) -> Result<(), String> {
+    let hwp = pcm.hw_params_any().map_err(|e| e.to_string())?;
+    hwp.set_access(access).map_err(|e| e.to_string())?;
+    let channels = match dir {
         PcmDir::Capture => cfg.in_channels as u32,
         PcmDir::Playback => cfg.out_channels as u32,
-    })
-    .map_err(|e| e.to_string())?;
-    hwp.set_rate(cfg.sample_rate as u32, ValueOr::Nearest)
-        .map_err(|e| e.to_string())?;
-    hwp.set_format(Format::float()).map_err(|e| e.to_string())?;
-    let period = cfg.buffer_frames as i64;
+    };
+    hwp.set_channels(channels)
+        .map_err(|e| format!("{channels} channels not supported: {e}"))?;
+    hwp.set_rate(cfg.sample_rate, ValueOr::Nearest)
+        .map_err(|e| format!("sample rate {} not supported: {e}", cfg.sample_rate))?;
+    let format = alsa_common::device_format(cfg.format, Format::float());
+    hwp.set_format(format)
+        .map_err(|e| format!("{format} format rejected: {e}"))?;
+    if !cfg.buffer_frames.is_power_of_two() {
+        let rounded = round_up_pow2(cfg.buffer_frames);
+        log.emit(
+            log::Level::Warn,
+            format_args!(
+                "buffer_frames {} is not a power of two, using {rounded}",
+                cfg.buffer_frames
+            ),
+        );
+        cfg.buffer_frames = rounded;
+    }
+    let period = cfg.buffer_frames as Frames;
     hwp.set_period_size(period, ValueOr::Nearest)
-        .map_err(|e| e.to_string())?;
-    hwp.set_buffer_size(period * 2).map_err(|e| e.to_string())?; // 2 periods buffer
+        .map_err(|e| format!("period of {period} frames not supported: {e}"))?;
+    let buffer = period * periods as Frames;
+    hwp.set_buffer_size(buffer)
+        .map_err(|e| format!("buffer of {buffer} frames not supported: {e}"))?;
     pcm.hw_params(&hwp).map_err(|e| e.to_string())?;
+    let period = pcm.current_period_size().map_err(|e| e.to_string())?;
+    if period != cfg.buffer_frames as Frames {
+        log.emit(
+            log::Level::Info,
+            format_args!(
+                "{dir:?} period is {period} frames, not the {} requested",
+                cfg.buffer_frames
+            ),
+        );
+        cfg.buffer_frames = period as u32;
+    }
+    pcm.sw_setup(period).map_err(|e| e.to_string())
+}
 
-    let swp = pcm.sw_params_current().map_err(|e| e.to_string())?;
-    swp.set_start_threshold(period).map_err(|e| e.to_string())?;
-    swp.set_avail_min(period).map_err(|e| e.to_string())?;
-    pcm.sw_params(&swp).map_err(|e| e.to_string())?;
-    Ok(())
+/// Opens the playback PCM briefly and reads the hardware's rate and period limits.
+fn probe_hw_limits(name: &str) -> Result<(Vec<u32>, u32, u32), String> {
+    let pcm = PCM::new(name, PcmDir::Playback, false).map_err(|e| e.to_string())?;
+    let hwp = HwParams::any(&pcm).map_err(|e| e.to_string())?;
+    let min_rate = hwp.get_rate_min().map_err(|e| e.to_string())?;
+    let max_rate = hwp.get_rate_max().map_err(|e| e.to_string())?;
+    let rates = CANDIDATE_RATES
+        .iter()
+        .copied()
+        .filter(|r| (min_rate..=max_rate).contains(r) && hwp.test_rate(*r).is_ok())
+        .collect();
+    let min_period = hwp.get_period_size_min().map_err(|e| e.to_string())?;
+    let max_period = hwp.get_period_size_max().map_err(|e| e.to_string())?;
+    Ok((
+        rates,
+        min_period.clamp(0, u32::MAX as i64) as u32,
+        max_period.clamp(0, u32::MAX as i64) as u32,
+    ))
 }
 
 unsafe fn driver_thread(selfp: *mut Driver) {
+    (*selfp)
+        .state
+        .hints
+        .apply_to_current_thread(&(*selfp).state.log);
+    match (*selfp).state.gate.wait(&(*selfp).state.running) {
+        None => return,
+        Some(true) => {
+            let state = &mut (*selfp).state;
+            let samples = state.cfg.buffer_frames as usize * state.cfg.out_channels as usize;
+            let sample_bytes = state.converter.sample_bytes();
+            let io = &mut state.io;
+            // A mmap stream takes its silent period through the ring; writei would be refused.
+            alsa_common::start_devices(io.cap.as_ref(), io.pb.as_ref(), |pb| {
+                let _ = match io.pb_ring.as_mut() {
+                    Some(ring) => mmap::write(pb, ring, &vec![0.0; samples]),
+                    None => pb.io_bytes().writei(&vec![0u8; samples * sample_bytes]),
+                };
+            });
+        }
+        Some(false) => {}
+    }
     loop {
         let driver = &mut *selfp;
         if !driver.state.running.load(Ordering::Acquire) {
@@ -125,163 +457,643 @@ unsafe fn driver_thread(selfp: *mut Driver) {
         let frames = driver.state.cfg.buffer_frames as usize;
         let ich = driver.state.cfg.in_channels as usize;
         let och = driver.state.cfg.out_channels as usize;
+        // start_stream sized the buffers with checked multiplies.
+        debug_assert!(frames * ich.max(och) < isize::MAX as usize);
         let interleaved = matches!(
             driver.state.cfg.layout,
             sys::oa_buffer_layout::OA_BUF_INTERLEAVED
         );
 
+        let passthrough = driver.state.passthrough;
+        let conv = driver.state.converter;
+        let width = conv.sample_bytes();
         if let Some(cap) = driver.state.io.cap.as_ref() {
-            let res = cap
-                .io_f32()
-                .and_then(|io| io.readi(&mut driver.state.in_buf[..frames * ich]));
-            if let Err(e) = res {
-                if e.errno() == nix::errno::Errno::EPIPE as i32 {
-                    let _ = cap.prepare();
-                    driver.state.underruns.fetch_add(1, Ordering::Relaxed);
+            let buf = &mut driver.state.in_buf[..frames * ich];
+            let res = match driver.state.io.cap_ring.as_mut() {
+                // Mapped rings hold float32, the only format they are mapped for.
+                Some(ring) => mmap::read(cap, ring, buf),
+                None => {
+                    let hw = &mut driver.state.in_hw.bytes_mut()[..frames * ich * width];
+                    cap.io_bytes().readi(hw).inspect(|&read| {
+                        if !passthrough {
+                            conv.convert_in(&hw[..read * ich * width], &mut buf[..read * ich])
+                        }
+                    })
+                }
+            };
+            match res {
+                // process reads the device's samples as they are.
+                Ok(_) if passthrough => {}
+                Ok(read) => driver
+                    .state
+                    .in_gains
+                    .apply_interleaved(&mut driver.state.in_buf[..read * ich], ich),
+                Err(e) => {
+                    if let Some(how) = alsa_common::recover_pcm(cap, e.errno()) {
+                        log!(driver, Warn, "capture xrun, {how}");
+                        sys::report_xrun(
+                            &driver.state.host,
+                            driver.state.host_user,
+                            &driver.state.underruns,
+                            sys::oa_xrun_kind::OA_XRUN_UNDERRUN,
+                        );
+                    }
                 }
             }
         }
 
+        let device_time = match driver.state.io.pb.as_ref() {
+            Some(pb) => alsa_common::device_time_ns(pb, driver.state.dev_time0_ns),
+            None => 0,
+        };
         let ti = sys::oa_time_info {
             host_time_ns: driver.state.time0.elapsed().as_nanos() as u64,
-            device_time_ns: 0,
+            device_time_ns: device_time,
             underruns: driver.state.underruns.load(Ordering::Relaxed),
             overruns: driver.state.overruns.load(Ordering::Relaxed),
+            near_xrun: driver.state.near_xruns.load(Ordering::Relaxed),
+            sample_position: driver.state.frames_processed.load(Ordering::Relaxed),
         };
-        if !driver.state.host.is_null() {
-            let host = &*driver.state.host;
-            if let Some(cb) = host.process {
-                let in_ptr: *const c_void;
-                let out_ptr: *mut c_void;
-                if interleaved {
-                    in_ptr = if ich > 0 {
-                        driver.state.in_buf.as_ptr() as *const c_void
-                    } else {
-                        ptr::null()
-                    };
-                    out_ptr = driver.state.out_buf.as_mut_ptr() as *mut c_void;
+        let paused = driver.state.paused.load(Ordering::Acquire);
+        if paused && passthrough {
+            driver.state.out_hw.bytes_mut()[..frames * och * width].fill(0);
+        } else if paused {
+            driver.state.out_buf[..frames * och].fill(0.0);
+        } else if let Some(cb) = driver.state.host.process {
+            let in_ptr: *const c_void;
+            let out_ptr: *mut c_void;
+            if let Some(block) = driver.state.f64_block.as_mut() {
+                block.load_input(&driver.state.in_buf[..frames * ich], ich);
+                block.silence_output();
+                (in_ptr, out_ptr) = block.host_ptrs();
+            } else if passthrough {
+                in_ptr = if ich > 0 {
+                    driver.state.in_hw.bytes().as_ptr() as *const c_void
                 } else {
-                    let mut in_planes: Vec<*const f32> = (0..ich)
-                        .map(|c| driver.state.in_buf.as_ptr().wrapping_add(c))
-                        .collect();
-                    let mut out_planes: Vec<*mut f32> = (0..och)
-                        .map(|c| driver.state.out_buf.as_mut_ptr().wrapping_add(c))
-                        .collect();
-                    in_ptr = if ich > 0 {
-                        in_planes.as_ptr() as *const c_void
-                    } else {
-                        ptr::null()
-                    };
-                    out_ptr = out_planes.as_mut_ptr() as *mut c_void;
-                }
-                cb(
+                    ptr::null()
+                };
+                out_ptr = driver.state.out_hw.bytes_mut().as_mut_ptr() as *mut c_void;
+            } else if interleaved {
+                in_ptr = if ich > 0 {
+                    driver.state.in_buf.as_ptr() as *const c_void
+                } else {
+                    ptr::null()
+                };
+                out_ptr = driver.state.out_buf.as_mut_ptr() as *mut c_void;
+            } else {
+                let in_planes: Vec<*const f32> = (0..ich)
+                    .map(|c| driver.state.in_buf.as_ptr().wrapping_add(c))
+                    .collect();
+                let mut out_planes: Vec<*mut f32> = (0..och)
+                    .map(|c| driver.state.out_buf.as_mut_ptr().wrapping_add(c))
+                    .collect();
+                in_ptr = if ich > 0 {
+                    in_planes.as_ptr() as *const c_void
+                } else {
+                    ptr::null()
+                };
+                out_ptr = out_planes.as_mut_ptr() as *mut c_void;
+            }
+            let began = Instant::now();
+            cb(
+                driver.state.host_user,
+                in_ptr,
+                out_ptr,
+                frames as u32,
+                &ti as *const _,
+                &driver.state.cfg as *const _,
+            );
+            let took = began.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            driver
+                .state
+                .max_callback_ns
+                .fetch_max(took, Ordering::Relaxed);
+            let limit = driver.state.callback_threshold_ns;
+            if limit != 0 && took > limit {
+                sys::report_xrun(
+                    &driver.state.host,
                     driver.state.host_user,
-                    in_ptr,
-                    out_ptr,
-                    frames as u32,
-                    &ti as *const _,
-                    &driver.state.cfg as *const _,
+                    &driver.state.slow_callbacks,
+                    sys::oa_xrun_kind::OA_XRUN_CALLBACK,
                 );
             }
+            if let Some(block) = driver.state.f64_block.as_ref() {
+                block.store_output(&mut driver.state.out_buf[..frames * och], och);
+            }
         }
 
         if let Some(pb) = driver.state.io.pb.as_ref() {
-            let res = pb
-                .io_f32()
-                .and_then(|io| io.writei(&driver.state.out_buf[..frames * och]));
+            if !passthrough {
+                driver
+                    .state
+                    .out_gains
+                    .apply_interleaved(&mut driver.state.out_buf[..frames * och], och);
+            }
+            let buf = &driver.state.out_buf[..frames * och];
+            let res = match driver.state.io.pb_ring.as_mut() {
+                Some(ring) => mmap::write(pb, ring, buf),
+                None => {
+                    let hw = &mut driver.state.out_hw.bytes_mut()[..frames * och * width];
+                    if !passthrough {
+                        conv.convert_out(buf, hw);
+                    }
+                    pb.io_bytes().writei(hw)
+                }
+            };
             if let Err(e) = res {
-                if e.errno() == nix::errno::Errno::EPIPE as i32 {
-                    let _ = pb.prepare();
-                    driver.state.underruns.fetch_add(1, Ordering::Relaxed);
+                if let Some(how) = alsa_common::recover_pcm(pb, e.errno()) {
+                    log!(driver, Warn, "playback underrun, {how}");
+                    sys::report_xrun(
+                        &driver.state.host,
+                        driver.state.host_user,
+                        &driver.state.underruns,
+                        sys::oa_xrun_kind::OA_XRUN_UNDERRUN,
+                    );
+                }
+            } else if let Ok(status) = pb.status() {
+                // hw_setup sizes the buffer at period_count periods, so whatever is queued
+                // beyond the block just written is what the device had left when it arrived.
+                let queued = (driver.state.period_count as i64 - 1) * frames as i64;
+                let left = queued - status.get_avail();
+                if left < (frames as u32 / NEAR_XRUN_FRACTION) as i64 {
+                    let n = driver.state.near_xruns.fetch_add(1, Ordering::Relaxed) + 1;
+                    log!(
+                        driver,
+                        Debug,
+                        "near xrun #{n}: {left} frames left in playback buffer"
+                    );
                 }
             }
         }
+        if !paused {
+            driver
+                .state
+                .frames_processed
+                .fetch_add(frames as u64, Ordering::Relaxed);
+        }
     }
 }
 
 unsafe extern "C" fn get_default_config(
-    _selfp: *mut sys::oa_driver,
+    selfp: *mut sys::oa_driver,
     out: *mut sys::oa_stream_config,
 ) -> i32 {
-    (*out).sample_rate = 48000;
-    (*out).buffer_frames = 128;
-    (*out).in_channels = 2;
-    (*out).out_channels = 2;
-    (*out).format = sys::oa_sample_format::OA_SAMPLE_F32;
-    (*out).layout = sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_default_config");
+    let mut cfg = sys::oa_stream_config::default();
+    // A device that cannot be opened right now (streaming, or gone) keeps the float default.
+    let name = s.state.dev_name.as_deref().unwrap_or("default");
+    if let Ok(pcm) = PCM::new(name, PcmDir::Playback, true) {
+        cfg.format = alsa_common::default_host_format(&pcm, Format::float());
+    }
+    sys::write_stream_config(&cfg, out, s.state.cfg_size);
     sys::OA_OK
 }
 
+/// Open `name` for playback, and for capture when `cfg` has inputs, and set both up for `cfg`.
+/// With `mmap`, first tries mapping both DMA rings, and settles for read/write access when the
+/// device cannot be mapped. Returns the PCMs and the config they settled on, or the code and
+/// message for `fail`.
+fn open_pcms(
+    name: &str,
+    cfg: &sys::oa_stream_config,
+    periods: u32,
+    mmap: bool,
+    log: &sys::LogSink,
+) -> Result<(Io, sys::oa_stream_config), (i32, String)> {
+    if mmap {
+        let mapped = open_with(name, cfg, periods, Access::MMapInterleaved, log).and_then(
+            |(pb, cap, actual)| {
+                map_rings(pb, cap)
+                    .map(|io| (io, actual))
+                    .map_err(|e| (sys::OA_ERR_BACKEND, format!("mapping {name} failed: {e}")))
+            },
+        );
+        match mapped {
+            Ok(mapped) => return Ok(mapped),
+            Err((_, msg)) => log.emit(
+                log::Level::Warn,
+                format_args!("mmap access unavailable ({msg}), using read/write access"),
+            ),
+        }
+    }
+    let (pb, cap, actual) = open_with(name, cfg, periods, Access::RWInterleaved, log)?;
+    let io = Io {
+        pb: Some(pb),
+        cap,
+        ..Io::default()
+    };
+    Ok((io, actual))
+}
+
+/// Map the DMA rings of PCMs set up for `MMapInterleaved`. alsa-lib plugins have no ring of their
+/// own to map, so this fails on anything but a `hw:` device.
+fn map_rings(pb: PCM, cap: Option<PCM>) -> alsa::Result<Io> {
+    let pb_ring = pb.direct_mmap_playback::<f32>()?;
+    let cap_ring = cap
+        .as_ref()
+        .map(|c| c.direct_mmap_capture::<f32>())
+        .transpose()?;
+    Ok(Io {
+        cap_ring,
+        pb_ring: Some(pb_ring),
+        cap,
+        pb: Some(pb),
+    })
+}
+
+/// `open_pcms` for one access mode.
+fn open_with(
+    name: &str,
+    cfg: &sys::oa_stream_config,
+    periods: u32,
+    access: Access,
+    log: &sys::LogSink,
+) -> Result<(PCM, Option<PCM>, sys::oa_stream_config), (i32, String)> {
+    let pb = PCM::new(name, PcmDir::Playback, false).map_err(|e| {
+        (
+            sys::open_errno_result(e.errno()),
+            format!("failed to open playback PCM {name}: {e}"),
+        )
+    })?;
+    let cap = if cfg.in_channels > 0 {
+        let cap_name = device::capture_name(name);
+        let cap = PCM::new(&cap_name, PcmDir::Capture, false).map_err(|e| {
+            (
+                sys::open_errno_result(e.errno()),
+                format!("failed to open capture PCM {cap_name}: {e}"),
+            )
+        })?;
+        Some(cap)
+    } else {
+        None
+    };
+
+    // Playback is set up with whatever period capture settled on, so both run in lockstep.
+    let mut actual = *cfg;
+    if let Some(ref c) = cap {
+        hw_setup(c, PcmDir::Capture, access, &mut actual, periods, log).map_err(|e| {
+            (
+                sys::OA_ERR_BACKEND,
+                format!("capture hw_setup on {name} failed: {e}"),
+            )
+        })?;
+    }
+    hw_setup(&pb, PcmDir::Playback, access, &mut actual, periods, log).map_err(|e| {
+        (
+            sys::OA_ERR_BACKEND,
+            format!("playback hw_setup on {name} failed: {e}"),
+        )
+    })?;
+    Ok((pb, cap, actual))
+}
+
 unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_stream_config) -> i32 {
+    start_stream(selfp, cfg, false)
+}
+
+unsafe extern "C" fn arm_start(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+) -> i32 {
+    start_stream(selfp, cfg, true)
+}
+
+/// `start` and `arm_start`: set up the PCMs and spawn the worker, which waits for
+/// `trigger_start` first when `armed`.
+unsafe fn start_stream(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+    armed: bool,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    s.state.last_error.clear();
     if cfg.is_null() {
-        return sys::OA_ERR_INVALID_ARG;
+        return s.fail(
+            sys::OA_ERR_INVALID_ARG,
+            "start without a stream config".into(),
+        );
     }
-    let cfg = &*cfg;
-    let s = &mut *(selfp as *mut Driver);
-    s.state.stop_worker();
-    s.state.io.pb = None;
-    s.state.io.cap = None;
+    let cfg = &sys::read_stream_config(cfg, s.state.cfg_size);
+    log!(
+        s,
+        Debug,
+        "{}(sample_rate={}, buffer_frames={}, in={}, out={}, format={}, layout={})",
+        if armed { "arm_start" } else { "start" },
+        cfg.sample_rate,
+        cfg.buffer_frames,
+        cfg.in_channels,
+        cfg.out_channels,
+        cfg.format,
+        cfg.layout
+    );
+    if sys::oa_stream_config_check(cfg) != sys::OA_OK {
+        return s.fail(
+            sys::OA_ERR_INVALID_ARG,
+            "rejecting invalid stream config".into(),
+        );
+    }
+    // The playback PCM is always opened, so there must be something to play.
+    if sys::validate_channel_counts(cfg, 1) != sys::OA_OK {
+        return s.fail(
+            sys::OA_ERR_INVALID_ARG,
+            format!(
+                "rejecting channel counts in={} out={}",
+                cfg.in_channels, cfg.out_channels
+            ),
+        );
+    }
+    // The device runs f32 and f64 hosts get a converted copy of each block; i16 hosts get the
+    // device's own S16 buffers, which are interleaved.
+    match (cfg.format, cfg.layout) {
+        (sys::oa_sample_format::OA_SAMPLE_F32 | sys::oa_sample_format::OA_SAMPLE_F64, _) => {}
+        (sys::oa_sample_format::OA_SAMPLE_I16, sys::oa_buffer_layout::OA_BUF_INTERLEAVED) => {}
+        (sys::oa_sample_format::OA_SAMPLE_I16, _) => {
+            return s.fail(
+                sys::OA_ERR_UNSUPPORTED,
+                "int16 streams must be interleaved".into(),
+            );
+        }
+        _ => {
+            return s.fail(
+                sys::OA_ERR_UNSUPPORTED,
+                format!("unsupported sample format {}", cfg.format),
+            );
+        }
+    }
+    if !s.state.phase.can_start() {
+        let msg = format!("start called while {:?}", s.state.phase);
+        return s.fail(s.state.phase.start_error(), msg);
+    }
+    if !s.state.stop_worker() {
+        return s.fail(
+            sys::OA_ERR_TIMEOUT,
+            format!("previous worker did not exit within {STOP_TIMEOUT:?}"),
+        );
+    }
+    s.state.io = Io::default();
     s.state.cfg = *cfg;
     s.state.time0 = Instant::now();
+    s.state.dev_time0_ns = alsa_common::monotonic_ns();
     s.state.underruns.store(0, Ordering::Relaxed);
     s.state.overruns.store(0, Ordering::Relaxed);
+    s.state.frames_processed.store(0, Ordering::Relaxed);
+    s.state.near_xruns.store(0, Ordering::Relaxed);
+    s.state.max_callback_ns.store(0, Ordering::Relaxed);
+    s.state.slow_callbacks.store(0, Ordering::Relaxed);
     let name = s
         .state
         .dev_name
         .clone()
         .unwrap_or_else(|| "default".to_string());
-
-    let pb = match PCM::new(&name, PcmDir::Playback, false) {
-        Ok(p) => p,
-        Err(_) => return sys::OA_ERR_DEVICE,
+    let exclusive = cfg.exclusive != sys::OA_FALSE;
+    let name = match alsa_common::stream_pcm_name(&name, exclusive) {
+        Ok(n) => n,
+        Err(why) => {
+            return s.fail(
+                sys::OA_ERR_DEVICE,
+                format!("{}: {why}", sys::OA_EXCLUSIVE_UNAVAILABLE),
+            );
+        }
     };
-    let cap = if cfg.in_channels > 0 {
-        match PCM::new(&name, PcmDir::Capture, false) {
-            Ok(c) => Some(c),
-            Err(_) => return sys::OA_ERR_DEVICE,
+    let plumbed = s.state.plumbed_name.clone().filter(|_| !exclusive);
+    let periods = s.state.period_count;
+    // Mapped rings are float32 only; S16 streams use read/write access.
+    let mmap = s.state.use_mmap && cfg.format != sys::oa_sample_format::OA_SAMPLE_I16;
+    let opened = match open_pcms(
+        plumbed.as_deref().unwrap_or(&name),
+        cfg,
+        periods,
+        mmap,
+        &s.state.log,
+    ) {
+        Err((sys::OA_ERR_BACKEND, msg)) if plumbed.is_none() => {
+            match device::plug_fallback(&name, exclusive, &msg) {
+                Some(plug) => match open_pcms(&plug, cfg, periods, mmap, &s.state.log) {
+                    Ok(opened) => {
+                        log!(s, Info, "{msg}; converting through {plug}");
+                        s.state.plumbed_name = Some(plug);
+                        Ok(opened)
+                    }
+                    // The device's own complaint says more than the plug layer's.
+                    Err(_) => Err((sys::OA_ERR_BACKEND, msg)),
+                },
+                None => Err((sys::OA_ERR_BACKEND, msg)),
+            }
         }
-    } else {
-        None
+        opened => opened,
     };
+    let (io, actual) = match opened {
+        Ok(opened) => opened,
+        Err((code, msg)) => return s.fail(code, msg),
+    };
+    // get_status and every process call report the period actually in use.
+    s.state.cfg = actual;
 
-    if let Some(ref c) = cap {
-        if hw_setup(c, PcmDir::Capture, cfg).is_err() {
-            return sys::OA_ERR_BACKEND;
+    let frames = actual.buffer_frames as usize;
+    let ich = cfg.in_channels as usize;
+    let och = cfg.out_channels as usize;
+    // driver_thread slices these buffers with unchecked products of the same values.
+    let (Some(in_len), Some(out_len)) = (frames.checked_mul(ich.max(1)), frames.checked_mul(och))
+    else {
+        return s.fail(
+            sys::OA_ERR_INVALID_ARG,
+            format!("buffer size overflow: {frames} frames x {ich}/{och} channels"),
+        );
+    };
+    if let Some(pb) = io.pb.as_ref() {
+        match alsa_common::converter_of(pb) {
+            Ok(c) => s.state.converter = c,
+            Err(e) => return s.fail(sys::OA_ERR_BACKEND, format!("{name}: {e}")),
         }
     }
-    if hw_setup(&pb, PcmDir::Playback, cfg).is_err() {
-        return sys::OA_ERR_BACKEND;
+    // f32 streams keep the f32 stage, which mapped rings and the channel gains work on.
+    s.state.passthrough = actual.format == sys::oa_sample_format::OA_SAMPLE_I16
+        && alsa_common::native_format(s.state.converter.format()) == Some(actual.format);
+    let width = s.state.converter.sample_bytes();
+    s.state.in_hw.resize(in_len * width);
+    s.state.in_buf.resize(in_len, 0.0);
+    s.state.out_buf.resize(out_len, 0.0);
+    s.state.out_hw.resize(out_len * width);
+    s.state.f64_block = (actual.format == sys::oa_sample_format::OA_SAMPLE_F64)
+        .then(|| sys::F64Block::new(&actual));
+    s.state.io = io;
+    if armed {
+        s.state.gate.arm();
+    } else {
+        s.state.gate.open();
     }
-
-    let frames = cfg.buffer_frames as usize;
-    let ich = cfg.in_channels as usize;
-    let och = cfg.out_channels as usize;
-    s.state.in_buf.resize(frames * ich.max(1), 0.0);
-    s.state.out_buf.resize(frames * och, 0.0);
-    s.state.io.pb = Some(pb);
-    s.state.io.cap = cap;
     s.state.running.store(true, Ordering::Release);
     let driver_ptr = selfp as *mut Driver as usize;
     s.state.worker = Some(std::thread::spawn(move || unsafe {
         driver_thread(driver_ptr as *mut Driver);
     }));
 
+    s.state.phase = sys::DriverPhase::Running;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn trigger_start(selfp: *mut sys::oa_driver, start_ns: u64) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "trigger_start({start_ns})");
+    if s.state.phase != sys::DriverPhase::Running || !s.state.gate.trigger(start_ns) {
+        log!(s, Error, "trigger_start called without arm_start");
+        return sys::OA_ERR_STATE;
+    }
     sys::OA_OK
 }
 
+unsafe extern "C" fn get_status(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_stream_status,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    let status = sys::oa_stream_status {
+        running: s.state.running.load(Ordering::Acquire) as sys::oa_bool,
+        actual_sample_rate: s.state.cfg.sample_rate,
+        actual_buffer_frames: s.state.cfg.buffer_frames,
+        frames_processed: s.state.frames_processed.load(Ordering::Relaxed),
+        near_xrun_count: s.state.near_xruns.load(Ordering::Relaxed),
+        underruns: s.state.underruns.load(Ordering::Relaxed),
+        overruns: s.state.overruns.load(Ordering::Relaxed),
+        max_callback_ns: s.state.max_callback_ns.load(Ordering::Relaxed),
+        ..Default::default()
+    };
+    sys::write_stream_status(&status, out)
+}
+
 unsafe extern "C" fn stop(selfp: *mut sys::oa_driver) -> i32 {
+    debug_assert_alive(selfp);
     let s = &mut *(selfp as *mut Driver);
-    s.state.stop_worker();
-    s.state.io.pb = None;
-    s.state.io.cap = None;
+    log!(s, Debug, "stop");
+    if s.state.phase != sys::DriverPhase::Running {
+        log!(s, Error, "stop called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    // Play out what the worker already queued; closing the PCM would discard it.
+    if let Some(pb) = s.state.io.pb.as_ref() {
+        if pb.state() == State::Running {
+            let _ = pb.drain();
+        }
+    }
+    s.state.io = Io::default();
+    s.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn abort(selfp: *mut sys::oa_driver) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "abort");
+    if s.state.phase != sys::DriverPhase::Running {
+        log!(s, Error, "abort called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    // Dropping discards the queued frames and fails a worker blocked in readi/writei with
+    // EBADFD, so it sees `running` go false without waiting out the period.
+    s.state.running.store(false, Ordering::Release);
+    for pcm in s.state.io.cap.iter().chain(s.state.io.pb.iter()) {
+        let _ = pcm.drop();
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    s.state.io = Io::default();
+    s.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_last_error(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    s.state.last_error.write(buf, len)
+}
+
+unsafe extern "C" fn set_option(
+    selfp: *mut sys::oa_driver,
+    key: *const c_char,
+    value: *const c_char,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    let (Some(key), Some(value)) = (sys::option_arg(key), sys::option_arg(value)) else {
+        return s.fail(sys::OA_ERR_INVALID_ARG, "set_option without text".into());
+    };
+    log!(s, Debug, "set_option({key}={value})");
+    match s.state.set_option(key, value) {
+        Ok(()) => sys::OA_OK,
+        Err((code, msg)) => s.fail(code, msg),
+    }
+}
+
+unsafe extern "C" fn get_option(
+    selfp: *mut sys::oa_driver,
+    key: *const c_char,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    match sys::option_arg(key).and_then(|key| s.state.option(key)) {
+        Some(value) => sys::write_option(&value, buf, len),
+        None => sys::OA_ERR_UNSUPPORTED,
+    }
+}
+
+unsafe extern "C" fn pause(selfp: *mut sys::oa_driver) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "pause");
+    if s.state.phase != sys::DriverPhase::Running || s.state.paused.load(Ordering::Relaxed) {
+        log!(s, Error, "pause called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    // Set first, so a worker that was between blocks plays silence rather than calling process.
+    s.state.paused.store(true, Ordering::Release);
+    s.state.hw_paused =
+        alsa_common::pause_pcms(s.state.io.cap.as_ref(), s.state.io.pb.as_ref(), true);
+    if !s.state.hw_paused {
+        log!(s, Debug, "PCM cannot pause; playing silence instead");
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn resume(selfp: *mut sys::oa_driver) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "resume");
+    if s.state.phase != sys::DriverPhase::Running || !s.state.paused.load(Ordering::Relaxed) {
+        log!(s, Error, "resume called while not paused");
+        return sys::OA_ERR_STATE;
+    }
+    if std::mem::take(&mut s.state.hw_paused) {
+        alsa_common::pause_pcms(s.state.io.cap.as_ref(), s.state.io.pb.as_ref(), false);
+    }
+    s.state.paused.store(false, Ordering::Release);
     sys::OA_OK
 }
 
 unsafe extern "C" fn get_latency(
-    _: *mut sys::oa_driver,
+    selfp: *mut sys::oa_driver,
     in_lat: *mut u32,
     out_lat: *mut u32,
 ) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_latency");
     if !in_lat.is_null() {
         *in_lat = 0;
     }
@@ -290,14 +1102,323 @@ unsafe extern "C" fn get_latency(
     }
     sys::OA_OK
 }
-unsafe extern "C" fn set_sr(_: *mut sys::oa_driver, _: u32) -> i32 {
+unsafe extern "C" fn set_sr(selfp: *mut sys::oa_driver, sr: u32) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_sample_rate({sr})");
     sys::OA_ERR_UNSUPPORTED
 }
-unsafe extern "C" fn set_buf(_: *mut sys::oa_driver, _: u32) -> i32 {
+unsafe extern "C" fn set_buf(selfp: *mut sys::oa_driver, frames: u32) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_buffer_frames({frames})");
     sys::OA_ERR_UNSUPPORTED
 }
 
-#[no_mangle]
+unsafe extern "C" fn query_supported_configs(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_supported_configs,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "query_supported_configs");
+    if out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let name = s.state.dev_name.as_deref().unwrap_or("default");
+    let (rates, min_period, max_period) = match probe_hw_limits(name) {
+        Ok(limits) => limits,
+        Err(e) => {
+            log!(s, Error, "failed to probe {name}: {e}");
+            return sys::OA_ERR_DEVICE;
+        }
+    };
+    s.state.supported_rates = rates;
+    (*out).sample_rates = s.state.supported_rates.as_ptr();
+    (*out).sample_rate_count = s.state.supported_rates.len() as u32;
+    (*out).min_buffer_frames = min_period;
+    (*out).max_buffer_frames = max_period;
+    (*out).formats = FORMATS;
+    sys::OA_OK
+}
+
+/// The hardware's rate, channel and period limits as one range. Capture is optional, so input
+/// channels start at 0, and a device whose capture PCM does not open reports none.
+fn probe_config_range(name: &str) -> alsa::Result<sys::oa_config_range> {
+    let clamp = |v: alsa::pcm::Frames| v.clamp(0, u32::MAX as alsa::pcm::Frames) as u32;
+    let pcm = PCM::new(name, PcmDir::Playback, true)?;
+    let hwp = HwParams::any(&pcm)?;
+    let mut range = sys::oa_config_range {
+        min_rate: hwp.get_rate_min()?,
+        max_rate: hwp.get_rate_max()?,
+        min_channels_out: hwp.get_channels_min()?,
+        max_channels_out: hwp.get_channels_max()?,
+        formats_mask: FORMATS,
+        min_buffer_frames: clamp(hwp.get_period_size_min()?),
+        max_buffer_frames: clamp(hwp.get_period_size_max()?),
+        ..Default::default()
+    };
+    // start runs capture with the same rate and period, so only what both accept is usable.
+    if let Ok(cap) = PCM::new(&device::capture_name(name), PcmDir::Capture, true) {
+        let hwp = HwParams::any(&cap)?;
+        range.max_channels_in = hwp.get_channels_max()?;
+        range.min_rate = range.min_rate.max(hwp.get_rate_min()?);
+        range.max_rate = range.max_rate.min(hwp.get_rate_max()?);
+        range.min_buffer_frames = range
+            .min_buffer_frames
+            .max(clamp(hwp.get_period_size_min()?));
+        range.max_buffer_frames = range
+            .max_buffer_frames
+            .min(clamp(hwp.get_period_size_max()?));
+    }
+    Ok(range)
+}
+
+unsafe extern "C" fn query_config_ranges(
+    selfp: *mut sys::oa_driver,
+    buf: *mut sys::oa_config_range,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_config_ranges(len={len})");
+    let name = s.state.dev_name.as_deref().unwrap_or("default");
+    match probe_config_range(name) {
+        Ok(range) => sys::write_config_ranges(&[range], buf, len),
+        Err(e) => {
+            log!(s, Error, "failed to probe {name}: {e}");
+            sys::OA_ERR_DEVICE
+        }
+    }
+}
+
+/// Channel position names from the PCM's channel maps, preferring a map with at least `count`
+/// channels. Empty when the device offers no chmap.
+fn chmap_names(name: &str, dir: PcmDir, count: usize) -> Result<Vec<String>, alsa::Error> {
+    let pcm = PCM::new(name, dir, true)?;
+    let maps: Vec<Vec<ChmapPosition>> = pcm.query_chmaps().map(|(_, map)| (&map).into()).collect();
+    let best = maps
+        .iter()
+        .find(|m| m.len() >= count)
+        .or_else(|| maps.iter().max_by_key(|m| m.len()));
+    Ok(best
+        .map(|m| m.iter().take(count).map(|p| p.to_string()).collect())
+        .unwrap_or_default())
+}
+
+unsafe extern "C" fn get_channel_names(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    names: *mut *const c_char,
+    count: u32,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &mut *(selfp as *mut Driver);
+    log!(
+        s,
+        Debug,
+        "get_channel_names(direction={direction}, count={count})"
+    );
+    let dir = match direction {
+        sys::OA_DIR_INPUT => PcmDir::Capture,
+        sys::OA_DIR_OUTPUT => PcmDir::Playback,
+        _ => return sys::OA_ERR_INVALID_ARG,
+    };
+    if names.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let name = s.state.dev_name.as_deref().unwrap_or("default");
+    let found = match chmap_names(name, dir, count as usize) {
+        Ok(found) => found,
+        Err(e) => {
+            log!(s, Error, "failed to query channel maps on {name}: {e}");
+            return sys::open_errno_result(e.errno());
+        }
+    };
+    s.state.channel_names = found
+        .into_iter()
+        .filter_map(|n| CString::new(n).ok())
+        .collect();
+    for (i, n) in s.state.channel_names.iter().enumerate() {
+        *names.add(i) = n.as_ptr();
+    }
+    s.state.channel_names.len() as i32
+}
+
+unsafe extern "C" fn query_supported_rates(
+    selfp: *mut sys::oa_driver,
+    buf: *mut u32,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_supported_rates(len={len})");
+    let name = s.state.dev_name.as_deref().unwrap_or("default");
+    match probe_hw_limits(name) {
+        Ok((rates, _, _)) => sys::write_supported_rates(&rates, buf, len),
+        Err(e) => {
+            log!(s, Error, "failed to probe {name}: {e}");
+            sys::OA_ERR_DEVICE
+        }
+    }
+}
+
+unsafe extern "C" fn enumerate_clock_sources(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "enumerate_clock_sources(len={len})");
+    let card = alsa_common::ctl_name(s.state.dev_name.as_deref().unwrap_or("default"));
+    alsa_common::enumerate_clock_sources(&s.state.log, &card, buf, len)
+}
+
+unsafe extern "C" fn select_clock_source(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    if name.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let name = CStr::from_ptr(name).to_string_lossy();
+    log!(s, Debug, "select_clock_source(name={name:?})");
+    if s.state.phase == sys::DriverPhase::Running {
+        log!(
+            s,
+            Error,
+            "select_clock_source called while {:?}",
+            s.state.phase
+        );
+        return sys::OA_ERR_STATE;
+    }
+    let card = alsa_common::ctl_name(s.state.dev_name.as_deref().unwrap_or("default"));
+    alsa_common::select_clock_source(&s.state.log, &card, &name)
+}
+
+unsafe extern "C" fn query_clock_sources(
+    selfp: *mut sys::oa_driver,
+    buf: *mut sys::oa_clock_source,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_clock_sources(len={len})");
+    let card = alsa_common::ctl_name(s.state.dev_name.as_deref().unwrap_or("default"));
+    alsa_common::query_clock_sources(&s.state.log, &card, buf, len)
+}
+
+unsafe extern "C" fn set_clock_source_index(selfp: *mut sys::oa_driver, index: u32) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_clock_source(index={index})");
+    if s.state.phase == sys::DriverPhase::Running {
+        log!(
+            s,
+            Error,
+            "set_clock_source called while {:?}",
+            s.state.phase
+        );
+        return sys::OA_ERR_STATE;
+    }
+    let card = alsa_common::ctl_name(s.state.dev_name.as_deref().unwrap_or("default"));
+    alsa_common::set_clock_source_index(&s.state.log, &card, index)
+}
+
+unsafe extern "C" fn query_device_info(
+    selfp: *mut sys::oa_driver,
+    name: *const c_char,
+    out: *mut sys::oa_device_info,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    let name = if name.is_null() || *name == 0 {
+        s.state
+            .dev_name
+            .clone()
+            .unwrap_or_else(|| "default".to_string())
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    log!(s, Debug, "query_device_info(name={name:?})");
+    match alsa_common::probe_device_info(&name) {
+        Ok(info) => sys::write_device_info(&info, out),
+        Err(e) => {
+            log!(s, Error, "failed to probe {name}: {e}");
+            sys::open_errno_result(e.errno())
+        }
+    }
+}
+
+unsafe extern "C" fn get_channel_name(
+    selfp: *mut sys::oa_driver,
+    direction: sys::oa_direction,
+    index: u32,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    sys::default_channel_name_for(&s.state.cfg, direction, index, buf, len)
+}
+
+unsafe extern "C" fn set_channel_gain(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    channel: u32,
+    gain: f32,
+) -> i32 {
+    debug_assert_alive(selfp);
+    let s = &*(selfp as *const Driver);
+    log!(
+        s,
+        Debug,
+        "set_channel_gain(direction={direction}, channel={channel}, gain={gain})"
+    );
+    sys::set_channel_gain(
+        &s.state.in_gains,
+        &s.state.out_gains,
+        direction,
+        channel,
+        gain,
+    )
+}
+
+static VTABLE: sys::oa_driver_vtable = sys::oa_driver_vtable {
+    get_caps: Some(get_caps),
+    query_devices: Some(query_devices),
+    open_device: Some(open_device),
+    close_device: Some(close_device),
+    get_default_config: Some(get_default_config),
+    start: Some(start),
+    stop: Some(stop),
+    get_latency: Some(get_latency),
+    set_sample_rate: Some(set_sr),
+    set_buffer_frames: Some(set_buf),
+    query_supported_configs: Some(query_supported_configs),
+    get_channel_names: Some(get_channel_names),
+    query_supported_rates: Some(query_supported_rates),
+    enumerate_clock_sources: Some(enumerate_clock_sources),
+    select_clock_source: Some(select_clock_source),
+    query_device_info: Some(query_device_info),
+    arm_start: Some(arm_start),
+    trigger_start: Some(trigger_start),
+    get_status: Some(get_status),
+    query_clock_sources: Some(query_clock_sources),
+    set_clock_source: Some(set_clock_source_index),
+    get_channel_name: Some(get_channel_name),
+    set_channel_gain: Some(set_channel_gain),
+    pause: Some(pause),
+    resume: Some(resume),
+    abort: Some(abort),
+    get_last_error: Some(get_last_error),
+    query_config_ranges: Some(query_config_ranges),
+    set_option: Some(set_option),
+    get_option: Some(get_option),
+    ..sys::oa_driver_vtable::empty()
+};
+
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
 pub unsafe extern "C" fn openasio_driver_create(
     params: *const sys::oa_create_params,
     out: *mut *mut sys::oa_driver,
@@ -306,55 +1427,787 @@ pub unsafe extern "C" fn openasio_driver_create(
         return sys::OA_ERR_INVALID_ARG;
     }
     let p = &*params;
-    if p.host.is_null() {
-        return sys::OA_ERR_INVALID_ARG;
+    let rc = sys::create_params_validate(p);
+    if rc != sys::OA_OK {
+        return rc;
     }
-    let drv = Box::new(Driver {
-        vt: sys::oa_driver_vtable {
-            struct_size: std::mem::size_of::<sys::oa_driver_vtable>() as u32,
-            get_caps: Some(get_caps),
-            query_devices: Some(query_devices),
-            open_device: Some(open_device),
-            close_device: Some(close_device),
-            get_default_config: Some(get_default_config),
-            start: Some(start),
-            stop: Some(stop),
-            get_latency: Some(get_latency),
-            set_sample_rate: Some(set_sr),
-            set_buffer_frames: Some(set_buf),
-        },
+    let options = match sys::CreateOptions::from_params(p) {
+        Ok(options) => options,
+        Err(msg) => {
+            sys::LogSink::from_params(p)
+                .emit(log::Level::Error, format_args!("bad create options: {msg}"));
+            return sys::OA_ERR_INVALID_ARG;
+        }
+    };
+    let mut drv = Box::new(Driver {
+        vt: &VTABLE,
         state: DriverState {
-            host: p.host,
+            host: sys::host_callbacks(p),
             host_user: p.host_user,
+            log: sys::LogSink::from_params(p),
+            hints: sys::ThreadHints::from_params(p),
+            phase: sys::DriverPhase::Created,
+            cfg_size: sys::stream_config_size(p),
+            period_count: alsa_common::DEFAULT_PERIOD_COUNT,
+            use_mmap: false,
+            options,
             dev_name: None,
-            io: Io {
-                cap: None,
-                pb: None,
-            },
-            cfg: sys::oa_stream_config {
-                sample_rate: 48000,
-                buffer_frames: 128,
-                in_channels: 2,
-                out_channels: 2,
-                format: sys::oa_sample_format::OA_SAMPLE_F32,
-                layout: sys::oa_buffer_layout::OA_BUF_INTERLEAVED,
-            },
+            plumbed_name: None,
+            io: Io::default(),
+            cfg: sys::oa_stream_config::default(),
             time0: Instant::now(),
+            dev_time0_ns: 0,
             underruns: AtomicU32::new(0),
             overruns: AtomicU32::new(0),
+            frames_processed: AtomicU64::new(0),
+            in_gains: sys::ChannelGains::default(),
+            out_gains: sys::ChannelGains::default(),
+            near_xruns: AtomicU32::new(0),
+            max_callback_ns: AtomicU64::new(0),
+            callback_threshold_ns: 0,
+            slow_callbacks: AtomicU32::new(0),
+            converter: &alsa_common::F32Converter,
+            passthrough: false,
+            in_hw: HwBuffer::default(),
             in_buf: Vec::new(),
             out_buf: Vec::new(),
+            out_hw: HwBuffer::default(),
+            f64_block: None,
             running: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            hw_paused: false,
+            gate: sys::StartGate::new(),
             worker: None,
+            supported_rates: Vec::new(),
+            channel_names: Vec::new(),
+            last_error: sys::LastError::default(),
         },
     });
+    let (options, log) = (drv.state.options.clone(), drv.state.log);
+    if let Err((code, msg)) = alsa_common::apply_create_options(&mut drv.state, &options, &log) {
+        log!(drv, Error, "{msg}");
+        return code;
+    }
     *out = Box::into_raw(drv) as *mut sys::oa_driver;
+    sys::track_driver(*out);
     sys::OA_OK
 }
 
-#[no_mangle]
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
 pub unsafe extern "C" fn openasio_driver_destroy(driver: *mut sys::oa_driver) {
-    if !driver.is_null() {
-        let _ = Box::from_raw(driver as *mut Driver);
+    if sys::release_driver(driver) {
+        let mut drv = Box::from_raw(driver as *mut Driver);
+        // Lets debug builds catch calls from the teardown below; dropping stops the worker.
+        drv.state.phase = sys::DriverPhase::Destroyed;
+        drop(drv);
+    }
+}
+
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
+pub unsafe extern "C" fn openasio_driver_get_info(out: *mut sys::oa_driver_info) -> i32 {
+    let info = sys::oa_driver_info::new(
+        "AMD Family 17h HDA (ALSA)",
+        "OpenASIO project",
+        env!("CARGO_PKG_VERSION"),
+    );
+    sys::write_driver_info(&info, out)
+}
+
+#[cfg(not(feature = "static-link"))]
+sys::oa_export_abi_version!();
+
+/// The Family 17h HDA driver, for hosts that link it in rather than load it; see [`sys::DriverFactory`].
+#[cfg(feature = "static-link")]
+pub struct Alsa17h;
+
+#[cfg(feature = "static-link")]
+impl sys::DriverFactory for Alsa17h {
+    unsafe extern "C" fn create(
+        params: *const sys::oa_create_params,
+        out: *mut *mut sys::oa_driver,
+    ) -> i32 {
+        openasio_driver_create(params, out)
+    }
+
+    unsafe extern "C" fn destroy(driver: *mut sys::oa_driver) {
+        openasio_driver_destroy(driver)
+    }
+
+    unsafe extern "C" fn get_info(out: *mut sys::oa_driver_info) -> i32 {
+        openasio_driver_get_info(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{HwSetupStep, MockPcm};
+
+    unsafe extern "C" fn process(
+        _user: *mut c_void,
+        _in_ptr: *const c_void,
+        _out_ptr: *mut c_void,
+        _frames: u32,
+        _time: *const sys::oa_time_info,
+        _cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        sys::OA_FALSE
+    }
+
+    #[test]
+    fn short_device_buffer_gets_the_size_not_a_cut_list() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..Default::default()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let mut short = [b'?' as c_char; 4];
+            let need = query_devices(drv, short.as_mut_ptr(), short.len());
+            assert!(need > short.len() as i32, "default alone needs 9 bytes");
+            assert_eq!(short[0], 0);
+            let mut buf = vec![0 as c_char; need as usize];
+            assert_eq!(query_devices(drv, buf.as_mut_ptr(), buf.len()), sys::OA_OK);
+            let list = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert_eq!(list.len() + 1, need as usize);
+            assert_eq!(list.lines().next(), Some("default"));
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn close_without_open_is_harmless() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            assert_eq!(open_device(drv, c"default".as_ptr()), sys::OA_OK);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn destroy_ignores_null_and_a_second_destroy() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            openasio_driver_destroy(ptr::null_mut());
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+            // Only debug builds track live drivers; in release this would be a double free.
+            #[cfg(debug_assertions)]
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn device_info_unknown_device() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..Default::default()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let mut info = sys::oa_device_info::default();
+            let rc = query_device_info(drv, c"hw:NoSuchCard".as_ptr(), &mut info);
+            assert_eq!(rc, sys::OA_ERR_DEVICE);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn default_config_is_the_sys_default() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv, &mut cfg), sys::OA_OK);
+            assert_eq!(cfg, sys::oa_stream_config::default());
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn create_requires_process() {
+        let host = sys::oa_host_callbacks::empty();
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                openasio_driver_create(&params, &mut drv),
+                sys::OA_ERR_INVALID_ARG
+            );
+        }
+        assert!(drv.is_null());
+    }
+
+    #[test]
+    fn create_rejects_truncated_params() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let full = sys::oa_create_params::new(&host, ptr::null_mut());
+        // Too short to hold `host_user`, and too long to be any revision of the struct.
+        for struct_size in [8, sys::OA_MAX_STRUCT_SIZE + 1] {
+            let params = sys::oa_create_params {
+                struct_size,
+                ..full
+            };
+            let mut drv = ptr::null_mut();
+            let rc = unsafe { openasio_driver_create(&params, &mut drv) };
+            assert_eq!(rc, sys::OA_ERR_INVALID_ARG, "struct_size {struct_size}");
+            assert!(drv.is_null());
+        }
+    }
+
+    #[test]
+    fn thread_hints_survive_create() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        // Priority 80 is more than an unprivileged test may have; creation must not care.
+        let params = sys::oa_create_params {
+            rt_priority: 80,
+            cpu_affinity_mask: 1,
+            ..sys::oa_create_params::new(&host, ptr::null_mut())
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let hints = (*(drv as *const Driver)).state.hints;
+            assert_eq!(
+                hints,
+                sys::ThreadHints {
+                    rt_priority: 80,
+                    cpu_affinity_mask: 1
+                }
+            );
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn start_rejects_blocks_too_large_to_size() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let cfg = sys::oa_stream_config {
+            buffer_frames: 65535,
+            in_channels: 65,
+            out_channels: 65,
+            ..sys::oa_stream_config::default()
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(start(drv, &cfg), sys::OA_ERR_INVALID_ARG);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn round_up_pow2_values() {
+        assert_eq!(round_up_pow2(100), 128);
+        assert_eq!(round_up_pow2(128), 128);
+        assert_eq!(round_up_pow2(0), 1);
+        assert_eq!(round_up_pow2(u32::MAX), 1 << 31);
+    }
+
+    fn quiet_log() -> sys::LogSink {
+        let host = sys::oa_host_callbacks::empty();
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        sys::LogSink::from_params(&params)
+    }
+
+    fn mock_setup(fail_on: Option<HwSetupStep>) -> (MockPcm, Result<(), String>) {
+        let pcm = MockPcm::new(fail_on);
+        let mut cfg = sys::oa_stream_config {
+            sample_rate: 44100,
+            buffer_frames: 200,
+            ..sys::oa_stream_config::default()
+        };
+        let res = hw_setup(
+            &pcm,
+            PcmDir::Playback,
+            Access::RWInterleaved,
+            &mut cfg,
+            alsa_common::DEFAULT_PERIOD_COUNT,
+            &quiet_log(),
+        );
+        (pcm, res)
+    }
+
+    #[test]
+    fn hw_setup_installs_the_config() {
+        let (pcm, res) = mock_setup(None);
+        assert_eq!(res, Ok(()));
+        assert_eq!(pcm.get_access(), Some(Access::RWInterleaved));
+        assert_eq!(pcm.get_channels(), 2);
+        assert_eq!(pcm.get_rate(), 44100);
+        assert_eq!(pcm.get_format(), Some(Format::float()));
+        // 200 frames is rounded up to a power of two, with two periods in the buffer.
+        assert_eq!(pcm.get_period_size(), 256);
+        assert_eq!(pcm.get_buffer_size(), 512);
+        assert_eq!(pcm.get_avail_min(), 256);
+    }
+
+    #[test]
+    fn hw_setup_reports_a_rejected_rate() {
+        let (pcm, res) = mock_setup(Some(HwSetupStep::SetRate));
+        let err = res.unwrap_err();
+        assert!(err.starts_with("sample rate 44100 not supported"), "{err}");
+        assert_eq!(
+            pcm.get_rate(),
+            0,
+            "parameters were installed after a failed step"
+        );
+    }
+
+    #[test]
+    fn hw_setup_names_an_absurd_rate() {
+        let pcm = MockPcm::new(None);
+        let mut cfg = sys::oa_stream_config {
+            sample_rate: 999_999,
+            buffer_frames: 256,
+            ..sys::oa_stream_config::default()
+        };
+        let err = hw_setup(
+            &pcm,
+            PcmDir::Playback,
+            Access::RWInterleaved,
+            &mut cfg,
+            alsa_common::DEFAULT_PERIOD_COUNT,
+            &quiet_log(),
+        )
+        .unwrap_err();
+        assert!(err.contains("999999"), "{err}");
+    }
+
+    #[test]
+    fn hw_setup_reports_a_rejected_period() {
+        let (pcm, res) = mock_setup(Some(HwSetupStep::SetPeriodSize));
+        let err = res.unwrap_err();
+        assert!(
+            err.starts_with("period of 256 frames not supported"),
+            "{err}"
+        );
+        assert_eq!(pcm.get_period_size(), 0);
+    }
+
+    #[test]
+    fn hw_setup_stops_at_any_failed_step() {
+        let steps = [
+            HwSetupStep::Any,
+            HwSetupStep::SetAccess,
+            HwSetupStep::SetChannels,
+            HwSetupStep::SetRate,
+            HwSetupStep::SetFormat,
+            HwSetupStep::SetPeriodSize,
+            HwSetupStep::SetBufferSize,
+            HwSetupStep::HwParams,
+            HwSetupStep::SwParams,
+        ];
+        for step in steps {
+            let (pcm, res) = mock_setup(Some(step));
+            assert!(res.is_err(), "{step:?} failed but hw_setup succeeded");
+            assert_eq!(pcm.get_avail_min(), 0, "{step:?}");
+        }
+    }
+
+    #[test]
+    fn period_count_option_sizes_the_next_hw_setup() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, ptr::null_mut());
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let vt = &*(*drv).vt;
+            let set = sys::oa_vtable_field!(vt, set_option).unwrap();
+            let get = sys::oa_vtable_field!(vt, get_option).unwrap();
+            assert_eq!(
+                set(drv, c"period_count".as_ptr(), c"4".as_ptr()),
+                sys::OA_OK
+            );
+            let mut buf = [0 as c_char; 8];
+            assert_eq!(
+                get(drv, c"period_count".as_ptr(), buf.as_mut_ptr(), buf.len()),
+                1
+            );
+            assert_eq!(CStr::from_ptr(buf.as_ptr()), c"4");
+
+            for (key, value, rc) in [
+                (c"period_count", c"1", sys::OA_ERR_UNSUPPORTED),
+                (c"period_count", c"four", sys::OA_ERR_INVALID_ARG),
+                (c"access", c"dma", sys::OA_ERR_INVALID_ARG),
+                (c"rt_priority", c"100", sys::OA_ERR_INVALID_ARG),
+                (c"no_such_option", c"1", sys::OA_ERR_UNSUPPORTED),
+            ] {
+                assert_eq!(
+                    set(drv, key.as_ptr(), value.as_ptr()),
+                    rc,
+                    "{key:?}={value:?}"
+                );
+            }
+            let get_no_such = get(drv, c"no_such_option".as_ptr(), buf.as_mut_ptr(), buf.len());
+            assert_eq!(get_no_such, sys::OA_ERR_UNSUPPORTED);
+
+            let state = &(*(drv as *const Driver)).state;
+            assert_eq!(
+                state.period_count, 4,
+                "rejected values replaced the setting"
+            );
+            let pcm = MockPcm::new(None);
+            let mut cfg = sys::oa_stream_config::default();
+            hw_setup(
+                &pcm,
+                PcmDir::Playback,
+                Access::RWInterleaved,
+                &mut cfg,
+                state.period_count,
+                &quiet_log(),
+            )
+            .unwrap();
+            assert_eq!(pcm.get_buffer_size(), 4 * pcm.get_period_size());
+            openasio_driver_destroy(drv);
+        }
+    }
+    #[test]
+    fn create_options_set_the_device_and_period_count() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let create = |options: &CStr| {
+            let params = sys::oa_create_params {
+                options: options.as_ptr(),
+                ..sys::oa_create_params::new(&host, ptr::null_mut())
+            };
+            let mut drv = ptr::null_mut();
+            let rc = unsafe { openasio_driver_create(&params, &mut drv) };
+            (rc, drv)
+        };
+        unsafe {
+            let (rc, drv) = create(c"device=\"plughw:1,0\",period_count=3,colour=blue");
+            assert_eq!(rc, sys::OA_OK);
+            assert_eq!((*(drv as *const Driver)).state.period_count, 3);
+            assert_eq!(
+                ((*(*drv).vt).open_device.unwrap())(drv, ptr::null()),
+                sys::OA_OK
+            );
+            let state = &(*(drv as *const Driver)).state;
+            assert_eq!(state.dev_name.as_deref(), Some("plughw:1,0"));
+            openasio_driver_destroy(drv);
+
+            for bad in [c"period_count=1", c"period_count", c"device=\"hw:0"] {
+                let (rc, drv) = create(bad);
+                assert_ne!(rc, sys::OA_OK, "{bad:?}");
+                assert!(drv.is_null());
+            }
+        }
+    }
+
+    /// Writes channel `c` as `0.25 * (c + 1)` through f64 planes and counts the calls.
+    unsafe extern "C" fn fill_f64_planes(
+        user: *mut c_void,
+        _in_ptr: *const c_void,
+        out_ptr: *mut c_void,
+        frames: u32,
+        _time: *const sys::oa_time_info,
+        cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        let planes =
+            std::slice::from_raw_parts(out_ptr as *const *mut f64, (*cfg).out_channels as usize);
+        for (c, &plane) in planes.iter().enumerate() {
+            std::slice::from_raw_parts_mut(plane, frames as usize).fill(0.25 * (c + 1) as f64);
+        }
+        (*(user as *const AtomicU32)).fetch_add(1, Ordering::Release);
+        // The null PCM never blocks; keep the worker from spinning a core.
+        std::thread::sleep(Duration::from_millis(1));
+        sys::OA_TRUE
+    }
+
+    #[test]
+    fn f64_hosts_get_f64_planes() {
+        let calls = AtomicU32::new(0);
+        let host = sys::oa_host_callbacks {
+            process: Some(fill_f64_planes),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, &calls as *const AtomicU32 as *mut c_void);
+        let cfg = sys::oa_stream_config {
+            in_channels: 0,
+            format: sys::oa_sample_format::OA_SAMPLE_F64,
+            layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED,
+            ..sys::oa_stream_config::default()
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(open_device(drv, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while calls.load(Ordering::Acquire) < 2 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            assert!(calls.load(Ordering::Acquire) >= 2);
+            // The device side stays interleaved f32.
+            let state = &(*(drv as *const Driver)).state;
+            let frames = state.cfg.buffer_frames as usize;
+            assert_eq!(state.out_buf[..frames * 2], [0.25, 0.5].repeat(frames));
+
+            let mut default = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv, &mut default), sys::OA_OK);
+            assert_eq!(default.format, sys::oa_sample_format::OA_SAMPLE_F32);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    /// Writes interleaved stereo i16, +0.5 left and -0.5 right, and counts the calls.
+    unsafe extern "C" fn fill_i16(
+        user: *mut c_void,
+        _in_ptr: *const c_void,
+        out_ptr: *mut c_void,
+        frames: u32,
+        _time: *const sys::oa_time_info,
+        _cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        let out = std::slice::from_raw_parts_mut(out_ptr as *mut i16, frames as usize * 2);
+        for frame in out.chunks_exact_mut(2) {
+            frame.copy_from_slice(&[16384, -16384]);
+        }
+        (*(user as *const AtomicU32)).fetch_add(1, Ordering::Release);
+        std::thread::sleep(Duration::from_millis(1));
+        sys::OA_TRUE
+    }
+
+    #[test]
+    fn i16_hosts_write_the_s16_device_buffer() {
+        let calls = AtomicU32::new(0);
+        let host = sys::oa_host_callbacks {
+            process: Some(fill_i16),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, &calls as *const AtomicU32 as *mut c_void);
+        let cfg = sys::oa_stream_config {
+            in_channels: 0,
+            format: sys::oa_sample_format::OA_SAMPLE_I16,
+            ..sys::oa_stream_config::default()
+        };
+        let planar = sys::oa_stream_config {
+            layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED,
+            ..cfg
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(open_device(drv, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv, &planar), sys::OA_ERR_UNSUPPORTED);
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while calls.load(Ordering::Acquire) < 2 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            assert!(calls.load(Ordering::Acquire) >= 2);
+            let state = &(*(drv as *const Driver)).state;
+            assert_eq!(state.converter.format(), Format::s16());
+            let frames = state.cfg.buffer_frames as usize;
+            let frame = [16384i16.to_ne_bytes(), (-16384i16).to_ne_bytes()].concat();
+            assert_eq!(state.out_hw.bytes(), frame.repeat(frames));
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    /// Counts process calls and the `OA_XRUN_CALLBACK` totals the driver reports.
+    #[derive(Default)]
+    struct SlowHost {
+        calls: AtomicU32,
+        slow_total: AtomicU32,
+    }
+
+    /// Takes 5 ms over every block.
+    unsafe extern "C" fn slow_process(
+        user: *mut c_void,
+        _in_ptr: *const c_void,
+        _out_ptr: *mut c_void,
+        _frames: u32,
+        _time: *const sys::oa_time_info,
+        _cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        std::thread::sleep(Duration::from_millis(5));
+        (*(user as *const SlowHost))
+            .calls
+            .fetch_add(1, Ordering::Release);
+        sys::OA_TRUE
+    }
+
+    unsafe extern "C" fn note_slow(user: *mut c_void, kind: sys::oa_xrun_kind, count: u32) {
+        if kind == sys::oa_xrun_kind::OA_XRUN_CALLBACK {
+            (*(user as *const SlowHost))
+                .slow_total
+                .store(count, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn slow_callbacks_are_timed_and_reported() {
+        let seen = SlowHost::default();
+        let host = sys::oa_host_callbacks {
+            process: Some(slow_process),
+            xrun: Some(note_slow),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params {
+            options: c"callback_threshold_ns=1000000".as_ptr(),
+            ..sys::oa_create_params::new(&host, &seen as *const SlowHost as *mut c_void)
+        };
+        let cfg = sys::oa_stream_config {
+            in_channels: 0,
+            ..sys::oa_stream_config::default()
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(open_device(drv, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while seen.calls.load(Ordering::Acquire) < 3 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            let calls = seen.calls.load(Ordering::Acquire);
+            assert!(calls >= 3);
+            let mut status = sys::oa_stream_status::default();
+            assert_eq!(get_status(drv, &mut status), sys::OA_OK);
+            assert!(
+                status.max_callback_ns >= 5_000_000,
+                "{}",
+                status.max_callback_ns
+            );
+            assert_eq!(seen.slow_total.load(Ordering::Acquire), calls);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    /// The `device_time_ns` of every block the host has seen.
+    unsafe extern "C" fn note_device_time(
+        user: *mut c_void,
+        _in_ptr: *const c_void,
+        _out_ptr: *mut c_void,
+        _frames: u32,
+        time: *const sys::oa_time_info,
+        _cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        let seen = &*(user as *const std::sync::Mutex<Vec<u64>>);
+        seen.lock().unwrap().push((*time).device_time_ns);
+        sys::OA_TRUE
+    }
+
+    #[test]
+    fn device_time_is_stamped_and_advances() {
+        let seen = std::sync::Mutex::new(Vec::<u64>::new());
+        let host = sys::oa_host_callbacks {
+            process: Some(note_device_time),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, &seen as *const _ as *mut c_void);
+        let cfg = sys::oa_stream_config {
+            in_channels: 0,
+            ..sys::oa_stream_config::default()
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_eq!(open_device(drv, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while seen.lock().unwrap().len() < 6 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
+        let times = seen.into_inner().unwrap();
+        assert!(times.len() >= 6, "only {} callbacks", times.len());
+        assert!(times[0] > 0, "first block carried no device time");
+        assert!(times.windows(2).all(|w| w[0] <= w[1]), "{times:?}");
+        assert!(times[times.len() - 1] > times[0]);
+    }
+
+    #[test]
+    fn mmap_access_falls_back_where_nothing_maps() {
+        let calls = AtomicU32::new(0);
+        let host = sys::oa_host_callbacks {
+            process: Some(fill_f64_planes),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = sys::oa_create_params::new(&host, &calls as *const AtomicU32 as *mut c_void);
+        let cfg = sys::oa_stream_config {
+            in_channels: 0,
+            format: sys::oa_sample_format::OA_SAMPLE_F64,
+            layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED,
+            ..sys::oa_stream_config::default()
+        };
+        let mut drv = ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            assert_ne!(get_caps(drv) & sys::OA_CAP_MMAP, 0);
+            assert_eq!(
+                set_option(drv, c"access".as_ptr(), c"mmap".as_ptr()),
+                sys::OA_OK
+            );
+            let mut buf = [0 as c_char; 8];
+            assert_eq!(
+                get_option(drv, c"access".as_ptr(), buf.as_mut_ptr(), buf.len()),
+                4
+            );
+            assert_eq!(CStr::from_ptr(buf.as_ptr()), c"mmap");
+            // The null PCM is a plugin with no DMA ring, so the stream runs read/write.
+            assert_eq!(open_device(drv, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv, &cfg), sys::OA_OK);
+            let state = &(*(drv as *const Driver)).state;
+            assert!(state.io.pb.is_some() && state.io.pb_ring.is_none());
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while calls.load(Ordering::Acquire) < 2 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(stop(drv), sys::OA_OK);
+            assert!(calls.load(Ordering::Acquire) >= 2);
+            assert_eq!(close_device(drv), sys::OA_OK);
+            openasio_driver_destroy(drv);
+        }
     }
 }
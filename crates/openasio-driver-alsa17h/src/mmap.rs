@@ -0,0 +1,72 @@
+//! Block I/O through the device's DMA ring for `access=mmap` streams. The kernel maps the ring
+//! and its pointers into the process, so blocks are copied straight in and out instead of through
+//! `readi`/`writei`. Only `hw:` devices map this way; alsa-lib plugins such as `plughw:` and
+//! `dmix` do not, and `start` falls back to read/write access for them.
+use alsa::direct::pcm::{MmapCapture, MmapPlayback};
+use alsa::pcm::{State, PCM};
+use nix::errno::Errno;
+
+/// Longest one wait for the device lasts before the stream state is checked again, so a PCM
+/// dropped by `abort` is noticed.
+const WAIT_MS: u32 = 100;
+
+/// Fail the way `readi`/`writei` would on a stream that cannot move data, so `recover_pcm`
+/// handles both access modes: `EPIPE` after an xrun, `ESTRPIPE` while suspended, `EBADFD` once
+/// the stream was dropped or the device is gone.
+fn check_state(pcm: &PCM) -> alsa::Result<()> {
+    let errno = match pcm.state() {
+        State::Prepared | State::Running | State::Paused | State::Draining => return Ok(()),
+        State::XRun => Errno::EPIPE,
+        State::Suspended => Errno::ESTRPIPE,
+        _ => Errno::EBADFD,
+    };
+    Err(alsa::Error::new("snd_pcm_state", errno as i32))
+}
+
+/// Fill `buf`, whole frames of interleaved samples, from the capture ring. Blocks like `readi`,
+/// and starts a prepared stream, which a mapped ring never does by itself.
+pub fn read(pcm: &PCM, ring: &mut MmapCapture<f32>, buf: &mut [f32]) -> alsa::Result<usize> {
+    let ch = (ring.channels() as usize).max(1);
+    let frames = buf.len() / ch;
+    let mut done = 0;
+    while done < frames {
+        check_state(pcm)?;
+        if pcm.state() == State::Prepared {
+            pcm.start()?;
+        }
+        let avail = ring.avail().max(0) as usize;
+        if avail == 0 {
+            pcm.wait(Some(WAIT_MS))?;
+            continue;
+        }
+        let n = avail.min(frames - done);
+        // The iterator commits what it read when it is dropped.
+        let dst = &mut buf[done * ch..(done + n) * ch];
+        for (d, s) in dst.iter_mut().zip(ring.iter()) {
+            *d = s;
+        }
+        done += n;
+    }
+    Ok(done)
+}
+
+/// Queue `buf`, whole frames of interleaved samples, on the playback ring. Blocks like `writei`,
+/// and starts the stream once something is queued.
+pub fn write(pcm: &PCM, ring: &mut MmapPlayback<f32>, buf: &[f32]) -> alsa::Result<usize> {
+    let ch = (ring.channels() as usize).max(1);
+    let frames = buf.len() / ch;
+    let mut samples = buf[..frames * ch].iter().copied();
+    let mut done = 0;
+    while done < frames {
+        check_state(pcm)?;
+        if ring.avail() <= 0 {
+            pcm.wait(Some(WAIT_MS))?;
+            continue;
+        }
+        done += ring.write(&mut samples) as usize;
+        if pcm.state() == State::Prepared {
+            pcm.start()?;
+        }
+    }
+    Ok(done)
+}
@@ -0,0 +1,173 @@
+//! A PCM stand-in for exercising `hw_setup` without ALSA hardware.
+
+use crate::{HwParamsLike, PcmLike};
+use alsa::pcm::{Access, Format, Frames};
+use alsa::ValueOr;
+use std::cell::Cell;
+
+/// A call `hw_setup` makes, for [`MockPcm::new`] to fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HwSetupStep {
+    Any,
+    SetAccess,
+    SetChannels,
+    SetRate,
+    SetFormat,
+    SetPeriodSize,
+    SetBufferSize,
+    HwParams,
+    SwParams,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Settings {
+    access: Option<Access>,
+    channels: u32,
+    rate: u32,
+    format: Option<Format>,
+    period_size: Frames,
+    buffer_size: Frames,
+}
+
+/// Highest rate a [`MockPcm`] accepts, as for most USB and PCI hardware.
+pub const MAX_RATE: u32 = 768_000;
+
+/// Accepts whatever it is asked for, except at the step given to [`MockPcm::new`] and for rates
+/// above [`MAX_RATE`], which fail with `EINVAL` as ALSA would. The getters report what was
+/// installed, zero before `hw_params`.
+pub struct MockPcm {
+    fail_on: Option<HwSetupStep>,
+    installed: Cell<Settings>,
+    avail_min: Cell<Frames>,
+}
+
+impl MockPcm {
+    pub fn new(fail_on: Option<HwSetupStep>) -> Self {
+        Self {
+            fail_on,
+            installed: Cell::new(Settings::default()),
+            avail_min: Cell::new(0),
+        }
+    }
+
+    pub fn get_access(&self) -> Option<Access> {
+        self.installed.get().access
+    }
+    pub fn get_channels(&self) -> u32 {
+        self.installed.get().channels
+    }
+    pub fn get_rate(&self) -> u32 {
+        self.installed.get().rate
+    }
+    pub fn get_format(&self) -> Option<Format> {
+        self.installed.get().format
+    }
+    pub fn get_period_size(&self) -> Frames {
+        self.installed.get().period_size
+    }
+    pub fn get_buffer_size(&self) -> Frames {
+        self.installed.get().buffer_size
+    }
+    /// The software wakeup point, set once `hw_setup` got through `sw_params`.
+    pub fn get_avail_min(&self) -> Frames {
+        self.avail_min.get()
+    }
+
+    fn step(&self, step: HwSetupStep, func: &'static str) -> alsa::Result<()> {
+        if self.fail_on == Some(step) {
+            return Err(alsa::Error::new(func, libc::EINVAL));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters being negotiated; nothing reaches the [`MockPcm`] until `hw_params`.
+pub struct MockHwParams<'a> {
+    pcm: &'a MockPcm,
+    pending: Cell<Settings>,
+}
+
+impl MockHwParams<'_> {
+    fn update(
+        &self,
+        step: HwSetupStep,
+        func: &'static str,
+        f: impl FnOnce(&mut Settings),
+    ) -> alsa::Result<()> {
+        self.pcm.step(step, func)?;
+        let mut s = self.pending.get();
+        f(&mut s);
+        self.pending.set(s);
+        Ok(())
+    }
+}
+
+impl PcmLike for MockPcm {
+    type HwParams<'a> = MockHwParams<'a>;
+    fn hw_params_any(&self) -> alsa::Result<MockHwParams<'_>> {
+        self.step(HwSetupStep::Any, "snd_pcm_hw_params_any")?;
+        Ok(MockHwParams {
+            pcm: self,
+            pending: Cell::new(Settings::default()),
+        })
+    }
+    fn hw_params(&self, hwp: &MockHwParams<'_>) -> alsa::Result<()> {
+        self.step(HwSetupStep::HwParams, "snd_pcm_hw_params")?;
+        self.installed.set(hwp.pending.get());
+        Ok(())
+    }
+    fn current_period_size(&self) -> alsa::Result<Frames> {
+        Ok(self.get_period_size())
+    }
+    fn sw_setup(&self, period: Frames) -> alsa::Result<()> {
+        self.step(HwSetupStep::SwParams, "snd_pcm_sw_params")?;
+        self.avail_min.set(period);
+        Ok(())
+    }
+}
+
+impl HwParamsLike for MockHwParams<'_> {
+    fn set_access(&self, v: Access) -> alsa::Result<()> {
+        self.update(
+            HwSetupStep::SetAccess,
+            "snd_pcm_hw_params_set_access",
+            |s| s.access = Some(v),
+        )
+    }
+    fn set_channels(&self, v: u32) -> alsa::Result<()> {
+        self.update(
+            HwSetupStep::SetChannels,
+            "snd_pcm_hw_params_set_channels",
+            |s| s.channels = v,
+        )
+    }
+    fn set_rate(&self, v: u32, _dir: ValueOr) -> alsa::Result<()> {
+        if v > MAX_RATE {
+            return Err(alsa::Error::new("snd_pcm_hw_params_set_rate", libc::EINVAL));
+        }
+        self.update(HwSetupStep::SetRate, "snd_pcm_hw_params_set_rate", |s| {
+            s.rate = v
+        })
+    }
+    fn set_format(&self, v: Format) -> alsa::Result<()> {
+        self.update(
+            HwSetupStep::SetFormat,
+            "snd_pcm_hw_params_set_format",
+            |s| s.format = Some(v),
+        )
+    }
+    fn set_period_size(&self, v: Frames, _dir: ValueOr) -> alsa::Result<()> {
+        self.update(
+            HwSetupStep::SetPeriodSize,
+            "snd_pcm_hw_params_set_period_size",
+            |s| s.period_size = v,
+        )
+    }
+    fn set_buffer_size(&self, v: Frames) -> alsa::Result<()> {
+        self.update(
+            HwSetupStep::SetBufferSize,
+            "snd_pcm_hw_params_set_buffer_size",
+            |s| s.buffer_size = v,
+        )
+    }
+}
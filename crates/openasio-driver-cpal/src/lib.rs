@@ -1,14 +1,28 @@
 //! CPAL-backed OpenASIO driver (v1.0.0). Full-duplex with interleaved & non-interleaved support.
+#![allow(clippy::missing_safety_doc)]
+mod ring;
+
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use openasio_sys as sys;
+use ring::Ring;
 use std::ffi::CStr;
-use std::os::raw::c_void;
-use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::time::Instant;
 
+/// Log through the host's `log_cb` when it installed one, otherwise through the `log` crate.
+macro_rules! log {
+    ($drv:expr, $lvl:ident, $($arg:tt)+) => { $drv.state.log.emit(log::Level::$lvl, format_args!($($arg)+)) };
+}
+
 struct DriverState {
     host: sys::oa_host_callbacks,
     host_user: *mut c_void,
+    log: sys::LogSink,
+    phase: sys::DriverPhase,
+    last_error: sys::LastError,
+    cfg_size: usize, // the host's sizeof(oa_stream_config)
+    backend: Option<cpal::HostId>, // the `host` create option; None for the platform default
     out_device: Option<cpal::Device>,
     in_device: Option<cpal::Device>,
     out_stream: Option<cpal::Stream>,
@@ -17,14 +31,29 @@ struct DriverState {
     time0: Instant,
     underruns: AtomicU32,
     overruns: AtomicU32,
+    frames_processed: AtomicU64, // since the last start
+    paused: AtomicBool, // the output callback plays silence; for backends whose streams cannot pause
 
-    // Input staging (latest block). We keep interleaved f32 internally.
+    // Input staging: the input callback queues interleaved f32 on in_ring and the output callback
+    // takes one block at a time into in_buf for process.
+    in_ring: Ring,
     in_buf: Vec<f32>,
-    in_seq: AtomicUsize,
+    in_seq: AtomicUsize, // input callbacks since start; before the first, an empty ring is expected
+    in_overflows: AtomicU32, // pushes that found the ring full, reported from the output callback
+    in_planes: Vec<*const f32>, // a planar host's view of in_buf
+    f64_block: Option<sys::F64Block>, // what process sees on an f64 stream
+
+    // A planar host's output, interleaved into cpal's buffer after process. Sized by start.
+    out_scratch: Vec<f32>,
+    out_planes: Vec<*mut f32>,
+
+    // Trim gains, applied to the interleaved device blocks.
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
 }
 
 #[repr(C)]
-struct Driver { vt: sys::oa_driver_vtable, state: DriverState }
+struct Driver { vt: *const sys::oa_driver_vtable, state: DriverState }
 
 #[derive(Copy, Clone)]
 struct DriverPtr(*mut Driver);
@@ -44,58 +73,191 @@ impl DriverPtr {
 unsafe impl Send for DriverPtr {}
 unsafe impl Sync for DriverPtr {}
 
-unsafe extern "C" fn get_caps(_selfp:*mut sys::oa_driver)->u32 {
-    (sys::OA_CAP_OUTPUT | sys::OA_CAP_INPUT | sys::OA_CAP_FULL_DUPLEX) as u32
+impl DriverState {
+    /// The cpal host the `host` create option chose, or the platform default. A chosen host that
+    /// has since become unavailable (a JACK server that quit) also falls back to the default.
+    fn cpal_host(&self) -> cpal::Host {
+        self.backend.and_then(|id| cpal::host_from_id(id).ok()).unwrap_or_else(cpal::default_host)
+    }
 }
 
-unsafe extern "C" fn query_devices(_selfp:*mut sys::oa_driver, buf:*mut i8, len: usize)->i32{
-    let host = cpal::default_host();
-    let mut names = String::new();
-    if let Ok(mut devs) = host.output_devices(){
-        while let Some(d)=devs.next(){ if let Ok(n)=d.name(){ names.push_str(&n); names.push('\n'); } }
+impl Driver {
+    /// Log `msg` and keep it for `get_last_error`, then return `code`.
+    fn fail(&mut self, code: i32, msg: String) -> i32 {
+        log!(self, Error, "{msg}");
+        self.state.last_error.set(msg);
+        code
     }
-    let bytes = names.as_bytes(); let n = bytes.len().min(len.saturating_sub(1));
-    if n>0 { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, n); }
-    if len>0 { *buf.add(n) = 0; }
-    sys::OA_OK
 }
 
-unsafe extern "C" fn open_device(selfp:*mut sys::oa_driver, name:*const i8)->i32{
-    let s = &mut *(selfp as *mut Driver);
-    let host = cpal::default_host();
+/// Periods the input ring holds. The output callback leaves at most half of them queued,
+/// dropping the oldest, which bounds the added latency and leaves the input callback room for
+/// a burst.
+const RING_PERIODS: usize = 4;
 
-    // Output device
-    let out = if name.is_null(){ host.default_output_device() } else {
-        let needle = CStr::from_ptr(name).to_string_lossy().to_string();
-        let mut found=None; if let Ok(mut it)=host.output_devices(){ while let Some(d)=it.next(){ if let Ok(n)=d.name(){ if n.contains(&needle){ found=Some(d); break; }}}}
-        found
-    };
-    // Input device: try to match same name; else default input
-    let inp = if let Some(ref od) = out {
-        let od_name = od.name().ok();
-        let mut found=None;
-        if let (Some(needle), Ok(mut it)) = (od_name, host.input_devices()) {
-            let n = needle;
-            while let Some(d)=it.next(){ if let Ok(nm)=d.name(){ if nm==n { found=Some(d); break; } } }
+const CAPS: sys::OaCaps = sys::OaCaps::OUTPUT.union(sys::OaCaps::INPUT).union(sys::OaCaps::FULL_DUPLEX).union(sys::OaCaps::SAMPLE_POSITION);
+
+unsafe extern "C" fn get_caps(selfp:*mut sys::oa_driver)->u32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_caps");
+    CAPS.bits()
+}
+
+unsafe extern "C" fn query_devices(selfp:*mut sys::oa_driver, buf:*mut c_char, len: usize)->i32{
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_devices(len={len})");
+    let host = s.state.cpal_host();
+    let names = |devs: Result<_, cpal::DevicesError>| -> Vec<String> { devs.map(|it: DeviceIter| it.filter_map(|d| d.name().ok()).collect()).unwrap_or_default() };
+    let list: String = device_entries(names(host.output_devices()), names(host.input_devices())).into_iter().map(|e| e + "\n").collect();
+    sys::write_device_list(&list, buf, len)
+}
+
+/// Directions a `query_devices` entry streams in, written after the cpal name as ` [in]`,
+/// ` [out]` or ` [duplex]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Dirs { In, Out, Duplex }
+
+impl Dirs {
+    const TAGS: [(Self, &'static str); 3] = [(Self::In, " [in]"), (Self::Out, " [out]"), (Self::Duplex, " [duplex]")];
+    fn tag(self) -> &'static str { Self::TAGS.iter().find(|t| t.0 == self).map_or("", |t| t.1) }
+}
+
+/// The `query_devices` list: every output and input name once, sorted, tagged with the
+/// directions cpal lists it under.
+fn device_entries(outs: Vec<String>, ins: Vec<String>) -> Vec<String> {
+    let mut dirs = std::collections::BTreeMap::<String, (bool, bool)>::new();
+    for n in outs { dirs.entry(n).or_default().1 = true; }
+    for n in ins { dirs.entry(n).or_default().0 = true; }
+    dirs.into_iter().map(|(n, io)| {
+        let d = match io { (true, true) => Dirs::Duplex, (true, false) => Dirs::In, _ => Dirs::Out };
+        n + d.tag()
+    }).collect()
+}
+
+/// Split a `query_devices` entry into its cpal name and tag. Untagged names, as hosts that
+/// predate the tags pass them, come back with `None`.
+fn split_tag(name: &str) -> (&str, Option<Dirs>) {
+    Dirs::TAGS.iter().find_map(|&(d, t)| name.strip_suffix(t).map(|n| (n, Some(d)))).unwrap_or((name, None))
+}
+
+/// What cpal's `output_devices` and `input_devices` return on success.
+type DeviceIter = std::iter::Filter<cpal::Devices, fn(&cpal::Device) -> bool>;
+
+/// First device named exactly `name`, or containing it when `exact` is false.
+fn find_device(devs: Result<DeviceIter, cpal::DevicesError>, name: &str, exact: bool) -> Option<cpal::Device> {
+    devs.ok()?.find(|d| d.name().is_ok_and(|n| if exact { n == name } else { n.contains(name) }))
+}
+
+/// The output and input devices `open_device(name)` uses. A tagged entry matches its name
+/// exactly: `[duplex]` takes both directions of that device, `[out]` and `[in]` pair it with the
+/// other direction's default device. An untagged name picks the first output containing it, and
+/// the input of the same name or the default one. `None` takes both defaults.
+fn resolve_devices(host: &cpal::Host, name: Option<&str>) -> (Option<cpal::Device>, Option<cpal::Device>) {
+    let Some(name) = name else { return (host.default_output_device(), host.default_input_device()) };
+    match split_tag(name) {
+        (n, Some(Dirs::Duplex)) => (find_device(host.output_devices(), n, true), find_device(host.input_devices(), n, true)),
+        (n, Some(Dirs::Out)) => (find_device(host.output_devices(), n, true), host.default_input_device()),
+        (n, Some(Dirs::In)) => (host.default_output_device(), find_device(host.input_devices(), n, true)),
+        (n, None) => {
+            let out = find_device(host.output_devices(), n, false);
+            let same = out.as_ref().and_then(|o| o.name().ok()).and_then(|on| find_device(host.input_devices(), &on, true));
+            (out, same.or_else(|| host.default_input_device()))
         }
-        found.or_else(|| host.default_input_device())
-    } else { host.default_input_device() };
+    }
+}
 
-    match (out, inp) {
-        (Some(o), i) => { s.state.out_device = Some(o); s.state.in_device = i; 0 }
-        _ => sys::OA_ERR_DEVICE,
+/// Largest channel count and buffer range over a device's supported configs (buffer 0/0 if unknown).
+fn config_limits(configs: impl Iterator<Item = cpal::SupportedStreamConfigRange>) -> (u32, u32, u32) {
+    let (mut ch, mut min, mut max) = (0u32, u32::MAX, 0u32);
+    for c in configs {
+        ch = ch.max(c.channels() as u32);
+        if let cpal::SupportedBufferSize::Range{ min: lo, max: hi } = *c.buffer_size() { min = min.min(lo); max = max.max(hi); }
+    }
+    if max == 0 { min = 0; }
+    (ch, min, max)
+}
+
+unsafe extern "C" fn query_device_info(selfp:*mut sys::oa_driver, name:*const c_char, out:*mut sys::oa_device_info)->i32{
+    let s = &*(selfp as *const Driver);
+    let host = s.state.cpal_host();
+    let requested = if name.is_null() || *name == 0 { None } else { Some(CStr::from_ptr(name).to_string_lossy().to_string()) };
+    log!(s, Debug, "query_device_info(name={requested:?})");
+    // Only the named device itself, not the default open_device pairs a one-way entry with.
+    let (out_dev, in_dev) = match requested.as_deref().map(split_tag) {
+        None => (s.state.out_device.clone().or_else(|| host.default_output_device()), None),
+        Some((n, Some(Dirs::In))) => (None, find_device(host.input_devices(), n, true)),
+        Some((n, tag)) => (find_device(host.output_devices(), n, tag.is_some()), None),
+    };
+    let Some(dev_name) = out_dev.as_ref().or(in_dev.as_ref()).map(|d| d.name().unwrap_or_default()) else { log!(s, Error, "no device matching {requested:?}"); return sys::OA_ERR_DEVICE; };
+    let in_dev = in_dev.or_else(|| find_device(host.input_devices(), &dev_name, true));
+    let out_limits = out_dev.and_then(|d| d.supported_output_configs().ok().map(config_limits));
+    let in_limits = in_dev.and_then(|d| d.supported_input_configs().ok().map(config_limits));
+    let (out_ch, in_ch) = (out_limits.map_or(0, |l| l.0), in_limits.map_or(0, |l| l.0));
+    let (_, min, max) = out_limits.or(in_limits).unwrap_or((0, 0, 0));
+    let mut info = sys::oa_device_info{ max_in_channels: in_ch, max_out_channels: out_ch, min_buffer_frames: min, max_buffer_frames: max, ..Default::default() };
+    info.set_strings(&dev_name, &dev_name);
+    sys::write_device_info(&info, out)
+}
+
+/// One range per F32 or F64 output config of the open device, the formats streams are built in.
+/// Inputs come from the paired input device, which the output config does not constrain.
+unsafe extern "C" fn query_config_ranges(selfp:*mut sys::oa_driver, buf:*mut sys::oa_config_range, len: usize)->i32{
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_config_ranges(len={len})");
+    let Some(dev) = s.state.out_device.as_ref() else { log!(s, Error, "query_config_ranges without an open device"); return sys::OA_ERR_STATE; };
+    let configs = match dev.supported_output_configs() {
+        Ok(it) => it,
+        Err(e) => { log!(s, Error, "supported_output_configs failed: {e}"); return sys::OA_ERR_DEVICE; }
+    };
+    let max_in = s.state.in_device.as_ref().and_then(|d| d.supported_input_configs().ok().map(config_limits)).map_or(0, |l| l.0);
+    let ranges: Vec<sys::oa_config_range> = configs.filter_map(|c| {
+        let format = match c.sample_format() {
+            cpal::SampleFormat::F32 => sys::oa_sample_format::OA_SAMPLE_F32,
+            cpal::SampleFormat::F64 => sys::oa_sample_format::OA_SAMPLE_F64,
+            _ => return None,
+        };
+        let (min_buf, max_buf) = match *c.buffer_size() { cpal::SupportedBufferSize::Range{ min, max } => (min, max), cpal::SupportedBufferSize::Unknown => (0, 0) };
+        Some(sys::oa_config_range{
+            min_rate: c.min_sample_rate().0, max_rate: c.max_sample_rate().0,
+            min_channels_in: 0, max_channels_in: max_in,
+            min_channels_out: c.channels() as u32, max_channels_out: c.channels() as u32,
+            formats_mask: sys::oa_format_bit(format),
+            min_buffer_frames: min_buf, max_buffer_frames: max_buf,
+        })
+    }).collect();
+    sys::write_config_ranges(&ranges, buf, len)
+}
+
+unsafe extern "C" fn open_device(selfp:*mut sys::oa_driver, name:*const c_char)->i32{
+    let s = &mut *(selfp as *mut Driver);
+    s.state.last_error.clear();
+    if s.state.phase != sys::DriverPhase::Created { let msg = format!("open_device called while {:?}", s.state.phase); return s.fail(sys::OA_ERR_STATE, msg); }
+    let host = s.state.cpal_host();
+    let requested = if name.is_null(){ None } else { Some(CStr::from_ptr(name).to_string_lossy().to_string()) };
+    log!(s, Debug, "open_device(name={requested:?})");
+
+    let requested_dirs = requested.as_deref().and_then(|n| split_tag(n).1);
+    match resolve_devices(&host, requested.as_deref()) {
+        // The output stream drives process, so an input-only entry still needs the default output.
+        (None, _) => s.fail(sys::OA_ERR_DEVICE, format!("no output device for {requested:?}")),
+        (Some(_), None) if matches!(requested_dirs, Some(Dirs::In | Dirs::Duplex)) => s.fail(sys::OA_ERR_DEVICE, format!("no input device for {requested:?}")),
+        (Some(o), i) => { s.state.out_device = Some(o); s.state.in_device = i; s.state.phase = sys::DriverPhase::Opened; sys::OA_OK }
     }
 }
 
 unsafe extern "C" fn close_device(selfp:*mut sys::oa_driver)->i32{
     let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "close_device");
+    if s.state.phase == sys::DriverPhase::Running { log!(s, Error, "close_device called while {:?}", s.state.phase); return sys::OA_ERR_STATE; }
     s.state.out_stream=None; s.state.in_stream=None;
     s.state.out_device=None; s.state.in_device=None;
+    s.state.phase = sys::DriverPhase::Created;
     sys::OA_OK
 }
 
 unsafe extern "C" fn get_default_config(selfp:*mut sys::oa_driver, out:*mut sys::oa_stream_config)->i32{
     let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "get_default_config");
     let dev = match &s.state.out_device{ Some(d)=>d, None=>return sys::OA_ERR_DEVICE };
     if let Ok(c)=dev.default_output_config(){
         (*out).sample_rate = c.sample_rate().0;
@@ -109,185 +271,527 @@ unsafe extern "C" fn get_default_config(selfp:*mut sys::oa_driver, out:*mut sys:
 }
 
 unsafe extern "C" fn start(selfp:*mut sys::oa_driver, cfg:*const sys::oa_stream_config)->i32{
+    if cfg.is_null(){ return sys::OA_ERR_INVALID_ARG; }
     let s = &mut *(selfp as *mut Driver);
-    let out_dev = match &s.state.out_device{ Some(d)=>d.clone(), None=>return sys::OA_ERR_DEVICE };
+    s.state.last_error.clear();
+    let cfg = &sys::read_stream_config(cfg, s.state.cfg_size);
+    log!(s, Debug, "start(sample_rate={}, buffer_frames={}, in={}, out={}, format={}, layout={})",
+        cfg.sample_rate, cfg.buffer_frames, cfg.in_channels, cfg.out_channels, cfg.format, cfg.layout);
+    if sys::oa_stream_config_check(cfg) != sys::OA_OK { return s.fail(sys::OA_ERR_INVALID_ARG, "rejecting invalid stream config".into()); }
+    if sys::validate_channel_counts(cfg, 1) != sys::OA_OK { return s.fail(sys::OA_ERR_INVALID_ARG, format!("rejecting channel counts in={} out={}", cfg.in_channels, cfg.out_channels)); }
+    let wide = match cfg.format {
+        sys::oa_sample_format::OA_SAMPLE_F32 => false,
+        sys::oa_sample_format::OA_SAMPLE_F64 => true,
+        other => return s.fail(sys::OA_ERR_UNSUPPORTED, format!("unsupported sample format {other}")),
+    };
+    // cpal opens every backend, WASAPI included, in shared mode only.
+    if cfg.exclusive != sys::OA_FALSE { return s.fail(sys::OA_ERR_UNSUPPORTED, format!("{}: cpal opens devices shared", sys::OA_EXCLUSIVE_UNAVAILABLE)); }
+    if !s.state.phase.can_start() { let msg = format!("start called while {:?}", s.state.phase); return s.fail(s.state.phase.start_error(), msg); }
+    let sink = s.state.log;
+    let out_dev = match &s.state.out_device{ Some(d)=>d.clone(), None=>return s.fail(sys::OA_ERR_NOT_OPEN, "start without an open device".into()) };
     let in_dev = s.state.in_device.clone();
+    // Only some backends stream f64; elsewhere there is no device format to carry it.
+    if wide && !has_f64_output(&out_dev, cfg) {
+        return s.fail(sys::OA_ERR_UNSUPPORTED, format!("no f64 output at {} Hz, {} channels on this device", cfg.sample_rate, cfg.out_channels));
+    }
 
     s.state.cfg = *cfg;
-    s.state.in_buf.resize(((*cfg).buffer_frames as usize) * ((*cfg).in_channels as usize).max(1), 0.0);
+    s.state.in_buf.resize((cfg.buffer_frames as usize) * (cfg.in_channels as usize).max(1), 0.0);
+    s.state.in_ring = Ring::new(RING_PERIODS * cfg.buffer_frames as usize * cfg.in_channels as usize);
     s.state.in_seq.store(0, std::sync::atomic::Ordering::Relaxed);
+    s.state.in_overflows.store(0, Ordering::Relaxed);
+    s.state.f64_block = wide.then(|| sys::F64Block::new(cfg));
+    size_planar_buffers(&mut s.state, cfg);
+    s.state.frames_processed.store(0, Ordering::Relaxed);
+    s.state.paused.store(false, Ordering::Relaxed);
 
-    // Build input stream if available
-    if let (Some(id), in_ch) = (in_dev, (*cfg).in_channels) {
+    // Build input stream if available. Streams stay local until both play, so a failure drops,
+    // and with that stops, whatever was already built.
+    let mut istream = None;
+    if let (Some(id), in_ch) = (in_dev, cfg.in_channels) {
         if in_ch > 0 {
-            if let Ok(dc)=id.default_input_config(){
-                let mut sc: cpal::StreamConfig = dc.into();
-                sc.channels = in_ch;
-                sc.sample_rate = cpal::SampleRate((*cfg).sample_rate);
-                sc.buffer_size = cpal::BufferSize::Default;
-                let state_ptr = DriverPtr(selfp as *mut Driver);
-                let istream = id.build_input_stream(&sc,
-                    {
-                        let state_ptr = state_ptr;
-                        move |data:&[f32], _| unsafe {
-                            state_ptr.with(|st| {
-                                // store latest
-                                let frames = data.len() / (st.state.cfg.in_channels as usize).max(1);
-                                let len = frames * (st.state.cfg.in_channels as usize).max(1);
-                                if st.state.in_buf.len() < len { st.state.in_buf.resize(len, 0.0); }
-                                st.state.in_buf[..len].copy_from_slice(&data[..len]);
-                                st.state.in_seq.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            });
-                        }
-                    },
-                    move |err| { eprintln!("openasio-cpal input error: {err}"); },
-                    None
-                ).expect("build_input_stream");
-                istream.play().expect("input play");
-                s.state.in_stream = Some(istream);
-            }
+            let dc = match id.default_input_config() {
+                Ok(dc) => dc,
+                Err(e) => return s.fail(sys::OA_ERR_DEVICE, format!("no default input config: {e}")),
+            };
+            let mut sc: cpal::StreamConfig = dc.into();
+            sc.channels = in_ch;
+            sc.sample_rate = cpal::SampleRate(cfg.sample_rate);
+            sc.buffer_size = cpal::BufferSize::Default;
+            let state_ptr = DriverPtr(selfp as *mut Driver);
+            let built = id.build_input_stream(&sc,
+                move |data: &[f32], _| unsafe { state_ptr.with(|st| capture(st, data)) },
+                move |err| { sink.emit(log::Level::Error, format_args!("input stream error: {err}")); },
+                None
+            );
+            let stream = match built {
+                Ok(stream) => stream,
+                Err(e) => return s.fail(build_error_code(&e), format!("building the input stream failed: {e}")),
+            };
+            if let Err(e) = stream.play() { return s.fail(play_error_code(&e), format!("starting the input stream failed: {e}")); }
+            istream = Some(stream);
         }
     }
 
     // Output stream drives the host.process
-    let mut out_cfg = out_dev.default_output_config().expect("default output config");
+    let out_cfg = match out_dev.default_output_config() {
+        Ok(c) => c,
+        Err(e) => return s.fail(sys::OA_ERR_DEVICE, format!("no default output config: {e}")),
+    };
     let mut sc: cpal::StreamConfig = out_cfg.clone().into();
-    sc.channels = (*cfg).out_channels;
-    sc.sample_rate = cpal::SampleRate((*cfg).sample_rate);
+    sc.channels = cfg.out_channels;
+    sc.sample_rate = cpal::SampleRate(cfg.sample_rate);
     sc.buffer_size = cpal::BufferSize::Default;
     let state_ptr = DriverPtr(selfp as *mut Driver);
 
-    let ostream = out_dev.build_output_stream(&sc,
-        {
-            let state_ptr = state_ptr;
-            move |data:&mut [f32], _| unsafe {
-                state_ptr.with(|st| {
-                    let out_ch = (st.state.cfg.out_channels as usize).max(1);
-                    let frames = (data.len() / out_ch) as u32;
-
-                    // Prepare input pointers based on layout
-                    let mut in_planes: Vec<*const f32> = Vec::new();
-                    let in_ptr: *const c_void = if matches!(st.state.cfg.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED) {
-                        if st.state.cfg.in_channels > 0 {
-                            st.state.in_buf.as_ptr() as *const c_void
-                        } else {
-                            std::ptr::null()
-                        }
-                    } else if st.state.cfg.in_channels == 0 {
-                        std::ptr::null()
-                    } else {
-                        let ch = st.state.cfg.in_channels as usize;
-                        in_planes.resize(ch, std::ptr::null());
-                        for c in 0..ch {
-                            // deinterleave view: plane c points to first sample of that channel
-                            // We'll assume host reads strided by ch; for strict non-interleaved we'd keep true planes.
-                            in_planes[c] = unsafe { st.state.in_buf.as_ptr().add(c) };
-                        }
-                        in_planes.as_ptr() as *const c_void
-                    };
-
-                    if matches!(st.state.cfg.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED) {
-                        if let Some(cb) = st.state.host.process {
-                            let ti = sys::oa_time_info {
-                                host_time_ns: st.state.time0.elapsed().as_nanos() as u64,
-                                device_time_ns: 0,
-                                underruns: st.state.underruns.load(Ordering::Relaxed),
-                                overruns: st.state.overruns.load(Ordering::Relaxed),
-                            };
-                            let _keep = cb(
-                                st.state.host_user,
-                                in_ptr,
-                                data.as_mut_ptr() as *mut c_void,
-                                frames,
-                                &ti as *const _,
-                                &st.state.cfg as *const _,
-                            );
-                        }
-                    } else {
-                        // Non-interleaved: provide channel planes pointing into a staging area.
-                        // For simplicity, we reuse a scratch buffer then interleave after callback.
-                        static mut SCRATCH: Vec<f32> = Vec::new();
-                        let ch = st.state.cfg.out_channels as usize;
-                        let frames_usize = frames as usize;
-                        let needed = frames_usize * ch;
-                        unsafe {
-                            if SCRATCH.len() < needed {
-                                SCRATCH.resize(needed, 0.0);
-                            }
-                            let mut planes: Vec<*mut f32> = Vec::with_capacity(ch);
-                            for c in 0..ch {
-                                planes.push(SCRATCH.as_mut_ptr().add(c * frames_usize));
-                            }
-                            if let Some(cb) = st.state.host.process {
-                                let ti = sys::oa_time_info {
-                                    host_time_ns: st.state.time0.elapsed().as_nanos() as u64,
-                                    device_time_ns: 0,
-                                    underruns: st.state.underruns.load(Ordering::Relaxed),
-                                    overruns: st.state.overruns.load(Ordering::Relaxed),
-                                };
-                                let _keep = cb(
-                                    st.state.host_user,
-                                    in_ptr,
-                                    planes.as_mut_ptr() as *mut c_void,
-                                    frames,
-                                    &ti as *const _,
-                                    &st.state.cfg as *const _,
-                                );
-                            }
-                            for f in 0..frames_usize {
-                                for c in 0..ch {
-                                    data[f * ch + c] = *SCRATCH.as_ptr().add(c * frames_usize + f);
-                                }
-                            }
-                        }
-                    }
-                });
-            }
-        },
-        move |err| { eprintln!("openasio-cpal output error: {err}"); }, None
-    ).expect("build_output_stream");
-    ostream.play().expect("output play");
+    let ostream = if wide {
+        out_dev.build_output_stream(&sc,
+            move |data: &mut [f64], _| unsafe { state_ptr.with(|st| render_f64(st, data)) },
+            move |err| { sink.emit(log::Level::Error, format_args!("output stream error: {err}")); }, None)
+    } else {
+        out_dev.build_output_stream(&sc,
+            move |data: &mut [f32], _| unsafe { state_ptr.with(|st| render_f32(st, data)) },
+            move |err| { sink.emit(log::Level::Error, format_args!("output stream error: {err}")); }, None)
+    };
+    let ostream = match ostream {
+        Ok(stream) => stream,
+        Err(e) => return s.fail(build_error_code(&e), format!("building the output stream failed: {e}")),
+    };
+    if let Err(e) = ostream.play() { return s.fail(play_error_code(&e), format!("starting the output stream failed: {e}")); }
+    s.state.in_stream = istream;
     s.state.out_stream = Some(ostream);
+    s.state.phase = sys::DriverPhase::Running;
     sys::OA_OK
 }
 
+/// `OA_ERR_*` for a stream cpal could not build: the device is gone, or it does not take the
+/// config, or the backend failed.
+fn build_error_code(e: &cpal::BuildStreamError) -> i32 {
+    match e {
+        cpal::BuildStreamError::DeviceNotAvailable => sys::OA_ERR_DEVICE,
+        cpal::BuildStreamError::StreamConfigNotSupported | cpal::BuildStreamError::InvalidArgument => sys::OA_ERR_UNSUPPORTED,
+        _ => sys::OA_ERR_BACKEND,
+    }
+}
+
+/// `OA_ERR_*` for a stream cpal built but could not start.
+fn play_error_code(e: &cpal::PlayStreamError) -> i32 {
+    match e {
+        cpal::PlayStreamError::DeviceNotAvailable => sys::OA_ERR_DEVICE,
+        _ => sys::OA_ERR_BACKEND,
+    }
+}
+
+/// The input callback: queue `data` for the output callback, noting an overflow when the ring
+/// has no room for all of it. Xruns are reported from the output callback, the thread `process`
+/// and the host's `xrun` callback run on.
+unsafe fn capture(st: &mut Driver, data: &[f32]) {
+    // A paused stream does not drain the ring, so it would only overflow.
+    if st.state.paused.load(Ordering::Acquire) { return; }
+    let ch = (st.state.cfg.in_channels as usize).max(1);
+    let whole = data.len() / ch * ch;
+    if st.state.in_ring.push(&data[..whole]) > 0 { st.state.in_overflows.fetch_add(1, Ordering::Relaxed); }
+    st.state.in_seq.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Take the next `frames` frames of input off the ring into `in_buf` and apply the input gains.
+/// A ring that runs short is padded with silence and counts an underrun, once input has
+/// started; one left holding more than `RING_PERIODS / 2` periods loses its oldest frames and
+/// counts an overrun, as does each overflow `capture` noted since the last block.
+unsafe fn take_input(st: &mut Driver, frames: usize) {
+    for _ in 0..st.state.in_overflows.swap(0, Ordering::Relaxed) {
+        sys::report_xrun(&st.state.host, st.state.host_user, &st.state.overruns, sys::oa_xrun_kind::OA_XRUN_OVERRUN);
+    }
+    let ch = st.state.cfg.in_channels as usize;
+    if ch == 0 { return; }
+    let n = frames * ch;
+    if st.state.in_buf.len() < n { st.state.in_buf.resize(n, 0.0); }
+    let slack = RING_PERIODS / 2 * st.state.cfg.buffer_frames as usize * ch;
+    let (padded, dropped) = st.state.in_ring.pop(&mut st.state.in_buf[..n], slack);
+    if padded > 0 && st.state.in_seq.load(Ordering::Relaxed) > 0 {
+        sys::report_xrun(&st.state.host, st.state.host_user, &st.state.underruns, sys::oa_xrun_kind::OA_XRUN_UNDERRUN);
+    }
+    if dropped > 0 {
+        sys::report_xrun(&st.state.host, st.state.host_user, &st.state.overruns, sys::oa_xrun_kind::OA_XRUN_OVERRUN);
+    }
+    st.state.in_gains.apply_interleaved(&mut st.state.in_buf[..n], ch);
+}
+
+/// Size the planar staging for `cfg`, so the output callback runs without allocating.
+fn size_planar_buffers(state: &mut DriverState, cfg: &sys::oa_stream_config) {
+    let (in_ch, out_ch) = (cfg.in_channels as usize, cfg.out_channels as usize);
+    state.out_scratch.resize(cfg.buffer_frames as usize * out_ch, 0.0);
+    state.in_planes.clear();
+    state.in_planes.reserve(in_ch);
+    state.out_planes.clear();
+    state.out_planes.reserve(out_ch.max(1));
+}
+
+/// The output callback of an f32 stream. Planar hosts write into `out_scratch` through
+/// `out_planes`, both owned by this driver and sized by `start`, so nothing is allocated here
+/// unless cpal asks for more than a block at once.
+unsafe fn render_f32(st: &mut Driver, data: &mut [f32]) {
+    if st.state.paused.load(Ordering::Acquire) { data.fill(0.0); return; }
+    let out_ch = (st.state.cfg.out_channels as usize).max(1);
+    let frames = data.len() / out_ch;
+    let planar = !matches!(st.state.cfg.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED);
+    let in_ch = st.state.cfg.in_channels as usize;
+    take_input(st, frames);
+    let in_ptr: *const c_void = if in_ch == 0 {
+        std::ptr::null()
+    } else if planar {
+        // Plane c starts at channel c of the interleaved input; the host reads it strided by in_ch.
+        st.state.in_planes.clear();
+        st.state.in_planes.extend((0..in_ch).map(|c| st.state.in_buf.as_ptr().wrapping_add(c)));
+        st.state.in_planes.as_ptr() as *const c_void
+    } else {
+        st.state.in_buf.as_ptr() as *const c_void
+    };
+    let out_ptr: *mut c_void = if planar {
+        let needed = frames * out_ch;
+        // cpal's default buffer size may exceed the configured block; grow once and keep it.
+        if st.state.out_scratch.len() < needed { st.state.out_scratch.resize(needed, 0.0); }
+        st.state.out_scratch[..needed].fill(0.0);
+        let base = st.state.out_scratch.as_mut_ptr();
+        st.state.out_planes.clear();
+        st.state.out_planes.extend((0..out_ch).map(|c| base.wrapping_add(c * frames)));
+        st.state.out_planes.as_mut_ptr() as *mut c_void
+    } else {
+        data.as_mut_ptr() as *mut c_void
+    };
+    if let Some(cb) = st.state.host.process {
+        let ti = sys::oa_time_info {
+            host_time_ns: st.state.time0.elapsed().as_nanos() as u64,
+            device_time_ns: 0,
+            underruns: st.state.underruns.load(Ordering::Relaxed),
+            overruns: st.state.overruns.load(Ordering::Relaxed),
+            sample_position: st.state.frames_processed.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        let _keep = cb(st.state.host_user, in_ptr, out_ptr, frames as u32, &ti, &st.state.cfg);
+    }
+    if planar { sys::layout::interleave(&st.state.out_scratch, frames, out_ch, &mut data[..frames * out_ch]); }
+    st.state.out_gains.apply_interleaved(data, out_ch);
+    st.state.frames_processed.fetch_add(frames as u64, Ordering::Relaxed);
+}
+
+/// Whether `dev` lists an f64 output config that `cfg`'s rate and output channels fit.
+fn has_f64_output(dev: &cpal::Device, cfg: &sys::oa_stream_config) -> bool {
+    dev.supported_output_configs().is_ok_and(|mut configs| configs.any(|c| {
+        c.sample_format() == cpal::SampleFormat::F64 && c.channels() == cfg.out_channels
+            && (c.min_sample_rate().0..=c.max_sample_rate().0).contains(&cfg.sample_rate)
+    }))
+}
+
+/// The output callback of an f64 stream. cpal may ask for more than a block, so the host is
+/// called once per block-sized slice of `data`, through the preallocated `F64Block`.
+unsafe fn render_f64(st: &mut Driver, data: &mut [f64]) {
+    if st.state.paused.load(Ordering::Acquire) { data.fill(0.0); return; }
+    if st.state.f64_block.is_none() { data.fill(0.0); return; }
+    let (in_ch, out_ch) = (st.state.cfg.in_channels as usize, (st.state.cfg.out_channels as usize).max(1));
+    let block_frames = (st.state.cfg.buffer_frames as usize).max(1);
+    for chunk in data.chunks_mut(block_frames * out_ch) {
+        let frames = chunk.len() / out_ch;
+        take_input(st, frames);
+        let Some(block) = st.state.f64_block.as_mut() else { return; };
+        block.load_input(&st.state.in_buf[..frames * in_ch], in_ch);
+        block.silence_output();
+        let (in_ptr, out_ptr) = block.host_ptrs();
+        if let Some(cb) = st.state.host.process {
+            let ti = sys::oa_time_info {
+                host_time_ns: st.state.time0.elapsed().as_nanos() as u64,
+                underruns: st.state.underruns.load(Ordering::Relaxed),
+                overruns: st.state.overruns.load(Ordering::Relaxed),
+                sample_position: st.state.frames_processed.load(Ordering::Relaxed),
+                ..Default::default()
+            };
+            let _keep = cb(st.state.host_user, in_ptr, out_ptr, frames as u32, &ti, &st.state.cfg);
+        }
+        block.store_output(chunk, out_ch);
+        st.state.out_gains.apply_interleaved(chunk, out_ch);
+        st.state.frames_processed.fetch_add(frames as u64, Ordering::Relaxed);
+    }
+}
+
 unsafe extern "C" fn stop(selfp:*mut sys::oa_driver)->i32{
     let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "stop");
+    if s.state.phase != sys::DriverPhase::Running { log!(s, Error, "stop called while {:?}", s.state.phase); return sys::OA_ERR_STATE; }
     s.state.out_stream=None; s.state.in_stream=None;
+    s.state.phase = sys::DriverPhase::Configured;
     sys::OA_OK
 }
 
-unsafe extern "C" fn get_latency(_:*mut sys::oa_driver, in_lat:*mut u32, out_lat:*mut u32)->i32{
+unsafe extern "C" fn get_status(selfp:*mut sys::oa_driver, out:*mut sys::oa_stream_status)->i32{
+    let s = &*(selfp as *const Driver);
+    let in_ch = (s.state.cfg.in_channels as usize).max(1);
+    let status = sys::oa_stream_status {
+        running: (s.state.phase == sys::DriverPhase::Running) as sys::oa_bool,
+        actual_sample_rate: s.state.cfg.sample_rate, actual_buffer_frames: s.state.cfg.buffer_frames,
+        frames_processed: s.state.frames_processed.load(Ordering::Relaxed),
+        underruns: s.state.underruns.load(Ordering::Relaxed), overruns: s.state.overruns.load(Ordering::Relaxed),
+        input_queued_frames: (s.state.in_ring.len() / in_ch) as u32,
+        ..Default::default()
+    };
+    sys::write_stream_status(&status, out)
+}
+
+unsafe extern "C" fn pause(selfp:*mut sys::oa_driver)->i32{
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "pause");
+    if s.state.phase != sys::DriverPhase::Running || s.state.paused.load(Ordering::Relaxed) { log!(s, Error, "pause called while {:?}", s.state.phase); return sys::OA_ERR_STATE; }
+    // The flag covers backends that cannot pause a stream: their callback keeps running and plays silence.
+    s.state.paused.store(true, Ordering::Release);
+    for stream in s.state.out_stream.iter().chain(s.state.in_stream.iter()) {
+        if let Err(e) = stream.pause() { log!(s, Debug, "stream cannot pause ({e}); playing silence instead"); }
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn resume(selfp:*mut sys::oa_driver)->i32{
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "resume");
+    if s.state.phase != sys::DriverPhase::Running || !s.state.paused.load(Ordering::Relaxed) { log!(s, Error, "resume called while not paused"); return sys::OA_ERR_STATE; }
+    for stream in s.state.in_stream.iter().chain(s.state.out_stream.iter()) {
+        if let Err(e) = stream.play() { log!(s, Error, "resuming stream failed: {e}"); return sys::OA_ERR_BACKEND; }
+    }
+    s.state.paused.store(false, Ordering::Release);
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_last_error(selfp:*mut sys::oa_driver, buf:*mut c_char, len: usize)->i32{
+    let s = &*(selfp as *const Driver);
+    s.state.last_error.write(buf, len)
+}
+
+unsafe extern "C" fn get_latency(selfp:*mut sys::oa_driver, in_lat:*mut u32, out_lat:*mut u32)->i32{
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_latency");
     if !in_lat.is_null(){ *in_lat = 0; } // CPAL doesn't expose stable latency here
     if !out_lat.is_null(){ *out_lat = 0; }
     sys::OA_OK
 }
-unsafe extern "C" fn set_sr(_: *mut sys::oa_driver, _:u32)->i32{ sys::OA_ERR_UNSUPPORTED }
-unsafe extern "C" fn set_buf(_: *mut sys::oa_driver, _:u32)->i32{ sys::OA_ERR_UNSUPPORTED }
+unsafe extern "C" fn set_sr(selfp: *mut sys::oa_driver, sr:u32)->i32{ log!(&*(selfp as *const Driver), Debug, "set_sample_rate({sr})"); sys::OA_ERR_UNSUPPORTED }
+unsafe extern "C" fn set_buf(selfp: *mut sys::oa_driver, frames:u32)->i32{ log!(&*(selfp as *const Driver), Debug, "set_buffer_frames({frames})"); sys::OA_ERR_UNSUPPORTED }
+
+unsafe extern "C" fn get_channel_name(selfp:*mut sys::oa_driver, direction: sys::oa_direction, index: u32, buf:*mut c_char, len: usize)->i32{
+    let s = &*(selfp as *const Driver);
+    sys::default_channel_name_for(&s.state.cfg, direction, index, buf, len)
+}
 
-#[no_mangle]
+unsafe extern "C" fn set_channel_gain(selfp:*mut sys::oa_driver, direction: u32, channel: u32, gain: f32)->i32{
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "set_channel_gain(direction={direction}, channel={channel}, gain={gain})");
+    sys::set_channel_gain(&s.state.in_gains, &s.state.out_gains, direction, channel, gain)
+}
+
+static VTABLE: sys::oa_driver_vtable = sys::oa_driver_vtable {
+    get_caps: Some(get_caps),
+    query_devices: Some(query_devices),
+    open_device: Some(open_device),
+    close_device: Some(close_device),
+    get_default_config: Some(get_default_config),
+    start: Some(start), stop: Some(stop),
+    get_latency: Some(get_latency), set_sample_rate: Some(set_sr), set_buffer_frames: Some(set_buf),
+    query_device_info: Some(query_device_info),
+    query_config_ranges: Some(query_config_ranges),
+    query_clock_sources: Some(sys::internal_clock_sources),
+    set_clock_source: Some(sys::internal_set_clock_source),
+    get_channel_name: Some(get_channel_name),
+    set_channel_gain: Some(set_channel_gain),
+    pause: Some(pause), resume: Some(resume),
+    get_status: Some(get_status),
+    get_last_error: Some(get_last_error),
+    ..sys::oa_driver_vtable::empty()
+};
+
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
 pub unsafe extern "C" fn openasio_driver_create(params:*const sys::oa_create_params, out:*mut *mut sys::oa_driver)->i32{
     if params.is_null()||out.is_null(){ return sys::OA_ERR_INVALID_ARG; }
     let p=&*params;
-    let drv = Box::new(Driver{
-        vt: sys::oa_driver_vtable{
-            struct_size: std::mem::size_of::<sys::oa_driver_vtable>() as u32,
-            get_caps: Some(get_caps),
-            query_devices: Some(query_devices),
-            open_device: Some(open_device),
-            close_device: Some(close_device),
-            get_default_config: Some(get_default_config),
-            start: Some(start), stop: Some(stop),
-            get_latency: Some(get_latency), set_sample_rate: Some(set_sr), set_buffer_frames: Some(set_buf),
+    let rc = sys::create_params_validate(p); if rc != sys::OA_OK { return rc; }
+    let sink = sys::LogSink::from_params(p);
+    let options = match sys::CreateOptions::from_params(p) {
+        Ok(options) => options,
+        Err(msg) => { sink.emit(log::Level::Error, format_args!("bad create options: {msg}")); return sys::OA_ERR_INVALID_ARG; }
+    };
+    // `host` picks the cpal backend by name (jack, alsa); other keys are for other drivers.
+    for (key, _) in options.iter().filter(|&(key, _)| key != "host") { sink.emit(log::Level::Debug, format_args!("ignoring create option {key:?}")); }
+    let backend = match options.get("host") {
+        None => None,
+        Some(name) => match cpal::available_hosts().into_iter().find(|id| id.name().eq_ignore_ascii_case(name)) {
+            Some(id) => Some(id),
+            None => { sink.emit(log::Level::Error, format_args!("no cpal host {name:?} available")); return sys::OA_ERR_UNSUPPORTED; }
         },
+    };
+    let drv = Box::new(Driver{
+        vt: &VTABLE,
         state: DriverState{
-            host: *p.host, host_user: p.host_user,
+            host: sys::host_callbacks(p), host_user: p.host_user, log: sink, phase: sys::DriverPhase::Created,
+            last_error: sys::LastError::default(),
+            cfg_size: sys::stream_config_size(p),
+            backend,
             out_device: None, in_device: None, out_stream: None, in_stream: None,
-            cfg: sys::oa_stream_config{ sample_rate:48000, buffer_frames:256, in_channels:0, out_channels:2, format: sys::oa_sample_format::OA_SAMPLE_F32, layout: sys::oa_buffer_layout::OA_BUF_INTERLEAVED },
-            time0: Instant::now(), underruns: AtomicU32::new(0), overruns: AtomicU32::new(0),
-            in_buf: Vec::new(), in_seq: AtomicUsize::new(0),
+            cfg: sys::oa_stream_config{ in_channels:0, ..Default::default() },
+            time0: Instant::now(), underruns: AtomicU32::new(0), overruns: AtomicU32::new(0), frames_processed: AtomicU64::new(0),
+            paused: AtomicBool::new(false),
+            in_ring: Ring::new(0), in_buf: Vec::new(), in_seq: AtomicUsize::new(0), in_overflows: AtomicU32::new(0), in_planes: Vec::new(), f64_block: None,
+            out_scratch: Vec::new(), out_planes: Vec::new(),
+            in_gains: sys::ChannelGains::default(), out_gains: sys::ChannelGains::default(),
         },
     });
     *out = Box::into_raw(drv) as *mut sys::oa_driver; sys::OA_OK
 }
-#[no_mangle] pub unsafe extern "C" fn openasio_driver_destroy(driver:*mut sys::oa_driver){ if !driver.is_null(){ let _ = Box::from_raw(driver as *mut Driver); } }
+#[cfg_attr(not(feature = "static-link"), no_mangle)] pub unsafe extern "C" fn openasio_driver_destroy(driver:*mut sys::oa_driver){ if !driver.is_null(){ let _ = Box::from_raw(driver as *mut Driver); } }
+#[cfg_attr(not(feature = "static-link"), no_mangle)] pub unsafe extern "C" fn openasio_driver_get_info(out:*mut sys::oa_driver_info)->i32{ sys::write_driver_info(&sys::oa_driver_info::new("CPAL (ALSA/JACK)", "OpenASIO project", env!("CARGO_PKG_VERSION")), out) }
+#[cfg(not(feature = "static-link"))] sys::oa_export_abi_version!();
+
+/// The CPAL driver, for hosts that link it in rather than load it; see `sys::DriverFactory`.
+#[cfg(feature = "static-link")] pub struct Cpal;
+#[cfg(feature = "static-link")]
+impl sys::DriverFactory for Cpal {
+    unsafe extern "C" fn create(params:*const sys::oa_create_params, out:*mut *mut sys::oa_driver)->i32{ openasio_driver_create(params, out) }
+    unsafe extern "C" fn destroy(driver:*mut sys::oa_driver){ openasio_driver_destroy(driver) }
+    unsafe extern "C" fn get_info(out:*mut sys::oa_driver_info)->i32{ openasio_driver_get_info(out) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills output plane c with `*user + c`.
+    unsafe extern "C" fn fill_planes(user:*mut c_void, _in_ptr:*const c_void, out_ptr:*mut c_void, frames:u32, _time:*const sys::oa_time_info, cfg:*const sys::oa_stream_config)->sys::oa_bool{
+        let base = *(user as *const f32);
+        let planes = std::slice::from_raw_parts(out_ptr as *const *mut f32, (*cfg).out_channels as usize);
+        for (c, &plane) in planes.iter().enumerate() { std::slice::from_raw_parts_mut(plane, frames as usize).fill(base + c as f32); }
+        // Give the other stream a chance to run in between.
+        std::thread::yield_now();
+        sys::OA_TRUE
+    }
+
+    #[test]
+    fn concurrent_planar_streams_keep_their_own_output() {
+        let host = sys::oa_host_callbacks { process: Some(fill_planes), ..sys::oa_host_callbacks::empty() };
+        std::thread::scope(|scope| {
+            for base in [1.0f32, 10.0] {
+                let host = &host;
+                scope.spawn(move || unsafe {
+                    let params = sys::oa_create_params::new(host, &base as *const f32 as *mut c_void);
+                    let mut drv = std::ptr::null_mut();
+                    assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+                    // What start sets up, without a cpal device behind it.
+                    let st = &mut *(drv as *mut Driver);
+                    let cfg = sys::oa_stream_config { in_channels: 0, out_channels: 2, buffer_frames: 64, layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED, ..Default::default() };
+                    st.state.cfg = cfg;
+                    size_planar_buffers(&mut st.state, &cfg);
+                    let mut data = vec![0.0f32; 64 * 2];
+                    for _ in 0..1000 {
+                        render_f32(st, &mut data);
+                        assert!(data.chunks_exact(2).all(|f| f == [base, base + 1.0]), "stream {base} got {:?}", &data[..4]);
+                    }
+                    openasio_driver_destroy(drv);
+                });
+            }
+        });
+    }
+
+    /// Appends the block's mono input to the `Vec<f32>` behind `user`.
+    unsafe extern "C" fn record_input(user:*mut c_void, in_ptr:*const c_void, _out_ptr:*mut c_void, frames:u32, _time:*const sys::oa_time_info, _cfg:*const sys::oa_stream_config)->sys::oa_bool{
+        (*(user as *mut Vec<f32>)).extend_from_slice(std::slice::from_raw_parts(in_ptr as *const f32, frames as usize));
+        sys::OA_TRUE
+    }
+
+    #[test]
+    fn input_ring_keeps_samples_in_order_across_uneven_callbacks() {
+        let mut seen: Vec<f32> = Vec::new();
+        let host = sys::oa_host_callbacks { process: Some(record_input), ..sys::oa_host_callbacks::empty() };
+        let params = sys::oa_create_params::new(&host, &mut seen as *mut Vec<f32> as *mut c_void);
+        let mut drv = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            // What start sets up, with the two cpal callbacks played by hand: capture arrives in
+            // blocks of 16 to 80 frames, the output callback takes 64 at a time.
+            let st = &mut *(drv as *mut Driver);
+            let cfg = sys::oa_stream_config { in_channels: 1, out_channels: 1, buffer_frames: 64, ..Default::default() };
+            st.state.cfg = cfg;
+            st.state.in_ring = Ring::new(RING_PERIODS * 64);
+            size_planar_buffers(&mut st.state, &cfg);
+            let (mut next, mut out) = (0u32, vec![0.0f32; 64]);
+            for i in 0..2000usize {
+                let block: Vec<f32> = (0..[16, 48, 64, 80][i % 4]).map(|_| { next += 1; next as f32 }).collect();
+                capture(st, &block);
+                while st.state.in_ring.len() >= 64 { render_f32(st, &mut out); }
+            }
+            assert_eq!((st.state.underruns.load(Ordering::Relaxed), st.state.overruns.load(Ordering::Relaxed)), (0, 0));
+            assert!(seen.len() > 50_000);
+            assert!(seen.iter().enumerate().all(|(i, &s)| s == (i + 1) as f32), "a sample was dropped or repeated");
+
+            let mut status = sys::oa_stream_status::default();
+            assert_eq!(get_status(drv, &mut status), sys::OA_OK);
+            assert_eq!(status.input_queued_frames as usize, st.state.in_ring.len());
+            // Starved, the block is padded; flooded, the oldest input goes.
+            while st.state.in_ring.len() >= 64 { render_f32(st, &mut out); }
+            render_f32(st, &mut out);
+            assert_eq!(st.state.underruns.load(Ordering::Relaxed), 1);
+            capture(st, &vec![1.0; 64 * RING_PERIODS]);
+            render_f32(st, &mut out);
+            assert_eq!(st.state.overruns.load(Ordering::Relaxed), 1);
+            assert_eq!(st.state.in_ring.len(), 64 * RING_PERIODS / 2);
+            // An overflowing push is only counted once the output side runs.
+            capture(st, &vec![1.0; 64 * RING_PERIODS]);
+            assert_eq!(st.state.overruns.load(Ordering::Relaxed), 1);
+            render_f32(st, &mut out);
+            assert_eq!(st.state.overruns.load(Ordering::Relaxed), 3, "the overflow, then the oldest input dropped");
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    unsafe extern "C" fn silence(_user:*mut c_void, _in_ptr:*const c_void, _out_ptr:*mut c_void, _frames:u32, _time:*const sys::oa_time_info, _cfg:*const sys::oa_stream_config)->sys::oa_bool{ sys::OA_TRUE }
+
+    #[test]
+    fn absurd_rates_fail_start_without_panicking() {
+        let host = sys::oa_host_callbacks { process: Some(silence), ..sys::oa_host_callbacks::empty() };
+        let params = sys::oa_create_params::new(&host, std::ptr::null_mut());
+        let mut drv = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            // Without an output device every start fails before reaching cpal; with one, cpal
+            // refuses the rate and start has to say so.
+            let opened = open_device(drv, std::ptr::null()) == sys::OA_OK;
+            for sample_rate in [7, sys::OA_MAX_SAMPLE_RATE - 1] {
+                let cfg = sys::oa_stream_config { sample_rate, in_channels: 0, ..Default::default() };
+                let rc = start(drv, &cfg);
+                assert!(rc < 0, "{sample_rate} Hz started (rc={rc}, device open: {opened})");
+                assert_eq!(stop(drv), sys::OA_ERR_STATE);
+            }
+            openasio_driver_destroy(drv);
+        }
+    }
+
+    #[test]
+    fn device_entries_list_each_name_once_with_its_directions() {
+        let names = |v: &[&str]| v.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let list = device_entries(names(&["usb", "hdmi", "usb"]), names(&["usb", "mic"]));
+        assert_eq!(list, ["hdmi [out]", "mic [in]", "usb [duplex]"]);
+        assert_eq!(split_tag("usb [duplex]"), ("usb", Some(Dirs::Duplex)));
+        assert_eq!(split_tag("mic [in]"), ("mic", Some(Dirs::In)));
+        assert_eq!(split_tag("usb"), ("usb", None), "names from before the tags");
+    }
+
+    #[test]
+    fn query_devices_tags_what_the_host_lists() {
+        let host = sys::oa_host_callbacks { process: Some(silence), ..sys::oa_host_callbacks::empty() };
+        let params = sys::oa_create_params::new(&host, std::ptr::null_mut());
+        unsafe {
+            let mut drv = std::ptr::null_mut();
+            assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+            let mut buf = vec![0 as c_char; 1 << 16];
+            assert_eq!(query_devices(drv, buf.as_mut_ptr(), buf.len()), sys::OA_OK);
+            let list = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            // Whatever the sandbox's ALSA host has: sorted, every name once and tagged.
+            let names: Vec<&str> = list.lines().map(|e| { let (n, d) = split_tag(e); assert!(d.is_some(), "{e:?} untagged"); n }).collect();
+            assert!(names.windows(2).all(|w| w[0] < w[1]), "{names:?}");
+            openasio_driver_destroy(drv);
+        }
+    }
+}
@@ -0,0 +1,679 @@
+//! OpenASIO loopback driver: routes one driver instance's output to another's input.
+//!
+//! The device name names a POSIX shared-memory ring (`default` when none is given). The first
+//! instance to open a name creates the ring and becomes the producer: whatever the host writes
+//! to its outputs is pushed into the ring and its inputs read silence. The second instance
+//! attaches as the consumer and reads the ring on its inputs; its outputs are discarded. Both
+//! run on a timer at the configured sample rate, so no audio hardware is involved, which makes
+//! the pair useful for integration tests and for piping audio between two hosts.
+//!
+//! The producer removes the name when it closes. If a producer dies without closing, the stale
+//! region turns the next instance into a consumer; remove `/dev/shm/openasio-loopback-<name>`.
+#![allow(clippy::missing_safety_doc)]
+mod shm;
+
+use openasio_sys as sys;
+use shm::{Region, Role};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+const CAPS: sys::OaCaps = sys::OaCaps::OUTPUT
+    .union(sys::OaCaps::INPUT)
+    .union(sys::OaCaps::FULL_DUPLEX)
+    .union(sys::OaCaps::SET_SAMPLERATE)
+    .union(sys::OaCaps::SET_BUFFRAMES)
+    .union(sys::OaCaps::LOOPBACK)
+    .union(sys::OaCaps::SAMPLE_POSITION);
+
+/// Ring name used when the host opens the default device.
+const DEFAULT_NAME: &str = "default";
+
+/// How long stop/close wait for the worker thread before giving up with `OA_ERR_TIMEOUT`.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Log through the host's `log_cb` when it installed one, otherwise through the `log` crate.
+macro_rules! log {
+    ($drv:expr, $lvl:ident, $($arg:tt)+) => {
+        $drv.state.log.emit(log::Level::$lvl, format_args!($($arg)+))
+    };
+}
+
+/// Host-facing buffers and the interleaved block exchanged with the ring.
+#[derive(Default)]
+struct Buffers {
+    in_buf: Vec<f32>,
+    out_buf: Vec<f32>,
+    in_planes: Vec<*const f32>,
+    out_planes: Vec<*mut f32>,
+    scratch: Vec<f32>,
+}
+
+struct DriverState {
+    host: sys::oa_host_callbacks,
+    host_user: *mut c_void,
+    log: sys::LogSink,
+    phase: sys::DriverPhase,
+    cfg_size: usize, // the host's sizeof(oa_stream_config)
+    region: Option<Region>,
+    cfg: sys::oa_stream_config,
+    bufs: Buffers,
+    time0: Instant,
+    underruns: AtomicU32,
+    overruns: AtomicU32,
+    frames_processed: AtomicU64, // since the last start
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
+    running: AtomicBool,
+    paused: AtomicBool, // the worker keeps time but skips process
+    gate: sys::StartGate,
+    worker: Option<std::thread::JoinHandle<()>>,
+}
+
+#[repr(C)]
+struct Driver {
+    vt: *const sys::oa_driver_vtable,
+    state: DriverState,
+}
+
+impl DriverState {
+    /// Signal the worker to exit and wait up to `STOP_TIMEOUT` for it. Returns false, keeping the
+    /// handle, if it is still running; the ring must not be unmapped in that case.
+    fn stop_worker(&mut self) -> bool {
+        self.running.store(false, Ordering::Release);
+        self.paused.store(false, Ordering::Relaxed);
+        let Some(handle) = self.worker.take() else {
+            return true;
+        };
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                self.worker = Some(handle);
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let _ = handle.join();
+        true
+    }
+}
+
+impl Drop for DriverState {
+    fn drop(&mut self) {
+        if !self.stop_worker() {
+            if let Some(handle) = self.worker.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+/// One period: read the ring (consumer), run the host, write the ring (producer).
+unsafe fn run_block(s: &mut Driver) -> sys::oa_bool {
+    let state = &mut s.state;
+    let Some(region) = state.region.as_ref() else {
+        return sys::OA_FALSE;
+    };
+    let cfg = state.cfg;
+    let frames = cfg.buffer_frames as usize;
+    let (ich, och) = (cfg.in_channels as usize, cfg.out_channels as usize);
+    let interleaved = cfg.layout == sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    let bufs = &mut state.bufs;
+
+    if ich > 0 {
+        let block = if interleaved {
+            &mut bufs.in_buf[..frames * ich]
+        } else {
+            &mut bufs.scratch[..frames * ich]
+        };
+        if region.role() == Role::Consumer {
+            // Keep at most one block queued so latency stays at one period.
+            let (padded, dropped) = region.pop(block, ich, frames);
+            // A wholly silent block means the producer is not running, not that it fell behind.
+            if padded > 0 && padded < frames {
+                sys::report_xrun(
+                    &state.host,
+                    state.host_user,
+                    &state.underruns,
+                    sys::oa_xrun_kind::OA_XRUN_UNDERRUN,
+                );
+            }
+            if dropped > 0 {
+                sys::report_xrun(
+                    &state.host,
+                    state.host_user,
+                    &state.overruns,
+                    sys::oa_xrun_kind::OA_XRUN_OVERRUN,
+                );
+            }
+        } else {
+            block.fill(0.0);
+        }
+        state.in_gains.apply_interleaved(block, ich);
+        if !interleaved {
+            sys::layout::deinterleave(
+                &bufs.scratch[..frames * ich],
+                ich,
+                &mut bufs.in_buf,
+                frames,
+            );
+        }
+    }
+
+    let ti = sys::oa_time_info {
+        host_time_ns: state.time0.elapsed().as_nanos() as u64,
+        device_time_ns: 0,
+        underruns: state.underruns.load(Ordering::Relaxed),
+        overruns: state.overruns.load(Ordering::Relaxed),
+        sample_position: state.frames_processed.load(Ordering::Relaxed),
+        ..Default::default()
+    };
+    let (in_ptr, out_ptr) = match (interleaved, ich > 0) {
+        (true, true) => (
+            bufs.in_buf.as_ptr() as *const c_void,
+            bufs.out_buf.as_mut_ptr() as *mut c_void,
+        ),
+        (false, true) => (
+            bufs.in_planes.as_ptr() as *const c_void,
+            bufs.out_planes.as_mut_ptr() as *mut c_void,
+        ),
+        (true, false) => (ptr::null(), bufs.out_buf.as_mut_ptr() as *mut c_void),
+        (false, false) => (ptr::null(), bufs.out_planes.as_mut_ptr() as *mut c_void),
+    };
+    let keep_going = match state.host.process {
+        Some(process) => process(
+            state.host_user,
+            in_ptr,
+            out_ptr,
+            frames as u32,
+            &ti,
+            &state.cfg,
+        ),
+        None => sys::OA_FALSE,
+    };
+
+    if och > 0 && region.role() == Role::Producer && region.has_consumer() {
+        let block = if interleaved {
+            &mut bufs.out_buf[..frames * och]
+        } else {
+            sys::layout::interleave(
+                &bufs.out_buf,
+                frames,
+                och,
+                &mut bufs.scratch[..frames * och],
+            );
+            &mut bufs.scratch[..frames * och]
+        };
+        state.out_gains.apply_interleaved(block, och);
+        if region.push(block, och) > 0 {
+            sys::report_xrun(
+                &state.host,
+                state.host_user,
+                &state.overruns,
+                sys::oa_xrun_kind::OA_XRUN_OVERRUN,
+            );
+        }
+    }
+    keep_going
+}
+
+unsafe fn driver_thread(selfp: *mut Driver) {
+    let (frames, rate) = {
+        let cfg = &(*selfp).state.cfg;
+        (cfg.buffer_frames, cfg.sample_rate)
+    };
+    let period = Duration::from_secs_f64(frames as f64 / rate as f64);
+    // No device to start: a synchronized start only has to begin the timer on time.
+    if (*selfp).state.gate.wait(&(*selfp).state.running).is_none() {
+        return;
+    }
+    let mut next = Instant::now();
+    loop {
+        let driver = &mut *selfp;
+        if !driver.state.running.load(Ordering::Acquire) {
+            break;
+        }
+        if driver.state.paused.load(Ordering::Acquire) {
+            sleep_until(&mut next, period, &driver.state.running);
+            continue;
+        }
+        let keep_going = run_block(driver);
+        driver
+            .state
+            .frames_processed
+            .fetch_add(frames.into(), Ordering::Relaxed);
+        if keep_going == sys::OA_FALSE {
+            log!(driver, Debug, "host asked to stop");
+            driver.state.running.store(false, Ordering::Release);
+            break;
+        }
+        sleep_until(&mut next, period, &driver.state.running);
+    }
+}
+
+/// Advance `next` by one period and sleep until then, or until `abort` clears `running` and
+/// unparks the worker. `running` is only checked after a wakeup: a `stop` that clears it while
+/// the block is still being produced must not skip the wait.
+fn sleep_until(next: &mut Instant, period: Duration, running: &AtomicBool) {
+    *next += period;
+    if *next <= Instant::now() {
+        // Fell behind (e.g. the host overran); resync instead of bursting to catch up.
+        *next = Instant::now();
+        return;
+    }
+    // park_timeout may wake early, so loop until the deadline.
+    loop {
+        let now = Instant::now();
+        if now >= *next {
+            break;
+        }
+        std::thread::park_timeout(*next - now);
+        if !running.load(Ordering::Acquire) {
+            break;
+        }
+    }
+}
+
+unsafe extern "C" fn get_caps(selfp: *mut sys::oa_driver) -> u32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_caps");
+    CAPS.bits()
+}
+
+unsafe extern "C" fn query_devices(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "query_devices(len={len})");
+    // Any name works; list the one used for the default device.
+    sys::write_device_list(DEFAULT_NAME, buf, len)
+}
+
+unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    if s.state.phase != sys::DriverPhase::Created {
+        log!(s, Error, "open_device called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    let name = if name.is_null() || *name == 0 {
+        DEFAULT_NAME.to_string()
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    log!(s, Debug, "open_device(name={name:?})");
+    match Region::open(&name) {
+        Ok(region) => {
+            log!(s, Info, "loopback {name:?} opened as {:?}", region.role());
+            s.state.region = Some(region);
+        }
+        Err((rc, msg)) => {
+            log!(s, Error, "{msg}");
+            return rc;
+        }
+    }
+    s.state.phase = sys::DriverPhase::Opened;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn close_device(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "close_device");
+    if s.state.phase == sys::DriverPhase::Running {
+        log!(s, Error, "close_device called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    s.state.region = None;
+    s.state.phase = sys::DriverPhase::Created;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_default_config(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_stream_config,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_default_config");
+    if out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    sys::write_stream_config(&sys::oa_stream_config::default(), out, s.state.cfg_size);
+    sys::OA_OK
+}
+
+unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_stream_config) -> i32 {
+    start_stream(selfp, cfg, false)
+}
+
+unsafe extern "C" fn arm_start(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+) -> i32 {
+    start_stream(selfp, cfg, true)
+}
+
+unsafe fn start_stream(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+    armed: bool,
+) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    if cfg.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let cfg = sys::read_stream_config(cfg, s.state.cfg_size);
+    log!(s, Debug, "start(cfg={cfg:?}, armed={armed})");
+    if !s.state.phase.can_start() {
+        log!(s, Error, "start called while {:?}", s.state.phase);
+        return s.state.phase.start_error();
+    }
+    // A consumer may be input-only: the ring is only written when there are outputs.
+    let rc = sys::oa_stream_config_check(&cfg);
+    if rc != sys::OA_OK {
+        log!(s, Error, "invalid stream config {cfg:?}");
+        return rc;
+    }
+    if cfg.format != sys::oa_sample_format::OA_SAMPLE_F32 {
+        log!(s, Error, "unsupported sample format {}", cfg.format);
+        return sys::OA_ERR_UNSUPPORTED;
+    }
+    if cfg.in_channels as usize > shm::CHANNELS
+        || cfg.out_channels as usize > shm::CHANNELS
+        || cfg.buffer_frames as usize > shm::CAPACITY / 2
+    {
+        log!(
+            s,
+            Error,
+            "loopback carries at most {} channels and {} frames per block",
+            shm::CHANNELS,
+            shm::CAPACITY / 2
+        );
+        return sys::OA_ERR_UNSUPPORTED;
+    }
+    if !s.state.stop_worker() {
+        log!(
+            s,
+            Error,
+            "previous worker did not exit within {STOP_TIMEOUT:?}"
+        );
+        return sys::OA_ERR_TIMEOUT;
+    }
+
+    let frames = cfg.buffer_frames as usize;
+    let (ich, och) = (cfg.in_channels as usize, cfg.out_channels as usize);
+    let bufs = &mut s.state.bufs;
+    bufs.in_buf = vec![0.0; frames * ich];
+    bufs.out_buf = vec![0.0; frames * och];
+    bufs.in_planes = (0..ich)
+        .map(|c| bufs.in_buf[c * frames..].as_ptr())
+        .collect();
+    bufs.out_planes = (0..och)
+        .map(|c| bufs.out_buf[c * frames..].as_mut_ptr())
+        .collect();
+    bufs.scratch = vec![0.0; frames * ich.max(och)];
+    s.state.cfg = cfg;
+    s.state.time0 = Instant::now();
+    s.state.underruns.store(0, Ordering::Relaxed);
+    s.state.overruns.store(0, Ordering::Relaxed);
+    s.state.frames_processed.store(0, Ordering::Relaxed);
+    if armed {
+        s.state.gate.arm();
+    } else {
+        s.state.gate.open();
+    }
+    s.state.running.store(true, Ordering::Release);
+    let driver_ptr = selfp as *mut Driver as usize;
+    s.state.worker = Some(std::thread::spawn(move || unsafe {
+        driver_thread(driver_ptr as *mut Driver);
+    }));
+    s.state.phase = sys::DriverPhase::Running;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn trigger_start(selfp: *mut sys::oa_driver, start_ns: u64) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "trigger_start({start_ns})");
+    if s.state.phase != sys::DriverPhase::Running || !s.state.gate.trigger(start_ns) {
+        log!(s, Error, "trigger_start called without arm_start");
+        return sys::OA_ERR_STATE;
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_status(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_stream_status,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    let status = sys::oa_stream_status {
+        running: s.state.running.load(Ordering::Acquire) as sys::oa_bool,
+        actual_sample_rate: s.state.cfg.sample_rate,
+        actual_buffer_frames: s.state.cfg.buffer_frames,
+        frames_processed: s.state.frames_processed.load(Ordering::Relaxed),
+        underruns: s.state.underruns.load(Ordering::Relaxed),
+        overruns: s.state.overruns.load(Ordering::Relaxed),
+        ..Default::default()
+    };
+    sys::write_stream_status(&status, out)
+}
+
+unsafe extern "C" fn stop(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "stop");
+    if s.state.phase != sys::DriverPhase::Running {
+        log!(s, Error, "stop called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    s.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+/// Like `stop`, but wakes the worker instead of letting it sleep out the block it last produced.
+unsafe extern "C" fn abort(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "abort");
+    if s.state.phase != sys::DriverPhase::Running {
+        log!(s, Error, "abort called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    s.state.running.store(false, Ordering::Release);
+    if let Some(handle) = s.state.worker.as_ref() {
+        handle.thread().unpark();
+    }
+    if !s.state.stop_worker() {
+        log!(s, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    s.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn pause(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "pause");
+    if s.state.phase != sys::DriverPhase::Running || s.state.paused.load(Ordering::Relaxed) {
+        log!(s, Error, "pause called while {:?}", s.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    s.state.paused.store(true, Ordering::Release);
+    sys::OA_OK
+}
+
+unsafe extern "C" fn resume(selfp: *mut sys::oa_driver) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "resume");
+    if s.state.phase != sys::DriverPhase::Running || !s.state.paused.load(Ordering::Relaxed) {
+        log!(s, Error, "resume called while not paused");
+        return sys::OA_ERR_STATE;
+    }
+    s.state.paused.store(false, Ordering::Release);
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_latency(
+    selfp: *mut sys::oa_driver,
+    in_lat: *mut u32,
+    out_lat: *mut u32,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(s, Debug, "get_latency");
+    if in_lat.is_null() || out_lat.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    // The consumer keeps up to one block queued in the ring.
+    *in_lat = s.state.cfg.buffer_frames;
+    *out_lat = s.state.cfg.buffer_frames;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn set_sr(selfp: *mut sys::oa_driver, sr: u32) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "set_sample_rate({sr})");
+    if s.state.phase == sys::DriverPhase::Running {
+        return sys::OA_ERR_STATE;
+    }
+    if sr == 0 {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    s.state.cfg.sample_rate = sr;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn set_buf(selfp: *mut sys::oa_driver, frames: u32) -> i32 {
+    let s = &mut *(selfp as *mut Driver);
+    log!(s, Debug, "set_buffer_frames({frames})");
+    if s.state.phase == sys::DriverPhase::Running {
+        return sys::OA_ERR_STATE;
+    }
+    if frames == 0 || frames as usize > shm::CAPACITY / 2 {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    s.state.cfg.buffer_frames = frames;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_channel_name(
+    selfp: *mut sys::oa_driver,
+    direction: sys::oa_direction,
+    index: u32,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    sys::default_channel_name_for(&s.state.cfg, direction, index, buf, len)
+}
+
+unsafe extern "C" fn set_channel_gain(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    channel: u32,
+    gain: f32,
+) -> i32 {
+    let s = &*(selfp as *const Driver);
+    log!(
+        s,
+        Debug,
+        "set_channel_gain(direction={direction}, channel={channel}, gain={gain})"
+    );
+    sys::set_channel_gain(
+        &s.state.in_gains,
+        &s.state.out_gains,
+        direction,
+        channel,
+        gain,
+    )
+}
+
+static VTABLE: sys::oa_driver_vtable = sys::oa_driver_vtable {
+    get_caps: Some(get_caps),
+    query_devices: Some(query_devices),
+    open_device: Some(open_device),
+    close_device: Some(close_device),
+    get_default_config: Some(get_default_config),
+    start: Some(start),
+    stop: Some(stop),
+    get_latency: Some(get_latency),
+    set_sample_rate: Some(set_sr),
+    set_buffer_frames: Some(set_buf),
+    arm_start: Some(arm_start),
+    trigger_start: Some(trigger_start),
+    get_status: Some(get_status),
+    query_clock_sources: Some(sys::internal_clock_sources),
+    set_clock_source: Some(sys::internal_set_clock_source),
+    get_channel_name: Some(get_channel_name),
+    set_channel_gain: Some(set_channel_gain),
+    pause: Some(pause),
+    resume: Some(resume),
+    abort: Some(abort),
+    ..sys::oa_driver_vtable::empty()
+};
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_create(
+    params: *const sys::oa_create_params,
+    out: *mut *mut sys::oa_driver,
+) -> i32 {
+    if params.is_null() || out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let p = &*params;
+    let rc = sys::create_params_validate(p);
+    if rc != sys::OA_OK {
+        return rc;
+    }
+    let drv = Box::new(Driver {
+        vt: &VTABLE,
+        state: DriverState {
+            host: sys::host_callbacks(p),
+            host_user: p.host_user,
+            log: sys::LogSink::from_params(p),
+            phase: sys::DriverPhase::Created,
+            cfg_size: sys::stream_config_size(p),
+            region: None,
+            cfg: sys::oa_stream_config::default(),
+            bufs: Buffers::default(),
+            time0: Instant::now(),
+            underruns: AtomicU32::new(0),
+            overruns: AtomicU32::new(0),
+            frames_processed: AtomicU64::new(0),
+            in_gains: sys::ChannelGains::default(),
+            out_gains: sys::ChannelGains::default(),
+            running: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            gate: sys::StartGate::new(),
+            worker: None,
+        },
+    });
+    *out = Box::into_raw(drv) as *mut sys::oa_driver;
+    sys::OA_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_destroy(driver: *mut sys::oa_driver) {
+    if !driver.is_null() {
+        let _ = Box::from_raw(driver as *mut Driver);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn openasio_driver_get_info(out: *mut sys::oa_driver_info) -> i32 {
+    let info = sys::oa_driver_info::new(
+        "OpenASIO Loopback",
+        "OpenASIO project",
+        env!("CARGO_PKG_VERSION"),
+    );
+    sys::write_driver_info(&info, out)
+}
+
+sys::oa_export_abi_version!();
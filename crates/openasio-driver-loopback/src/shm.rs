@@ -0,0 +1,251 @@
+//! Named POSIX shared-memory region holding a single-producer/single-consumer sample ring.
+//!
+//! The first driver instance to open a name creates the region and becomes the producer; the
+//! second maps it and becomes the consumer. The ring stores frames of `CHANNELS` slots so the
+//! two sides can run with different channel counts; missing channels read as silence.
+use openasio_sys as sys;
+use std::ffi::CString;
+use std::io;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Channel slots per frame in the ring.
+pub const CHANNELS: usize = 8;
+
+/// Ring capacity in frames.
+pub const CAPACITY: usize = 16384;
+
+/// Written last by the producer, so a consumer that sees it also sees the rest of the header.
+const MAGIC: u32 = u32::from_le_bytes(*b"OALB");
+
+/// How long a consumer waits for a producer that created the region but has not initialised it.
+const INIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[repr(C)]
+struct Header {
+    magic: AtomicU32,
+    channels: u32,
+    capacity: u32,
+    consumer: AtomicU32, // 1 while a consumer is attached
+    write: AtomicU64,    // frames pushed, owned by the producer
+    read: AtomicU64,     // frames popped, owned by the consumer
+}
+
+const REGION_LEN: usize = std::mem::size_of::<Header>() + CAPACITY * CHANNELS * 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Producer,
+    Consumer,
+}
+
+pub struct Region {
+    path: CString,
+    base: *mut u8,
+    role: Role,
+}
+
+// SAFETY: the producer only writes frames in [write, read + CAPACITY) and the consumer only
+// reads frames in [read, write); the Release/Acquire pairs on the counters order those accesses.
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+/// `/openasio-loopback-<name>`, the shm object backing a loopback device name.
+fn shm_path(name: &str) -> Result<CString, (i32, String)> {
+    if name.is_empty() || name.contains('/') {
+        return Err((
+            sys::OA_ERR_INVALID_ARG,
+            format!("loopback name {name:?} must be non-empty and contain no '/'"),
+        ));
+    }
+    CString::new(format!("/openasio-loopback-{name}"))
+        .map_err(|_| (sys::OA_ERR_INVALID_ARG, "loopback name contains NUL".into()))
+}
+
+fn os_error(what: &str) -> (i32, String) {
+    (
+        sys::OA_ERR_DEVICE,
+        format!("{what}: {}", io::Error::last_os_error()),
+    )
+}
+
+/// Map `fd` and close it; the mapping keeps the object alive.
+unsafe fn map(fd: i32) -> Option<*mut u8> {
+    let base = libc::mmap(
+        std::ptr::null_mut(),
+        REGION_LEN,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_SHARED,
+        fd,
+        0,
+    );
+    libc::close(fd);
+    (base != libc::MAP_FAILED).then_some(base as *mut u8)
+}
+
+/// Wait for the producer to finish initialising `h`, then claim its consumer slot.
+fn attach(h: &Header, name: &str) -> Result<(), (i32, String)> {
+    let deadline = Instant::now() + INIT_TIMEOUT;
+    while h.magic.load(Ordering::Acquire) != MAGIC {
+        if Instant::now() >= deadline {
+            return Err((
+                sys::OA_ERR_DEVICE,
+                format!("loopback {name:?} was never initialised"),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    if h.channels != CHANNELS as u32 || h.capacity != CAPACITY as u32 {
+        return Err((
+            sys::OA_ERR_DEVICE,
+            format!("loopback {name:?} has an incompatible layout"),
+        ));
+    }
+    h.consumer
+        .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire)
+        .map(|_| ())
+        .map_err(|_| {
+            (
+                sys::OA_ERR_BUSY,
+                format!("loopback {name:?} already has a producer and a consumer"),
+            )
+        })
+}
+
+impl Region {
+    /// Create the region for `name`, or attach to it as the consumer if it already exists.
+    pub fn open(name: &str) -> Result<Self, (i32, String)> {
+        let path = shm_path(name)?;
+        unsafe {
+            let fd = libc::shm_open(
+                path.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL,
+                0o600,
+            );
+            if fd >= 0 {
+                if libc::ftruncate(fd, REGION_LEN as libc::off_t) != 0 {
+                    let err = os_error("ftruncate");
+                    libc::close(fd);
+                    libc::shm_unlink(path.as_ptr());
+                    return Err(err);
+                }
+                let Some(base) = map(fd) else {
+                    let err = os_error("mmap");
+                    libc::shm_unlink(path.as_ptr());
+                    return Err(err);
+                };
+                // A fresh object is zero-filled, so only the layout needs writing.
+                let h = base as *mut Header;
+                (*h).channels = CHANNELS as u32;
+                (*h).capacity = CAPACITY as u32;
+                (*h).magic.store(MAGIC, Ordering::Release);
+                return Ok(Self {
+                    path,
+                    base,
+                    role: Role::Producer,
+                });
+            }
+            if io::Error::last_os_error().raw_os_error() != Some(libc::EEXIST) {
+                return Err(os_error("shm_open"));
+            }
+            let fd = libc::shm_open(path.as_ptr(), libc::O_RDWR, 0);
+            if fd < 0 {
+                return Err(os_error("shm_open"));
+            }
+            let mut st: libc::stat = std::mem::zeroed();
+            if libc::fstat(fd, &mut st) != 0 || (st.st_size as usize) < REGION_LEN {
+                libc::close(fd);
+                return Err((
+                    sys::OA_ERR_DEVICE,
+                    format!("{path:?} is not a loopback region"),
+                ));
+            }
+            let Some(base) = map(fd) else {
+                return Err(os_error("mmap"));
+            };
+            if let Err(e) = attach(&*(base as *const Header), name) {
+                libc::munmap(base as *mut libc::c_void, REGION_LEN);
+                return Err(e);
+            }
+            Ok(Self {
+                path,
+                base,
+                role: Role::Consumer,
+            })
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn has_consumer(&self) -> bool {
+        self.header().consumer.load(Ordering::Acquire) != 0
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.base as *const Header) }
+    }
+
+    fn slot(&self, frame: u64, ch: usize) -> *mut f32 {
+        let idx = (frame as usize % CAPACITY) * CHANNELS + ch;
+        unsafe { (self.base.add(std::mem::size_of::<Header>()) as *mut f32).add(idx) }
+    }
+
+    /// Producer side. Appends the interleaved `src` block of `ch` channels; returns the number
+    /// of frames discarded because the consumer fell behind.
+    pub fn push(&self, src: &[f32], ch: usize) -> usize {
+        let h = self.header();
+        let frames = src.len() / ch.max(1);
+        let write = h.write.load(Ordering::Relaxed);
+        let used = write.wrapping_sub(h.read.load(Ordering::Acquire)) as usize;
+        let n = frames.min(CAPACITY - used);
+        for f in 0..n {
+            for c in 0..CHANNELS {
+                let s = if c < ch { src[f * ch + c] } else { 0.0 };
+                unsafe { *self.slot(write + f as u64, c) = s };
+            }
+        }
+        h.write.store(write + n as u64, Ordering::Release);
+        frames - n
+    }
+
+    /// Consumer side. Fills the interleaved `out` block of `ch` channels, padding with silence
+    /// when the ring runs short, then drops the oldest frames if more than `slack` are left over.
+    /// Returns `(padded, dropped)` frames.
+    pub fn pop(&self, out: &mut [f32], ch: usize, slack: usize) -> (usize, usize) {
+        let h = self.header();
+        let frames = out.len() / ch.max(1);
+        let read = h.read.load(Ordering::Relaxed);
+        let avail = h.write.load(Ordering::Acquire).wrapping_sub(read) as usize;
+        let n = frames.min(avail);
+        for f in 0..n {
+            for c in 0..ch {
+                out[f * ch + c] = if c < CHANNELS {
+                    unsafe { *self.slot(read + f as u64, c) }
+                } else {
+                    0.0
+                };
+            }
+        }
+        out[n * ch..].fill(0.0);
+        let dropped = (avail - n).saturating_sub(slack);
+        h.read.store(read + (n + dropped) as u64, Ordering::Release);
+        (frames - n, dropped)
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        unsafe {
+            if self.role == Role::Consumer {
+                self.header().consumer.store(0, Ordering::Release);
+            }
+            libc::munmap(self.base as *mut libc::c_void, REGION_LEN);
+            // The name goes away with the producer; an attached consumer keeps its mapping.
+            if self.role == Role::Producer {
+                libc::shm_unlink(self.path.as_ptr());
+            }
+        }
+    }
+}
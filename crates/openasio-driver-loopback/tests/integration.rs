@@ -0,0 +1,289 @@
+//! Drives the loopback driver through its C entry points and vtable, with no wrapper in between:
+//! create, query_devices, open_device, get_default_config, start, process, stop, close_device,
+//! destroy.
+use openasio_sys as sys;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+/// The cdylib cargo built next to this test binary.
+fn driver_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let name = format!(
+        "{}openasio_driver_loopback{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    [deps, deps.parent().unwrap()]
+        .iter()
+        .map(|dir| dir.join(&name))
+        .find(|p| p.exists())
+        .expect("loopback driver library not built")
+}
+
+/// Unique per test so parallel tests never share a ring.
+fn ring_name(test: &str) -> String {
+    format!("integration-{}-{test}\0", std::process::id())
+}
+
+/// Counts the live values of a test's host state, so a test can check none outlive it.
+#[derive(Default)]
+struct DropTracking(Arc<AtomicUsize>);
+
+impl DropTracking {
+    fn track(&self) -> Tracked {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Tracked(self.0.clone())
+    }
+    fn live(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Host state behind `host_user`: counts callbacks up to `limit`, returning false from the one
+/// that reaches it, and notes which threads called.
+struct Counting {
+    limit: usize,
+    calls: Mutex<usize>,
+    reached: Condvar,
+    threads: Mutex<HashSet<ThreadId>>,
+    _tracked: Tracked,
+}
+
+impl Counting {
+    fn new(limit: usize, tracking: &DropTracking) -> Box<Self> {
+        Box::new(Self {
+            limit,
+            calls: Mutex::new(0),
+            reached: Condvar::new(),
+            threads: Mutex::new(HashSet::new()),
+            _tracked: tracking.track(),
+        })
+    }
+
+    /// Block until `n` callbacks have run.
+    fn wait_for(&self, n: usize) {
+        let calls = self.calls.lock().unwrap();
+        let (calls, timeout) = self
+            .reached
+            .wait_timeout_while(calls, Duration::from_secs(5), |c| *c < n)
+            .unwrap();
+        assert!(!timeout.timed_out(), "only {} callbacks arrived", *calls);
+    }
+
+    fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+unsafe extern "C" fn process(
+    user: *mut c_void,
+    _in_ptr: *const c_void,
+    _out_ptr: *mut c_void,
+    _frames: u32,
+    _time: *const sys::oa_time_info,
+    _cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    let host = &*(user as *const Counting);
+    host.threads
+        .lock()
+        .unwrap()
+        .insert(std::thread::current().id());
+    let mut calls = host.calls.lock().unwrap();
+    *calls += 1;
+    host.reached.notify_all();
+    if *calls >= host.limit {
+        sys::OA_FALSE
+    } else {
+        sys::OA_TRUE
+    }
+}
+
+static HOST: sys::oa_host_callbacks = sys::oa_host_callbacks {
+    process: Some(process),
+    ..sys::oa_host_callbacks::empty()
+};
+
+/// A driver instance and the library it came from.
+struct Instance {
+    lib: sys::loader::DriverLib,
+    drv: *mut sys::oa_driver,
+}
+
+/// Creation params for a driver calling back into `host`.
+fn params(host: &Counting) -> sys::oa_create_params {
+    sys::oa_create_params::new(&HOST, host as *const Counting as *mut c_void)
+}
+
+impl Instance {
+    fn create(host: &Counting) -> Self {
+        let lib = unsafe { sys::loader::DriverLib::load(driver_path()) }.unwrap();
+        let params = params(host);
+        let mut drv = ptr::null_mut();
+        assert_eq!(unsafe { (lib.create)(&params, &mut drv) }, sys::OA_OK);
+        assert!(!drv.is_null());
+        Self { lib, drv }
+    }
+
+    fn vt(&self) -> &sys::oa_driver_vtable {
+        unsafe { &*(*self.drv).vt }
+    }
+
+    fn open_device(&self, test: &str) -> i32 {
+        let name = ring_name(test);
+        unsafe { (self.vt().open_device.unwrap())(self.drv, name.as_ptr() as *const c_char) }
+    }
+
+    /// open_device on a private ring, then the driver's default config.
+    fn open(&self, test: &str) -> sys::oa_stream_config {
+        assert_eq!(self.open_device(test), sys::OA_OK);
+        let vt = self.vt();
+        unsafe {
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!((vt.get_default_config.unwrap())(self.drv, &mut cfg), sys::OA_OK);
+            sys::oa_stream_config { buffer_frames: 64, ..cfg }
+        }
+    }
+
+    fn start(&self, cfg: &sys::oa_stream_config) -> i32 {
+        unsafe { (self.vt().start.unwrap())(self.drv, cfg) }
+    }
+
+    fn destroy(self) {
+        unsafe { (self.lib.destroy)(self.drv) };
+    }
+}
+
+#[test]
+fn full_lifecycle_runs_ten_callbacks() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(10, &tracking);
+    let drv = Instance::create(&host);
+    let vt = drv.vt();
+    unsafe {
+        let mut buf = [0 as c_char; 256];
+        let rc = (vt.query_devices.unwrap())(drv.drv, buf.as_mut_ptr(), buf.len());
+        assert_eq!(rc, sys::OA_OK);
+        assert_ne!(buf[0], 0, "no devices listed");
+        // A buffer too small for the list gets nothing, and the size to ask again with.
+        let mut short = [b'?' as c_char; 4];
+        let need = (vt.query_devices.unwrap())(drv.drv, short.as_mut_ptr(), short.len());
+        assert_eq!(short[0], 0);
+        let mut sized = vec![0 as c_char; need as usize];
+        let rc = (vt.query_devices.unwrap())(drv.drv, sized.as_mut_ptr(), sized.len());
+        assert_eq!(rc, sys::OA_OK);
+        assert_eq!(CStr::from_ptr(sized.as_ptr()), CStr::from_ptr(buf.as_ptr()));
+    }
+    let cfg = drv.open("lifecycle");
+    assert_eq!(drv.start(&cfg), sys::OA_OK);
+    host.wait_for(10);
+    unsafe {
+        assert_eq!((vt.stop.unwrap())(drv.drv), sys::OA_OK);
+    }
+    // The tenth callback returned false, so the worker made no eleventh.
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(host.calls(), 10);
+    unsafe {
+        assert_eq!((vt.close_device.unwrap())(drv.drv), sys::OA_OK);
+    }
+    drv.destroy();
+
+    assert_eq!(tracking.live(), 1);
+    drop(host);
+    assert_eq!(tracking.live(), 0, "host state outlived the test");
+}
+
+#[test]
+fn second_start_does_not_spawn_another_worker() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(usize::MAX, &tracking);
+    let drv = Instance::create(&host);
+    let cfg = drv.open("double-start");
+    assert_eq!(drv.start(&cfg), sys::OA_OK);
+    host.wait_for(1);
+    assert_eq!(drv.start(&cfg), sys::OA_ERR_STATE);
+    let seen = host.calls();
+    host.wait_for(seen + 10);
+    unsafe {
+        assert_eq!((drv.vt().stop.unwrap())(drv.drv), sys::OA_OK);
+    }
+    drv.destroy();
+
+    assert_eq!(host.threads.lock().unwrap().len(), 1, "process ran on two threads");
+    drop(host);
+    assert_eq!(tracking.live(), 0);
+}
+
+#[test]
+fn destroy_while_running_stops_the_worker() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(usize::MAX, &tracking);
+    let drv = Instance::create(&host);
+    let cfg = drv.open("destroy-running");
+    assert_eq!(drv.start(&cfg), sys::OA_OK);
+    host.wait_for(3);
+    drv.destroy();
+
+    // Had the worker survived destroy, it would keep calling into the host.
+    let after = host.calls();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(host.calls(), after, "process called after destroy");
+    drop(host);
+    assert_eq!(tracking.live(), 0);
+}
+
+#[test]
+fn start_before_open_is_not_open() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(usize::MAX, &tracking);
+    let drv = Instance::create(&host);
+    let cfg = sys::oa_stream_config {
+        buffer_frames: 64,
+        ..sys::oa_stream_config::default()
+    };
+    assert_eq!(drv.start(&cfg), sys::OA_ERR_NOT_OPEN);
+    drv.destroy();
+}
+
+#[test]
+fn third_instance_on_a_ring_is_busy() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(usize::MAX, &tracking);
+    let drvs: Vec<_> = (0..3).map(|_| Instance::create(&host)).collect();
+    assert_eq!(drvs[0].open_device("busy"), sys::OA_OK);
+    assert_eq!(drvs[1].open_device("busy"), sys::OA_OK);
+    assert_eq!(drvs[2].open_device("busy"), sys::OA_ERR_BUSY);
+    drvs.into_iter().for_each(Instance::destroy);
+}
+
+#[test]
+fn create_rejects_params_of_impossible_size() {
+    let tracking = DropTracking::default();
+    let host = Counting::new(usize::MAX, &tracking);
+    let lib = unsafe { sys::loader::DriverLib::load(driver_path()) }.unwrap();
+    for struct_size in [4, sys::OA_MAX_STRUCT_SIZE + 1, u32::MAX] {
+        let params = sys::oa_create_params {
+            struct_size,
+            ..params(&host)
+        };
+        let mut drv = ptr::null_mut();
+        let rc = unsafe { (lib.create)(&params, &mut drv) };
+        assert_eq!(rc, sys::OA_ERR_INVALID_ARG, "struct_size {struct_size}");
+        assert!(drv.is_null());
+    }
+}
@@ -0,0 +1,509 @@
+//! Runs a producer and a consumer loopback instance through the `openasio` wrapper and checks
+//! that what one writes arrives on the other.
+use openasio::{Driver, DriverError, HostProcess, StreamConfig, TimeInfo};
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// The cdylib cargo built next to this test binary.
+fn driver_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let name = format!(
+        "{}openasio_driver_loopback{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    [deps, deps.parent().unwrap()]
+        .iter()
+        .map(|dir| dir.join(&name))
+        .find(|p| p.exists())
+        .expect("loopback driver library not built")
+}
+
+/// Unique per test so parallel tests never share a ring.
+fn ring_name(test: &str) -> String {
+    format!("test-{}-{test}", std::process::id())
+}
+
+/// Writes a running frame counter, starting at 1, to every output channel.
+struct Counter(u32);
+
+impl HostProcess for Counter {
+    fn process(
+        &mut self,
+        _in: *const c_void,
+        out: *mut c_void,
+        frames: u32,
+        cfg: &StreamConfig,
+    ) -> bool {
+        let ch = cfg.out_channels as usize;
+        let out = unsafe { std::slice::from_raw_parts_mut(out as *mut f32, frames as usize * ch) };
+        for frame in out.chunks_exact_mut(ch) {
+            self.0 += 1;
+            frame.fill(self.0 as f32);
+        }
+        true
+    }
+}
+
+/// Keeps the first input channel's non-silent samples.
+struct Recorder(Arc<Mutex<Vec<f32>>>);
+
+impl HostProcess for Recorder {
+    fn process(
+        &mut self,
+        inp: *const c_void,
+        _out: *mut c_void,
+        frames: u32,
+        cfg: &StreamConfig,
+    ) -> bool {
+        let ch = cfg.in_channels as usize;
+        let inp = unsafe { std::slice::from_raw_parts(inp as *const f32, frames as usize * ch) };
+        let mut got = self.0.lock().unwrap();
+        got.extend(inp.chunks_exact(ch).map(|f| f[0]).filter(|&s| s != 0.0));
+        true
+    }
+}
+
+/// Records each block's sample position and length.
+struct Positions(Arc<Mutex<Vec<(u64, u32)>>>);
+
+impl HostProcess for Positions {
+    fn process(&mut self, _: *const c_void, _: *mut c_void, _: u32, _: &StreamConfig) -> bool {
+        true
+    }
+    fn process_with_time(
+        &mut self,
+        _in: *const c_void,
+        _out: *mut c_void,
+        frames: u32,
+        _cfg: &StreamConfig,
+        time: &TimeInfo,
+    ) -> bool {
+        self.0.lock().unwrap().push((time.sample_position, frames));
+        true
+    }
+}
+
+/// Sends after every block, so a test can act right after one was produced.
+struct Ticks(mpsc::Sender<()>);
+
+impl HostProcess for Ticks {
+    fn process(&mut self, _: *const c_void, _: *mut c_void, _: u32, _: &StreamConfig) -> bool {
+        let _ = self.0.send(());
+        true
+    }
+}
+
+fn load(host: Box<dyn HostProcess>) -> Driver {
+    Driver::load(driver_path(), host, StreamConfig::default(), true).unwrap()
+}
+
+#[test]
+fn output_arrives_on_the_other_instance() {
+    let name = ring_name("roundtrip");
+    let got = Arc::new(Mutex::new(Vec::new()));
+    let mut producer = load(Box::new(Counter(0)));
+    let mut consumer = load(Box::new(Recorder(got.clone())));
+    assert!(producer.caps().contains(sys::OaCaps::LOOPBACK));
+    producer.open_by_name(Some(&name)).unwrap();
+    consumer.open_by_name(Some(&name)).unwrap();
+
+    let cfg = StreamConfig {
+        buffer_frames: 64,
+        ..Default::default()
+    };
+    let rx = consumer.start_stream(cfg).unwrap();
+    let tx = producer.start_stream(cfg).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    drop(tx);
+    drop(rx);
+
+    let got = got.lock().unwrap();
+    assert!(got.len() >= 1000, "only {} samples looped back", got.len());
+    // Drift corrections may skip frames, but never reorder or invent them.
+    assert!(got.windows(2).all(|w| w[1] > w[0]), "samples out of order");
+    assert!(got.iter().all(|s| s.fract() == 0.0));
+}
+
+#[test]
+fn output_gain_scales_what_arrives() {
+    let name = ring_name("gain");
+    let got = Arc::new(Mutex::new(Vec::new()));
+    let mut producer = load(Box::new(Counter(0)));
+    let mut consumer = load(Box::new(Recorder(got.clone())));
+    producer.open_by_name(Some(&name)).unwrap();
+    consumer.open_by_name(Some(&name)).unwrap();
+    producer.set_output_gain(0, 2.0).unwrap();
+
+    let cfg = StreamConfig {
+        buffer_frames: 64,
+        ..Default::default()
+    };
+    let rx = consumer.start_stream(cfg).unwrap();
+    let tx = producer.start_stream(cfg).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    drop(tx);
+    drop(rx);
+
+    let got = got.lock().unwrap();
+    assert!(!got.is_empty(), "nothing looped back");
+    // The counter writes whole numbers, so doubled samples are all even.
+    assert!(
+        got.iter().all(|s| s % 2.0 == 0.0),
+        "{:?}",
+        &got[..got.len().min(8)]
+    );
+    assert!(got.windows(2).any(|w| w[1] - w[0] == 2.0));
+}
+
+#[test]
+fn sample_position_advances_by_frames_and_resets_on_restart() {
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let mut driver = load(Box::new(Positions(blocks.clone())));
+    assert!(driver.caps().contains(sys::OaCaps::SAMPLE_POSITION));
+    driver.open_by_name(Some(&ring_name("position"))).unwrap();
+    let cfg = StreamConfig {
+        buffer_frames: 64,
+        ..Default::default()
+    };
+    for _ in 0..2 {
+        let stream = driver.start_stream(cfg).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        drop(stream);
+    }
+
+    let blocks = blocks.lock().unwrap();
+    let starts = blocks.iter().filter(|(pos, _)| *pos == 0).count();
+    assert_eq!(starts, 2, "{blocks:?}");
+    assert_eq!(blocks[0].0, 0);
+    for w in blocks.windows(2) {
+        let ((pos, frames), (next, _)) = (w[0], w[1]);
+        assert!(next == pos + u64::from(frames) || next == 0, "{w:?}");
+    }
+    assert!(blocks.len() > 4, "only {} blocks", blocks.len());
+}
+
+#[test]
+fn pause_skips_process_and_resume_carries_on() {
+    let blocks = Arc::new(Mutex::new(Vec::new()));
+    let mut driver = load(Box::new(Positions(blocks.clone())));
+    driver.open_by_name(Some(&ring_name("pause"))).unwrap();
+    let cfg = StreamConfig {
+        buffer_frames: 64,
+        ..Default::default()
+    };
+    let mut stream = driver.start_stream(cfg).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    stream.pause().unwrap();
+    // A block already in process when pause was called may still finish.
+    std::thread::sleep(Duration::from_millis(10));
+    let paused_at = blocks.lock().unwrap().len();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(
+        blocks.lock().unwrap().len(),
+        paused_at,
+        "process ran while paused"
+    );
+    stream.resume().unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    drop(stream);
+
+    let blocks = blocks.lock().unwrap();
+    assert!(blocks.len() > paused_at, "nothing after resume: {blocks:?}");
+    // Resumed, not restarted: the position carries on from where it paused.
+    for w in blocks.windows(2) {
+        let ((pos, frames), (next, _)) = (w[0], w[1]);
+        assert_eq!(next, pos + u64::from(frames), "{w:?}");
+    }
+}
+
+#[test]
+fn abort_returns_without_waiting_out_the_block() {
+    let cfg = StreamConfig {
+        buffer_frames: 8192,
+        ..Default::default()
+    };
+    let period = Duration::from_secs_f64(8192.0 / f64::from(cfg.sample_rate));
+    let (tx, rx) = mpsc::channel();
+    let mut driver = load(Box::new(Ticks(tx)));
+    driver.open_by_name(Some(&ring_name("abort"))).unwrap();
+
+    let stream = driver.start_stream(cfg).unwrap();
+    rx.recv().unwrap();
+    let t = Instant::now();
+    stream.stop();
+    let stop_took = t.elapsed();
+
+    let stream = driver.start_stream(cfg).unwrap();
+    rx.recv().unwrap();
+    let t = Instant::now();
+    stream.abort();
+    let abort_took = t.elapsed();
+
+    // stop lets the block just produced play out; abort drops it.
+    assert!(stop_took >= period / 2, "stop returned after {stop_took:?} of a {period:?} block");
+    assert!(abort_took < period / 4, "abort took {abort_took:?} of a {period:?} block");
+}
+
+#[test]
+fn third_instance_is_busy() {
+    let name = ring_name("busy");
+    let mut producer = load(Box::new(Counter(0)));
+    let mut consumer = load(Box::new(Counter(0)));
+    let mut third = load(Box::new(Counter(0)));
+    producer.open_by_name(Some(&name)).unwrap();
+    consumer.open_by_name(Some(&name)).unwrap();
+    let err = third.open_by_name(Some(&name)).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<DriverError>(), Some(DriverError::Busy)),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn synchronized_start_runs_both_instances() {
+    let name = ring_name("sync");
+    let got = Arc::new(Mutex::new(Vec::new()));
+    let mut producer = load(Box::new(Counter(0)));
+    let mut consumer = load(Box::new(Recorder(got.clone())));
+    producer.open_by_name(Some(&name)).unwrap();
+    consumer.open_by_name(Some(&name)).unwrap();
+
+    openasio::start_synchronized(&mut [&mut consumer, &mut producer]).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    drop(producer);
+    drop(consumer);
+
+    let got = got.lock().unwrap();
+    assert!(got.len() >= 1000, "only {} samples looped back", got.len());
+    // Started together, the consumer's first block already carries the producer's first frames.
+    assert!(
+        got[0] <= 2.0 * 256.0,
+        "first sample {} arrived late",
+        got[0]
+    );
+}
+
+#[test]
+fn open_with_fallback_uses_first_enumerated_device() {
+    let mut driver = load(Box::new(Counter(0)));
+    // '/' is not allowed in loopback names, so the preferred name fails to open.
+    let opened = driver.open_with_fallback(Some("no/such")).unwrap();
+    assert_eq!(opened, driver.enumerate_devices().unwrap()[0]);
+}
+
+#[test]
+fn stats_are_readable_from_another_thread() {
+    let mut driver = load(Box::new(Counter(0)));
+    driver.open_by_name(Some(&ring_name("stats"))).unwrap();
+    let stats = driver.stats_handle();
+    let stream = driver
+        .start_stream(StreamConfig {
+            buffer_frames: 64,
+            ..Default::default()
+        })
+        .unwrap();
+    let reader = std::thread::spawn(move || {
+        let mut seen = 0;
+        for _ in 0..100 {
+            let now = stats.callbacks_dispatched();
+            assert!(now >= seen, "callback count went backwards");
+            seen = now;
+            std::thread::sleep(Duration::from_millis(3));
+        }
+        stats
+    });
+    let stats = reader.join().unwrap();
+    drop(stream);
+
+    assert!(stats.callbacks_dispatched() > 10, "{stats:?}");
+    assert!(stats.max_callback_ns() > 0);
+    stats.reset();
+    assert_eq!(stats.callbacks_dispatched(), 0);
+    assert_eq!(stats.max_callback_ns(), 0);
+}
+
+#[test]
+fn status_tracks_the_running_stream() {
+    let mut driver = load(Box::new(Counter(0)));
+    driver.open_by_name(Some(&ring_name("status"))).unwrap();
+    assert!(!driver.status().unwrap().running);
+
+    let cfg = StreamConfig {
+        sample_rate: 44100,
+        buffer_frames: 64,
+        ..Default::default()
+    };
+    let stream = driver.start_stream(cfg).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let first = stream.status().unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let second = stream.status().unwrap();
+    assert!(first.running && second.running);
+    assert_eq!((second.sample_rate, second.buffer_frames), (44100, 64));
+    assert!(
+        second.frames_processed > first.frames_processed,
+        "{first:?} -> {second:?}"
+    );
+    drop(stream);
+
+    let stopped = driver.status().unwrap();
+    assert!(!stopped.running);
+    assert!(stopped.frames_processed >= second.frames_processed);
+}
+
+/// Compile `source` into a shared library at `path` with the system C compiler.
+fn build_c_library(path: &std::path::Path, source: &str) {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    let built = std::process::Command::new(cc)
+        .args(["-shared", "-fPIC", "-x", "c", "-", "-o"])
+        .arg(path)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            use std::io::Write;
+            child.stdin.take().unwrap().write_all(source.as_bytes())?;
+            child.wait()
+        })
+        .unwrap();
+    assert!(built.success());
+}
+
+#[test]
+fn load_from_takes_the_first_directory_with_the_driver() {
+    let root = std::env::temp_dir().join(format!("openasio-search-{}", std::process::id()));
+    let (empty, first, second) = (root.join("empty"), root.join("first"), root.join("second"));
+    let file = sys::loader::driver_filename("loopback");
+    for dir in [&empty, &first, &second] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    for dir in [&first, &second] {
+        std::fs::copy(driver_path(), dir.join(&file)).unwrap();
+    }
+
+    let (lib, path) = unsafe {
+        sys::loader::DriverLib::load_from("loopback", [empty.clone(), first.clone(), second])
+    }
+    .unwrap();
+    assert_eq!(path, first.join(&file));
+    drop(lib);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn load_finds_the_driver_by_its_bare_name() {
+    let bare = driver_path().with_file_name("openasio_driver_loopback");
+    assert!(!bare.exists());
+    let lib = unsafe { sys::loader::DriverLib::load(&bare) }.unwrap();
+    assert!(lib.info().is_some());
+    let (_, opened) = unsafe { sys::loader::open(&bare) }.unwrap();
+    assert_eq!(opened, driver_path());
+}
+
+#[test]
+fn enumerate_lists_only_driver_libraries() {
+    let dir = std::env::temp_dir().join(format!("openasio-enumerate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lib_name = |stem: &str| {
+        format!(
+            "{}{stem}{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        )
+    };
+    let driver = dir.join(lib_name("openasio_driver_loopback"));
+    std::fs::copy(driver_path(), &driver).unwrap();
+    // A real shared library that exports no OpenASIO entry points.
+    build_c_library(&dir.join(lib_name("plain")), "int plain_value = 1;\n");
+    std::fs::write(dir.join(lib_name("not_a_library")), b"not an ELF file").unwrap();
+
+    let found = unsafe { sys::loader::enumerate(&[dir.as_path()]) };
+    assert_eq!(found.len(), 1, "{found:?}");
+    assert_eq!(found[0].path, driver);
+    assert!(found[0].is_loadable());
+    let info = found[0].info.as_ref().unwrap();
+    assert_eq!(info.name, "OpenASIO Loopback");
+    assert_eq!(
+        info.version.0,
+        env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn built_drivers_report_the_api_version() {
+    let driver = load(Box::new(Counter(0)));
+    let info = driver.info().unwrap();
+    assert_eq!(
+        info.api_version,
+        (sys::OA_VERSION_MAJOR, sys::OA_VERSION_MINOR)
+    );
+    // Every other driver cargo built alongside; those missing from this build are skipped.
+    let dir = driver_path().parent().unwrap().to_path_buf();
+    let found = unsafe { sys::loader::enumerate(&[dir.as_path()]) };
+    assert!(found.iter().any(|e| e.path == driver_path()));
+    for entry in found {
+        let info = entry
+            .info
+            .unwrap_or_else(|| panic!("{} has no info", entry.path.display()));
+        assert_eq!(
+            info.api_version.0,
+            sys::OA_VERSION_MAJOR,
+            "{}",
+            entry.path.display()
+        );
+    }
+}
+
+#[test]
+fn abi_version_gates_loading() {
+    let dir = std::env::temp_dir().join(format!("openasio-abi-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    const ENTRY_POINTS: &str =
+        "int openasio_driver_create(const void *p, void **out) { return -1; }\n\
+                                void openasio_driver_destroy(void *d) {}\n";
+    let future = dir.join("future.so");
+    build_c_library(
+        &future,
+        &format!("{ENTRY_POINTS}unsigned openasio_abi_version(void) {{ return 0x2a0003; }}\n"),
+    );
+    let old = dir.join("old.so");
+    build_c_library(&old, ENTRY_POINTS);
+
+    // Built alongside this test: the same ABI.
+    let lib = unsafe { sys::loader::DriverLib::load(driver_path()) }.unwrap();
+    assert_eq!(lib.abi_version(), Some(sys::OA_ABI_VERSION));
+    let driver = load(Box::new(Counter(0)));
+    assert_eq!(
+        driver.abi_version(),
+        Some((sys::OA_VERSION_MAJOR, sys::OA_VERSION_MINOR))
+    );
+
+    // Another major version is refused before anything is created.
+    match unsafe { sys::loader::DriverLib::load(&future) } {
+        Err(sys::loader::LoadError::AbiMismatch { found }) => assert_eq!(found, 0x2a0003),
+        other => panic!("loaded a 42.3 driver: {:?}", other.map(|_| ())),
+    }
+    let err = Driver::load(&future, Box::new(Counter(0)), StreamConfig::default(), true)
+        .err()
+        .expect("loaded a 42.3 driver");
+    match err.downcast_ref::<openasio::LoadError>() {
+        Some(openasio::LoadError::AbiMismatch { path, found }) => {
+            assert_eq!((path, *found), (&future, 0x2a0003))
+        }
+        _ => panic!("unexpected error: {err:#}"),
+    }
+    assert!(err.to_string().contains("ABI 42.3"), "{err}");
+
+    // 1.0 drivers predate the export and still load.
+    let lib = unsafe { sys::loader::DriverLib::load(&old) }.unwrap();
+    assert_eq!(lib.abi_version(), None);
+    drop(lib);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,149 @@
+//! The per-period work of the UMC202HD worker: S32 to float and back, planar to interleaved, and a
+//! whole period with a pass-through host. Only scalar paths exist so far; vectorised ones belong
+//! next to them under the same group names, so the reports compare like with like.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use openasio_driver_alsa_common::{FormatConverter, I32Converter};
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::ptr;
+
+const SAMPLES: usize = 1024;
+const FRAMES: usize = 512;
+const CHANNELS: usize = 2;
+/// Period of the simulated stream; the hardware default.
+const PERIOD: usize = 128;
+const RATES: [u32; 3] = [44100, 48000, 192000];
+
+/// A full-scale ramp, so no sample takes a shortcut through zero or clipping.
+fn test_signal(len: usize) -> Vec<f32> {
+    (0..len).map(|i| (i as f32 / len as f32) * 2.0 - 1.0).collect()
+}
+
+fn conversion(c: &mut Criterion) {
+    let floats = test_signal(SAMPLES);
+    let mut ints = vec![0u8; SAMPLES * 4];
+    I32Converter.convert_out(&floats, &mut ints);
+    let mut out_f = vec![0f32; SAMPLES];
+    let mut out_i = vec![0u8; SAMPLES * 4];
+
+    let mut group = c.benchmark_group("convert");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function(BenchmarkId::new("i32_to_f32", "scalar"), |b| {
+        b.iter(|| I32Converter.convert_in(black_box(&ints), &mut out_f))
+    });
+    group.bench_function(BenchmarkId::new("f32_to_i32", "scalar"), |b| {
+        b.iter(|| I32Converter.convert_out(black_box(&floats), &mut out_i))
+    });
+    group.finish();
+}
+
+fn layout(c: &mut Criterion) {
+    let planar = test_signal(FRAMES * CHANNELS);
+    let mut interleaved = vec![0f32; FRAMES * CHANNELS];
+    let mut back = vec![0f32; FRAMES * CHANNELS];
+
+    let mut group = c.benchmark_group("layout");
+    group.throughput(Throughput::Elements((FRAMES * CHANNELS) as u64));
+    group.bench_function(BenchmarkId::new("interleave", "scalar"), |b| {
+        b.iter(|| sys::layout::interleave(black_box(&planar), FRAMES, CHANNELS, &mut interleaved))
+    });
+    group.bench_function(BenchmarkId::new("deinterleave", "scalar"), |b| {
+        b.iter(|| sys::layout::deinterleave(black_box(&interleaved), CHANNELS, &mut back, FRAMES))
+    });
+    group.finish();
+}
+
+/// Copies input to output, like a monitoring host.
+unsafe extern "C" fn pass_through(
+    _user: *mut c_void,
+    in_ptr: *const c_void,
+    out_ptr: *mut c_void,
+    frames: u32,
+    _time: *const sys::oa_time_info,
+    cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    let n = frames as usize * (*cfg).out_channels as usize;
+    ptr::copy_nonoverlapping(in_ptr as *const f32, out_ptr as *mut f32, n);
+    sys::OA_TRUE
+}
+
+/// The worker's buffers, with the PCMs replaced by a captured block and a playback sink.
+struct Period {
+    cfg: sys::oa_stream_config,
+    captured: Vec<u8>,
+    in_hw: Vec<u8>,
+    in_buf: Vec<f32>,
+    out_buf: Vec<f32>,
+    out_hw: Vec<u8>,
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
+}
+
+impl Period {
+    fn new(sample_rate: u32) -> Self {
+        let n = PERIOD * CHANNELS;
+        let mut captured = vec![0; n * 4];
+        I32Converter.convert_out(&test_signal(n), &mut captured);
+        let in_gains = sys::ChannelGains::default();
+        // One channel off unity, so the gain loop is not skipped.
+        in_gains.set(1, 0.5);
+        Self {
+            cfg: sys::oa_stream_config {
+                sample_rate,
+                buffer_frames: PERIOD as u32,
+                in_channels: CHANNELS as u16,
+                out_channels: CHANNELS as u16,
+                format: sys::oa_sample_format::OA_SAMPLE_F32,
+                ..Default::default()
+            },
+            captured,
+            in_hw: vec![0; n * 4],
+            in_buf: vec![0.0; n],
+            out_buf: vec![0.0; n],
+            out_hw: vec![0; n * 4],
+            in_gains,
+            out_gains: sys::ChannelGains::default(),
+        }
+    }
+
+    /// readi, convert, trim, process, trim, convert, writei.
+    fn run(&mut self) {
+        let n = PERIOD * CHANNELS;
+        self.in_hw.copy_from_slice(&self.captured);
+        I32Converter.convert_in(&self.in_hw, &mut self.in_buf);
+        self.in_gains.apply_interleaved(&mut self.in_buf, CHANNELS);
+        self.out_buf.fill(0.0);
+        let ti = sys::oa_time_info::default();
+        unsafe {
+            pass_through(
+                ptr::null_mut(),
+                self.in_buf.as_ptr() as *const c_void,
+                self.out_buf.as_mut_ptr() as *mut c_void,
+                PERIOD as u32,
+                &ti,
+                &self.cfg,
+            );
+        }
+        self.out_gains.apply_interleaved(&mut self.out_buf[..n], CHANNELS);
+        I32Converter.convert_out(&self.out_buf, &mut self.out_hw);
+        black_box(&self.out_hw);
+    }
+}
+
+/// One second of stereo audio per iteration at each rate; a time well under a second is the
+/// headroom the worker leaves for the host.
+fn driver_thread(c: &mut Criterion) {
+    let mut group = c.benchmark_group("driver_thread");
+    for rate in RATES {
+        let mut period = Period::new(rate);
+        let periods = (rate as usize).div_ceil(PERIOD);
+        group.throughput(Throughput::Elements(rate as u64));
+        group.bench_with_input(BenchmarkId::new("scalar", rate), &periods, |b, &periods| {
+            b.iter(|| (0..periods).for_each(|_| period.run()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, conversion, layout, driver_thread);
+criterion_main!(benches);
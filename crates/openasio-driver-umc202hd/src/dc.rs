@@ -0,0 +1,84 @@
+//! First-order high-pass (DC blocker) for the capture path: the preamp sits measurably off zero at
+//! high gain.
+
+/// Environment variable overriding the filter's time constant, in milliseconds.
+pub const TIME_CONSTANT_ENV: &str = "OA_UMC_DC_TAU_MS";
+
+/// Time constant used when `OA_UMC_DC_TAU_MS` is unset or not a positive number.
+pub const DEFAULT_TIME_CONSTANT_S: f32 = 0.010;
+
+/// The time constant to build the filter with, from `OA_UMC_DC_TAU_MS` or the default.
+pub fn time_constant_s() -> f32 {
+    std::env::var(TIME_CONSTANT_ENV)
+        .ok()
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|ms| ms.is_finite() && *ms > 0.0)
+        .map_or(DEFAULT_TIME_CONSTANT_S, |ms| ms / 1000.0)
+}
+
+/// `y[n] = x[n] - x[n-1] + R*y[n-1]`, run independently on each channel of an interleaved buffer.
+pub struct DcBlocker {
+    r: f32,
+    x1: Vec<f32>,
+    y1: Vec<f32>,
+}
+
+impl DcBlocker {
+    /// Filter for `channels` channels at `sample_rate` with time constant `tau_s`, i.e. a cutoff of
+    /// `1 / (2π tau_s)` Hz and `R = 1 - 2π f_c / sample_rate`.
+    pub fn new(channels: usize, sample_rate: u32, tau_s: f32) -> Self {
+        let f_c = 1.0 / (2.0 * std::f32::consts::PI * tau_s);
+        let r = 1.0 - 2.0 * std::f32::consts::PI * f_c / sample_rate.max(1) as f32;
+        Self {
+            r: r.clamp(0.0, 1.0),
+            x1: vec![0.0; channels],
+            y1: vec![0.0; channels],
+        }
+    }
+
+    /// Filter `buf` in place. Its length must be a multiple of the channel count.
+    pub fn process(&mut self, buf: &mut [f32]) {
+        let channels = self.x1.len();
+        if channels == 0 {
+            return;
+        }
+        for frame in buf.chunks_exact_mut(channels) {
+            for (c, s) in frame.iter_mut().enumerate() {
+                let y = *s - self.x1[c] + self.r * self.y1[c];
+                self.x1[c] = *s;
+                self.y1[c] = y;
+                *s = y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_dc_offset_after_warmup() {
+        const RATE: u32 = 48_000;
+        const OFFSET: f32 = 0.25;
+        let mut dc = DcBlocker::new(2, RATE, DEFAULT_TIME_CONSTANT_S);
+        let warmup = RATE as usize / 10;
+        // One second of a 440 Hz sine riding on the offset, same on both channels.
+        let mut buf: Vec<f32> = (0..RATE as usize)
+            .flat_map(|n| {
+                let s = OFFSET
+                    + 0.5 * (2.0 * std::f32::consts::PI * 440.0 * n as f32 / RATE as f32).sin();
+                [s, s]
+            })
+            .collect();
+        for block in buf.chunks_mut(256) {
+            dc.process(block);
+        }
+        let settled = &buf[warmup * 2..];
+        for c in 0..2 {
+            let ch = settled.iter().skip(c).step_by(2);
+            let mean = ch.clone().sum::<f32>() / ch.count() as f32;
+            assert!(mean.abs() < 1e-3, "channel {c} mean {mean}");
+        }
+    }
+}
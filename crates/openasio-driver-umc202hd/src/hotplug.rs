@@ -0,0 +1,94 @@
+//! Watches `/dev/snd` for sound cards coming and going, for the host's `device_list_changed`.
+use openasio_sys as sys;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the watcher wakes to check whether it should exit.
+const POLL: Duration = Duration::from_millis(250);
+/// A card creates or removes several nodes at once; wait this long so they report as one change.
+const SETTLE: Duration = Duration::from_millis(200);
+
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Start watching; `changed` runs on the watcher thread once per batch of card changes.
+    pub fn spawn(log: sys::LogSink, changed: impl Fn() + Send + 'static) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let wd = unsafe {
+            libc::inotify_add_watch(fd, c"/dev/snd".as_ptr(), libc::IN_CREATE | libc::IN_DELETE)
+        };
+        if wd < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !flag.load(Ordering::Acquire) {
+                if !wait_readable(fd) || !drain(fd) {
+                    continue;
+                }
+                std::thread::sleep(SETTLE);
+                drain(fd);
+                log.emit(log::Level::Info, format_args!("sound card list changed"));
+                changed();
+            }
+            unsafe { libc::close(fd) };
+        });
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Wait up to `POLL` for inotify events.
+fn wait_readable(fd: i32) -> bool {
+    let mut pfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut pfd, 1, POLL.as_millis() as i32) > 0 }
+}
+
+/// Read all pending events; true if any of them was a card's control node appearing or going.
+fn drain(fd: i32) -> bool {
+    let mut buf = [0u8; 4096];
+    let mut card = false;
+    loop {
+        let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n <= 0 {
+            return card;
+        }
+        let mut off = 0;
+        while off + std::mem::size_of::<libc::inotify_event>() <= n as usize {
+            let ev =
+                unsafe { (buf.as_ptr().add(off) as *const libc::inotify_event).read_unaligned() };
+            let name_at = off + std::mem::size_of::<libc::inotify_event>();
+            let name = &buf[name_at..(name_at + ev.len as usize).min(n as usize)];
+            // Every card has exactly one controlC<n>; its PCM nodes would repeat the news.
+            card |= name.starts_with(b"controlC");
+            off = name_at + ev.len as usize;
+        }
+    }
+}
@@ -1,23 +1,54 @@
 //! OpenASIO driver specialized for the Behringer UMC202HD USB interface (ALSA backend).
 #![allow(clippy::missing_safety_doc)]
+mod dc;
+mod hotplug;
+mod midi;
+
 use alsa::device_name::HintIter;
-use alsa::pcm::{Access, Format, HwParams, PCM};
+use alsa::pcm::{Access, Format, HwParams, State, TstampType, PCM};
 use alsa::{Direction as PcmDir, ValueOr};
+use openasio_driver_alsa_common::{self as alsa_common, DriverOptions, FormatConverter, HwBuffer};
 use openasio_sys as sys;
 use std::ffi::CStr;
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 type Result<T> = std::result::Result<T, String>;
 
-const CAP_OUTPUT: u32 = sys::OA_CAP_OUTPUT as u32;
-const CAP_INPUT: u32 = sys::OA_CAP_INPUT as u32;
-const CAP_FULL_DUPLEX: u32 = sys::OA_CAP_FULL_DUPLEX as u32;
-const CAPS: u32 = CAP_OUTPUT | CAP_INPUT | CAP_FULL_DUPLEX;
+const CAPS: sys::OaCaps = sys::OaCaps::OUTPUT
+    .union(sys::OaCaps::INPUT)
+    .union(sys::OaCaps::FULL_DUPLEX)
+    .union(sys::OaCaps::SAMPLE_POSITION)
+    .union(sys::OaCaps::CHANNEL_MASK)
+    .union(sys::OaCaps::EXCLUSIVE);
 
 const SUPPORTED_SAMPLE_RATES: &[u32] = &[44100, 48000, 88200, 96000, 176400, 192000];
+const MIN_BUFFER_FRAMES: u32 = 16;
+const MAX_BUFFER_FRAMES: u32 = 4096;
+/// Formats a host may ask for: i24 is the device's own packed S24_3LE, i32 and i16 run the device
+/// in S32 and S16, and f32 and f64 are converted per block.
+const FORMATS: u32 = sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_F32)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_I32)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_I24)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_I16)
+    | sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_F64);
+const INPUT_CHANNEL_NAMES: [&CStr; 2] = [c"Input 1 (Mic/Line)", c"Input 2 (Mic/Line)"];
+const OUTPUT_CHANNEL_NAMES: [&CStr; 2] = [c"Main Out L", c"Main Out R"];
+const RECONNECT_POLL: Duration = Duration::from_millis(500);
+/// How long stop/close wait for the worker thread before giving up with `OA_ERR_TIMEOUT`.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Log through the host's `log_cb` when it installed one, otherwise through the `log` crate.
+macro_rules! log {
+    ($drv:expr, $lvl:ident, $($arg:tt)+) => {
+        $drv.state.log.emit(log::Level::$lvl, format_args!($($arg)+))
+    };
+}
+
+/// Opens a stream PCM by name, direction and non-blocking flag, as `PCM::new` does.
+type PcmOpener = fn(&str, PcmDir, bool) -> alsa::Result<PCM>;
 
 struct Io {
     cap: Option<PCM>,
@@ -27,41 +58,138 @@ struct Io {
 struct DriverState {
     host: sys::oa_host_callbacks,
     host_user: *mut c_void,
+    log: sys::LogSink,
+    hints: sys::ThreadHints, // applied to the worker thread
+    phase: sys::DriverPhase,
+    cfg_size: usize,   // the host's sizeof(oa_stream_config)
+    period_count: u32, // periods in the device buffer, from set_option
+    options: sys::CreateOptions, // from oa_create_params.options
     dev_name: Option<String>,
+    open_pcm: PcmOpener, // PCM::new; tests stand in for the device with it
     io: Io,
     cfg: sys::oa_stream_config,
+    channel_mask: sys::oa_channel_mask, // applied by the next start
+    host_cfg: sys::oa_stream_config,    // cfg with the masked channel counts, as process sees it
     time0: Instant,
+    dev_time0_ns: u64,
     underruns: AtomicU32,
     overruns: AtomicU32,
-    in_hw: Vec<i32>,
+    frames_processed: AtomicU64, // since the last start
+    in_gains: sys::ChannelGains,
+    out_gains: sys::ChannelGains,
+    dc_filter: AtomicBool,
+    dc: Option<dc::DcBlocker>, // rebuilt by every start for its rate and channel count
+    converter: &'static dyn FormatConverter, // for the format hw_setup negotiated
+    passthrough: bool, // the host format is the device's, so process gets in_hw/out_hw
+    in_hw: HwBuffer,
     in_buf: Vec<f32>,
     out_buf: Vec<f32>,
-    out_hw: Vec<i32>,
+    out_hw: HwBuffer,
     scratch_out: Vec<f32>,
     in_planes: Vec<*const f32>,
     out_planes: Vec<*mut f32>,
+    f64_block: Option<sys::F64Block>, // what process sees when the host asked for f64
     running: AtomicBool,
+    paused: AtomicBool, // the worker plays silence instead of calling process
+    hw_paused: bool,    // the PCMs themselves are paused (snd_pcm_pause)
+    gate: sys::StartGate,
     worker: Option<std::thread::JoinHandle<()>>,
+    device_lost: AtomicBool, // set by the worker on ENODEV, cleared once the device opens again
+    watching: AtomicBool,    // the reconnect thread runs until close_device clears it
+    reconnect_worker: Option<std::thread::JoinHandle<()>>,
+    hotplug: Option<hotplug::Watcher>,
+    midi: Option<midi::Port>, // the card's rawmidi port, while a device is open
+    midi_in: Vec<sys::oa_midi_event>, // capacity midi::MAX_PENDING, never grown
+    midi_since_ns: u64,       // when the worker last drained MIDI input
+    last_error: sys::LastError,
 }
 
 #[repr(C)]
 struct Driver {
-    vt: sys::oa_driver_vtable,
+    vt: *const sys::oa_driver_vtable,
     state: DriverState,
 }
 
 impl DriverState {
-    fn stop_worker(&mut self) {
+    /// Signal the worker to exit and wait up to `STOP_TIMEOUT` for it. Returns false, keeping the
+    /// handle, if it is still running; the PCMs must not be touched in that case.
+    fn stop_worker(&mut self) -> bool {
         self.running.store(false, Ordering::Release);
-        if let Some(handle) = self.worker.take() {
+        // A worker blocked on a paused PCM would never see the request.
+        if std::mem::take(&mut self.hw_paused) {
+            alsa_common::pause_pcms(self.io.cap.as_ref(), self.io.pb.as_ref(), false);
+        }
+        self.paused.store(false, Ordering::Relaxed);
+        let Some(handle) = self.worker.take() else {
+            return true;
+        };
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                self.worker = Some(handle);
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let _ = handle.join();
+        true
+    }
+
+    fn stop_reconnect(&mut self) {
+        self.watching.store(false, Ordering::Release);
+        if let Some(handle) = self.reconnect_worker.take() {
+            handle.thread().unpark();
             let _ = handle.join();
         }
     }
 }
 
+impl DriverOptions for DriverState {
+    fn set_option(&mut self, key: &str, value: &str) -> std::result::Result<(), (i32, String)> {
+        if let Some(r) =
+            alsa_common::set_common_option(&mut self.period_count, &mut self.hints, key, value)
+        {
+            return r;
+        }
+        match key {
+            "access" => {
+                if alsa_common::parse_access(value)? {
+                    return Err((
+                        sys::OA_ERR_UNSUPPORTED,
+                        "mmap access is not implemented".into(),
+                    ));
+                }
+            }
+            _ => return Err((sys::OA_ERR_UNSUPPORTED, format!("unknown option {key:?}"))),
+        }
+        Ok(())
+    }
+
+    fn option(&self, key: &str) -> Option<String> {
+        match key {
+            "access" => Some("rw".into()),
+            _ => alsa_common::common_option(self.period_count, &self.hints, key),
+        }
+    }
+}
+
+impl Driver {
+    /// Log `msg` and keep it for `get_last_error`, then return `code`.
+    fn fail(&mut self, code: i32, msg: String) -> i32 {
+        log!(self, Error, "{msg}");
+        self.state.last_error.set(msg);
+        code
+    }
+}
+
 impl Drop for DriverState {
     fn drop(&mut self) {
-        self.stop_worker();
+        if !self.stop_worker() {
+            if let Some(handle) = self.worker.take() {
+                let _ = handle.join();
+            }
+        }
+        self.stop_reconnect();
     }
 }
 
@@ -108,7 +236,18 @@ fn default_device_name() -> String {
         .unwrap_or_else(|| "hw:UMC202HD".to_string())
 }
 
-fn hw_setup(pcm: &PCM, dir: PcmDir, cfg: &sys::oa_stream_config) -> Result<()> {
+/// Integer streams run the device in their own format and take its buffers as they are.
+fn is_integer(format: sys::oa_sample_format) -> bool {
+    matches!(
+        format,
+        sys::oa_sample_format::OA_SAMPLE_I16
+            | sys::oa_sample_format::OA_SAMPLE_I24
+            | sys::oa_sample_format::OA_SAMPLE_I32
+    )
+}
+
+/// Configure `pcm` for `cfg` with a buffer of `periods` periods.
+fn hw_setup(pcm: &PCM, dir: PcmDir, cfg: &sys::oa_stream_config, periods: u32) -> Result<()> {
     let hwp = HwParams::any(pcm).map_err(|e| e.to_string())?;
     hwp.set_access(Access::RWInterleaved)
         .map_err(|e| e.to_string())?;
@@ -116,49 +255,119 @@ fn hw_setup(pcm: &PCM, dir: PcmDir, cfg: &sys::oa_stream_config) -> Result<()> {
         PcmDir::Capture => cfg.in_channels,
         PcmDir::Playback => cfg.out_channels,
     } as u32;
-    hwp.set_channels(channels).map_err(|e| e.to_string())?;
+    hwp.set_channels(channels)
+        .map_err(|e| format!("{channels} channels not supported: {e}"))?;
     hwp.set_rate(cfg.sample_rate, ValueOr::Nearest)
-        .map_err(|e| e.to_string())?;
-    hwp.set_format(Format::s32()).map_err(|e| e.to_string())?;
+        .map_err(|e| format!("sample rate {} not supported: {e}", cfg.sample_rate))?;
+    // Float streams take the interface's packed 24-bit samples, which save a byte per sample on
+    // the bus, and settle for S32 on PCMs that do not offer them.
+    let mut format = alsa_common::device_format(cfg.format, Format::S243LE);
+    if !is_integer(cfg.format) && hwp.test_format(format).is_err() {
+        format = Format::s32();
+    }
+    hwp.set_format(format)
+        .map_err(|e| format!("{format} format rejected: {e}"))?;
     let period = cfg.buffer_frames as i64;
     if period <= 0 {
         return Err("invalid buffer size".into());
     }
     hwp.set_period_size(period, ValueOr::Nearest)
-        .map_err(|e| e.to_string())?;
-    hwp.set_buffer_size(period * 2).map_err(|e| e.to_string())?;
+        .map_err(|e| format!("period of {period} frames not supported: {e}"))?;
+    let buffer = period * periods as i64;
+    hwp.set_buffer_size(buffer)
+        .map_err(|e| format!("buffer of {buffer} frames not supported: {e}"))?;
     pcm.hw_params(&hwp).map_err(|e| e.to_string())?;
 
     let swp = pcm.sw_params_current().map_err(|e| e.to_string())?;
     swp.set_start_threshold(period).map_err(|e| e.to_string())?;
     swp.set_avail_min(period).map_err(|e| e.to_string())?;
+    // Hardware timestamps feed oa_time_info::device_time_ns; not every plugin supports them.
+    let _ = swp.set_tstamp_mode(true);
+    let _ = swp.set_tstamp_type(TstampType::Monotonic);
     pcm.sw_params(&swp).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-fn i32_to_f32(src: &[i32], dst: &mut [f32]) {
-    const SCALE: f32 = 1.0 / 2147483648.0;
-    for (s, d) in src.iter().zip(dst.iter_mut()) {
-        *d = (*s as f32) * SCALE;
+/// Called from the worker when the PCM reports ENODEV (USB unplug). It only flags the loss and
+/// wakes the reconnect thread, which tells the host; the PCMs stay open until `stop`, `abort` or
+/// `close_device` has joined the worker.
+fn device_lost(state: &DriverState) {
+    state.running.store(false, Ordering::Release);
+    state.device_lost.store(true, Ordering::Release);
+    if let Some(handle) = state.reconnect_worker.as_ref() {
+        handle.thread().unpark();
     }
 }
 
-fn f32_to_i32(src: &[f32], dst: &mut [i32]) {
-    const MAX: f32 = 2147483647.0;
-    for (s, d) in src.iter().zip(dst.iter_mut()) {
-        let mut v = *s;
-        if v >= 1.0 {
-            *d = i32::MAX;
-        } else if v <= -1.0 {
-            *d = i32::MIN;
-        } else {
-            v *= MAX;
-            *d = v.round() as i32;
+/// Start the reconnect thread for `name` unless it is already running. It lives from the first
+/// start until `close_device`, so a device lost while stopped is still polled for.
+unsafe fn spawn_reconnect(selfp: *mut Driver, name: String) {
+    let driver = &mut *selfp;
+    if driver.state.reconnect_worker.is_some() {
+        return;
+    }
+    driver.state.watching.store(true, Ordering::Release);
+    let driver_ptr = selfp as usize;
+    driver.state.reconnect_worker = Some(std::thread::spawn(move || unsafe {
+        reconnect_thread(driver_ptr as *const Driver, name);
+    }));
+}
+
+/// Wait for the worker to flag a lost device, ask the host to reset, then poll until the device
+/// opens again and report its latency.
+unsafe fn reconnect_thread(selfp: *const Driver, name: String) {
+    let driver = &*selfp;
+    while driver.state.watching.load(Ordering::Acquire) {
+        if !driver.state.device_lost.load(Ordering::Acquire) {
+            std::thread::park_timeout(RECONNECT_POLL);
+            continue;
+        }
+        log!(
+            driver,
+            Error,
+            "device disconnected (ENODEV), stopping stream"
+        );
+        if let Some(cb) = driver.state.host.reset_request {
+            cb(driver.state.host_user);
+        }
+        while driver.state.watching.load(Ordering::Acquire) {
+            std::thread::sleep(RECONNECT_POLL);
+            if (driver.state.open_pcm)(&name, PcmDir::Playback, false).is_err() {
+                continue;
+            }
+            log!(driver, Info, "{name} reconnected");
+            driver.state.device_lost.store(false, Ordering::Release);
+            if let Some(cb) = driver.state.host.latency_changed {
+                let frames = driver.state.cfg.buffer_frames;
+                let in_lat = if driver.state.cfg.in_channels > 0 {
+                    frames
+                } else {
+                    0
+                };
+                cb(driver.state.host_user, in_lat, frames);
+            }
+            break;
         }
     }
 }
 
 unsafe fn driver_thread(selfp: *mut Driver) {
+    (*selfp)
+        .state
+        .hints
+        .apply_to_current_thread(&(*selfp).state.log);
+    match (*selfp).state.gate.wait(&(*selfp).state.running) {
+        None => return,
+        Some(true) => {
+            let state = &(*selfp).state;
+            let samples = state.cfg.buffer_frames as usize * state.cfg.out_channels as usize;
+            let silence = vec![0u8; samples * state.converter.sample_bytes()];
+            alsa_common::start_devices(state.io.cap.as_ref(), state.io.pb.as_ref(), |pb| {
+                let _ = pb.io_bytes().writei(&silence);
+            });
+        }
+        Some(false) => {}
+    }
     loop {
         let driver = &mut *selfp;
         if !driver.state.running.load(Ordering::Acquire) {
@@ -167,71 +376,150 @@ unsafe fn driver_thread(selfp: *mut Driver) {
 
         let frames = driver.state.cfg.buffer_frames as usize;
         let ich = driver.state.cfg.in_channels as usize;
+        let hich = driver.state.host_cfg.in_channels as usize; // enabled by the channel mask
         let och = driver.state.cfg.out_channels as usize;
+        // start_stream sized the buffers with checked multiplies.
+        debug_assert!(frames * ich.max(och) < isize::MAX as usize);
         let interleaved = matches!(
             driver.state.cfg.layout,
             sys::oa_buffer_layout::OA_BUF_INTERLEAVED
         );
+        let passthrough = driver.state.passthrough;
+        let conv = driver.state.converter;
+        let width = conv.sample_bytes();
 
+        let mut lost = false;
         if let Some(cap) = driver.state.io.cap.as_ref() {
             let total = frames * ich;
             let res = cap
-                .io_i32()
-                .and_then(|io| io.readi(&mut driver.state.in_hw[..total]));
+                .io_bytes()
+                .readi(&mut driver.state.in_hw.bytes_mut()[..total * width]);
             match res {
                 Ok(read) => {
-                    let samples = read * ich;
-                    i32_to_f32(
-                        &driver.state.in_hw[..samples],
-                        &mut driver.state.in_buf[..samples],
-                    );
-                    if samples < total {
+                    // Masked-off channels are dropped before conversion, so gains and the DC
+                    // filter index the packed channels just as process does.
+                    let in_mask = driver.state.channel_mask.in_mask;
+                    driver.state.in_hw.compact(width, read, ich, in_mask);
+                    let samples = read * hich;
+                    let total = frames * hich;
+                    if passthrough {
+                        driver.state.in_hw.bytes_mut()[samples * width..total * width].fill(0);
+                    } else {
+                        conv.convert_in(
+                            &driver.state.in_hw.bytes()[..samples * width],
+                            &mut driver.state.in_buf[..samples],
+                        );
+                        driver
+                            .state
+                            .in_gains
+                            .apply_interleaved(&mut driver.state.in_buf[..samples], hich);
+                        if driver.state.dc_filter.load(Ordering::Relaxed) {
+                            if let Some(dc) = driver.state.dc.as_mut() {
+                                dc.process(&mut driver.state.in_buf[..samples]);
+                            }
+                        }
                         driver.state.in_buf[samples..total].fill(0.0);
                     }
                 }
                 Err(e) => {
-                    if e.errno() == nix::errno::Errno::EPIPE as i32 {
-                        let _ = cap.prepare();
-                        driver.state.overruns.fetch_add(1, Ordering::Relaxed);
+                    if let Some(how) = alsa_common::recover_pcm(cap, e.errno()) {
+                        log!(driver, Warn, "capture overrun, {how}");
+                        sys::report_xrun(
+                            &driver.state.host,
+                            driver.state.host_user,
+                            &driver.state.overruns,
+                            sys::oa_xrun_kind::OA_XRUN_OVERRUN,
+                        );
+                    } else if e.errno() == nix::errno::Errno::ENODEV as i32 {
+                        lost = true;
                     }
+                    driver.state.in_hw.bytes_mut()[..total * width].fill(0);
                     driver.state.in_buf[..total].fill(0.0);
                 }
             }
         }
+        if lost {
+            device_lost(&driver.state);
+            break;
+        }
 
-        if interleaved {
+        if passthrough {
+            driver.state.out_hw.bytes_mut()[..frames * och * width].fill(0);
+        } else if interleaved || driver.state.f64_block.is_some() {
             driver.state.out_buf[..frames * och].fill(0.0);
         } else {
             driver.state.scratch_out[..frames * och].fill(0.0);
         }
 
+        let device_time = match driver.state.io.pb.as_ref() {
+            Some(pb) => alsa_common::device_time_ns(pb, driver.state.dev_time0_ns),
+            None => 0,
+        };
         let ti = sys::oa_time_info {
             host_time_ns: driver.state.time0.elapsed().as_nanos() as u64,
-            device_time_ns: 0,
+            device_time_ns: device_time,
             underruns: driver.state.underruns.load(Ordering::Relaxed),
             overruns: driver.state.overruns.load(Ordering::Relaxed),
+            sample_position: driver.state.frames_processed.load(Ordering::Relaxed),
+            ..Default::default()
         };
 
-        if let Some(cb) = driver.state.host.process {
-            let in_ptr: *const c_void = if ich == 0 {
+        let paused = driver.state.paused.load(Ordering::Acquire);
+        if paused {
+            // The output buffers were cleared above; play that.
+        } else if let Some(cb) = driver.state.host.process {
+            let wide = driver.state.f64_block.as_mut().map(|block| {
+                block.load_input(&driver.state.in_buf[..frames * hich], hich);
+                block.silence_output();
+                block.host_ptrs()
+            });
+            let in_ptr: *const c_void = if let Some((in_ptr, _)) = wide {
+                in_ptr
+            } else if hich == 0 {
                 ptr::null()
+            } else if passthrough {
+                driver.state.in_hw.bytes().as_ptr() as *const c_void
             } else if interleaved {
                 driver.state.in_buf.as_ptr() as *const c_void
             } else {
                 driver.state.in_planes.as_ptr() as *const c_void
             };
-            let out_ptr: *mut c_void = if interleaved {
+            let out_ptr: *mut c_void = if let Some((_, out_ptr)) = wide {
+                out_ptr
+            } else if passthrough {
+                driver.state.out_hw.bytes_mut().as_mut_ptr() as *mut c_void
+            } else if interleaved {
                 driver.state.out_buf.as_mut_ptr() as *mut c_void
             } else {
                 driver.state.out_planes.as_mut_ptr() as *mut c_void
             };
+            if let (Some(port), Some(midi_in)) =
+                (driver.state.midi.as_ref(), driver.state.host.midi_in)
+            {
+                let now = alsa_common::monotonic_ns();
+                let since = std::mem::replace(&mut driver.state.midi_since_ns, now);
+                driver.state.midi_in.clear();
+                port.drain_input(
+                    &mut driver.state.midi_in,
+                    since,
+                    driver.state.cfg.sample_rate,
+                    frames as u32,
+                );
+                if !driver.state.midi_in.is_empty() {
+                    midi_in(
+                        driver.state.host_user,
+                        driver.state.midi_in.as_ptr(),
+                        driver.state.midi_in.len() as u32,
+                    );
+                }
+            }
             let keep = cb(
                 driver.state.host_user,
                 in_ptr,
                 out_ptr,
                 frames as u32,
                 &ti as *const _,
-                &driver.state.cfg as *const _,
+                &driver.state.host_cfg as *const _,
             );
             if keep == sys::OA_FALSE {
                 driver.state.running.store(false, Ordering::Release);
@@ -239,90 +527,179 @@ unsafe fn driver_thread(selfp: *mut Driver) {
             }
         }
 
-        if !interleaved {
-            let frames_usize = frames;
-            for f in 0..frames_usize {
-                for c in 0..och {
-                    let plane = driver.state.scratch_out.as_ptr().add(c * frames_usize);
-                    driver.state.out_buf[f * och + c] = *plane.add(f);
-                }
+        if let Some(block) = driver.state.f64_block.as_ref() {
+            if !paused {
+                block.store_output(&mut driver.state.out_buf[..frames * och], och);
             }
+        } else if !interleaved && !passthrough {
+            sys::layout::interleave(
+                &driver.state.scratch_out,
+                frames,
+                och,
+                &mut driver.state.out_buf[..frames * och],
+            );
         }
 
-        f32_to_i32(
-            &driver.state.out_buf[..frames * och],
-            &mut driver.state.out_hw[..frames * och],
-        );
+        if !passthrough {
+            driver
+                .state
+                .out_gains
+                .apply_interleaved(&mut driver.state.out_buf[..frames * och], och);
+            conv.convert_out(
+                &driver.state.out_buf[..frames * och],
+                &mut driver.state.out_hw.bytes_mut()[..frames * och * width],
+            );
+        }
 
         if let Some(pb) = driver.state.io.pb.as_ref() {
             let res = pb
-                .io_i32()
-                .and_then(|io| io.writei(&driver.state.out_hw[..frames * och]));
+                .io_bytes()
+                .writei(&driver.state.out_hw.bytes()[..frames * och * width]);
             if let Err(e) = res {
-                if e.errno() == nix::errno::Errno::EPIPE as i32 {
-                    let _ = pb.prepare();
-                    driver.state.underruns.fetch_add(1, Ordering::Relaxed);
+                if let Some(how) = alsa_common::recover_pcm(pb, e.errno()) {
+                    log!(driver, Warn, "playback underrun, {how}");
+                    sys::report_xrun(
+                        &driver.state.host,
+                        driver.state.host_user,
+                        &driver.state.underruns,
+                        sys::oa_xrun_kind::OA_XRUN_UNDERRUN,
+                    );
+                } else if e.errno() == nix::errno::Errno::ENODEV as i32 {
+                    lost = true;
                 }
             }
         }
+        if lost {
+            device_lost(&driver.state);
+            break;
+        }
+        if !paused {
+            driver
+                .state
+                .frames_processed
+                .fetch_add(frames as u64, Ordering::Relaxed);
+        }
     }
 }
 
-unsafe extern "C" fn get_caps(_: *mut sys::oa_driver) -> u32 {
-    CAPS
+unsafe extern "C" fn get_caps(selfp: *mut sys::oa_driver) -> u32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "get_caps");
+    let card = alsa_common::ctl_name(driver.state.dev_name.as_deref().unwrap_or("hw:UMC202HD"));
+    let mut caps = CAPS;
+    if matches!(alsa_common::clock_sources(&card), Ok(sources) if !sources.is_empty()) {
+        caps |= sys::OaCaps::CLOCK_SELECT;
+    }
+    if driver.state.midi.is_some() {
+        caps |= sys::OaCaps::MIDI;
+    }
+    caps.bits()
 }
 
-unsafe extern "C" fn query_devices(_selfp: *mut sys::oa_driver, buf: *mut i8, len: usize) -> i32 {
-    let names = enumerate_umc202hd_devices().join("\n");
-    let bytes = names.as_bytes();
-    let n = bytes.len().min(len.saturating_sub(1));
-    if n > 0 {
-        ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, n);
-    }
-    if len > 0 {
-        *buf.add(n) = 0;
+unsafe extern "C" fn query_devices(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    log!(driver, Debug, "query_devices(len={len})");
+    if buf.is_null() && len > 0 {
+        return driver.fail(
+            sys::OA_ERR_INVALID_ARG,
+            "query_devices with a null buffer".into(),
+        );
     }
-    sys::OA_OK
+    let names = enumerate_umc202hd_devices().join("\n");
+    sys::write_device_list(&names, buf, len)
 }
 
-unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const i8) -> i32 {
+unsafe extern "C" fn open_device(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
     let driver = &mut *(selfp as *mut Driver);
+    driver.state.last_error.clear();
+    if driver.state.phase != sys::DriverPhase::Created {
+        let msg = format!("open_device called while {:?}", driver.state.phase);
+        return driver.fail(sys::OA_ERR_STATE, msg);
+    }
     let chosen = if name.is_null() {
-        default_device_name()
+        let device = alsa_common::device_option(&driver.state.options);
+        device.unwrap_or_else(default_device_name)
     } else {
         CStr::from_ptr(name).to_string_lossy().to_string()
     };
+    log!(driver, Debug, "open_device(name={chosen:?})");
+    if let Some(port) = midi::port_name(&alsa_common::ctl_name(&chosen)) {
+        match midi::Port::open(&port, driver.state.log) {
+            Ok(p) => driver.state.midi = Some(p),
+            Err(e) => log!(driver, Debug, "no MIDI port {port}: {e}"),
+        }
+    }
     driver.state.dev_name = Some(chosen);
+    driver.state.phase = sys::DriverPhase::Opened;
     sys::OA_OK
 }
 
 unsafe extern "C" fn close_device(selfp: *mut sys::oa_driver) -> i32 {
     let driver = &mut *(selfp as *mut Driver);
-    driver.state.stop_worker();
+    log!(driver, Debug, "close_device");
+    if driver.state.phase == sys::DriverPhase::Running {
+        log!(
+            driver,
+            Error,
+            "close_device called while {:?}",
+            driver.state.phase
+        );
+        return sys::OA_ERR_STATE;
+    }
+    if !driver.state.stop_worker() {
+        log!(driver, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    driver.state.stop_reconnect();
     driver.state.io.cap = None;
     driver.state.io.pb = None;
+    driver.state.midi = None;
+    driver.state.phase = sys::DriverPhase::Created;
     sys::OA_OK
 }
 
 unsafe extern "C" fn get_default_config(
-    _selfp: *mut sys::oa_driver,
+    selfp: *mut sys::oa_driver,
     out: *mut sys::oa_stream_config,
 ) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "get_default_config");
     if out.is_null() {
         return sys::OA_ERR_INVALID_ARG;
     }
-    (*out).sample_rate = 48000;
-    (*out).buffer_frames = 128;
-    (*out).in_channels = 2;
-    (*out).out_channels = 2;
-    (*out).format = sys::oa_sample_format::OA_SAMPLE_F32;
-    (*out).layout = sys::oa_buffer_layout::OA_BUF_INTERLEAVED;
+    let mut cfg = sys::oa_stream_config::default();
+    // A device this driver cannot open right now (streaming, or gone) keeps the float default.
+    let name = driver.state.dev_name.clone().unwrap_or_else(default_device_name);
+    if let Ok(pcm) = PCM::new(&name, PcmDir::Playback, true) {
+        let packed = HwParams::any(&pcm).is_ok_and(|hwp| hwp.test_format(Format::S243LE).is_ok());
+        cfg.format = if packed {
+            sys::oa_sample_format::OA_SAMPLE_I24
+        } else {
+            alsa_common::default_host_format(&pcm, Format::s32())
+        };
+    }
+    sys::write_stream_config(&cfg, out, driver.state.cfg_size);
     sys::OA_OK
 }
 
 fn validate_config(cfg: &sys::oa_stream_config) -> Result<()> {
-    if cfg.format != sys::oa_sample_format::OA_SAMPLE_F32 {
-        return Err("UMC202HD driver only supports float32".into());
+    // Ahead of the generic checks, which would reject it with a vaguer message.
+    if cfg.buffer_frames == 0 {
+        return Err("buffer must be > 0".into());
+    }
+    if sys::validate_stream_config(cfg) != sys::OA_OK {
+        return Err("invalid stream config".into());
+    }
+    if sys::validate_channel_counts(cfg, 1) != sys::OA_OK {
+        return Err("invalid channel counts".into());
+    }
+    // Float streams take either layout; validate_stream_config turned away unknown formats.
+    if is_integer(cfg.format) && cfg.layout != sys::oa_buffer_layout::OA_BUF_INTERLEAVED {
+        return Err("UMC202HD integer streams must be interleaved".into());
     }
     if cfg.out_channels != 2 {
         return Err("UMC202HD playback requires 2 channels".into());
@@ -331,25 +708,70 @@ fn validate_config(cfg: &sys::oa_stream_config) -> Result<()> {
         return Err("UMC202HD capture supports 0 or 2 channels".into());
     }
     if !SUPPORTED_SAMPLE_RATES.contains(&cfg.sample_rate) {
-        return Err("unsupported sample rate".into());
+        return Err(format!("unsupported sample rate {}", cfg.sample_rate));
     }
-    if cfg.buffer_frames == 0 {
-        return Err("buffer must be > 0".into());
+    if !(MIN_BUFFER_FRAMES..=MAX_BUFFER_FRAMES).contains(&cfg.buffer_frames) {
+        return Err("buffer size out of range".into());
     }
     Ok(())
 }
 
 unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_stream_config) -> i32 {
+    start_stream(selfp, cfg, false)
+}
+
+unsafe extern "C" fn arm_start(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+) -> i32 {
+    start_stream(selfp, cfg, true)
+}
+
+/// Shared by `start` and `arm_start`; an `armed` worker waits for `trigger_start` before it
+/// touches the PCMs.
+unsafe fn start_stream(
+    selfp: *mut sys::oa_driver,
+    cfg: *const sys::oa_stream_config,
+    armed: bool,
+) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    driver.state.last_error.clear();
     if cfg.is_null() {
-        return sys::OA_ERR_INVALID_ARG;
+        return driver.fail(sys::OA_ERR_INVALID_ARG, "start without a stream config".into());
     }
-    let cfg = &*cfg;
-    let driver = &mut *(selfp as *mut Driver);
-    if validate_config(cfg).is_err() {
-        return sys::OA_ERR_UNSUPPORTED;
+    let cfg = &sys::read_stream_config(cfg, driver.state.cfg_size);
+    log!(
+        driver,
+        Debug,
+        "{}(sample_rate={}, buffer_frames={}, in={}, out={}, format={}, layout={})",
+        if armed { "arm_start" } else { "start" },
+        cfg.sample_rate,
+        cfg.buffer_frames,
+        cfg.in_channels,
+        cfg.out_channels,
+        cfg.format,
+        cfg.layout
+    );
+    if sys::oa_stream_config_check(cfg) != sys::OA_OK {
+        return driver.fail(sys::OA_ERR_INVALID_ARG, "rejecting invalid stream config".into());
+    }
+    if let Err(e) = validate_config(cfg) {
+        return driver.fail(
+            sys::OA_ERR_UNSUPPORTED,
+            format!("rejecting stream config: {e}"),
+        );
     }
 
-    driver.state.stop_worker();
+    if !driver.state.phase.can_start() {
+        let msg = format!("start called while {:?}", driver.state.phase);
+        return driver.fail(driver.state.phase.start_error(), msg);
+    }
+    if !driver.state.stop_worker() {
+        return driver.fail(
+            sys::OA_ERR_TIMEOUT,
+            format!("previous worker did not exit within {STOP_TIMEOUT:?}"),
+        );
+    }
     driver.state.io.cap = None;
     driver.state.io.pb = None;
 
@@ -358,41 +780,90 @@ unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_strea
         .dev_name
         .clone()
         .unwrap_or_else(default_device_name);
+    let name = match alsa_common::stream_pcm_name(&name, cfg.exclusive != sys::OA_FALSE) {
+        Ok(n) => n,
+        Err(why) => {
+            return driver.fail(
+                sys::OA_ERR_DEVICE,
+                format!("{}: {why}", sys::OA_EXCLUSIVE_UNAVAILABLE),
+            );
+        }
+    };
 
-    let pb = match PCM::new(&name, PcmDir::Playback, false) {
+    let pb = match (driver.state.open_pcm)(&name, PcmDir::Playback, false) {
         Ok(p) => p,
-        Err(_) => return sys::OA_ERR_DEVICE,
+        Err(e) => {
+            return driver.fail(
+                sys::open_errno_result(e.errno()),
+                format!("failed to open playback PCM {name}: {e}"),
+            );
+        }
     };
     let cap = if cfg.in_channels > 0 {
-        match PCM::new(&name, PcmDir::Capture, false) {
+        match (driver.state.open_pcm)(&name, PcmDir::Capture, false) {
             Ok(c) => Some(c),
-            Err(_) => return sys::OA_ERR_DEVICE,
+            Err(e) => {
+                return driver.fail(
+                    sys::open_errno_result(e.errno()),
+                    format!("failed to open capture PCM {name}: {e}"),
+                );
+            }
         }
     } else {
         None
     };
 
-    if hw_setup(&pb, PcmDir::Playback, cfg).is_err() {
-        return sys::OA_ERR_BACKEND;
+    if let Err(e) = hw_setup(&pb, PcmDir::Playback, cfg, driver.state.period_count) {
+        return driver.fail(
+            sys::OA_ERR_BACKEND,
+            format!("playback hw_setup on {name} failed: {e}"),
+        );
     }
     if let Some(ref c) = cap {
-        if hw_setup(c, PcmDir::Capture, cfg).is_err() {
-            return sys::OA_ERR_BACKEND;
+        if let Err(e) = hw_setup(c, PcmDir::Capture, cfg, driver.state.period_count) {
+            return driver.fail(
+                sys::OA_ERR_BACKEND,
+                format!("capture hw_setup on {name} failed: {e}"),
+            );
         }
     }
 
     let frames = cfg.buffer_frames as usize;
     let ich = cfg.in_channels as usize;
     let och = cfg.out_channels as usize;
+    let host_cfg = driver.state.channel_mask.apply(cfg);
+    if cfg.in_channels > 0 && host_cfg.in_channels == 0 {
+        return driver.fail(
+            sys::OA_ERR_INVALID_ARG,
+            "the channel mask disables every input".into(),
+        );
+    }
+    let hich = host_cfg.in_channels as usize;
+    // driver_thread slices these buffers with unchecked products of the same values.
+    let (Some(in_len), Some(out_len)) = (frames.checked_mul(ich.max(1)), frames.checked_mul(och))
+    else {
+        return driver.fail(
+            sys::OA_ERR_INVALID_ARG,
+            format!("buffer size overflow: {frames} frames x {ich}/{och} channels"),
+        );
+    };
 
-    driver.state.in_hw.resize(frames * ich.max(1), 0);
-    driver.state.in_buf.resize(frames * ich.max(1), 0.0);
-    driver.state.out_buf.resize(frames * och, 0.0);
-    driver.state.out_hw.resize(frames * och, 0);
-    driver.state.scratch_out.resize(frames * och, 0.0);
+    let converter = match alsa_common::converter_of(&pb) {
+        Ok(c) => c,
+        Err(e) => return driver.fail(sys::OA_ERR_BACKEND, format!("{name}: {e}")),
+    };
+    driver.state.converter = converter;
+    driver.state.passthrough =
+        alsa_common::native_format(converter.format()) == Some(cfg.format);
+    let width = converter.sample_bytes();
+    driver.state.in_hw.resize(in_len * width);
+    driver.state.in_buf.resize(in_len, 0.0);
+    driver.state.out_buf.resize(out_len, 0.0);
+    driver.state.out_hw.resize(out_len * width);
+    driver.state.scratch_out.resize(out_len, 0.0);
     driver.state.in_planes.clear();
-    if ich > 0 {
-        for c in 0..ich {
+    if hich > 0 {
+        for c in 0..hich {
             let ptr = driver.state.in_buf.as_ptr().wrapping_add(c);
             driver.state.in_planes.push(ptr);
         }
@@ -409,26 +880,190 @@ unsafe extern "C" fn start(selfp: *mut sys::oa_driver, cfg: *const sys::oa_strea
         }
     }
 
+    driver.state.f64_block = (cfg.format == sys::oa_sample_format::OA_SAMPLE_F64)
+        .then(|| sys::F64Block::new(&host_cfg));
+
+    driver.state.dc = Some(dc::DcBlocker::new(
+        hich,
+        cfg.sample_rate,
+        dc::time_constant_s(),
+    ));
     driver.state.cfg = *cfg;
+    driver.state.host_cfg = host_cfg;
     driver.state.time0 = Instant::now();
+    driver.state.dev_time0_ns = alsa_common::monotonic_ns();
     driver.state.underruns.store(0, Ordering::Relaxed);
     driver.state.overruns.store(0, Ordering::Relaxed);
+    driver.state.frames_processed.store(0, Ordering::Relaxed);
+    driver.state.midi_since_ns = alsa_common::monotonic_ns();
     driver.state.io.pb = Some(pb);
     driver.state.io.cap = cap;
+    if armed {
+        driver.state.gate.arm();
+    } else {
+        driver.state.gate.open();
+    }
+    spawn_reconnect(selfp as *mut Driver, name);
     driver.state.running.store(true, Ordering::Release);
-    let driver_ptr = selfp as *mut Driver;
+    let driver_ptr = selfp as *mut Driver as usize;
     driver.state.worker = Some(std::thread::spawn(move || unsafe {
-        driver_thread(driver_ptr);
+        driver_thread(driver_ptr as *mut Driver);
     }));
 
+    driver.state.phase = sys::DriverPhase::Running;
     sys::OA_OK
 }
 
+unsafe extern "C" fn trigger_start(selfp: *mut sys::oa_driver, start_ns: u64) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "trigger_start({start_ns})");
+    if driver.state.phase != sys::DriverPhase::Running || !driver.state.gate.trigger(start_ns) {
+        log!(driver, Error, "trigger_start without a pending arm_start");
+        return sys::OA_ERR_STATE;
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_status(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_stream_status,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    let status = sys::oa_stream_status {
+        running: driver.state.running.load(Ordering::Acquire) as sys::oa_bool,
+        actual_sample_rate: driver.state.cfg.sample_rate,
+        actual_buffer_frames: driver.state.cfg.buffer_frames,
+        frames_processed: driver.state.frames_processed.load(Ordering::Relaxed),
+        underruns: driver.state.underruns.load(Ordering::Relaxed),
+        overruns: driver.state.overruns.load(Ordering::Relaxed),
+        ..Default::default()
+    };
+    sys::write_stream_status(&status, out)
+}
+
 unsafe extern "C" fn stop(selfp: *mut sys::oa_driver) -> i32 {
     let driver = &mut *(selfp as *mut Driver);
-    driver.state.stop_worker();
+    log!(driver, Debug, "stop");
+    if driver.state.phase != sys::DriverPhase::Running {
+        log!(driver, Error, "stop called while {:?}", driver.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    if !driver.state.stop_worker() {
+        log!(driver, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
+    // Play out what the worker already queued; closing the PCM would discard it.
+    if let Some(pb) = driver.state.io.pb.as_ref() {
+        if pb.state() == State::Running {
+            let _ = pb.drain();
+        }
+    }
+    driver.state.io.cap = None;
+    driver.state.io.pb = None;
+    driver.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn abort(selfp: *mut sys::oa_driver) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    log!(driver, Debug, "abort");
+    if driver.state.phase != sys::DriverPhase::Running {
+        log!(driver, Error, "abort called while {:?}", driver.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    // Dropping discards the queued frames and fails a worker blocked in readi/writei with
+    // EBADFD, so it sees `running` go false without waiting out the period.
+    driver.state.running.store(false, Ordering::Release);
+    for pcm in driver.state.io.cap.iter().chain(driver.state.io.pb.iter()) {
+        let _ = pcm.drop();
+    }
+    if !driver.state.stop_worker() {
+        log!(driver, Error, "worker did not exit within {STOP_TIMEOUT:?}");
+        return sys::OA_ERR_TIMEOUT;
+    }
     driver.state.io.cap = None;
     driver.state.io.pb = None;
+    driver.state.phase = sys::DriverPhase::Configured;
+    sys::OA_OK
+}
+
+unsafe extern "C" fn get_last_error(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    driver.state.last_error.write(buf, len)
+}
+
+unsafe extern "C" fn set_option(
+    selfp: *mut sys::oa_driver,
+    key: *const c_char,
+    value: *const c_char,
+) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    let (Some(key), Some(value)) = (sys::option_arg(key), sys::option_arg(value)) else {
+        return driver.fail(sys::OA_ERR_INVALID_ARG, "set_option without text".into());
+    };
+    log!(driver, Debug, "set_option({key}={value})");
+    match driver.state.set_option(key, value) {
+        Ok(()) => sys::OA_OK,
+        Err((code, msg)) => driver.fail(code, msg),
+    }
+}
+
+unsafe extern "C" fn get_option(
+    selfp: *mut sys::oa_driver,
+    key: *const c_char,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    match sys::option_arg(key).and_then(|key| driver.state.option(key)) {
+        Some(value) => sys::write_option(&value, buf, len),
+        None => sys::OA_ERR_UNSUPPORTED,
+    }
+}
+
+unsafe extern "C" fn pause(selfp: *mut sys::oa_driver) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    log!(driver, Debug, "pause");
+    if driver.state.phase != sys::DriverPhase::Running
+        || driver.state.paused.load(Ordering::Relaxed)
+    {
+        log!(driver, Error, "pause called while {:?}", driver.state.phase);
+        return sys::OA_ERR_STATE;
+    }
+    // Set first, so a worker that was between blocks plays silence rather than calling process.
+    driver.state.paused.store(true, Ordering::Release);
+    driver.state.hw_paused = alsa_common::pause_pcms(
+        driver.state.io.cap.as_ref(),
+        driver.state.io.pb.as_ref(),
+        true,
+    );
+    if !driver.state.hw_paused {
+        log!(driver, Debug, "PCM cannot pause; playing silence instead");
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn resume(selfp: *mut sys::oa_driver) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    log!(driver, Debug, "resume");
+    if driver.state.phase != sys::DriverPhase::Running
+        || !driver.state.paused.load(Ordering::Relaxed)
+    {
+        log!(driver, Error, "resume called while not paused");
+        return sys::OA_ERR_STATE;
+    }
+    if std::mem::take(&mut driver.state.hw_paused) {
+        alsa_common::pause_pcms(
+            driver.state.io.cap.as_ref(),
+            driver.state.io.pb.as_ref(),
+            false,
+        );
+    }
+    driver.state.paused.store(false, Ordering::Release);
     sys::OA_OK
 }
 
@@ -438,6 +1073,7 @@ unsafe extern "C" fn get_latency(
     out_lat: *mut u32,
 ) -> i32 {
     let driver = &mut *(selfp as *mut Driver);
+    log!(driver, Debug, "get_latency");
     if !in_lat.is_null() {
         *in_lat = if driver.state.cfg.in_channels > 0 {
             driver.state.cfg.buffer_frames
@@ -451,15 +1087,346 @@ unsafe extern "C" fn get_latency(
     sys::OA_OK
 }
 
-unsafe extern "C" fn set_sr(_: *mut sys::oa_driver, _: u32) -> i32 {
+unsafe extern "C" fn set_sr(selfp: *mut sys::oa_driver, sr: u32) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "set_sample_rate({sr})");
     sys::OA_ERR_UNSUPPORTED
 }
 
-unsafe extern "C" fn set_buf(_: *mut sys::oa_driver, _: u32) -> i32 {
+unsafe extern "C" fn set_buf(selfp: *mut sys::oa_driver, frames: u32) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "set_buffer_frames({frames})");
     sys::OA_ERR_UNSUPPORTED
 }
 
-#[no_mangle]
+unsafe extern "C" fn query_supported_configs(
+    selfp: *mut sys::oa_driver,
+    out: *mut sys::oa_supported_configs,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "query_supported_configs");
+    if out.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    (*out).sample_rates = SUPPORTED_SAMPLE_RATES.as_ptr();
+    (*out).sample_rate_count = SUPPORTED_SAMPLE_RATES.len() as u32;
+    (*out).min_buffer_frames = MIN_BUFFER_FRAMES;
+    (*out).max_buffer_frames = MAX_BUFFER_FRAMES;
+    (*out).formats = FORMATS;
+    sys::OA_OK
+}
+
+/// Every rate, with and without capture: `validate_config` takes 0 or 2 inputs but never 1, which
+/// one range cannot express.
+fn config_ranges() -> Vec<sys::oa_config_range> {
+    SUPPORTED_SAMPLE_RATES
+        .iter()
+        .flat_map(|&rate| {
+            [0, 2].map(|ins| sys::oa_config_range {
+                min_rate: rate,
+                max_rate: rate,
+                min_channels_in: ins,
+                max_channels_in: ins,
+                min_channels_out: 2,
+                max_channels_out: 2,
+                formats_mask: FORMATS,
+                min_buffer_frames: MIN_BUFFER_FRAMES,
+                max_buffer_frames: MAX_BUFFER_FRAMES,
+            })
+        })
+        .collect()
+}
+
+unsafe extern "C" fn query_config_ranges(
+    selfp: *mut sys::oa_driver,
+    buf: *mut sys::oa_config_range,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "query_config_ranges(len={len})");
+    sys::write_config_ranges(&config_ranges(), buf, len)
+}
+
+unsafe extern "C" fn get_channel_names(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    names: *mut *const c_char,
+    count: u32,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(
+        driver,
+        Debug,
+        "get_channel_names(direction={direction}, count={count})"
+    );
+    let table = match direction {
+        sys::OA_DIR_INPUT => &INPUT_CHANNEL_NAMES,
+        sys::OA_DIR_OUTPUT => &OUTPUT_CHANNEL_NAMES,
+        _ => return sys::OA_ERR_INVALID_ARG,
+    };
+    if names.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let n = table.len().min(count as usize);
+    for (i, name) in table.iter().take(n).enumerate() {
+        *names.add(i) = name.as_ptr();
+    }
+    n as i32
+}
+
+unsafe extern "C" fn get_channel_name(
+    _selfp: *mut sys::oa_driver,
+    direction: sys::oa_direction,
+    index: u32,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let table = match direction {
+        sys::oa_direction::OA_DIR_INPUT => &INPUT_CHANNEL_NAMES,
+        sys::oa_direction::OA_DIR_OUTPUT => &OUTPUT_CHANNEL_NAMES,
+        _ => return sys::OA_ERR_INVALID_ARG,
+    };
+    match table.get(index as usize) {
+        Some(name) => sys::write_channel_name(&name.to_string_lossy(), buf, len),
+        None => sys::OA_ERR_INVALID_ARG,
+    }
+}
+
+unsafe extern "C" fn query_supported_rates(
+    selfp: *mut sys::oa_driver,
+    buf: *mut u32,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "query_supported_rates(len={len})");
+    sys::write_supported_rates(SUPPORTED_SAMPLE_RATES, buf, len)
+}
+
+unsafe extern "C" fn enumerate_clock_sources(
+    selfp: *mut sys::oa_driver,
+    buf: *mut c_char,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "enumerate_clock_sources(len={len})");
+    let card = alsa_common::ctl_name(driver.state.dev_name.as_deref().unwrap_or("hw:UMC202HD"));
+    alsa_common::enumerate_clock_sources(&driver.state.log, &card, buf, len)
+}
+
+unsafe extern "C" fn select_clock_source(selfp: *mut sys::oa_driver, name: *const c_char) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    if name.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let name = CStr::from_ptr(name).to_string_lossy();
+    log!(driver, Debug, "select_clock_source(name={name:?})");
+    if driver.state.phase == sys::DriverPhase::Running {
+        log!(
+            driver,
+            Error,
+            "select_clock_source called while {:?}",
+            driver.state.phase
+        );
+        return sys::OA_ERR_STATE;
+    }
+    let card = alsa_common::ctl_name(driver.state.dev_name.as_deref().unwrap_or("hw:UMC202HD"));
+    alsa_common::select_clock_source(&driver.state.log, &card, &name)
+}
+
+unsafe extern "C" fn query_clock_sources(
+    selfp: *mut sys::oa_driver,
+    buf: *mut sys::oa_clock_source,
+    len: usize,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "query_clock_sources(len={len})");
+    let card = alsa_common::ctl_name(driver.state.dev_name.as_deref().unwrap_or("hw:UMC202HD"));
+    alsa_common::query_clock_sources(&driver.state.log, &card, buf, len)
+}
+
+unsafe extern "C" fn set_clock_source_index(selfp: *mut sys::oa_driver, index: u32) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(driver, Debug, "set_clock_source(index={index})");
+    if driver.state.phase == sys::DriverPhase::Running {
+        log!(
+            driver,
+            Error,
+            "set_clock_source called while {:?}",
+            driver.state.phase
+        );
+        return sys::OA_ERR_STATE;
+    }
+    let card = alsa_common::ctl_name(driver.state.dev_name.as_deref().unwrap_or("hw:UMC202HD"));
+    alsa_common::set_clock_source_index(&driver.state.log, &card, index)
+}
+
+unsafe extern "C" fn enable_dc_filter(selfp: *mut sys::oa_driver, enable: sys::oa_bool) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    let enable = enable != sys::OA_FALSE;
+    log!(driver, Debug, "enable_dc_filter({enable})");
+    driver.state.dc_filter.store(enable, Ordering::Relaxed);
+    sys::OA_OK
+}
+
+unsafe extern "C" fn set_channel_gain(
+    selfp: *mut sys::oa_driver,
+    direction: u32,
+    channel: u32,
+    gain: f32,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    log!(
+        driver,
+        Debug,
+        "set_channel_gain(direction={direction}, channel={channel}, gain={gain})"
+    );
+    sys::set_channel_gain(
+        &driver.state.in_gains,
+        &driver.state.out_gains,
+        direction,
+        channel,
+        gain,
+    )
+}
+
+/// Capture can be masked down to one channel; playback always carries both.
+unsafe extern "C" fn set_channel_mask(
+    selfp: *mut sys::oa_driver,
+    mask: *const sys::oa_channel_mask,
+) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    let mask = if mask.is_null() {
+        sys::oa_channel_mask::ALL
+    } else {
+        *mask
+    };
+    log!(
+        driver,
+        Debug,
+        "set_channel_mask(in={:#x}, out={:#x})",
+        mask.in_mask,
+        mask.out_mask
+    );
+    if driver.state.phase == sys::DriverPhase::Running {
+        return driver.fail(sys::OA_ERR_STATE, "set_channel_mask while running".into());
+    }
+    if sys::enabled_channels(mask.out_mask, 2) != 2 {
+        return driver.fail(
+            sys::OA_ERR_UNSUPPORTED,
+            "both playback channels must stay enabled".into(),
+        );
+    }
+    driver.state.channel_mask = mask;
+    sys::OA_OK
+}
+
+/// Queues on the MIDI thread, so it is safe to call from `process`; hence no debug log.
+unsafe extern "C" fn send_midi(
+    selfp: *mut sys::oa_driver,
+    events: *const sys::oa_midi_event,
+    count: u32,
+) -> i32 {
+    let driver = &mut *(selfp as *mut Driver);
+    if !driver.state.midi.as_ref().is_some_and(|p| p.has_output()) {
+        return sys::OA_ERR_UNSUPPORTED;
+    }
+    if count == 0 {
+        return sys::OA_OK;
+    }
+    if events.is_null() {
+        return sys::OA_ERR_INVALID_ARG;
+    }
+    let events = std::slice::from_raw_parts(events, count as usize);
+    if let Some(bad) = events
+        .iter()
+        .find(|e| sys::midi_message_len(e.data[0]) != Some(e.size as usize))
+    {
+        let msg = format!("send_midi: malformed message {:02x?}", bad.bytes());
+        return driver.fail(sys::OA_ERR_INVALID_ARG, msg);
+    }
+    let now = alsa_common::monotonic_ns();
+    let rate = match driver.state.phase {
+        sys::DriverPhase::Running => driver.state.cfg.sample_rate as u64,
+        _ => 0,
+    };
+    let delay_ns = |e: &sys::oa_midi_event| match rate {
+        0 => 0,
+        rate => e.sample_offset as u64 * 1_000_000_000 / rate,
+    };
+    let queued = driver
+        .state
+        .midi
+        .as_ref()
+        .is_some_and(|port| events.iter().all(|e| port.send(now + delay_ns(e), *e)));
+    if !queued {
+        return driver.fail(sys::OA_ERR_BACKEND, "MIDI output queue full".into());
+    }
+    sys::OA_OK
+}
+
+unsafe extern "C" fn query_device_info(
+    selfp: *mut sys::oa_driver,
+    name: *const c_char,
+    out: *mut sys::oa_device_info,
+) -> i32 {
+    let driver = &*(selfp as *const Driver);
+    let name = if name.is_null() || *name == 0 {
+        driver
+            .state
+            .dev_name
+            .clone()
+            .unwrap_or_else(default_device_name)
+    } else {
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    log!(driver, Debug, "query_device_info(name={name:?})");
+    match alsa_common::probe_device_info(&name) {
+        Ok(info) => sys::write_device_info(&info, out),
+        Err(e) => {
+            log!(driver, Error, "failed to probe {name}: {e}");
+            sys::open_errno_result(e.errno())
+        }
+    }
+}
+
+static VTABLE: sys::oa_driver_vtable = sys::oa_driver_vtable {
+    get_caps: Some(get_caps),
+    query_devices: Some(query_devices),
+    open_device: Some(open_device),
+    close_device: Some(close_device),
+    get_default_config: Some(get_default_config),
+    start: Some(start),
+    stop: Some(stop),
+    get_latency: Some(get_latency),
+    set_sample_rate: Some(set_sr),
+    set_buffer_frames: Some(set_buf),
+    query_supported_configs: Some(query_supported_configs),
+    get_channel_names: Some(get_channel_names),
+    query_supported_rates: Some(query_supported_rates),
+    enumerate_clock_sources: Some(enumerate_clock_sources),
+    select_clock_source: Some(select_clock_source),
+    query_device_info: Some(query_device_info),
+    arm_start: Some(arm_start),
+    trigger_start: Some(trigger_start),
+    get_status: Some(get_status),
+    query_clock_sources: Some(query_clock_sources),
+    set_clock_source: Some(set_clock_source_index),
+    enable_dc_filter: Some(enable_dc_filter),
+    get_channel_name: Some(get_channel_name),
+    set_channel_gain: Some(set_channel_gain),
+    pause: Some(pause),
+    resume: Some(resume),
+    abort: Some(abort),
+    get_last_error: Some(get_last_error),
+    query_config_ranges: Some(query_config_ranges),
+    set_channel_mask: Some(set_channel_mask),
+    send_midi: Some(send_midi),
+    set_option: Some(set_option),
+    get_option: Some(get_option),
+    ..sys::oa_driver_vtable::empty()
+};
+
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
 pub unsafe extern "C" fn openasio_driver_create(
     params: *const sys::oa_create_params,
     out: *mut *mut sys::oa_driver,
@@ -468,62 +1435,648 @@ pub unsafe extern "C" fn openasio_driver_create(
         return sys::OA_ERR_INVALID_ARG;
     }
     let p = &*params;
-    if p.host.is_null() {
-        return sys::OA_ERR_INVALID_ARG;
+    let rc = sys::create_params_validate(p);
+    if rc != sys::OA_OK {
+        return rc;
     }
+    let options = match sys::CreateOptions::from_params(p) {
+        Ok(options) => options,
+        Err(msg) => {
+            sys::LogSink::from_params(p).emit(
+                log::Level::Error,
+                format_args!("bad create options: {msg}"),
+            );
+            return sys::OA_ERR_INVALID_ARG;
+        }
+    };
 
-    let drv = Box::new(Driver {
-        vt: sys::oa_driver_vtable {
-            struct_size: std::mem::size_of::<sys::oa_driver_vtable>() as u32,
-            get_caps: Some(get_caps),
-            query_devices: Some(query_devices),
-            open_device: Some(open_device),
-            close_device: Some(close_device),
-            get_default_config: Some(get_default_config),
-            start: Some(start),
-            stop: Some(stop),
-            get_latency: Some(get_latency),
-            set_sample_rate: Some(set_sr),
-            set_buffer_frames: Some(set_buf),
-        },
+    let mut drv = Box::new(Driver {
+        vt: &VTABLE,
         state: DriverState {
-            host: *p.host,
+            host: sys::host_callbacks(p),
             host_user: p.host_user,
+            log: sys::LogSink::from_params(p),
+            hints: sys::ThreadHints::from_params(p),
+            phase: sys::DriverPhase::Created,
+            cfg_size: sys::stream_config_size(p),
+            period_count: alsa_common::DEFAULT_PERIOD_COUNT,
+            options,
             dev_name: None,
+            open_pcm: PCM::new,
             io: Io {
                 cap: None,
                 pb: None,
             },
-            cfg: sys::oa_stream_config {
-                sample_rate: 48000,
-                buffer_frames: 128,
-                in_channels: 2,
-                out_channels: 2,
-                format: sys::oa_sample_format::OA_SAMPLE_F32,
-                layout: sys::oa_buffer_layout::OA_BUF_INTERLEAVED,
-            },
+            cfg: sys::oa_stream_config::default(),
+            channel_mask: sys::oa_channel_mask::ALL,
+            host_cfg: sys::oa_stream_config::default(),
             time0: Instant::now(),
+            dev_time0_ns: 0,
             underruns: AtomicU32::new(0),
             overruns: AtomicU32::new(0),
-            in_hw: Vec::new(),
+            frames_processed: AtomicU64::new(0),
+            in_gains: sys::ChannelGains::default(),
+            out_gains: sys::ChannelGains::default(),
+            dc_filter: AtomicBool::new(false),
+            dc: None,
+            converter: &alsa_common::I32Converter,
+            passthrough: false,
+            in_hw: HwBuffer::default(),
             in_buf: Vec::new(),
             out_buf: Vec::new(),
-            out_hw: Vec::new(),
+            out_hw: HwBuffer::default(),
             scratch_out: Vec::new(),
             in_planes: Vec::new(),
             out_planes: Vec::new(),
+            f64_block: None,
             running: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            hw_paused: false,
+            gate: sys::StartGate::new(),
             worker: None,
+            device_lost: AtomicBool::new(false),
+            watching: AtomicBool::new(false),
+            reconnect_worker: None,
+            hotplug: None,
+            midi: None,
+            midi_in: Vec::with_capacity(midi::MAX_PENDING),
+            midi_since_ns: 0,
+            last_error: sys::LastError::default(),
         },
     });
+    let (options, log) = (drv.state.options.clone(), drv.state.log);
+    if let Err((code, msg)) = alsa_common::apply_create_options(&mut drv.state, &options, &log) {
+        log!(drv, Error, "{msg}");
+        return code;
+    }
+    if let Some(cb) = drv.state.host.device_list_changed {
+        let user = drv.state.host_user as usize;
+        match hotplug::Watcher::spawn(drv.state.log, move || unsafe { cb(user as *mut c_void) }) {
+            Ok(watcher) => drv.state.hotplug = Some(watcher),
+            Err(e) => log!(drv, Warn, "cannot watch /dev/snd for hot-plug: {e}"),
+        }
+    }
 
     *out = Box::into_raw(drv) as *mut sys::oa_driver;
+    sys::track_driver(*out);
     sys::OA_OK
 }
 
-#[no_mangle]
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
 pub unsafe extern "C" fn openasio_driver_destroy(driver: *mut sys::oa_driver) {
-    if !driver.is_null() {
+    if sys::release_driver(driver) {
         let _ = Box::from_raw(driver as *mut Driver);
     }
 }
+
+#[cfg_attr(not(feature = "static-link"), no_mangle)]
+pub unsafe extern "C" fn openasio_driver_get_info(out: *mut sys::oa_driver_info) -> i32 {
+    let info = sys::oa_driver_info::new(
+        "Behringer UMC202HD",
+        "OpenASIO project",
+        env!("CARGO_PKG_VERSION"),
+    );
+    sys::write_driver_info(&info, out)
+}
+
+#[cfg(not(feature = "static-link"))]
+sys::oa_export_abi_version!();
+
+/// The UMC202HD driver, for hosts that link it in rather than load it; see [`sys::DriverFactory`].
+#[cfg(feature = "static-link")]
+pub struct Umc202hd;
+
+#[cfg(feature = "static-link")]
+impl sys::DriverFactory for Umc202hd {
+    unsafe extern "C" fn create(
+        params: *const sys::oa_create_params,
+        out: *mut *mut sys::oa_driver,
+    ) -> i32 {
+        openasio_driver_create(params, out)
+    }
+
+    unsafe extern "C" fn destroy(driver: *mut sys::oa_driver) {
+        openasio_driver_destroy(driver)
+    }
+
+    unsafe extern "C" fn get_info(out: *mut sys::oa_driver_info) -> i32 {
+        openasio_driver_get_info(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    unsafe extern "C" fn process(
+        _user: *mut c_void,
+        _in_ptr: *const c_void,
+        _out_ptr: *mut c_void,
+        _frames: u32,
+        _time: *const sys::oa_time_info,
+        _cfg: *const sys::oa_stream_config,
+    ) -> sys::oa_bool {
+        sys::OA_FALSE
+    }
+
+    /// A driver from `openasio_driver_create`, destroyed when dropped.
+    struct TestDriver(*mut sys::oa_driver);
+
+    impl TestDriver {
+        /// Created with `process` as the only host callback.
+        fn new() -> Self {
+            Self::with_params(|params| params)
+        }
+
+        /// Created with `process` as the only host callback and the params `edit` returns.
+        fn with_params(edit: impl FnOnce(sys::oa_create_params) -> sys::oa_create_params) -> Self {
+            let host = sys::oa_host_callbacks {
+                process: Some(process),
+                ..sys::oa_host_callbacks::empty()
+            };
+            Self::create(&edit(sys::oa_create_params::new(&host, ptr::null_mut())))
+        }
+
+        fn create(params: &sys::oa_create_params) -> Self {
+            let mut drv = ptr::null_mut();
+            assert_eq!(
+                unsafe { openasio_driver_create(params, &mut drv) },
+                sys::OA_OK
+            );
+            Self(drv)
+        }
+
+        fn state(&self) -> &DriverState {
+            unsafe { &(*(self.0 as *const Driver)).state }
+        }
+
+        fn driver_mut(&mut self) -> &mut Driver {
+            unsafe { &mut *(self.0 as *mut Driver) }
+        }
+    }
+
+    impl Drop for TestDriver {
+        fn drop(&mut self) {
+            unsafe { openasio_driver_destroy(self.0) };
+        }
+    }
+
+    #[test]
+    fn thread_hints_survive_create() {
+        // Priority 80 is more than an unprivileged test may have; creation must not care.
+        let drv = TestDriver::with_params(|params| sys::oa_create_params {
+            rt_priority: 80,
+            cpu_affinity_mask: 1,
+            ..params
+        });
+        assert_eq!(
+            drv.state().hints,
+            sys::ThreadHints {
+                rt_priority: 80,
+                cpu_affinity_mask: 1
+            }
+        );
+    }
+
+    #[test]
+    fn start_rejects_blocks_too_large_to_size() {
+        let cfg = sys::oa_stream_config {
+            buffer_frames: 65535,
+            in_channels: 65,
+            out_channels: 65,
+            ..sys::oa_stream_config::default()
+        };
+        unsafe {
+            let drv = TestDriver::new();
+            // Refused by the shared checks before validate_config sees it.
+            assert_eq!(start(drv.0, &cfg), sys::OA_ERR_INVALID_ARG);
+        }
+    }
+
+    #[test]
+    fn destroy_ignores_null_and_a_second_destroy() {
+        unsafe { openasio_driver_destroy(ptr::null_mut()) };
+        let drv = TestDriver::new();
+        unsafe { openasio_driver_destroy(drv.0) };
+        // Only debug builds track live drivers, so only there may the guard destroy it again.
+        if cfg!(debug_assertions) {
+            drop(drv);
+        } else {
+            std::mem::forget(drv);
+        }
+    }
+
+    #[test]
+    fn rejected_rate_is_described_by_last_error() {
+        let cfg = sys::oa_stream_config {
+            sample_rate: 12345,
+            ..sys::oa_stream_config::default()
+        };
+        let mut buf = [0 as c_char; 128];
+        unsafe {
+            let drv = TestDriver::new();
+            assert_eq!(get_last_error(drv.0, buf.as_mut_ptr(), buf.len()), 0);
+            assert_eq!(start(drv.0, &cfg), sys::OA_ERR_UNSUPPORTED);
+            let len = get_last_error(drv.0, buf.as_mut_ptr(), buf.len());
+            let msg = CStr::from_ptr(buf.as_ptr()).to_str().unwrap();
+            assert_eq!(len as usize, msg.len());
+            assert!(msg.contains("sample rate 12345"), "{msg}");
+        }
+    }
+
+    #[test]
+    fn config_ranges_cover_exactly_the_known_rates() {
+        unsafe {
+            let drv = TestDriver::new();
+            let count = query_config_ranges(drv.0, ptr::null_mut(), 0);
+            let mut ranges = vec![sys::oa_config_range::default(); count as usize];
+            assert_eq!(query_config_ranges(drv.0, ranges.as_mut_ptr(), ranges.len()), count);
+            let mut rates: Vec<u32> = ranges.iter().map(|r| r.min_rate).collect();
+            rates.dedup();
+            assert_eq!(rates, SUPPORTED_SAMPLE_RATES);
+            for r in &ranges {
+                assert_eq!(r.min_rate, r.max_rate);
+                let cfg = sys::oa_stream_config {
+                    sample_rate: r.min_rate,
+                    in_channels: r.max_channels_in as u16,
+                    out_channels: r.max_channels_out as u16,
+                    buffer_frames: r.max_buffer_frames,
+                    ..sys::oa_stream_config::default()
+                };
+                assert_eq!(validate_config(&cfg), Ok(()), "{r:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn period_count_option_sizes_the_next_hw_setup() {
+        unsafe {
+            let drv = TestDriver::new();
+            assert_eq!(set_option(drv.0, c"period_count".as_ptr(), c"4".as_ptr()), sys::OA_OK);
+            assert_eq!(set_option(drv.0, c"rt_priority".as_ptr(), c"60".as_ptr()), sys::OA_OK);
+            let mmap = set_option(drv.0, c"access".as_ptr(), c"mmap".as_ptr());
+            assert_eq!(mmap, sys::OA_ERR_UNSUPPORTED);
+            assert_eq!(set_option(drv.0, c"bogus".as_ptr(), c"1".as_ptr()), sys::OA_ERR_UNSUPPORTED);
+            assert_eq!(set_option(drv.0, ptr::null(), c"1".as_ptr()), sys::OA_ERR_INVALID_ARG);
+            let mut buf = [0 as c_char; 8];
+            assert_eq!(get_option(drv.0, c"rt_priority".as_ptr(), buf.as_mut_ptr(), buf.len()), 2);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()), c"60");
+
+            // The null PCM takes any buffer, so it keeps exactly what hw_setup asked for.
+            let state = drv.state();
+            let pcm = PCM::new("null", PcmDir::Playback, false).unwrap();
+            let cfg = sys::oa_stream_config::default();
+            hw_setup(&pcm, PcmDir::Playback, &cfg, state.period_count).unwrap();
+            let hwp = pcm.hw_params_current().unwrap();
+            assert_eq!(hwp.get_buffer_size().unwrap(), 4 * hwp.get_period_size().unwrap());
+        }
+    }
+
+    #[test]
+    fn create_options_set_the_device_and_ignore_unknown_keys() {
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params = |options: &CStr| sys::oa_create_params {
+            options: options.as_ptr(),
+            ..sys::oa_create_params::new(&host, ptr::null_mut())
+        };
+        unsafe {
+            let options = c"device=null, period_count=3, rt_priority=50, mixer=\"a,b\"";
+            let drv = TestDriver::create(&params(options));
+            assert_eq!(open_device(drv.0, ptr::null()), sys::OA_OK);
+            let state = drv.state();
+            assert_eq!(state.dev_name.as_deref(), Some("null"));
+            assert_eq!((state.period_count, state.hints.rt_priority), (3, 50));
+            assert_eq!(state.options.get("mixer"), Some("a,b"));
+            assert_eq!(close_device(drv.0), sys::OA_OK);
+            drop(drv);
+
+            let mut bad = ptr::null_mut();
+            let rc = openasio_driver_create(&params(c"rt_priority=high"), &mut bad);
+            assert_eq!(rc, sys::OA_ERR_INVALID_ARG);
+            assert!(bad.is_null());
+        }
+    }
+
+
+    #[test]
+    fn send_midi_needs_a_port() {
+        assert_eq!(midi::port_name("hw:1").as_deref(), Some("hw:1,0"));
+        assert_eq!(midi::port_name(&alsa_common::ctl_name("null")), None);
+        let note = sys::oa_midi_event::new(0, &[0x90, 60, 100]).unwrap();
+        unsafe {
+            let drv = TestDriver::new();
+            assert_eq!(open_device(drv.0, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(get_caps(drv.0) & sys::OA_CAP_MIDI, 0);
+            assert_eq!(send_midi(drv.0, &note, 1), sys::OA_ERR_UNSUPPORTED);
+        }
+    }
+
+    #[test]
+    fn mono_capture_mask_packs_the_right_input() {
+        let right = sys::oa_channel_mask {
+            in_mask: 0b10,
+            ..sys::oa_channel_mask::ALL
+        };
+        let mono_out = sys::oa_channel_mask {
+            out_mask: 0b01,
+            ..sys::oa_channel_mask::ALL
+        };
+        unsafe {
+            let drv = TestDriver::new();
+            assert!(sys::OaCaps::from(get_caps(drv.0)).contains(sys::OaCaps::CHANNEL_MASK));
+            assert_eq!(set_channel_mask(drv.0, &mono_out), sys::OA_ERR_UNSUPPORTED);
+            assert_eq!(set_channel_mask(drv.0, &right), sys::OA_OK);
+            let state = drv.state();
+            let host_cfg = state.channel_mask.apply(&sys::oa_stream_config::default());
+            assert_eq!((host_cfg.in_channels, host_cfg.out_channels), (1, 2));
+        }
+        // What the worker does to a capture period: the left channel is gone, not zeroed.
+        let mut hw = [10, 11, 20, 21, 30, 31];
+        assert_eq!(sys::compact_interleaved(&mut hw, 3, 2, right.in_mask), 1);
+        assert_eq!(hw[..3], [11, 21, 31]);
+    }
+
+    #[test]
+    fn default_config_is_the_sys_default() {
+        unsafe {
+            let drv = TestDriver::new();
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv.0, &mut cfg), sys::OA_OK);
+            assert_eq!(cfg, sys::oa_stream_config::default());
+        }
+    }
+
+    #[test]
+    fn i16_stream_runs_the_device_in_s16() {
+        let cfg = sys::oa_stream_config {
+            format: sys::oa_sample_format::OA_SAMPLE_I16,
+            ..sys::oa_stream_config::default()
+        };
+        unsafe {
+            let drv = TestDriver::new();
+            assert_eq!(open_device(drv.0, c"null".as_ptr()), sys::OA_OK);
+            assert_eq!(start(drv.0, &cfg), sys::OA_OK);
+            let state = drv.state();
+            assert_eq!(state.converter.format(), Format::s16());
+            assert!(state.passthrough);
+            assert_eq!(state.out_hw.bytes().len(), 128 * 2 * 2);
+            assert_eq!(stop(drv.0), sys::OA_OK);
+            assert_eq!(close_device(drv.0), sys::OA_OK);
+        }
+    }
+
+    #[test]
+    fn streams_prefer_packed_24_bit_samples() {
+        unsafe {
+            let drv = TestDriver::new();
+            assert_eq!(open_device(drv.0, c"null".as_ptr()), sys::OA_OK);
+            // The null PCM takes S24_3LE, so it is the default and what float streams run in.
+            let mut cfg = sys::oa_stream_config::zeroed();
+            assert_eq!(get_default_config(drv.0, &mut cfg), sys::OA_OK);
+            assert_eq!(cfg.format, sys::oa_sample_format::OA_SAMPLE_I24);
+            for (format, passthrough) in [
+                (sys::oa_sample_format::OA_SAMPLE_F32, false),
+                (sys::oa_sample_format::OA_SAMPLE_I24, true),
+            ] {
+                let cfg = sys::oa_stream_config {
+                    format,
+                    ..sys::oa_stream_config::default()
+                };
+                assert_eq!(start(drv.0, &cfg), sys::OA_OK, "{format}");
+                let state = drv.state();
+                assert_eq!(state.converter.format(), Format::S243LE, "{format}");
+                assert_eq!(state.passthrough, passthrough, "{format}");
+                assert_eq!(state.out_hw.bytes().len(), 128 * 2 * 3);
+                assert_eq!(stop(drv.0), sys::OA_OK);
+            }
+            assert_eq!(close_device(drv.0), sys::OA_OK);
+        }
+    }
+
+    /// Opens made through `flaky_open`: the first finds the device still unplugged.
+    static FLAKY_OPENS: AtomicU32 = AtomicU32::new(0);
+
+    fn flaky_open(_name: &str, dir: PcmDir, nonblock: bool) -> alsa::Result<PCM> {
+        if FLAKY_OPENS.fetch_add(1, Ordering::AcqRel) == 0 {
+            return Err(alsa::Error::new("snd_pcm_open", libc::ENODEV));
+        }
+        PCM::new("null", dir, nonblock)
+    }
+
+    /// What the host heard about the unplug and the reconnect.
+    #[derive(Default)]
+    struct ReconnectHost {
+        resets: AtomicU32,
+        latency: Mutex<Option<(u32, u32)>>,
+    }
+
+    unsafe extern "C" fn note_reset(user: *mut c_void) {
+        (*(user as *const ReconnectHost))
+            .resets
+            .fetch_add(1, Ordering::AcqRel);
+    }
+
+    unsafe extern "C" fn note_latency(user: *mut c_void, in_latency: u32, out_latency: u32) {
+        let seen = &*(user as *const ReconnectHost);
+        *seen.latency.lock().unwrap() = Some((in_latency, out_latency));
+    }
+
+    #[test]
+    fn lost_device_is_polled_until_it_opens_again() {
+        let seen = ReconnectHost::default();
+        let host = sys::oa_host_callbacks {
+            process: Some(process),
+            reset_request: Some(note_reset),
+            latency_changed: Some(note_latency),
+            ..sys::oa_host_callbacks::empty()
+        };
+        let params =
+            sys::oa_create_params::new(&host, &seen as *const ReconnectHost as *mut c_void);
+        unsafe {
+            let mut drv = TestDriver::create(&params);
+            let d = drv.driver_mut();
+            d.state.open_pcm = flaky_open;
+            d.state.dev_name = Some("null".into());
+            d.state.cfg = sys::oa_stream_config::default();
+            d.state.running.store(true, Ordering::Release);
+            spawn_reconnect(d, "null".into());
+
+            // What the worker does when a read or write fails with ENODEV.
+            device_lost(&d.state);
+            assert!(!d.state.running.load(Ordering::Acquire));
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while seen.latency.lock().unwrap().is_none() {
+                assert!(Instant::now() < deadline, "never reconnected");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(seen.resets.load(Ordering::Acquire), 1);
+            assert_eq!(*seen.latency.lock().unwrap(), Some((128, 128)));
+            assert_eq!(FLAKY_OPENS.load(Ordering::Acquire), 2);
+            assert!(!d.state.device_lost.load(Ordering::Acquire));
+        }
+    }
+
+    fn rejected(cfg: sys::oa_stream_config) -> String {
+        validate_config(&cfg).expect_err("config should be rejected")
+    }
+
+    #[test]
+    fn validate_config_accepts_the_default() {
+        assert_eq!(validate_config(&sys::oa_stream_config::default()), Ok(()));
+        // f64 is converted from the device's i32 block, in either layout.
+        let planar_f64 = sys::oa_stream_config {
+            format: sys::oa_sample_format::OA_SAMPLE_F64,
+            layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED,
+            ..sys::oa_stream_config::default()
+        };
+        assert_eq!(validate_config(&planar_f64), Ok(()));
+        let i16 = sys::oa_stream_config {
+            format: sys::oa_sample_format::OA_SAMPLE_I16,
+            ..sys::oa_stream_config::default()
+        };
+        assert_eq!(validate_config(&i16), Ok(()));
+    }
+
+    #[test]
+    fn validate_config_requires_interleaved_integers() {
+        for format in [
+            sys::oa_sample_format::OA_SAMPLE_I16,
+            sys::oa_sample_format::OA_SAMPLE_I24,
+            sys::oa_sample_format::OA_SAMPLE_I32,
+        ] {
+            let cfg = sys::oa_stream_config {
+                format,
+                ..sys::oa_stream_config::default()
+            };
+            assert_eq!(validate_config(&cfg), Ok(()), "{format}");
+            let planar = sys::oa_stream_config {
+                layout: sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED,
+                ..cfg
+            };
+            assert!(rejected(planar).contains("interleaved"), "{format}");
+        }
+    }
+
+    #[test]
+    fn validate_config_requires_stereo_playback() {
+        for out_channels in [1, 3, 8] {
+            let cfg = sys::oa_stream_config {
+                out_channels,
+                ..sys::oa_stream_config::default()
+            };
+            assert!(rejected(cfg).contains("playback requires 2 channels"));
+        }
+    }
+
+    #[test]
+    fn validate_config_capture_is_off_or_stereo() {
+        for in_channels in [0, 2] {
+            let cfg = sys::oa_stream_config {
+                in_channels,
+                ..sys::oa_stream_config::default()
+            };
+            assert_eq!(validate_config(&cfg), Ok(()), "in_channels={in_channels}");
+        }
+        let mono = sys::oa_stream_config {
+            in_channels: 1,
+            ..sys::oa_stream_config::default()
+        };
+        assert!(rejected(mono).contains("capture supports 0 or 2 channels"));
+    }
+
+    #[test]
+    fn validate_config_only_0_or_2_inputs_pass() {
+        for in_channels in 0..=255u16 {
+            let cfg = sys::oa_stream_config {
+                in_channels,
+                ..sys::oa_stream_config::default()
+            };
+            assert_eq!(
+                validate_config(&cfg).is_ok(),
+                in_channels == 0 || in_channels == 2,
+                "in_channels={in_channels}"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_config_sample_rates() {
+        for &sample_rate in SUPPORTED_SAMPLE_RATES {
+            let cfg = sys::oa_stream_config {
+                sample_rate,
+                ..sys::oa_stream_config::default()
+            };
+            assert_eq!(validate_config(&cfg), Ok(()), "{sample_rate} Hz");
+        }
+        let cfg = sys::oa_stream_config {
+            sample_rate: 41000,
+            ..sys::oa_stream_config::default()
+        };
+        assert!(rejected(cfg).contains("unsupported sample rate 41000"));
+    }
+
+    #[test]
+    fn validate_config_buffer_limits() {
+        let empty = sys::oa_stream_config {
+            buffer_frames: 0,
+            ..sys::oa_stream_config::default()
+        };
+        assert!(rejected(empty).contains("buffer must be > 0"));
+        let huge = sys::oa_stream_config {
+            buffer_frames: MAX_BUFFER_FRAMES * 2,
+            ..sys::oa_stream_config::default()
+        };
+        assert!(rejected(huge).contains("buffer size out of range"));
+    }
+
+    /// The sample conversions the worker runs on every period, over arbitrary input.
+    mod conversion {
+        use openasio_sys::convert;
+        use proptest::prelude::*;
+
+        fn to_i32(s: f32) -> i32 {
+            let mut d = [0];
+            convert::f32_to_i32(&[s], &mut d);
+            d[0]
+        }
+
+        fn to_f32(s: i32) -> f32 {
+            let mut d = [0.0];
+            convert::i32_to_f32(&[s], &mut d);
+            d[0]
+        }
+
+        proptest! {
+            #[test]
+            fn in_range_samples_round_trip(s in -1.0f32..=1.0) {
+                let back = to_f32(to_i32(s));
+                prop_assert!((back - s).abs() < 1e-7, "{} -> {}", s, back);
+            }
+
+            #[test]
+            fn subnormals_round_trip(s in proptest::num::f32::SUBNORMAL) {
+                prop_assert!((to_f32(to_i32(s)) - s).abs() < 1e-7);
+            }
+
+            #[test]
+            fn loud_samples_clip(s in 1.0f32..=f32::MAX) {
+                prop_assert_eq!(to_i32(s), i32::MAX);
+                prop_assert_eq!(to_i32(-s), i32::MIN);
+            }
+        }
+
+        #[test]
+        fn special_values() {
+            assert_eq!(to_i32(f32::INFINITY), i32::MAX);
+            assert_eq!(to_i32(f32::NEG_INFINITY), i32::MIN);
+            // NaN survives the clamp; the saturating cast then makes it silence.
+            assert_eq!(to_i32(f32::NAN), 0);
+            assert_eq!(to_f32(i32::MIN), -1.0);
+        }
+    }
+}
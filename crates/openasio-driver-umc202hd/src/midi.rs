@@ -0,0 +1,267 @@
+//! The card's rawmidi port, for `midi_in` and `send_midi`. The UMC202HD has no MIDI jacks of its
+//! own, so this serves whatever rawmidi device shares its card, or the port `OA_UMC_MIDI_PORT`
+//! names. A thread reads the input and writes queued output when it falls due; the worker only
+//! ever `try_lock`s the queues it shares with that thread.
+use alsa::poll::Descriptors;
+use alsa::rawmidi::Rawmidi;
+use alsa::Direction;
+use openasio_driver_alsa_common::monotonic_ns;
+use openasio_sys as sys;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Environment variable naming the rawmidi port to use instead of the card's first, e.g.
+/// `hw:VirMIDI,0,0`.
+pub const PORT_ENV: &str = "OA_UMC_MIDI_PORT";
+
+/// Messages held per direction; input past this is dropped until the worker drains the queue.
+pub const MAX_PENDING: usize = 256;
+
+/// Longest the thread sleeps, so queued output is sent within about this much of its due time.
+const POLL: Duration = Duration::from_millis(1);
+
+/// The rawmidi port for the PCM device `card` belongs to (its control name, see
+/// `alsa_common::ctl_name`), or `None` when neither it nor `OA_UMC_MIDI_PORT` names one.
+pub fn port_name(card: &str) -> Option<String> {
+    match std::env::var(PORT_ENV) {
+        Ok(name) if !name.is_empty() => Some(name),
+        _ => (card != "default").then(|| format!("{card},0")),
+    }
+}
+
+/// Splits a byte stream into whole messages, applying running status and skipping system
+/// exclusive.
+#[derive(Default)]
+pub struct Parser {
+    msg: [u8; 3],
+    len: usize,
+    need: usize,
+    running: Option<u8>,
+    sysex: bool,
+}
+
+impl Parser {
+    /// Feed one byte; returns the message it completes, at offset 0.
+    pub fn push(&mut self, byte: u8) -> Option<sys::oa_midi_event> {
+        if byte >= 0xF8 {
+            // Real-time messages may appear anywhere, even inside another message.
+            return sys::oa_midi_event::new(0, &[byte]);
+        }
+        if byte >= 0x80 {
+            self.len = 0;
+            self.sysex = byte == 0xF0;
+            // Only channel messages set running status; system common ones cancel it.
+            self.running = (byte < 0xF0).then_some(byte);
+            self.need = sys::midi_message_len(byte).unwrap_or(0);
+            if self.need == 0 {
+                return None;
+            }
+        } else if self.sysex {
+            return None;
+        } else if self.len == 0 {
+            let status = self.running?;
+            self.need = sys::midi_message_len(status)?;
+            self.msg[0] = status;
+            self.len = 1;
+        }
+        self.msg[self.len] = byte;
+        self.len += 1;
+        if self.len < self.need {
+            return None;
+        }
+        self.len = 0;
+        sys::oa_midi_event::new(0, &self.msg[..self.need])
+    }
+}
+
+struct Shared {
+    stop: AtomicBool,
+    /// Received messages with their arrival (`monotonic_ns`).
+    incoming: Mutex<Vec<(u64, sys::oa_midi_event)>>,
+    /// Messages to send with their due time (`monotonic_ns`).
+    outgoing: Mutex<Vec<(u64, sys::oa_midi_event)>>,
+}
+
+pub struct Port {
+    shared: Arc<Shared>,
+    has_output: bool,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Port {
+    /// Open `name` in whichever directions it has and start the thread serving it.
+    pub fn open(name: &str, log: sys::LogSink) -> alsa::Result<Self> {
+        let input = Rawmidi::new(name, Direction::Capture, true);
+        let output = Rawmidi::new(name, Direction::Playback, false);
+        let (input, output) = match (input, output) {
+            (Err(e), Err(_)) => return Err(e),
+            (i, o) => (i.ok(), o.ok()),
+        };
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            incoming: Mutex::new(Vec::with_capacity(MAX_PENDING)),
+            outgoing: Mutex::new(Vec::with_capacity(MAX_PENDING)),
+        });
+        let has_output = output.is_some();
+        let thread_shared = shared.clone();
+        let handle = std::thread::spawn(move || run(input, output, &thread_shared, log));
+        Ok(Self {
+            shared,
+            has_output,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn has_output(&self) -> bool {
+        self.has_output
+    }
+
+    /// Queue `event` to be sent at `due_ns` (`monotonic_ns`). False if the queue is full.
+    pub fn send(&self, due_ns: u64, event: sys::oa_midi_event) -> bool {
+        let Ok(mut queue) = self.shared.outgoing.lock() else {
+            return false;
+        };
+        if queue.len() >= MAX_PENDING {
+            return false;
+        }
+        queue.push((due_ns, event));
+        true
+    }
+
+    /// Move the messages received so far into `out`, placing each by how long after `since_ns`
+    /// it arrived, at `rate`, within a block of `frames`. Never blocks: when the thread holds the
+    /// queue, its messages wait for the next block. `out` needs room for `MAX_PENDING`.
+    pub fn drain_input(
+        &self,
+        out: &mut Vec<sys::oa_midi_event>,
+        since_ns: u64,
+        rate: u32,
+        frames: u32,
+    ) {
+        let Ok(mut queue) = self.shared.incoming.try_lock() else {
+            return;
+        };
+        let last = frames.saturating_sub(1) as u64;
+        for (at, mut event) in queue.drain(..) {
+            let frame = at.saturating_sub(since_ns) * rate as u64 / 1_000_000_000;
+            event.sample_offset = frame.min(last) as u32;
+            out.push(event);
+        }
+    }
+}
+
+impl Drop for Port {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run(input: Option<Rawmidi>, output: Option<Rawmidi>, shared: &Shared, log: sys::LogSink) {
+    let mut fds = match input.as_ref().map(|i| i.get()).transpose() {
+        Ok(fds) => fds.unwrap_or_default(),
+        Err(e) => {
+            log.emit(
+                log::Level::Warn,
+                format_args!("MIDI input not pollable: {e}"),
+            );
+            Vec::new()
+        }
+    };
+    let mut parser = Parser::default();
+    let mut due = Vec::new();
+    let mut dropped = false;
+    while !shared.stop.load(Ordering::Acquire) {
+        if fds.is_empty() {
+            std::thread::sleep(POLL);
+        } else {
+            let _ = alsa::poll::poll(&mut fds, POLL.as_millis() as i32);
+        }
+        if let Some(input) = input.as_ref() {
+            let mut buf = [0u8; 256];
+            while let Ok(n @ 1..) = input.io().read(&mut buf) {
+                let now = monotonic_ns();
+                let Ok(mut queue) = shared.incoming.lock() else {
+                    break;
+                };
+                for event in buf[..n].iter().filter_map(|&b| parser.push(b)) {
+                    if queue.len() < MAX_PENDING {
+                        queue.push((now, event));
+                        dropped = false;
+                    } else if !std::mem::replace(&mut dropped, true) {
+                        log.emit(
+                            log::Level::Warn,
+                            format_args!("MIDI input queue full, dropping"),
+                        );
+                    }
+                }
+            }
+        }
+        if let Some(output) = output.as_ref() {
+            if let Ok(mut queue) = shared.outgoing.lock() {
+                let now = monotonic_ns();
+                queue.sort_by_key(|&(at, _)| at);
+                let n = queue.partition_point(|&(at, _)| at <= now);
+                due.extend(queue.drain(..n).map(|(_, event)| event));
+            }
+            for event in due.drain(..) {
+                if let Err(e) = output.io().write_all(event.bytes()) {
+                    log.emit(log::Level::Warn, format_args!("MIDI output failed: {e}"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut parser = Parser::default();
+        bytes
+            .iter()
+            .filter_map(|&b| parser.push(b))
+            .map(|e| e.bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn running_status_repeats_the_last_channel_status() {
+        assert_eq!(
+            parse(&[0x90, 60, 100, 62, 100, 0xC0, 5, 6]),
+            [
+                vec![0x90, 60, 100],
+                vec![0x90, 62, 100],
+                vec![0xC0, 5],
+                vec![0xC0, 6]
+            ],
+        );
+    }
+
+    #[test]
+    fn real_time_bytes_interrupt_without_breaking_a_message() {
+        assert_eq!(
+            parse(&[0x90, 60, 0xF8, 100]),
+            [vec![0xF8], vec![0x90, 60, 100]]
+        );
+    }
+
+    #[test]
+    fn sysex_and_stray_data_are_skipped() {
+        assert_eq!(
+            parse(&[1, 2, 0xF0, 0x7E, 0x90, 0xF7, 3, 0x80, 60, 0]),
+            [vec![0x80, 60, 0]],
+        );
+        // System common cancels running status.
+        assert_eq!(
+            parse(&[0x90, 60, 100, 0xF6, 61, 100]),
+            [vec![0x90, 60, 100], vec![0xF6]]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! MIDI through a snd-virmidi port whose sequencer output is wired back to its own input, with the
+//! `null` PCM standing in for the interface. Needs `--features virmidi-tests` and the module loaded
+//! (`modprobe snd-virmidi`); without a VirMIDI card the tests return early.
+#![cfg(feature = "virmidi-tests")]
+use alsa::seq::{Addr, ClientIter, PortSubscribe, Seq};
+use openasio_driver_umc202hd::{openasio_driver_create, openasio_driver_destroy};
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static RECEIVED: Mutex<Vec<sys::oa_midi_event>> = Mutex::new(Vec::new());
+
+unsafe extern "C" fn process(
+    _user: *mut c_void,
+    _in_ptr: *const c_void,
+    _out_ptr: *mut c_void,
+    _frames: u32,
+    _time: *const sys::oa_time_info,
+    _cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    // The null PCM never blocks; keep the worker from spinning a core.
+    std::thread::sleep(Duration::from_millis(1));
+    sys::OA_TRUE
+}
+
+unsafe extern "C" fn midi_in(_user: *mut c_void, events: *const sys::oa_midi_event, count: u32) {
+    let events = std::slice::from_raw_parts(events, count as usize);
+    RECEIVED.lock().unwrap().extend_from_slice(events);
+}
+
+/// Point the driver at the first VirMIDI port and route that port's output back into it, or
+/// `None` when there is no VirMIDI card. The returned sequencer keeps the route up.
+fn loop_virmidi() -> Option<Seq> {
+    let ctl = alsa::Ctl::new("hw:VirMIDI", false).ok()?;
+    let card = ctl.card_info().ok()?.get_card().get_index();
+    let seq = Seq::open(None, None, false).ok()?;
+    let name = format!("Virtual Raw MIDI {card}-0");
+    let client = ClientIter::new(&seq).find(|c| c.get_name().is_ok_and(|n| n == name))?;
+    let port = Addr {
+        client: client.get_client(),
+        port: 0,
+    };
+    let sub = PortSubscribe::empty().ok()?;
+    sub.set_sender(port);
+    sub.set_dest(port);
+    seq.subscribe_port(&sub).ok()?;
+    std::env::set_var("OA_UMC_MIDI_PORT", "hw:VirMIDI,0,0");
+    Some(seq)
+}
+
+#[test]
+fn sent_midi_comes_back_through_midi_in() {
+    let Some(_route) = loop_virmidi() else {
+        eprintln!("no VirMIDI card (modprobe snd-virmidi); skipping");
+        return;
+    };
+    let host = sys::oa_host_callbacks {
+        process: Some(process),
+        midi_in: Some(midi_in),
+        ..sys::oa_host_callbacks::empty()
+    };
+    let params = sys::oa_create_params::new(&host, ptr::null_mut());
+    let cfg = sys::oa_stream_config::default();
+    let note_on = sys::oa_midi_event::new(0, &[0x90, 60, 100]).unwrap();
+    let bad = sys::oa_midi_event { size: 2, ..note_on };
+    let mut drv = ptr::null_mut();
+    unsafe {
+        assert_eq!(openasio_driver_create(&params, &mut drv), sys::OA_OK);
+        let vt = &*(*drv).vt;
+        let send_midi = sys::oa_vtable_field!(vt, send_midi).unwrap();
+        assert_eq!((vt.open_device.unwrap())(drv, c"null".as_ptr()), sys::OA_OK);
+        assert_ne!((vt.get_caps.unwrap())(drv) & sys::OA_CAP_MIDI, 0);
+        assert_eq!(send_midi(drv, &bad, 1), sys::OA_ERR_INVALID_ARG);
+        assert_eq!((vt.start.unwrap())(drv, &cfg), sys::OA_OK);
+        assert_eq!(send_midi(drv, &note_on, 1), sys::OA_OK);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while RECEIVED.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!((vt.stop.unwrap())(drv), sys::OA_OK);
+        assert_eq!((vt.close_device.unwrap())(drv), sys::OA_OK);
+        openasio_driver_destroy(drv);
+    }
+    let received = RECEIVED.lock().unwrap();
+    assert_eq!(received.len(), 1, "{received:?}");
+    assert_eq!(received[0].bytes(), note_on.bytes());
+    assert!(received[0].sample_offset < cfg.buffer_frames);
+}
@@ -0,0 +1,50 @@
+//! Locating the test impulse in a recording.
+
+/// Root-mean-square level of `x`; 0 for an empty slice.
+pub fn rms(x: &[f32]) -> f32 {
+    if x.is_empty() { return 0.0; }
+    (x.iter().map(|s| s * s).sum::<f32>() / x.len() as f32).sqrt()
+}
+
+/// Cross-correlate `template` against every offset of `signal` and return the lag with the largest
+/// magnitude and that magnitude. Magnitude, because the analog path may invert polarity. `None`
+/// when `signal` is shorter than `template`.
+pub fn xcorr_peak(signal: &[f32], template: &[f32]) -> Option<(usize, f32)> {
+    if template.is_empty() || signal.len() < template.len() { return None; }
+    let mut best = (0, 0.0f32);
+    for lag in 0..=signal.len() - template.len() {
+        let c = signal[lag..].iter().zip(template).map(|(s, t)| s * t).sum::<f32>().abs();
+        if c > best.1 { best = (lag, c); }
+    }
+    Some(best)
+}
+
+/// Where the stimulus shows up in `recorded`, which starts at the frame the stimulus was played:
+/// the correlation peak, provided it stands `min_ratio` times above `noise_rms` (the level before
+/// the stimulus) and above `min_level`. A loopback cable attenuates, so the bar is relative.
+pub fn detect(recorded: &[f32], stimulus: &[f32], noise_rms: f32, min_ratio: f32, min_level: f32) -> Option<usize> {
+    let (lag, peak) = xcorr_peak(recorded, stimulus)?;
+    let energy = stimulus.iter().map(|s| s * s).sum::<f32>().sqrt();
+    (peak > noise_rms * energy * min_ratio && peak > min_level).then_some(lag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_an_attenuated_inverted_impulse_in_noise() {
+        // Deterministic pseudo-noise around -40 dBFS.
+        let mut seed = 1u32;
+        let mut noise = || { seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223); (seed >> 8) as f32 / (1 << 24) as f32 * 0.02 - 0.01 };
+        let floor: Vec<f32> = (0..4800).map(|_| noise()).collect();
+        let mut recorded: Vec<f32> = (0..9600).map(|_| noise()).collect();
+        recorded[1234] = -0.3;
+        let level = rms(&floor);
+        assert!(level > 0.0 && level < 0.01);
+        assert_eq!(detect(&recorded, &[1.0], level, 10.0, 1e-3), Some(1234));
+        recorded[1234] = 0.0;
+        assert_eq!(detect(&recorded, &[1.0], level, 10.0, 1e-3), None);
+        assert_eq!(xcorr_peak(&[0.0, 0.5, 1.0, 0.5], &[0.5, 1.0, 0.5]), Some((1, 1.5)));
+    }
+}
@@ -0,0 +1,148 @@
+//! Measure a device's round-trip latency: play an impulse on one output, record one input (which
+//! should be cabled back to that output) and find where the impulse arrives.
+//!
+//! Usage: `openasio-latency-test <driver.so> [device] [--channel-out N] [--channel-in N]`.
+//! Channels are zero-based and default to 0. The measured figure includes everything between the
+//! host's output buffer and its input buffer: driver buffering, converters and the analog path.
+mod dsp;
+
+use anyhow::{bail, Context, Result};
+use openasio::{Driver, HostProcess, StreamConfig};
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: openasio-latency-test <driver> [device] [--channel-out N] [--channel-in N]";
+/// Silence before the impulse: lets the device settle and gives the noise floor to detect against.
+const PRE_ROLL: Duration = Duration::from_millis(500);
+/// How long to wait for the impulse to come back.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// What is played at the impulse frame; a single full-scale sample.
+const STIMULUS: [f32; 1] = [1.0];
+/// The impulse must correlate this many times above the pre-roll noise floor...
+const DETECT_RATIO: f32 = 10.0;
+/// ...and above this absolute level (-60 dBFS), so digital silence never matches.
+const DETECT_MIN_LEVEL: f32 = 1e-3;
+/// Highest rate the preallocated recording has room for.
+const MAX_RATE: u32 = 192_000;
+/// How often the main thread looks at the recording.
+const POLL: Duration = Duration::from_millis(50);
+/// Keep recording this long past a first detection, in case a larger peak follows it.
+const SETTLE: Duration = Duration::from_millis(20);
+
+/// Input samples shared with the audio thread. Preallocated; the RT side only stores and bumps
+/// `len` (Release) once per block, and the main thread reads up to `len` (Acquire).
+struct Recording {
+    samples: Box<[AtomicU32]>, // f32 bits
+    len: AtomicUsize,
+}
+
+impl Recording {
+    fn new(frames: usize) -> Self { Self { samples: (0..frames).map(|_| AtomicU32::new(0)).collect(), len: AtomicUsize::new(0) } }
+    fn snapshot(&self) -> Vec<f32> {
+        let len = self.len.load(Ordering::Acquire);
+        self.samples[..len].iter().map(|s| f32::from_bits(s.load(Ordering::Relaxed))).collect()
+    }
+}
+
+/// Plays silence with one impulse on `out_ch` after the pre-roll, and records `in_ch`. Input and
+/// output frames of one `process` call share an index, so an input index minus `impulse_at` is the
+/// round trip in frames.
+struct ImpulseHost {
+    rec: Arc<Recording>,
+    in_ch: usize,
+    out_ch: usize,
+    impulse_at: Option<usize>, // set from the stream's rate on the first block
+    frame: usize,
+}
+
+/// Pointer to sample `frame` of channel `ch` in an f32 buffer of either layout.
+unsafe fn sample_ptr(buf: *const c_void, cfg: &StreamConfig, channels: u16, ch: usize, frame: usize) -> *mut f32 {
+    if cfg.interleaved { (buf as *mut f32).add(frame * channels as usize + ch) } else { (*(buf as *const *mut f32).add(ch)).add(frame) }
+}
+
+impl HostProcess for ImpulseHost {
+    fn process(&mut self, inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool {
+        let frames = frames as usize;
+        let impulse_at = *self.impulse_at.get_or_insert_with(|| pre_roll_frames(cfg.sample_rate));
+        let mut len = self.rec.len.load(Ordering::Relaxed);
+        for f in 0..frames {
+            let n = self.frame + f;
+            unsafe {
+                for c in 0..cfg.out_channels as usize {
+                    let s = match n.checked_sub(impulse_at) { Some(i) if c == self.out_ch => STIMULUS.get(i).copied().unwrap_or(0.0), _ => 0.0 };
+                    *sample_ptr(outputs, cfg, cfg.out_channels, c, f) = s;
+                }
+                if len < self.rec.samples.len() {
+                    self.rec.samples[len].store((*sample_ptr(inputs, cfg, cfg.in_channels, self.in_ch, f)).to_bits(), Ordering::Relaxed);
+                    len += 1;
+                }
+            }
+        }
+        self.rec.len.store(len, Ordering::Release);
+        self.frame += frames;
+        true
+    }
+}
+
+fn pre_roll_frames(rate: u32) -> usize { rate as usize * PRE_ROLL.as_millis() as usize / 1000 }
+
+fn frames_to_ms(frames: usize, rate: u32) -> f64 { frames as f64 * 1000.0 / rate as f64 }
+
+fn parse_channel(value: Option<String>, flag: &str) -> Result<usize> {
+    value.with_context(|| format!("{flag} needs a value"))?.parse().with_context(|| format!("{flag} takes a channel number"))
+}
+
+fn main() -> Result<()> {
+    let (mut path, mut device, mut in_ch, mut out_ch) = (None, None, 0usize, 0usize);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--channel-out" => out_ch = parse_channel(args.next(), "--channel-out")?,
+            "--channel-in" => in_ch = parse_channel(args.next(), "--channel-in")?,
+            "-h" | "--help" => { println!("{USAGE}"); return Ok(()); }
+            _ if arg.starts_with('-') => bail!("unknown flag {arg:?}\n{USAGE}"),
+            _ if path.is_none() => path = Some(arg),
+            _ if device.is_none() => device = Some(arg),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let Some(path) = path else { bail!(USAGE) };
+
+    let rec = Arc::new(Recording::new(MAX_RATE as usize * (PRE_ROLL + TIMEOUT + SETTLE).as_millis() as usize / 1000));
+    let host = ImpulseHost { rec: rec.clone(), in_ch, out_ch, impulse_at: None, frame: 0 };
+    let mut driver = Driver::load(&path, Box::new(host), StreamConfig::default(), true)?;
+    if !driver.caps().contains(sys::OaCaps::FULL_DUPLEX) { bail!("{path} does not support full duplex"); }
+    driver.open_by_name(device.as_deref())?;
+    let cfg = StreamConfig { format: sys::OA_SAMPLE_F32, interleaved: true, ..driver.default_config()? };
+    if in_ch >= cfg.in_channels as usize { bail!("--channel-in {in_ch}: the device has {} inputs", cfg.in_channels); }
+    if out_ch >= cfg.out_channels as usize { bail!("--channel-out {out_ch}: the device has {} outputs", cfg.out_channels); }
+    if cfg.sample_rate > MAX_RATE { bail!("{} Hz is above the {MAX_RATE} Hz this test records at", cfg.sample_rate); }
+    let rate = cfg.sample_rate;
+    let impulse_at = pre_roll_frames(rate);
+    println!("{rate} Hz, {} frames; output {out_ch} -> input {in_ch}", cfg.buffer_frames);
+
+    let stream = driver.start_stream(cfg)?;
+    let reported = stream.latency();
+    let deadline = Instant::now() + PRE_ROLL + TIMEOUT;
+    let (mut found, mut settle_until) = (None, None);
+    while Instant::now() < deadline && settle_until.is_none_or(|t| Instant::now() < t) {
+        std::thread::sleep(POLL);
+        let recorded = rec.snapshot();
+        if recorded.len() <= impulse_at { continue; }
+        let noise = dsp::rms(&recorded[..impulse_at]);
+        found = dsp::detect(&recorded[impulse_at..], &STIMULUS, noise, DETECT_RATIO, DETECT_MIN_LEVEL);
+        if found.is_some() && settle_until.is_none() { settle_until = Some(Instant::now() + SETTLE); }
+    }
+    stream.stop();
+
+    match reported {
+        Ok((input, output)) => println!("reported latency: {input} in + {output} out = {} frames ({:.2} ms)", input + output, frames_to_ms((input + output) as usize, rate)),
+        Err(e) => println!("reported latency: {e:#}"),
+    }
+    let Some(lag) = found else { bail!("no impulse on input {in_ch} within {TIMEOUT:?}; is output {out_ch} cabled to it?") };
+    println!("measured round trip: {lag} frames ({:.2} ms)", frames_to_ms(lag, rate));
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Copy a driver's inputs straight to its outputs, for checking full duplex by ear: plug in a
+//! microphone and listen.
+//!
+//! Usage: `openasio-passthrough --driver <driver.so> [--device NAME] [--delay FRAMES] [--gain G]`.
+//! Input channel `c % inputs` feeds output channel `c`, so a mono input reaches every output.
+//! Runs until interrupted or the stream stops, printing the xrun counts every five seconds.
+mod ring;
+
+use anyhow::{bail, Context, Result};
+use openasio::{Driver, HostProcess, StreamConfig};
+use openasio_sys as sys;
+use ring::Ring;
+use std::os::raw::c_void;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+const USAGE: &str = "usage: openasio-passthrough --driver <path> [--device NAME] [--delay FRAMES] [--gain G]";
+/// Frames copied through the delay line at a time; the ring holds the delay plus one chunk.
+const CHUNK: usize = 256;
+const REPORT_EVERY: Duration = Duration::from_secs(5);
+
+/// Pointer to sample `frame` of channel `ch` in an f32 buffer of either layout.
+unsafe fn sample_ptr(buf: *const c_void, cfg: &StreamConfig, channels: u16, ch: usize, frame: usize) -> *mut f32 {
+    if cfg.interleaved { (buf as *mut f32).add(frame * channels as usize + ch) } else { (*(buf as *const *mut f32).add(ch)).add(frame) }
+}
+
+struct Passthrough {
+    /// Set, and primed with the delay's worth of silence, before the stream starts: its size
+    /// depends on the output channel count, known only once the device is open.
+    ring: Arc<OnceLock<Ring>>,
+    gain: f32,
+    scratch: Vec<f32>, // one chunk of interleaved output frames
+}
+
+impl HostProcess for Passthrough {
+    fn process(&mut self, inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool {
+        let (frames, ich, och) = (frames as usize, cfg.in_channels as usize, cfg.out_channels as usize);
+        let Some(ring) = self.ring.get() else { return true };
+        let mut done = 0;
+        while done < frames {
+            let n = (frames - done).min(CHUNK);
+            let block = &mut self.scratch[..n * och];
+            for (f, frame) in block.chunks_exact_mut(och).enumerate() {
+                for (c, s) in frame.iter_mut().enumerate() {
+                    *s = if ich == 0 { 0.0 } else { self.gain * unsafe { *sample_ptr(inputs, cfg, cfg.in_channels, c % ich, done + f) } };
+                }
+            }
+            ring.push(block);
+            ring.pop(block);
+            for (f, frame) in block.chunks_exact(och).enumerate() {
+                for (c, &s) in frame.iter().enumerate() {
+                    unsafe { *sample_ptr(outputs, cfg, cfg.out_channels, c, done + f) = s };
+                }
+            }
+            done += n;
+        }
+        true
+    }
+}
+
+fn main() -> Result<()> {
+    let (mut path, mut device, mut delay, mut gain) = (None, None, 0usize, 1.0f32);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--driver" => path = Some(value()?),
+            "--device" => device = Some(value()?),
+            "--delay" => delay = value()?.parse().context("--delay takes a frame count")?,
+            "--gain" => gain = value()?.parse().context("--gain takes a linear gain")?,
+            "-h" | "--help" => { println!("{USAGE}"); return Ok(()); }
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let Some(path) = path else { bail!(USAGE) };
+    if !gain.is_finite() { bail!("--gain must be a finite number"); }
+
+    let ring = Arc::new(OnceLock::new());
+    let host = Passthrough { ring: ring.clone(), gain, scratch: vec![0.0; CHUNK * sys::OA_MAX_CHANNELS as usize] };
+    let mut driver = Driver::load(&path, Box::new(host), StreamConfig::default(), true)?;
+    if !driver.caps().contains(sys::OaCaps::FULL_DUPLEX) { bail!("{path} does not support full duplex"); }
+    driver.open_by_name(device.as_deref())?;
+    let cfg = StreamConfig { format: sys::OA_SAMPLE_F32, interleaved: true, ..driver.default_config()? };
+    if cfg.in_channels == 0 || cfg.out_channels == 0 { bail!("the device has {} inputs and {} outputs", cfg.in_channels, cfg.out_channels); }
+    let och = cfg.out_channels as usize;
+    let delay_line = Ring::new((delay + CHUNK) * och);
+    delay_line.push(&vec![0.0; delay * och]);
+    let _ = ring.set(delay_line);
+
+    let stopped = driver.stopped_notifier();
+    println!("{} Hz, {} frames, {} in / {} out; delay {delay} frames, gain {gain}", cfg.sample_rate, cfg.buffer_frames, cfg.in_channels, cfg.out_channels);
+    let stream = driver.start_stream(cfg)?;
+    loop {
+        if let Some(reason) = stopped.wait(REPORT_EVERY) {
+            println!("stream stopped: {reason:?}");
+            break;
+        }
+        let xruns = stream.xrun_stats();
+        println!("xruns: {} underruns, {} overruns", xruns.underruns, xruns.overruns);
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Single-producer/single-consumer sample ring: the delay line between input and output.
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Interleaved f32 ring. Callers push and pop whole frames, so the fill level stays frame-aligned.
+pub struct Ring {
+    buf: Box<[UnsafeCell<f32>]>,
+    head: AtomicUsize, // read position, owned by the consumer
+    tail: AtomicUsize, // write position, owned by the producer
+}
+
+// SAFETY: the producer only writes slots in [tail, head + cap) and the consumer only reads
+// slots in [head, tail); the Release/Acquire pairs on head and tail order those accesses.
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    pub fn new(capacity: usize) -> Self {
+        Self { buf: (0..capacity).map(|_| UnsafeCell::new(0.0)).collect(), head: AtomicUsize::new(0), tail: AtomicUsize::new(0) }
+    }
+
+    pub fn len(&self) -> usize { self.tail.load(Ordering::Acquire).wrapping_sub(self.head.load(Ordering::Acquire)) }
+
+    /// Producer side. Appends as much of `src` as fits and returns how many samples that was.
+    pub fn push(&self, src: &[f32]) -> usize {
+        let cap = self.buf.len();
+        let tail = self.tail.load(Ordering::Relaxed);
+        let n = src.len().min(cap - self.len());
+        for (i, &s) in src[..n].iter().enumerate() {
+            unsafe { *self.buf[(tail + i) % cap].get() = s };
+        }
+        self.tail.store(tail.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// Consumer side. Fills `out`, padding with silence when the ring runs short; returns how
+    /// many samples were real.
+    pub fn pop(&self, out: &mut [f32]) -> usize {
+        let cap = self.buf.len();
+        let head = self.head.load(Ordering::Relaxed);
+        let n = out.len().min(self.len());
+        for (i, o) in out[..n].iter_mut().enumerate() {
+            *o = unsafe { *self.buf[(head + i) % cap].get() };
+        }
+        out[n..].fill(0.0);
+        self.head.store(head.wrapping_add(n), Ordering::Release);
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefilled_ring_delays_by_its_fill() {
+        let ring = Ring::new(8);
+        assert_eq!(ring.push(&[0.0; 3]), 3);
+        let mut out = [0.0; 4];
+        let mut got = Vec::new();
+        for block in [[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0]] {
+            assert_eq!(ring.push(&block), 4);
+            assert_eq!(ring.pop(&mut out), 4);
+            got.extend_from_slice(&out);
+        }
+        assert_eq!(got, [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.push(&[0.0; 6]), 5);
+    }
+}
@@ -0,0 +1,92 @@
+//! Check that an OpenASIO driver library works without writing a host: load it, list its devices
+//! and print what each one reports.
+//!
+//! Usage: `openasio-probe <driver.so> [--start]`. With `--start`, each device also streams silence
+//! for one second and the xrun counts are printed.
+use anyhow::{bail, Result};
+use openasio::{Driver, HostProcess, StreamConfig};
+use std::os::raw::c_void;
+use std::time::Duration;
+
+const USAGE: &str = "usage: openasio-probe <driver> [--start]";
+const STREAM_TIME: Duration = Duration::from_secs(1);
+
+/// Writes silence and keeps the stream running.
+struct Silence;
+
+impl HostProcess for Silence {
+    fn process(&mut self, _inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool {
+        let bytes = frames as usize * cfg.format.bytes_per_sample();
+        if outputs.is_null() || cfg.out_channels == 0 { return true; }
+        unsafe {
+            if cfg.interleaved {
+                std::ptr::write_bytes(outputs as *mut u8, 0, bytes * cfg.out_channels as usize);
+            } else {
+                for c in 0..cfg.out_channels as usize {
+                    let ch = *(outputs as *const *mut u8).add(c);
+                    if !ch.is_null() { std::ptr::write_bytes(ch, 0, bytes); }
+                }
+            }
+        }
+        true
+    }
+}
+
+fn describe(cfg: &StreamConfig) -> String {
+    format!("{} Hz, {} frames, {} in / {} out, {} {}", cfg.sample_rate, cfg.buffer_frames, cfg.in_channels, cfg.out_channels,
+        cfg.format, if cfg.interleaved { "interleaved" } else { "non-interleaved" })
+}
+
+fn load(path: &str) -> Result<Driver> { Driver::load(path, Box::new(Silence), StreamConfig::default(), true) }
+
+/// Open `device` on a fresh driver instance and print its config and latency; a driver opens one
+/// device per instance, so each device gets its own.
+fn probe_device(path: &str, device: &str, start: bool) -> Result<()> {
+    let mut driver = load(path)?;
+    driver.open_by_name(Some(device))?;
+    let cfg = driver.default_config()?;
+    println!("    default config: {}", describe(&cfg));
+    match driver.latency() {
+        Ok((input, output)) => println!("    latency: {input} frames in, {output} frames out"),
+        Err(e) => println!("    latency: {e:#}"),
+    }
+    if start {
+        let stream = driver.start_stream(cfg)?;
+        std::thread::sleep(STREAM_TIME);
+        let xruns = stream.xrun_stats();
+        stream.stop();
+        println!("    streamed {:?}: {} underruns, {} overruns", STREAM_TIME, xruns.underruns, xruns.overruns);
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let (mut path, mut start) = (None, false);
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--start" => start = true,
+            "-h" | "--help" => { println!("{USAGE}"); return Ok(()); }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+    let Some(path) = path else { bail!(USAGE) };
+
+    let driver = load(&path)?;
+    let caps = driver.caps();
+    println!("driver: {path}");
+    println!("capabilities: {:#x} [{caps:?}]", caps.bits());
+    let devices = driver.enumerate_devices()?;
+    drop(driver);
+    if devices.is_empty() { println!("no devices"); }
+    let mut failed = 0;
+    for (i, device) in devices.iter().enumerate() {
+        println!("[{i}] {device}");
+        if let Err(e) = probe_device(&path, device, start) {
+            println!("    error: {e:#}");
+            failed += 1;
+        }
+    }
+    if failed > 0 { bail!("{failed} of {} devices failed", devices.len()); }
+    Ok(())
+}
@@ -1 +1,24 @@
-fn main(){ println!("cargo:rerun-if-changed=../../sdk/include/openasio/openasio.h"); println!("cargo:include=../../sdk/include"); }
+fn main() {
+    println!("cargo:rerun-if-changed=../../sdk/include/openasio/openasio.h");
+    println!("cargo:include=../../sdk/include");
+    #[cfg(feature = "generated")]
+    generate();
+}
+
+/// `$OUT_DIR/bindings.rs` for the `generated` feature, from the SDK header cbindgen emits or from
+/// another copy named by `OPENASIO_HEADER`.
+#[cfg(feature = "generated")]
+fn generate() {
+    println!("cargo:rerun-if-env-changed=OPENASIO_HEADER");
+    let header = std::env::var("OPENASIO_HEADER").unwrap_or_else(|_| "../../sdk/include/openasio/openasio.h".into());
+    let out = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("bindings.rs");
+    bindgen::Builder::default()
+        .header(header)
+        .allowlist_type("oa_.*")
+        .allowlist_var("OA_.*")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .generate()
+        .expect("bindgen could not read the OpenASIO header")
+        .write_to_file(out)
+        .expect("writing the generated bindings");
+}
@@ -1,10 +1,26 @@
 //! Raw FFI for OpenASIO v1.0.0
+//!
+//! ABI structs grow by appending fields within a major version. Those passed across the ABI begin
+//! with (or are sized by) a `struct_size` that the side filling them sets to the size it was built
+//! with, and the side reading them touches only what that size covers ([`struct_has`]); the rest
+//! keeps its zero or default value. A reader therefore accepts anything from the 1.0 size up to
+//! [`OA_MAX_STRUCT_SIZE`] and rejects the rest with `OA_ERR_INVALID_ARG`, so a newer host works
+//! with an older driver and the other way round.
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
-use std::os::raw::{c_char, c_int, c_void};
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
 
 pub const OA_VERSION_MAJOR: u32 = 1;
 pub const OA_VERSION_MINOR: u32 = 0;
 pub const OA_VERSION_PATCH: u32 = 0;
+/// What `openasio_abi_version` returns: the major version in the high 16 bits, the minor in the
+/// low 16. Hosts refuse drivers whose major differs from theirs; a newer minor only adds fields.
+pub const OA_ABI_VERSION: u32 = (OA_VERSION_MAJOR << 16) | OA_VERSION_MINOR;
+/// Split an `openasio_abi_version` value into `(major, minor)`.
+pub const fn oa_abi_split(version: u32) -> (u32, u32) { (version >> 16, version & 0xffff) }
 
 pub type oa_bool = i32;
 pub const OA_FALSE: oa_bool = 0;
@@ -18,20 +34,552 @@ pub const OA_ERR_INVALID_ARG: oa_result = -3;
 pub const OA_ERR_DEVICE: oa_result = -4;
 pub const OA_ERR_BACKEND: oa_result = -5;
 pub const OA_ERR_STATE: oa_result = -6;
+/// The device exists but another client holds it, e.g. a sound server; closing it may help.
+pub const OA_ERR_BUSY: oa_result = -7;
+/// The driver gave up waiting, e.g. for its worker thread to exit.
+pub const OA_ERR_TIMEOUT: oa_result = -8;
+/// The call needs an open device and none is: `start` before `open_device` or after `close_device`.
+pub const OA_ERR_NOT_OPEN: oa_result = -9;
+
+fn strerror_cstr(code: oa_result) -> &'static CStr {
+    match code {
+        OA_ERR_GENERIC => c"generic error",
+        OA_ERR_UNSUPPORTED => c"operation not supported",
+        OA_ERR_INVALID_ARG => c"invalid argument",
+        OA_ERR_DEVICE => c"device error",
+        OA_ERR_BACKEND => c"backend error",
+        OA_ERR_STATE => c"invalid driver state",
+        OA_ERR_BUSY => c"device busy",
+        OA_ERR_TIMEOUT => c"operation timed out",
+        OA_ERR_NOT_OPEN => c"no device open",
+        rc if rc >= 0 => c"ok",
+        _ => c"unknown error",
+    }
+}
+
+/// Stable human-readable description of an `oa_result`.
+pub fn oa_strerror(code: oa_result) -> &'static str {
+    strerror_cstr(code).to_str().unwrap_or("unknown error")
+}
+
+/// C export of [`oa_strerror`]; the returned string is static and must not be freed.
+#[no_mangle]
+pub extern "C" fn oa_strerror_c(code: oa_result) -> *const c_char {
+    strerror_cstr(code).as_ptr()
+}
+
+/// The `oa_result` for a failed device open or probe with `errno` (positive, as ALSA reports it):
+/// `OA_ERR_BUSY` when another client holds the device, `OA_ERR_DEVICE` otherwise, a missing or
+/// unplugged device included.
+#[cfg(target_os = "linux")]
+pub fn open_errno_result(errno: i32) -> oa_result {
+    match errno {
+        libc::EBUSY | libc::EAGAIN => OA_ERR_BUSY,
+        _ => OA_ERR_DEVICE,
+    }
+}
+
+/// `oa_result` with a readable `Display` ("ok", "err_device", ...) for log messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OaResult(pub oa_result);
+
+impl OaResult {
+    pub fn is_ok(self) -> bool { self.0 >= 0 }
+    pub fn is_err(self) -> bool { self.0 < 0 }
+}
+
+impl From<oa_result> for OaResult {
+    fn from(rc: oa_result) -> Self { Self(rc) }
+}
+
+impl fmt::Display for OaResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            OA_OK => "ok",
+            OA_ERR_GENERIC => "err_generic",
+            OA_ERR_UNSUPPORTED => "err_unsupported",
+            OA_ERR_INVALID_ARG => "err_invalid_arg",
+            OA_ERR_DEVICE => "err_device",
+            OA_ERR_BACKEND => "err_backend",
+            OA_ERR_STATE => "err_state",
+            OA_ERR_BUSY => "err_busy",
+            OA_ERR_TIMEOUT => "err_timeout",
+            OA_ERR_NOT_OPEN => "err_not_open",
+            rc if rc > 0 => return write!(f, "ok({rc})"),
+            rc => return write!(f, "err({rc})"),
+        };
+        f.write_str(name)
+    }
+}
+
+/// Debug for the C enums below: the constant's name, or the raw value when it is none of them.
+macro_rules! enum_debug {
+    ($name:ident { $($variant:ident),+ }) => {
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match *self { $(Self::$variant => f.write_str(stringify!($variant)),)+ Self(raw) => write!(f, "{}({raw})", stringify!($name)) }
+            }
+        }
+    };
+}
+
+// The C enums are `i32` newtypes rather than Rust enums: whatever a foreign driver or host
+// passes is a valid value, and `is_known` tells the listed ones apart. Each value is also a
+// free constant; the header gets those, as the impls are hidden from cbindgen.
+
+/// Which side of the device a channel is on, for `get_channel_name`.
+#[repr(transparent)] #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct oa_direction(pub i32);
+/// cbindgen:ignore
+impl oa_direction {
+    pub const OA_DIR_INPUT: Self = Self(OA_DIR_INPUT as i32);
+    pub const OA_DIR_OUTPUT: Self = Self(OA_DIR_OUTPUT as i32);
+    pub const fn is_known(self) -> bool { matches!(self, Self::OA_DIR_INPUT | Self::OA_DIR_OUTPUT) }
+    /// The `u32` form the `get_channel_names` and `set_channel_gain` entries take.
+    pub const fn as_u32(self) -> u32 { self.0 as u32 }
+}
+enum_debug!(oa_direction { OA_DIR_INPUT, OA_DIR_OUTPUT });
+
+/// `oa_direction` values as `get_channel_names` and `set_channel_gain` take them.
+pub const OA_DIR_INPUT: u32 = 0;
+pub const OA_DIR_OUTPUT: u32 = 1;
+
+/// A set of `OA_CAP_*` bits, as `get_caps` returns it.
+pub type oa_caps = u32;
 
 pub const OA_CAP_OUTPUT: u32 = 1<<0;
 pub const OA_CAP_INPUT: u32 = 1<<1;
 pub const OA_CAP_FULL_DUPLEX: u32 = 1<<2;
 pub const OA_CAP_SET_SAMPLERATE: u32 = 1<<3;
 pub const OA_CAP_SET_BUFFRAMES: u32 = 1<<4;
+pub const OA_CAP_CLOCK_SELECT: u32 = 1<<5;
+/// Output is routed back to an input through shared memory rather than to hardware.
+pub const OA_CAP_LOOPBACK: u32 = 1<<6;
+/// `oa_time_info::sample_position` is filled in. Without it hosts must count frames themselves.
+pub const OA_CAP_SAMPLE_POSITION: u32 = 1<<7;
+/// `set_channel_mask` is honored: disabled channels are left out of the buffers `process` sees.
+pub const OA_CAP_CHANNEL_MASK: u32 = 1<<8;
+/// `oa_stream_config::exclusive` is honored: the driver can bypass any sound server or software mixer.
+pub const OA_CAP_EXCLUSIVE: u32 = 1<<9;
+/// The open device has a MIDI port: `send_midi` is implemented and `midi_in` may be called.
+pub const OA_CAP_MIDI: u32 = 1<<10;
+/// The driver can move samples through the device's DMA buffer directly, without a copy through
+/// the kernel, once asked to with `set_option("access", "mmap")`.
+pub const OA_CAP_MMAP: u32 = 1<<11;
 
-#[repr(C)] #[derive(Clone, Copy, Debug)]
-pub enum oa_sample_format { OA_SAMPLE_F32 = 1, OA_SAMPLE_I16 = 2 }
+/// The `get_caps` bits as a typed set, so drivers name flags instead of or-ing raw shifts.
+/// Bits 0-23 are for caps this spec defines, assigned in order; bits 24-31 ([`OaCaps::VENDOR`])
+/// are left to individual drivers and mean nothing to other hosts. `const CAPS` items combine
+/// flags with [`OaCaps::union`], as `|` is not usable in constants.
+#[repr(transparent)] #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct OaCaps(u32);
+/// cbindgen:ignore
+impl OaCaps {
+    pub const OUTPUT: Self = Self(OA_CAP_OUTPUT);
+    pub const INPUT: Self = Self(OA_CAP_INPUT);
+    pub const FULL_DUPLEX: Self = Self(OA_CAP_FULL_DUPLEX);
+    pub const SET_SAMPLERATE: Self = Self(OA_CAP_SET_SAMPLERATE);
+    pub const SET_BUFFRAMES: Self = Self(OA_CAP_SET_BUFFRAMES);
+    pub const CLOCK_SELECT: Self = Self(OA_CAP_CLOCK_SELECT);
+    pub const LOOPBACK: Self = Self(OA_CAP_LOOPBACK);
+    pub const SAMPLE_POSITION: Self = Self(OA_CAP_SAMPLE_POSITION);
+    pub const CHANNEL_MASK: Self = Self(OA_CAP_CHANNEL_MASK);
+    pub const EXCLUSIVE: Self = Self(OA_CAP_EXCLUSIVE);
+    pub const MIDI: Self = Self(OA_CAP_MIDI);
+    pub const MMAP: Self = Self(OA_CAP_MMAP);
+    /// Bits reserved for caps this spec has yet to define.
+    pub const RESERVED: Self = Self(0x00FF_F000);
+    /// Bits reserved for driver-specific caps.
+    pub const VENDOR: Self = Self(0xFF00_0000);
+    /// Every named flag with its name, in bit order.
+    pub const NAMED: [(Self, &'static str); 12] = [
+        (Self::OUTPUT, "OUTPUT"), (Self::INPUT, "INPUT"), (Self::FULL_DUPLEX, "FULL_DUPLEX"),
+        (Self::SET_SAMPLERATE, "SET_SAMPLERATE"), (Self::SET_BUFFRAMES, "SET_BUFFRAMES"),
+        (Self::CLOCK_SELECT, "CLOCK_SELECT"), (Self::LOOPBACK, "LOOPBACK"),
+        (Self::SAMPLE_POSITION, "SAMPLE_POSITION"), (Self::CHANNEL_MASK, "CHANNEL_MASK"), (Self::EXCLUSIVE, "EXCLUSIVE"),
+        (Self::MIDI, "MIDI"), (Self::MMAP, "MMAP"),
+    ];
 
-#[repr(C)] #[derive(Clone, Copy, Debug)]
-pub enum oa_buffer_layout { OA_BUF_INTERLEAVED = 1, OA_BUF_NONINTERLEAVED = 2 }
+    pub const fn empty() -> Self { Self(0) }
+    /// Every bit of `bits` is kept, named or not, so caps from a newer driver survive a round trip.
+    pub const fn from_bits_retain(bits: u32) -> Self { Self(bits) }
+    pub const fn bits(self) -> u32 { self.0 }
+    pub const fn union(self, other: Self) -> Self { Self(self.0 | other.0) }
+    pub const fn contains(self, other: Self) -> bool { self.0 & other.0 == other.0 }
+    pub const fn is_empty(self) -> bool { self.0 == 0 }
+}
+
+impl From<u32> for OaCaps {
+    fn from(bits: u32) -> Self { Self(bits) }
+}
+
+impl From<OaCaps> for u32 {
+    fn from(caps: OaCaps) -> Self { caps.0 }
+}
+
+impl std::ops::BitOr for OaCaps {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self { self.union(rhs) }
+}
+
+impl std::ops::BitOrAssign for OaCaps {
+    fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0; }
+}
+
+impl std::ops::BitAnd for OaCaps {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self { Self(self.0 & rhs.0) }
+}
+
+/// The set flag names joined with ` | `, then any other bits in hex: `OUTPUT | INPUT | 0x1000000`.
+impl fmt::Debug for OaCaps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+        let mut parts = Vec::new();
+        for (flag, name) in Self::NAMED {
+            if self.contains(flag) { parts.push(name.to_string()); rest &= !flag.0; }
+        }
+        if rest != 0 || parts.is_empty() { parts.push(format!("{rest:#x}")); }
+        f.write_str(&parts.join(" | "))
+    }
+}
+
+#[repr(transparent)] #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct oa_sample_format(pub i32);
+/// cbindgen:ignore
+impl oa_sample_format {
+    pub const OA_SAMPLE_F32: Self = OA_SAMPLE_F32;
+    pub const OA_SAMPLE_I16: Self = OA_SAMPLE_I16;
+    pub const OA_SAMPLE_I32: Self = OA_SAMPLE_I32;
+    pub const OA_SAMPLE_I24: Self = OA_SAMPLE_I24;
+    pub const OA_SAMPLE_F64: Self = OA_SAMPLE_F64;
+    pub const fn is_known(self) -> bool {
+        matches!(self, Self::OA_SAMPLE_F32 | Self::OA_SAMPLE_I16 | Self::OA_SAMPLE_I32 | Self::OA_SAMPLE_I24 | Self::OA_SAMPLE_F64)
+    }
+}
+impl Default for oa_sample_format {
+    fn default() -> Self { Self::OA_SAMPLE_F32 }
+}
+enum_debug!(oa_sample_format { OA_SAMPLE_F32, OA_SAMPLE_I16, OA_SAMPLE_I32, OA_SAMPLE_I24, OA_SAMPLE_F64 });
+/// Native float, full scale [-1.0, +1.0].
+pub const OA_SAMPLE_F32: oa_sample_format = oa_sample_format(1);
+/// Signed 16-bit, full scale ±32768.
+pub const OA_SAMPLE_I16: oa_sample_format = oa_sample_format(2);
+/// Signed 32-bit, full scale ±2^31. 24-bit hardware is MSB-aligned (low byte zero).
+pub const OA_SAMPLE_I32: oa_sample_format = oa_sample_format(3);
+/// Signed 24-bit packed in 3 little-endian bytes, full scale ±2^23.
+pub const OA_SAMPLE_I24: oa_sample_format = oa_sample_format(4);
+/// Native double, full scale [-1.0, +1.0]. Never a driver's default; hosts ask for it.
+pub const OA_SAMPLE_F64: oa_sample_format = oa_sample_format(5);
+
+#[repr(transparent)] #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct oa_buffer_layout(pub i32);
+/// cbindgen:ignore
+impl oa_buffer_layout {
+    pub const OA_BUF_INTERLEAVED: Self = OA_BUF_INTERLEAVED;
+    pub const OA_BUF_NONINTERLEAVED: Self = OA_BUF_NONINTERLEAVED;
+    pub const fn is_known(self) -> bool { matches!(self, Self::OA_BUF_INTERLEAVED | Self::OA_BUF_NONINTERLEAVED) }
+}
+impl Default for oa_buffer_layout {
+    fn default() -> Self { Self::OA_BUF_INTERLEAVED }
+}
+enum_debug!(oa_buffer_layout { OA_BUF_INTERLEAVED, OA_BUF_NONINTERLEAVED });
+pub const OA_BUF_INTERLEAVED: oa_buffer_layout = oa_buffer_layout(1);
+pub const OA_BUF_NONINTERLEAVED: oa_buffer_layout = oa_buffer_layout(2);
+
+/// Which direction glitched, passed to `oa_host_callbacks::xrun`.
+#[repr(transparent)] #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct oa_xrun_kind(pub i32);
+/// cbindgen:ignore
+impl oa_xrun_kind {
+    pub const OA_XRUN_UNDERRUN: Self = OA_XRUN_UNDERRUN;
+    pub const OA_XRUN_OVERRUN: Self = OA_XRUN_OVERRUN;
+    pub const OA_XRUN_CALLBACK: Self = OA_XRUN_CALLBACK;
+    pub const fn is_known(self) -> bool { matches!(self, Self::OA_XRUN_UNDERRUN | Self::OA_XRUN_OVERRUN | Self::OA_XRUN_CALLBACK) }
+}
+enum_debug!(oa_xrun_kind { OA_XRUN_UNDERRUN, OA_XRUN_OVERRUN, OA_XRUN_CALLBACK });
+pub const OA_XRUN_UNDERRUN: oa_xrun_kind = oa_xrun_kind(1);
+pub const OA_XRUN_OVERRUN: oa_xrun_kind = oa_xrun_kind(2);
+/// One `process` call ran longer than the limit the host set with a driver option (the alsa17h
+/// driver's `callback_threshold_ns`); drivers send it only to hosts that set one.
+pub const OA_XRUN_CALLBACK: oa_xrun_kind = oa_xrun_kind(3);
+
+impl fmt::Display for oa_sample_format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OA_SAMPLE_F32 => f.write_str("f32"), Self::OA_SAMPLE_I16 => f.write_str("i16"),
+            Self::OA_SAMPLE_I32 => f.write_str("i32"), Self::OA_SAMPLE_I24 => f.write_str("i24"),
+            Self::OA_SAMPLE_F64 => f.write_str("f64"),
+            Self(raw) => write!(f, "unknown format {raw}"),
+        }
+    }
+}
+
+impl oa_sample_format {
+    /// Bytes one sample occupies in a buffer; 0 for an unknown format.
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::OA_SAMPLE_F32 | Self::OA_SAMPLE_I32 => 4, Self::OA_SAMPLE_I16 => 2, Self::OA_SAMPLE_I24 => 3,
+            Self::OA_SAMPLE_F64 => 8, _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for oa_buffer_layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OA_BUF_INTERLEAVED => f.write_str("interleaved"), Self::OA_BUF_NONINTERLEAVED => f.write_str("non-interleaved"),
+            Self(raw) => write!(f, "unknown layout {raw}"),
+        }
+    }
+}
+
+/// Bit for `fmt` in an `oa_supported_configs::formats` mask; 0 for values that have none.
+pub const fn oa_format_bit(fmt: oa_sample_format) -> u32 { if fmt.0 >= 0 && fmt.0 < 32 { 1 << fmt.0 } else { 0 } }
+
+/// Driver-independent sanity checks on a stream config: non-zero rate and buffer, at least one
+/// channel, known format and layout. Returns `OA_OK` or `OA_ERR_INVALID_ARG`; a buffer size that
+/// is not a power of two is allowed but logged, since many backends round it.
+pub fn validate_stream_config(cfg: &oa_stream_config) -> oa_result {
+    if cfg.sample_rate == 0 || cfg.buffer_frames == 0 { return OA_ERR_INVALID_ARG; }
+    if cfg.in_channels as u32 + cfg.out_channels as u32 == 0 { return OA_ERR_INVALID_ARG; }
+    if !cfg.format.is_known() || !cfg.layout.is_known() { return OA_ERR_INVALID_ARG; }
+    if !cfg.buffer_frames.is_power_of_two() {
+        log::warn!("buffer_frames={} is not a power of two", cfg.buffer_frames);
+    }
+    OA_OK
+}
+
+/// Most channels a stream may have in either direction.
+pub const OA_MAX_CHANNELS: u16 = 64;
+
+/// Channel-count checks drivers run before sizing buffers or opening devices: at least `min_out`
+/// output channels (1 for drivers that always play), at most [`OA_MAX_CHANNELS`] either way, and
+/// a `buffer_frames * channels` block that fits a `u32`. Returns `OA_OK` or `OA_ERR_INVALID_ARG`.
+pub fn validate_channel_counts(cfg: &oa_stream_config, min_out: u16) -> oa_result {
+    if cfg.out_channels < min_out || cfg.out_channels > OA_MAX_CHANNELS || cfg.in_channels > OA_MAX_CHANNELS { return OA_ERR_INVALID_ARG; }
+    let widest = cfg.in_channels.max(cfg.out_channels) as u32;
+    if cfg.buffer_frames.checked_mul(widest).is_none() { return OA_ERR_INVALID_ARG; }
+    OA_OK
+}
+
+/// Lowest sample rate a stream may run at.
+pub const OA_MIN_SAMPLE_RATE: u32 = 8_000;
+/// Highest sample rate a stream may run at.
+pub const OA_MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Every driver-independent check in one call, for drivers to run at the top of `start` and hosts
+/// before dispatching it: [`validate_stream_config`], a rate within [`OA_MIN_SAMPLE_RATE`] and
+/// [`OA_MAX_SAMPLE_RATE`], and [`validate_channel_counts`] with no output floor. Returns `OA_OK`
+/// or `OA_ERR_INVALID_ARG`.
+pub fn oa_stream_config_check(cfg: &oa_stream_config) -> oa_result {
+    let rc = validate_stream_config(cfg);
+    if rc != OA_OK { return rc; }
+    if !(OA_MIN_SAMPLE_RATE..=OA_MAX_SAMPLE_RATE).contains(&cfg.sample_rate) { return OA_ERR_INVALID_ARG; }
+    validate_channel_counts(cfg, 0)
+}
+
+/// Largest linear gain `set_channel_gain` accepts (about +12 dB); larger values are clamped.
+pub const OA_MAX_CHANNEL_GAIN: f32 = 4.0;
+
+/// Per-channel linear trim gains for one direction, set through `set_channel_gain` while the
+/// audio thread applies them. Every channel starts at 1.0.
+pub struct ChannelGains([AtomicU32; OA_MAX_CHANNELS as usize]);
+
+impl Default for ChannelGains {
+    fn default() -> Self { Self(std::array::from_fn(|_| AtomicU32::new(1.0f32.to_bits()))) }
+}
+
+impl ChannelGains {
+    pub fn get(&self, channel: usize) -> f32 {
+        self.0.get(channel).map_or(1.0, |g| f32::from_bits(g.load(Ordering::Relaxed)))
+    }
+
+    /// Clamp `gain` to [0.0, [`OA_MAX_CHANNEL_GAIN`]] and store it. `OA_ERR_INVALID_ARG` for a
+    /// channel past [`OA_MAX_CHANNELS`] or a NaN gain.
+    pub fn set(&self, channel: u32, gain: f32) -> oa_result {
+        let Some(slot) = self.0.get(channel as usize) else { return OA_ERR_INVALID_ARG };
+        if gain.is_nan() { return OA_ERR_INVALID_ARG; }
+        slot.store(gain.clamp(0.0, OA_MAX_CHANNEL_GAIN).to_bits(), Ordering::Relaxed);
+        OA_OK
+    }
+
+    /// Scale an interleaved block of `channels` channels in place.
+    pub fn apply_interleaved<T: convert::Float>(&self, buf: &mut [T], channels: usize) {
+        if channels == 0 { return; }
+        for c in 0..channels {
+            let g = f64::from(self.get(c));
+            if g == 1.0 { continue; }
+            // The product of two f32s is exact in f64, so f32 blocks round just as `*=` would.
+            for s in buf.iter_mut().skip(c).step_by(channels) { *s = T::from_f64(s.to_f64() * g); }
+        }
+    }
+}
+
+/// `set_channel_gain` for drivers keeping a [`ChannelGains`] per direction.
+pub fn set_channel_gain(in_gains: &ChannelGains, out_gains: &ChannelGains, direction: u32, channel: u32, gain: f32) -> oa_result {
+    match direction {
+        OA_DIR_INPUT => in_gains.set(channel, gain),
+        OA_DIR_OUTPUT => out_gains.set(channel, gain),
+        _ => OA_ERR_INVALID_ARG,
+    }
+}
+
+/// Sample conversions between f32 and the other formats. Float input outside [-1.0, +1.0] is
+/// clipped to the integer range; integer to float divides by the full-scale value.
+pub mod convert {
+    const I32_SCALE: f32 = 2147483648.0;
+    const I24_SCALE: f32 = 8388608.0;
+
+    pub fn f32_to_i32(src: &[f32], dst: &mut [i32]) {
+        for (s, d) in src.iter().zip(dst.iter_mut()) {
+            // `as` saturates, so +1.0 lands on i32::MAX rather than wrapping.
+            *d = (s.clamp(-1.0, 1.0) * I32_SCALE).round() as i32;
+        }
+    }
+
+    pub fn i32_to_f32(src: &[i32], dst: &mut [f32]) {
+        for (s, d) in src.iter().zip(dst.iter_mut()) { *d = *s as f32 / I32_SCALE; }
+    }
+
+    /// `dst` holds 3 bytes per sample.
+    pub fn f32_to_i24(src: &[f32], dst: &mut [u8]) {
+        for (s, d) in src.iter().zip(dst.chunks_exact_mut(3)) {
+            let v = ((s.clamp(-1.0, 1.0) * I24_SCALE).round() as i32).clamp(-(1 << 23), (1 << 23) - 1);
+            d.copy_from_slice(&v.to_le_bytes()[..3]);
+        }
+    }
+
+    /// `src` holds 3 bytes per sample.
+    pub fn i24_to_f32(src: &[u8], dst: &mut [f32]) {
+        for (s, d) in src.chunks_exact(3).zip(dst.iter_mut()) {
+            // Sign-extend by placing the 24 bits at the top of an i32 and shifting back down.
+            let v = i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8;
+            *d = v as f32 / I24_SCALE;
+        }
+    }
+
+    pub fn f32_to_f64(src: &[f32], dst: &mut [f64]) {
+        for (s, d) in src.iter().zip(dst.iter_mut()) { *d = f64::from(*s); }
+    }
+
+    /// Rounds to the nearest f32; no clipping, as between the float formats full scale is the same.
+    pub fn f64_to_f32(src: &[f64], dst: &mut [f32]) {
+        for (s, d) in src.iter().zip(dst.iter_mut()) { *d = *s as f32; }
+    }
+
+    /// A float sample the [`layout`](crate::layout) helpers copy, converting between widths as
+    /// they go.
+    pub trait Float: Copy {
+        fn from_f64(v: f64) -> Self;
+        fn to_f64(self) -> f64;
+    }
+
+    impl Float for f32 {
+        fn from_f64(v: f64) -> Self { v as f32 }
+        fn to_f64(self) -> f64 { f64::from(self) }
+    }
+
+    impl Float for f64 {
+        fn from_f64(v: f64) -> Self { v }
+        fn to_f64(self) -> f64 { self }
+    }
+}
+
+/// Copies between interleaved blocks and planar buffers, for drivers whose device layout differs
+/// from the host's. A planar buffer holds `ch` planes of `stride` samples each. Either side may
+/// be f32 or f64, so a driver can change the width in the same pass.
+pub mod layout {
+    use crate::convert::Float;
+
+    /// Fill the interleaved `dst` from `planar`, one frame per `ch` samples of `dst`.
+    pub fn interleave<S: Float, D: Float>(planar: &[S], stride: usize, ch: usize, dst: &mut [D]) {
+        if ch == 0 { return; }
+        for (f, frame) in dst.chunks_exact_mut(ch).enumerate() {
+            for (c, s) in frame.iter_mut().enumerate() { *s = D::from_f64(planar[c * stride + f].to_f64()); }
+        }
+    }
+
+    /// Spread the interleaved `src` across the planes of `planar`.
+    pub fn deinterleave<S: Float, D: Float>(src: &[S], ch: usize, planar: &mut [D], stride: usize) {
+        if ch == 0 { return; }
+        for (f, frame) in src.chunks_exact(ch).enumerate() {
+            for (c, &s) in frame.iter().enumerate() { planar[c * stride + f] = D::from_f64(s.to_f64()); }
+        }
+    }
+}
+
+/// The block an `OA_SAMPLE_F64` host reads and writes, for drivers that copy it to and from an
+/// interleaved device block. Holds either layout: interleaved, or one plane of `frames` samples
+/// per channel behind the pointer arrays `process` takes.
+#[derive(Default)]
+pub struct F64Block {
+    input: Vec<f64>,
+    output: Vec<f64>,
+    in_planes: Vec<*const f64>,
+    out_planes: Vec<*mut f64>,
+    frames: usize,
+    interleaved: bool,
+}
+
+impl F64Block {
+    /// Sized for `cfg`'s block and channels, so the audio thread never allocates.
+    pub fn new(cfg: &oa_stream_config) -> Self {
+        let frames = cfg.buffer_frames as usize;
+        let (ich, och) = (cfg.in_channels as usize, cfg.out_channels as usize);
+        let mut block = Self {
+            input: vec![0.0; frames * ich],
+            output: vec![0.0; frames * och],
+            frames,
+            interleaved: cfg.layout == OA_BUF_INTERLEAVED,
+            ..Default::default()
+        };
+        if !block.interleaved {
+            block.in_planes = (0..ich).map(|c| block.input[c * frames..].as_ptr()).collect();
+            block.out_planes = (0..och).map(|c| block.output[c * frames..].as_mut_ptr()).collect();
+        }
+        block
+    }
+
+    /// Widen the device's interleaved `src` into the input the host will read. `src` may be
+    /// shorter than the block, never longer.
+    pub fn load_input<T: convert::Float>(&mut self, src: &[T], channels: usize) {
+        if self.interleaved {
+            for (s, d) in src.iter().zip(self.input.iter_mut()) { *d = s.to_f64(); }
+        } else {
+            layout::deinterleave(src, channels, &mut self.input, self.frames);
+        }
+    }
 
-#[repr(C)] #[derive(Clone, Copy)]
+    /// Copy what the host wrote into the device's interleaved `dst`, at the device's width.
+    pub fn store_output<T: convert::Float>(&self, dst: &mut [T], channels: usize) {
+        if self.interleaved {
+            for (s, d) in self.output.iter().zip(dst.iter_mut()) { *d = T::from_f64(*s); }
+        } else {
+            layout::interleave(&self.output, self.frames, channels, dst);
+        }
+    }
+
+    /// Clear the output, for a host that may leave some of it unwritten.
+    pub fn silence_output(&mut self) { self.output.fill(0.0); }
+
+    /// The `in_ptr` and `out_ptr` to pass `process`; `in_ptr` is null without inputs.
+    pub fn host_ptrs(&mut self) -> (*const c_void, *mut c_void) {
+        let in_ptr = match (self.input.is_empty(), self.interleaved) {
+            (true, _) => std::ptr::null(),
+            (false, true) => self.input.as_ptr() as *const c_void,
+            (false, false) => self.in_planes.as_ptr() as *const c_void,
+        };
+        let out_ptr = if self.interleaved {
+            self.output.as_mut_ptr() as *mut c_void
+        } else {
+            self.out_planes.as_mut_ptr() as *mut c_void
+        };
+        (in_ptr, out_ptr)
+    }
+}
+
+#[repr(C)] #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct oa_stream_config {
     pub sample_rate: u32,
     pub buffer_frames: u32,
@@ -39,57 +587,2124 @@ pub struct oa_stream_config {
     pub out_channels: u16,
     pub format: oa_sample_format,
     pub layout: oa_buffer_layout,
+    /// Nonzero to require exclusive access to the hardware. Drivers that report
+    /// `OA_CAP_EXCLUSIVE` then refuse devices behind a mixer or converter with `OA_ERR_DEVICE`
+    /// and a `get_last_error` text starting with [`OA_EXCLUSIVE_UNAVAILABLE`]. Present only if
+    /// `oa_create_params::config_size` covers it; read through [`read_stream_config`].
+    pub exclusive: oa_bool,
+}
+
+/// Size of the 1.0 stream config (through `layout`), assumed when the host sets no `config_size`.
+pub const OA_STREAM_CONFIG_V1_SIZE: usize = std::mem::offset_of!(oa_stream_config, exclusive);
+
+/// How `get_last_error` starts when an exclusive start failed because the device is shared,
+/// which a host may want to tell apart from a missing or broken device.
+pub const OA_EXCLUSIVE_UNAVAILABLE: &str = "exclusive access unavailable";
+
+impl Default for oa_stream_config {
+    /// 48 kHz, 128 frames, stereo in and out, interleaved f32: what the ALSA drivers report from
+    /// `get_default_config`.
+    fn default() -> Self {
+        Self { sample_rate: 48000, buffer_frames: 128, in_channels: 2, out_channels: 2, format: oa_sample_format::default(), layout: oa_buffer_layout::default(), exclusive: OA_FALSE }
+    }
 }
 
-#[repr(C)] #[derive(Clone, Copy)]
+impl oa_stream_config {
+    /// All fields zero, for out-parameters a driver fills in. Not a valid config: the format and
+    /// layout are unknown values.
+    pub const fn zeroed() -> Self {
+        Self { sample_rate: 0, buffer_frames: 0, in_channels: 0, out_channels: 0, format: oa_sample_format(0), layout: oa_buffer_layout(0), exclusive: OA_FALSE }
+    }
+}
+
+#[repr(C)] #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct oa_time_info {
     pub host_time_ns: u64, pub device_time_ns: u64, pub underruns: u32, pub overruns: u32,
+    /// Blocks that reached the device with little of the previous one left to play, since the
+    /// last start: a warning before `underruns` moves. Absent from 1.0 drivers; see [`time_info_has_near_xrun`].
+    pub near_xrun: u32,
+    /// Frames passed to `process` since the last start, before this block: 0 in the first
+    /// callback, advancing by exactly `frames` per call. Only drivers reporting
+    /// `OA_CAP_SAMPLE_POSITION` have (and write) this field.
+    pub sample_position: u64,
+}
+
+impl oa_time_info {
+    /// No timestamps and no xruns; the `Default`, usable in `const` contexts.
+    pub const fn zeroed() -> Self { Self { host_time_ns: 0, device_time_ns: 0, underruns: 0, overruns: 0, near_xrun: 0, sample_position: 0 } }
+}
+
+/// Whether the driver's `oa_time_info` includes `near_xrun`. The field arrived with `get_status`,
+/// so a vtable without that slot means the shorter 1.0 struct.
+pub fn time_info_has_near_xrun(vt: &oa_driver_vtable) -> bool {
+    vtable_has(vt, std::mem::offset_of!(oa_driver_vtable, get_status))
 }
 
-#[repr(C)] #[derive(Clone, Copy)]
+/// Host entry points. Fields after `reset_request` exist only if `oa_create_params::host_size`
+/// covers them; drivers read the struct through [`host_callbacks`].
+#[repr(C)] #[derive(Clone, Copy, Debug, Default)]
 pub struct oa_host_callbacks {
+    /// Required; drivers refuse to be created without it. The other 1.0 callbacks,
+    /// `latency_changed` and `reset_request`, are optional.
     pub process: Option<unsafe extern "C" fn(user:*mut c_void,in_ptr:*const c_void,out_ptr:*mut c_void,frames:u32,time:*const oa_time_info,cfg:*const oa_stream_config)->oa_bool>,
     pub latency_changed: Option<unsafe extern "C" fn(user:*mut c_void,in_latency:u32,out_latency:u32)>,
     pub reset_request: Option<unsafe extern "C" fn(user:*mut c_void)>,
+    /// Optional. The set of devices `query_devices` would report has changed (hot-plug).
+    /// Called from a driver thread, never the RT thread.
+    pub device_list_changed: Option<unsafe extern "C" fn(user:*mut c_void)>,
+    /// Optional. An xrun happened; `count` is the new total for `kind`, the same counter
+    /// `oa_time_info` reports. Called from the driver's RT thread as soon as it is detected.
+    pub xrun: Option<unsafe extern "C" fn(user:*mut c_void,kind:oa_xrun_kind,count:u32)>,
+    /// Optional. MIDI that arrived during the block about to be processed, in time order, each
+    /// event's `sample_offset` counting frames into that block. Called from the RT thread right
+    /// before `process`, only by drivers reporting `OA_CAP_MIDI`; `events` is valid for the call only.
+    pub midi_in: Option<unsafe extern "C" fn(user:*mut c_void,events:*const oa_midi_event,count:u32)>,
+}
+
+/// Size of the 1.0 callbacks (through `reset_request`), assumed when the host sets no `host_size`.
+pub const OA_HOST_CALLBACKS_V1_SIZE: usize = std::mem::offset_of!(oa_host_callbacks, device_list_changed);
+
+impl oa_host_callbacks {
+    /// Every callback null; the `Default`, usable in `static` initializers.
+    pub const fn empty() -> Self { Self { process: None, latency_changed: None, reset_request: None, device_list_changed: None, xrun: None, midi_in: None } }
+}
+
+/// One short MIDI message: a status byte and its data bytes in `data[..size]`. `sample_offset`
+/// places it in a block, in frames from the block's first. System exclusive is not carried.
+#[repr(C)] #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct oa_midi_event {
+    pub sample_offset: u32,
+    pub size: u8,
+    pub data: [u8; 3],
+}
+
+impl oa_midi_event {
+    /// `bytes` as an event at `sample_offset`, or `None` unless it is one complete message of a
+    /// status [`midi_message_len`] knows.
+    pub fn new(sample_offset: u32, bytes: &[u8]) -> Option<Self> {
+        if midi_message_len(*bytes.first()?)? != bytes.len() { return None; }
+        let mut data = [0; 3];
+        data[..bytes.len()].copy_from_slice(bytes);
+        Some(Self { sample_offset, size: bytes.len() as u8, data })
+    }
+
+    /// The message, clamped to `data` should a driver have sent a bad `size`.
+    pub fn bytes(&self) -> &[u8] { &self.data[..(self.size as usize).min(3)] }
+}
+
+/// Length of the message starting with `status`, status byte included; `None` for data bytes,
+/// system exclusive and undefined statuses.
+pub fn midi_message_len(status: u8) -> Option<usize> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF | 0xF2 => Some(3),
+        0xC0..=0xDF | 0xF1 | 0xF3 => Some(2),
+        0xF6 | 0xF8 | 0xFA..=0xFC | 0xFE | 0xFF => Some(1),
+        _ => None,
+    }
+}
+
+/// Filled by `query_supported_configs`. `sample_rates` points at driver-owned storage that stays
+/// valid until the next call on the same driver or until it is destroyed.
+#[repr(C)] #[derive(Clone, Copy, Debug)]
+pub struct oa_supported_configs {
+    pub sample_rates: *const u32,
+    pub sample_rate_count: u32,
+    pub min_buffer_frames: u32,
+    pub max_buffer_frames: u32,
+    pub formats: u32, // OR of oa_format_bit(..)
+}
+
+pub const OA_DEVICE_INFO_STR_LEN: usize = 256;
+
+/// Filled by `query_device_info`. The host sets `struct_size`; drivers write only the fields it
+/// covers (see [`write_device_info`]).
+#[repr(C)] #[derive(Clone, Copy, Debug)]
+pub struct oa_device_info {
+    pub struct_size: u32,
+    pub id: [c_char; OA_DEVICE_INFO_STR_LEN],          // NUL-terminated, as passed to open_device
+    pub description: [c_char; OA_DEVICE_INFO_STR_LEN], // NUL-terminated, human readable
+    pub max_in_channels: u32,
+    pub max_out_channels: u32,
+    pub min_buffer_frames: u32,
+    pub max_buffer_frames: u32,
+}
+
+impl Default for oa_device_info {
+    fn default() -> Self {
+        Self { struct_size: std::mem::size_of::<Self>() as u32, id: [0; OA_DEVICE_INFO_STR_LEN], description: [0; OA_DEVICE_INFO_STR_LEN],
+               max_in_channels: 0, max_out_channels: 0, min_buffer_frames: 0, max_buffer_frames: 0 }
+    }
+}
+
+impl oa_device_info {
+    /// Store `id` and `description`, truncated to leave room for the NUL.
+    pub fn set_strings(&mut self, id: &str, description: &str) {
+        fill_str(&mut self.id, id);
+        fill_str(&mut self.description, description);
+    }
+}
+
+/// Copy `src` into a fixed C string field, truncated to leave room for the NUL.
+fn fill_str<const N: usize>(dst: &mut [c_char; N], src: &str) {
+    let n = src.len().min(N - 1);
+    for (d, &b) in dst.iter_mut().zip(&src.as_bytes()[..n]) { *d = b as c_char; }
+    dst[n] = 0;
+}
+
+/// `query_device_info` reply for drivers: copy `info` into the host's `out`, limited to the
+/// host's `struct_size` so older hosts with a shorter struct are not overrun.
+///
+/// # Safety
+/// `out`, if non-null, must point at least `(*out).struct_size` writable bytes.
+pub unsafe fn write_device_info(info: &oa_device_info, out: *mut oa_device_info) -> i32 {
+    write_sized(info, out)
+}
+
+/// Filled by `get_status`. The host sets `struct_size`; drivers write only the fields it covers
+/// (see [`write_stream_status`]).
+#[repr(C)] #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct oa_stream_status {
+    pub struct_size: u32,
+    pub running: oa_bool,
+    pub actual_sample_rate: u32,
+    pub actual_buffer_frames: u32,
+    pub frames_processed: u64, // since the last start
+    pub underruns: u32,
+    pub overruns: u32,
+    pub near_xrun_count: u32,
+    /// Captured frames waiting for `process`, for drivers that queue input between device
+    /// callbacks (the CPAL driver); 0 for the others.
+    pub input_queued_frames: u32,
+    /// Longest single `process` call since the last start, in nanoseconds; 0 from drivers that
+    /// do not time it.
+    pub max_callback_ns: u64,
+}
+
+impl Default for oa_stream_status {
+    fn default() -> Self {
+        Self { struct_size: std::mem::size_of::<Self>() as u32, running: OA_FALSE, actual_sample_rate: 0, actual_buffer_frames: 0,
+               frames_processed: 0, underruns: 0, overruns: 0, near_xrun_count: 0, input_queued_frames: 0, max_callback_ns: 0 }
+    }
+}
+
+/// `get_status` reply for drivers, limited to the host's `struct_size` like [`write_device_info`].
+///
+/// # Safety
+/// `out`, if non-null, must point at least `(*out).struct_size` writable bytes.
+pub unsafe fn write_stream_status(status: &oa_stream_status, out: *mut oa_stream_status) -> i32 {
+    write_sized(status, out)
+}
+
+pub const OA_DRIVER_INFO_STR_LEN: usize = 64;
+
+/// Filled by a library's optional `openasio_driver_get_info` export, which hosts may call without
+/// creating a driver (e.g. to show "Behringer UMC202HD 0.3.0 by OpenASIO project" in a driver
+/// list). The host sets `struct_size`.
+#[repr(C)] #[derive(Clone, Copy, Debug)]
+pub struct oa_driver_info {
+    pub struct_size: u32,
+    pub name: [c_char; OA_DRIVER_INFO_STR_LEN],   // NUL-terminated, human readable
+    pub vendor: [c_char; OA_DRIVER_INFO_STR_LEN], // NUL-terminated
+    pub version_major: u32, pub version_minor: u32, pub version_patch: u32, // the driver's own version
+    pub api_major: u32, pub api_minor: u32, // OA_VERSION_MAJOR/MINOR it was built against
+}
+
+impl Default for oa_driver_info {
+    fn default() -> Self {
+        Self { struct_size: std::mem::size_of::<Self>() as u32, name: [0; OA_DRIVER_INFO_STR_LEN], vendor: [0; OA_DRIVER_INFO_STR_LEN],
+               version_major: 0, version_minor: 0, version_patch: 0, api_major: 0, api_minor: 0 }
+    }
+}
+
+impl oa_driver_info {
+    /// Describe a driver built against this crate's API. `version` is `major.minor.patch` as in
+    /// `CARGO_PKG_VERSION`; a pre-release suffix is ignored and missing parts read as 0.
+    pub fn new(name: &str, vendor: &str, version: &str) -> Self {
+        let mut info = Self { api_major: OA_VERSION_MAJOR, api_minor: OA_VERSION_MINOR, ..Self::default() };
+        fill_str(&mut info.name, name);
+        fill_str(&mut info.vendor, vendor);
+        let mut parts = version.split(['.', '-', '+']).map(|p| p.parse().unwrap_or(0));
+        info.version_major = parts.next().unwrap_or(0);
+        info.version_minor = parts.next().unwrap_or(0);
+        info.version_patch = parts.next().unwrap_or(0);
+        info
+    }
+}
+
+/// `openasio_driver_get_info` reply, limited to the host's `struct_size` like [`write_device_info`].
+///
+/// # Safety
+/// `out`, if non-null, must point at least `(*out).struct_size` writable bytes.
+pub unsafe fn write_driver_info(info: &oa_driver_info, out: *mut oa_driver_info) -> i32 {
+    write_sized(info, out)
+}
+
+/// Copy `src` past its leading `struct_size` into `out`, up to the size the host declared there.
+unsafe fn write_sized<T>(src: &T, out: *mut T) -> i32 {
+    let head = std::mem::size_of::<u32>();
+    if out.is_null() || (*(out as *const u32) as usize) < head { return OA_ERR_INVALID_ARG; }
+    let n = (*(out as *const u32) as usize).min(std::mem::size_of::<T>());
+    std::ptr::copy_nonoverlapping((src as *const T as *const u8).add(head), (out as *mut u8).add(head), n - head);
+    OA_OK
+}
+
+pub const OA_LOG_ERROR: u32 = 1;
+pub const OA_LOG_WARN: u32 = 2;
+pub const OA_LOG_INFO: u32 = 3;
+pub const OA_LOG_DEBUG: u32 = 4;
+pub const OA_LOG_TRACE: u32 = 5;
+
+/// Receives driver log lines (`level` is one of `OA_LOG_*`, `msg` is NUL-terminated UTF-8).
+/// May be called from any driver thread, including the RT thread. `None` (NULL) = no callback.
+pub type oa_log_fn = Option<unsafe extern "C" fn(user:*mut c_void, level:u32, msg:*const c_char)>;
+
+#[repr(C)] #[derive(Debug)]
+pub struct oa_create_params {
+    pub struct_size:u32, pub host:*const oa_host_callbacks, pub host_user:*mut c_void,
+    pub log_cb: oa_log_fn, pub log_user: *mut c_void,
+    /// `sizeof(oa_host_callbacks)` as the host built it.
+    pub host_size: u32,
+    /// Scheduling for the driver's audio thread: `OA_RT_PRIORITY_DEFAULT` leaves it to the driver,
+    /// 0 asks for normal (non-real-time) scheduling and 1..=99 for `SCHED_FIFO` at that priority.
+    pub rt_priority: i32,
+    /// CPUs the audio thread may run on, bit n for CPU n; 0 leaves it unset.
+    pub cpu_affinity_mask: u64,
+    /// `sizeof(oa_stream_config)` as the host built it, for the configs it passes and receives.
+    pub config_size: u32,
+    /// Backend hints as comma-separated `key=value` text, e.g. `host=jack` or
+    /// `device=hw:2,period_count=3`; null or empty for none. A value holding a comma is
+    /// double-quoted, with `\"` and `\\` inside the quotes. Drivers ignore keys they do not
+    /// know; parse with [`CreateOptions`].
+    pub options: *const c_char,
+}
+
+/// cbindgen:ignore
+impl oa_create_params {
+    /// Params at this crate's struct sizes for `host` and its `host_user`: no log callback,
+    /// default scheduling and no options. Set the rest with struct update syntax.
+    pub fn new(host: *const oa_host_callbacks, host_user: *mut c_void) -> Self {
+        Self {
+            struct_size: std::mem::size_of::<Self>() as u32, host, host_user, log_cb: None, log_user: std::ptr::null_mut(),
+            host_size: std::mem::size_of::<oa_host_callbacks>() as u32, rt_priority: OA_RT_PRIORITY_DEFAULT, cpu_affinity_mask: 0,
+            config_size: std::mem::size_of::<oa_stream_config>() as u32, options: std::ptr::null(),
+        }
+    }
+}
+
+/// `oa_create_params::rt_priority`: let the driver choose.
+pub const OA_RT_PRIORITY_DEFAULT: i32 = -1;
+
+/// The scheduling a host asked for in `oa_create_params`, kept by drivers for their audio thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThreadHints { pub rt_priority: i32, pub cpu_affinity_mask: u64 }
+
+impl Default for ThreadHints {
+    fn default() -> Self { Self { rt_priority: OA_RT_PRIORITY_DEFAULT, cpu_affinity_mask: 0 } }
+}
+
+impl ThreadHints {
+    /// Take the hints from creation params, using the defaults if `struct_size` predates them.
+    pub fn from_params(p: &oa_create_params) -> Self {
+        if !struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, cpu_affinity_mask), std::mem::size_of::<u64>()) {
+            return Self::default();
+        }
+        Self { rt_priority: p.rt_priority, cpu_affinity_mask: p.cpu_affinity_mask }
+    }
+
+    /// Apply the hints to the calling thread. Drivers call it first thing on their audio thread.
+    /// Unprivileged processes usually may not raise their priority; failures are logged at warn
+    /// level and the thread carries on with what it has.
+    #[cfg(target_os = "linux")]
+    pub fn apply_to_current_thread(&self, log: &LogSink) {
+        unsafe {
+            if self.rt_priority != OA_RT_PRIORITY_DEFAULT {
+                let (policy, priority) = match self.rt_priority {
+                    p if p <= 0 => (libc::SCHED_OTHER, 0),
+                    p => (libc::SCHED_FIFO, p.clamp(libc::sched_get_priority_min(libc::SCHED_FIFO), libc::sched_get_priority_max(libc::SCHED_FIFO))),
+                };
+                let param = libc::sched_param { sched_priority: priority };
+                let rc = libc::pthread_setschedparam(libc::pthread_self(), policy, &param);
+                if rc != 0 {
+                    log.emit(log::Level::Warn, format_args!("cannot set audio thread priority {priority}: {}", std::io::Error::from_raw_os_error(rc)));
+                }
+            }
+            if self.cpu_affinity_mask != 0 {
+                let mut set: libc::cpu_set_t = std::mem::zeroed();
+                for cpu in (0..64).filter(|cpu| self.cpu_affinity_mask & (1 << cpu) != 0) { libc::CPU_SET(cpu, &mut set); }
+                if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                    log.emit(log::Level::Warn, format_args!("cannot pin audio thread to CPUs {:#x}: {}", self.cpu_affinity_mask, std::io::Error::last_os_error()));
+                }
+            }
+        }
+    }
+
+    /// Apply the hints to the calling thread; outside Linux they are only logged as unsupported.
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply_to_current_thread(&self, log: &LogSink) {
+        if *self != Self::default() { log.emit(log::Level::Debug, format_args!("thread hints {self:?} are not supported on this platform")); }
+    }
+
+    /// Take the priority from an `rt_priority` option: "default", or 0..=99 as in
+    /// `oa_create_params.rt_priority`. False, leaving it unchanged, for anything else.
+    pub fn set_priority_option(&mut self, value: &str) -> bool {
+        let priority = match value {
+            "default" => OA_RT_PRIORITY_DEFAULT,
+            v => match v.parse() { Ok(p @ 0..=99) => p, _ => return false },
+        };
+        self.rt_priority = priority;
+        true
+    }
+
+    /// The priority as an `rt_priority` option value, in the form `set_priority_option` takes.
+    pub fn priority_option(&self) -> String {
+        match self.rt_priority {
+            OA_RT_PRIORITY_DEFAULT => "default".to_string(),
+            p => p.to_string(),
+        }
+    }
+}
+
+/// Largest `struct_size` a reader accepts. No ABI struct comes near it, so a larger value is
+/// garbage, e.g. an uninitialised field, not a struct from a newer minor version.
+pub const OA_MAX_STRUCT_SIZE: u32 = 4096;
+
+/// True if a struct declaring `struct_size` bytes includes the `field_size` bytes at `field_offset`.
+/// Fields past the declared size belong to a newer revision and must not be read or written.
+pub const fn struct_has(struct_size: u32, field_offset: usize, field_size: usize) -> bool {
+    struct_size as usize >= field_offset + field_size
+}
+
+/// Check creation params before a driver reads them: `struct_size` must cover the 1.0 fields
+/// (`host`, `host_user`) and not exceed [`OA_MAX_STRUCT_SIZE`], `host` must be set with a `process` callback and `host_size`, if
+/// present, must cover the 1.0 callbacks. Later fields are gated by [`struct_has`].
+///
+/// # Safety
+/// `p.host`, if non-null, must point at the host's 1.0 callbacks or more.
+pub unsafe fn create_params_validate(p: &oa_create_params) -> oa_result {
+    if !struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, host_user), std::mem::size_of::<*mut c_void>())
+        || p.struct_size > OA_MAX_STRUCT_SIZE {
+        return OA_ERR_INVALID_ARG;
+    }
+    if p.host.is_null() || (*p.host).process.is_none() { return OA_ERR_INVALID_ARG; }
+    if struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, host_size), std::mem::size_of::<u32>())
+        && (p.host_size as usize) < OA_HOST_CALLBACKS_V1_SIZE {
+        return OA_ERR_INVALID_ARG;
+    }
+    if struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, config_size), std::mem::size_of::<u32>())
+        && (p.config_size as usize) < OA_STREAM_CONFIG_V1_SIZE {
+        return OA_ERR_INVALID_ARG;
+    }
+    OA_OK
+}
+
+/// The host's `sizeof(oa_stream_config)`, for [`read_stream_config`] and [`write_stream_config`].
+/// Hosts whose params predate `config_size` have the 1.0 config.
+pub fn stream_config_size(p: &oa_create_params) -> usize {
+    if struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, config_size), std::mem::size_of::<u32>()) {
+        p.config_size as usize
+    } else {
+        OA_STREAM_CONFIG_V1_SIZE
+    }
+}
+
+/// Copy a host's config of `size` bytes, leaving fields past it at their zero values.
+///
+/// # Safety
+/// `cfg` must point at `size` readable bytes, `size` coming from [`stream_config_size`].
+pub unsafe fn read_stream_config(cfg: *const oa_stream_config, size: usize) -> oa_stream_config {
+    let mut out = oa_stream_config::zeroed();
+    let n = size.min(std::mem::size_of::<oa_stream_config>());
+    std::ptr::copy_nonoverlapping(cfg as *const u8, &mut out as *mut _ as *mut u8, n);
+    out
+}
+
+/// Write `cfg` into a host's config of `size` bytes, dropping fields it does not have.
+///
+/// # Safety
+/// `out` must point at `size` writable bytes, `size` coming from [`stream_config_size`].
+pub unsafe fn write_stream_config(cfg: &oa_stream_config, out: *mut oa_stream_config, size: usize) {
+    let n = size.min(std::mem::size_of::<oa_stream_config>());
+    std::ptr::copy_nonoverlapping(cfg as *const _ as *const u8, out as *mut u8, n);
+}
+
+/// Count an xrun in `counter` (the one `oa_time_info` reports) and pass the new total to the
+/// host's `xrun` callback if it installed one.
+///
+/// # Safety
+/// `host` and `user` must come from the same valid creation params.
+pub unsafe fn report_xrun(host: &oa_host_callbacks, user: *mut c_void, counter: &AtomicU32, kind: oa_xrun_kind) {
+    let count = counter.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+    if let Some(cb) = host.xrun { cb(user, kind, count); }
+}
+
+/// Copy the host's callbacks, leaving entries past its `host_size` as `None`. Hosts whose params
+/// predate `host_size` have the 1.0 callbacks.
+///
+/// # Safety
+/// `p` must have passed [`create_params_validate`].
+pub unsafe fn host_callbacks(p: &oa_create_params) -> oa_host_callbacks {
+    let size = if struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, host_size), std::mem::size_of::<u32>()) {
+        p.host_size as usize
+    } else {
+        OA_HOST_CALLBACKS_V1_SIZE
+    };
+    let mut out = oa_host_callbacks::default();
+    let n = size.min(std::mem::size_of::<oa_host_callbacks>());
+    std::ptr::copy_nonoverlapping(p.host as *const u8, &mut out as *mut _ as *mut u8, n);
+    out
+}
+
+/// Lifecycle drivers track so out-of-order vtable calls fail with `OA_ERR_STATE`:
+/// `open_device` needs `Created`, `start` needs `Opened` or `Configured` (a stopped stream),
+/// `stop` needs `Running`, and `close_device` anything but `Running`. `start` with no device
+/// open fails with `OA_ERR_NOT_OPEN` instead; see [`DriverPhase::start_error`]. `Destroyed` is
+/// set by `openasio_driver_destroy` as teardown begins, so debug builds can assert on calls that
+/// reach the driver from there. It is not a fence: a call racing destroy from another thread, or
+/// made after destroy has returned, is undefined behaviour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DriverPhase { Created, Opened, Configured, Running, Destroyed }
+
+impl DriverPhase {
+    pub fn can_start(self) -> bool { matches!(self, Self::Opened | Self::Configured) }
+    /// What `start` returns in a phase where it cannot: `OA_ERR_NOT_OPEN` before `open_device`,
+    /// `OA_ERR_STATE` while running.
+    pub fn start_error(self) -> oa_result {
+        if self == Self::Created { OA_ERR_NOT_OPEN } else { OA_ERR_STATE }
+    }
 }
 
-#[repr(C)] pub struct oa_create_params { pub struct_size:u32, pub host:*const oa_host_callbacks, pub host_user:*mut c_void }
+/// Driver-side log destination: the host's `log_cb` when it supplied one, otherwise the `log` crate.
+#[derive(Clone, Copy)]
+pub struct LogSink { cb: oa_log_fn, user: *mut c_void }
+
+// SAFETY: hosts that install log_cb accept calls from any driver thread with their log_user.
+unsafe impl Send for LogSink {}
+unsafe impl Sync for LogSink {}
+
+impl LogSink {
+    /// Take the log callback from creation params, ignoring it if `struct_size` predates the field.
+    pub fn from_params(p: &oa_create_params) -> Self {
+        if !struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, log_user), std::mem::size_of::<*mut c_void>()) {
+            return Self { cb: None, user: std::ptr::null_mut() };
+        }
+        Self { cb: p.log_cb, user: p.log_user }
+    }
+    /// The host's callback and user pointer, for drivers that hand the same sink to sub-drivers.
+    pub fn raw(&self) -> (oa_log_fn, *mut c_void) { (self.cb, self.user) }
+    pub fn emit(&self, level: log::Level, args: std::fmt::Arguments) {
+        match self.cb {
+            Some(cb) => {
+                let Ok(msg) = std::ffi::CString::new(args.to_string()) else { return };
+                unsafe { cb(self.user, level as u32, msg.as_ptr()) };
+            }
+            None => log::log!(level, "{args}"),
+        }
+    }
+}
 
+/// Entries are only ever appended. `struct_size` is the size of the table the driver was built
+/// with; hosts read an entry only if it lies wholly inside that size and treat null or missing
+/// entries past the 1.0 table as unsupported.
 #[repr(C)]
+#[derive(Debug, Default)]
 pub struct oa_driver_vtable {
     pub struct_size: u32,
     pub get_caps: Option<unsafe extern "C" fn(*mut oa_driver)->u32>,
+    /// List device names, one per line, NUL-terminated. Returns `OA_OK` when the list fits in
+    /// `len` bytes; otherwise writes an empty string and returns the bytes needed, NUL included.
     pub query_devices: Option<unsafe extern "C" fn(*mut oa_driver,*mut c_char,usize)->i32>,
-    pub open_device: Option<unsafe extern "C" fn(*mut oa_driver,*const i8)->i32>,
+    pub open_device: Option<unsafe extern "C" fn(*mut oa_driver,*const c_char)->i32>,
     pub close_device: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
     pub get_default_config: Option<unsafe extern "C" fn(*mut oa_driver,*mut oa_stream_config)->i32>,
     pub start: Option<unsafe extern "C" fn(*mut oa_driver,*const oa_stream_config)->i32>,
+    /// Stop streaming once the audio already queued to the device has played out.
     pub stop: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
     pub get_latency: Option<unsafe extern "C" fn(*mut oa_driver,*mut u32,*mut u32)->i32>,
     pub set_sample_rate: Option<unsafe extern "C" fn(*mut oa_driver,u32)->i32>,
     pub set_buffer_frames: Option<unsafe extern "C" fn(*mut oa_driver,u32)->i32>,
+    pub query_supported_configs: Option<unsafe extern "C" fn(*mut oa_driver,*mut oa_supported_configs)->i32>,
+    /// Fill up to `count` pointers with channel names for `direction` (`OA_DIR_*`); returns the
+    /// number written. Strings are driver-owned and valid until the next call or destroy.
+    pub get_channel_names: Option<unsafe extern "C" fn(*mut oa_driver,u32,*mut *const c_char,u32)->i32>,
+    /// Copy the accepted sample rates into `buf[..len]`. Returns the number written, or the
+    /// required count (without writing) when `len` is too small.
+    pub query_supported_rates: Option<unsafe extern "C" fn(*mut oa_driver,*mut u32,usize)->i32>,
+    /// Newline-separated clock source names into `buf` (NUL-terminated, truncated to `len`).
+    /// Returns the number of sources, or `OA_ERR_UNSUPPORTED` without a selectable clock.
+    pub enumerate_clock_sources: Option<unsafe extern "C" fn(*mut oa_driver,*mut c_char,usize)->i32>,
+    /// Switch to the named clock source from `enumerate_clock_sources`. Not while running.
+    pub select_clock_source: Option<unsafe extern "C" fn(*mut oa_driver,*const c_char)->i32>,
+    /// Describe device `name` (NULL or "" = the open or default device) without opening it for
+    /// streaming. `OA_ERR_DEVICE` if no such device exists.
+    pub query_device_info: Option<unsafe extern "C" fn(*mut oa_driver,*const c_char,*mut oa_device_info)->i32>,
+    /// Like `start`, but the worker holds the device until `trigger_start`. The driver is
+    /// `Running` afterwards, so `stop` cancels an armed stream.
+    pub arm_start: Option<unsafe extern "C" fn(*mut oa_driver,*const oa_stream_config)->i32>,
+    /// Start an armed stream once `CLOCK_REALTIME` reaches `start_ns`. `OA_ERR_STATE` if not armed.
+    pub trigger_start: Option<unsafe extern "C" fn(*mut oa_driver,u64)->i32>,
+    /// Report whether the stream is running, its actual rate and period, and its frame and xrun
+    /// counts. Valid in any phase; a stopped driver reports `running = OA_FALSE`.
+    pub get_status: Option<unsafe extern "C" fn(*mut oa_driver,*mut oa_stream_status)->i32>,
+    /// Copy the device's clock sources into `buf[..len]` like `query_supported_rates`: returns the
+    /// number written, or the required count (without writing) when `len` is too small. Unlike
+    /// `enumerate_clock_sources` every driver answers; one without external sync reports a
+    /// single current "Internal" source.
+    pub query_clock_sources: Option<unsafe extern "C" fn(*mut oa_driver,*mut oa_clock_source,usize)->i32>,
+    /// Sync to the source with `index` from `query_clock_sources`. Not while running.
+    pub set_clock_source: Option<unsafe extern "C" fn(*mut oa_driver,u32)->i32>,
+    /// Hardware control: turn the capture DC blocker on or off. Allowed in any phase; takes
+    /// effect from the next period. Null when the driver has no such filter.
+    pub enable_dc_filter: Option<unsafe extern "C" fn(*mut oa_driver,oa_bool)->i32>,
+    /// Copy the name of channel `index` into `buf` (NUL-terminated, truncated to `len`) and
+    /// return its full length in bytes, so a return `>= len` means it was cut. Returns
+    /// `OA_ERR_INVALID_ARG` for an index past the last channel.
+    pub get_channel_name: Option<unsafe extern "C" fn(*mut oa_driver,oa_direction,u32,*mut c_char,usize)->i32>,
+    /// Linear trim gain for one channel in `direction` (`OA_DIR_*`), clamped to
+    /// [0.0, `OA_MAX_CHANNEL_GAIN`] and applied from the next period. Allowed in any phase.
+    pub set_channel_gain: Option<unsafe extern "C" fn(*mut oa_driver,u32,u32,f32)->i32>,
+    /// Stop calling `process` but keep the device configured, so `resume` carries on without
+    /// renegotiating it the way `stop`/`start` does. `OA_ERR_STATE` unless running and not
+    /// already paused. The driver stays `Running`: `stop` is valid while paused.
+    pub pause: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
+    /// Resume calling `process` after `pause`; `sample_position` continues from where it stopped.
+    /// `OA_ERR_STATE` if not paused.
+    pub resume: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
+    /// Drop all queued audio and stop as fast as possible, e.g. for a panic button. Leaves the
+    /// driver `Configured` like `stop`, which instead waits for the queued audio to play out.
+    /// `OA_ERR_STATE` unless running.
+    pub abort: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
+    /// Copy a description of the driver's most recent failure into `buf` (NUL-terminated,
+    /// truncated to `len`) and return its full length, so a return `>= len` means it was cut.
+    /// Returns 0 when nothing has failed yet.
+    pub get_last_error: Option<unsafe extern "C" fn(*mut oa_driver,*mut c_char,usize)->i32>,
+    /// Copy the configurations the open device accepts into `buf[..len]` like
+    /// `query_supported_rates`: returns the number written, or the required count (without
+    /// writing) when `len` is too small. A device with gaps in its ranges reports several.
+    pub query_config_ranges: Option<unsafe extern "C" fn(*mut oa_driver,*mut oa_config_range,usize)->i32>,
+    /// Carry only the device channels set in `mask` (null for all) from the next `start`. The
+    /// stream config still counts device channels; `process` gets the enabled ones packed in
+    /// channel order, and its config carries their counts. `OA_ERR_STATE` while running,
+    /// `OA_ERR_UNSUPPORTED` for a direction the driver cannot mask. Drivers report
+    /// `OA_CAP_CHANNEL_MASK`.
+    pub set_channel_mask: Option<unsafe extern "C" fn(*mut oa_driver,*const oa_channel_mask)->i32>,
+    /// Queue `events[..count]` for the device's MIDI output, each due `sample_offset` frames
+    /// after the call at the running stream's rate (at once when stopped). Returns
+    /// `OA_ERR_UNSUPPORTED` when the open device has no MIDI port, `OA_ERR_INVALID_ARG` for an
+    /// event [`midi_message_len`] rejects. Drivers report `OA_CAP_MIDI`.
+    pub send_midi: Option<unsafe extern "C" fn(*mut oa_driver,*const oa_midi_event,u32)->i32>,
+    /// Show the driver's own settings window, like an ASIO control panel. Null for drivers with
+    /// nothing to show, which is most on Linux; they take settings through `set_option` instead.
+    pub show_control_panel: Option<unsafe extern "C" fn(*mut oa_driver)->i32>,
+    /// Set the driver-specific setting `key` to `value`, both NUL-terminated text. Allowed in any
+    /// phase; settings that shape the stream take effect from the next `start`. Returns
+    /// `OA_ERR_UNSUPPORTED` for a key or value the driver does not take, `OA_ERR_INVALID_ARG`
+    /// for a value it cannot parse.
+    pub set_option: Option<unsafe extern "C" fn(*mut oa_driver,*const c_char,*const c_char)->i32>,
+    /// Copy the current value of `key` into `buf` (NUL-terminated, truncated to `len`) and return
+    /// its full length, like `get_last_error`. `OA_ERR_UNSUPPORTED` for a key `set_option` would
+    /// not take.
+    pub get_option: Option<unsafe extern "C" fn(*mut oa_driver,*const c_char,*mut c_char,usize)->i32>,
 }
 
-#[repr(C)] pub struct oa_driver { pub vt: *const oa_driver_vtable }
+/// Size of the 1.0 vtable (through `set_buffer_frames`). Hosts call those entries unconditionally,
+/// so a driver reporting less is unusable.
+pub const OA_VTABLE_V1_SIZE: usize = std::mem::offset_of!(oa_driver_vtable, query_supported_configs);
 
-pub type openasio_driver_create_fn = unsafe extern "C" fn(params:*const oa_create_params,out:*mut *mut oa_driver)->c_int;
+impl oa_driver_vtable {
+    /// A full-size table with every entry null. Drivers fill in what they implement and take the
+    /// rest from here: `static VTABLE: oa_driver_vtable = oa_driver_vtable { get_caps: Some(..), ..oa_driver_vtable::empty() };`
+    pub const fn empty() -> Self {
+        Self {
+            struct_size: std::mem::size_of::<Self>() as u32,
+            get_caps: None, query_devices: None, open_device: None, close_device: None,
+            get_default_config: None, start: None, stop: None, get_latency: None, set_sample_rate: None,
+            set_buffer_frames: None, query_supported_configs: None, get_channel_names: None,
+            query_supported_rates: None, enumerate_clock_sources: None, select_clock_source: None,
+            query_device_info: None, arm_start: None, trigger_start: None, get_status: None,
+            query_clock_sources: None, set_clock_source: None, enable_dc_filter: None, get_channel_name: None,
+            set_channel_gain: None, pause: None, resume: None, abort: None,
+            get_last_error: None, query_config_ranges: None, set_channel_mask: None, send_midi: None,
+            show_control_panel: None, set_option: None, get_option: None,
+        }
+    }
+}
+
+/// True if the driver's `struct_size` covers the (pointer-sized) vtable slot at `field_offset`.
+pub fn vtable_has(vt: &oa_driver_vtable, field_offset: usize) -> bool {
+    struct_has(vt.struct_size, field_offset, std::mem::size_of::<usize>())
+}
+
+/// The entry at byte `offset` of a driver's vtable, or `None` when the driver's `struct_size` does
+/// not cover the whole slot, the entry is null, or `offset` is not an entry at all. The untyped
+/// form of [`oa_vtable_field!`], for code that walks the table by offset.
+///
+/// # Safety
+/// `vt`, if non-null, must point to a driver's vtable, readable for its `struct_size` bytes.
+pub unsafe fn vtable_get_fn(vt: *const oa_driver_vtable, offset: usize) -> Option<*const ()> {
+    if vt.is_null() || offset < std::mem::offset_of!(oa_driver_vtable, get_caps) || !offset.is_multiple_of(std::mem::align_of::<usize>()) {
+        return None;
+    }
+    if !struct_has((*vt).struct_size, offset, std::mem::size_of::<usize>()) { return None; }
+    let entry = (vt as *const u8).add(offset).cast::<*const ()>().read();
+    (!entry.is_null()).then_some(entry)
+}
+
+/// Read an optional vtable entry, yielding `None` when the driver's vtable predates it. Applies
+/// the same `struct_size` check as [`vtable_get_fn`] and keeps the entry's type.
+///
+/// ```ignore
+/// if let Some(query) = oa_vtable_field!(vt, query_supported_rates) { /* ... */ }
+/// ```
+#[macro_export]
+macro_rules! oa_vtable_field {
+    ($vt:expr, $field:ident) => {{
+        let vt: &$crate::oa_driver_vtable = $vt;
+        if $crate::vtable_has(vt, ::std::mem::offset_of!($crate::oa_driver_vtable, $field)) { vt.$field } else { None }
+    }};
+}
+
+/// `query_supported_rates` reply for drivers: copy `rates` into `buf` if it fits, else report the
+/// required length.
+///
+/// # Safety
+/// `buf`, if non-null, must be valid for writes of `len` `u32`s.
+pub unsafe fn write_supported_rates(rates: &[u32], buf: *mut u32, len: usize) -> i32 {
+    if buf.is_null() || len < rates.len() { return rates.len() as i32; }
+    std::ptr::copy_nonoverlapping(rates.as_ptr(), buf, rates.len());
+    rates.len() as i32
+}
+
+pub const OA_CLOCK_SOURCE_NAME_LEN: usize = 64;
+
+/// One entry of `query_clock_sources`.
+#[repr(C)] #[derive(Clone, Copy, Debug)]
+pub struct oa_clock_source {
+    pub index: u32, // what `set_clock_source` takes
+    pub name: [c_char; OA_CLOCK_SOURCE_NAME_LEN], // NUL-terminated, e.g. "S/PDIF"
+    pub current: oa_bool, // the device is syncing to this source
+}
+
+impl Default for oa_clock_source {
+    fn default() -> Self { Self { index: 0, name: [0; OA_CLOCK_SOURCE_NAME_LEN], current: OA_FALSE } }
+}
+
+impl oa_clock_source {
+    /// `name` is truncated to leave room for the NUL.
+    pub fn new(index: u32, name: &str, current: bool) -> Self {
+        let mut source = Self { index, current: current as oa_bool, ..Self::default() };
+        fill_str(&mut source.name, name);
+        source
+    }
+    /// The only source of a device without external sync.
+    pub fn internal() -> Self { Self::new(0, "Internal", true) }
+}
+
+/// `query_clock_sources` reply for drivers, with the same contract as [`write_supported_rates`].
+///
+/// # Safety
+/// `buf`, if non-null, must be valid for writes of `len` entries.
+pub unsafe fn write_clock_sources(sources: &[oa_clock_source], buf: *mut oa_clock_source, len: usize) -> i32 {
+    if buf.is_null() || len < sources.len() { return sources.len() as i32; }
+    std::ptr::copy_nonoverlapping(sources.as_ptr(), buf, sources.len());
+    sources.len() as i32
+}
+
+/// One entry of `query_config_ranges`: every combination of the listed rates, channel counts,
+/// formats and buffer sizes is accepted. Ranges are inclusive.
+#[repr(C)] #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct oa_config_range {
+    pub min_rate: u32,
+    pub max_rate: u32,
+    pub min_channels_in: u32,
+    pub max_channels_in: u32,
+    pub min_channels_out: u32,
+    pub max_channels_out: u32,
+    pub formats_mask: u32, // OR of oa_format_bit(..)
+    pub min_buffer_frames: u32,
+    pub max_buffer_frames: u32,
+}
+
+/// Device channels a stream carries, bit n for channel n; see `set_channel_mask`.
+#[repr(C)] #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct oa_channel_mask {
+    pub in_mask: u64,
+    pub out_mask: u64,
+}
+
+/// cbindgen:ignore
+impl oa_channel_mask {
+    /// Every channel enabled, which is what drivers start with.
+    pub const ALL: Self = Self { in_mask: u64::MAX, out_mask: u64::MAX };
+
+    /// `cfg` as `process` sees it under this mask: the channel counts shrink to the enabled ones.
+    pub fn apply(&self, cfg: &oa_stream_config) -> oa_stream_config {
+        oa_stream_config {
+            in_channels: enabled_channels(self.in_mask, cfg.in_channels),
+            out_channels: enabled_channels(self.out_mask, cfg.out_channels),
+            ..*cfg
+        }
+    }
+}
+
+impl Default for oa_channel_mask {
+    fn default() -> Self { Self::ALL }
+}
+
+/// How many of the first `channels` device channels `mask` enables.
+pub fn enabled_channels(mask: u64, channels: u16) -> u16 {
+    let low = if channels >= 64 { u64::MAX } else { (1u64 << channels) - 1 };
+    (mask & low).count_ones() as u16
+}
+
+/// Move the channels enabled in `mask` to the front of each frame, in channel order, and return
+/// how many there are. `buf` holds `frames` interleaved frames of `channels` samples on entry and
+/// `frames` frames of the returned count on exit; disabled channels are dropped, not zeroed.
+pub fn compact_interleaved<T: Copy>(buf: &mut [T], frames: usize, channels: usize, mask: u64) -> usize {
+    let kept = enabled_channels(mask, channels.min(u16::MAX as usize) as u16) as usize;
+    if kept == channels { return kept; }
+    let mut dst = 0;
+    // Each write lands at or before the sample it copies, so working forwards is safe in place.
+    for f in 0..frames {
+        for c in (0..channels).filter(|&c| c < 64 && mask & (1 << c) != 0) {
+            buf[dst] = buf[f * channels + c];
+            dst += 1;
+        }
+    }
+    kept
+}
+
+/// `query_config_ranges` reply for drivers, with the same contract as [`write_supported_rates`].
+///
+/// # Safety
+/// `buf`, if non-null, must be valid for writes of `len` entries.
+pub unsafe fn write_config_ranges(ranges: &[oa_config_range], buf: *mut oa_config_range, len: usize) -> i32 {
+    if buf.is_null() || len < ranges.len() { return ranges.len() as i32; }
+    std::ptr::copy_nonoverlapping(ranges.as_ptr(), buf, ranges.len());
+    ranges.len() as i32
+}
+
+/// `query_clock_sources` for drivers that only run on their own clock.
+///
+/// # Safety
+/// As [`write_clock_sources`].
+pub unsafe extern "C" fn internal_clock_sources(_drv: *mut oa_driver, buf: *mut oa_clock_source, len: usize) -> i32 {
+    write_clock_sources(&[oa_clock_source::internal()], buf, len)
+}
+
+/// `set_clock_source` for drivers that only run on their own clock: index 0 is a no-op.
+///
+/// # Safety
+/// Never dereferences `_drv`.
+pub unsafe extern "C" fn internal_set_clock_source(_drv: *mut oa_driver, index: u32) -> i32 {
+    if index == 0 { OA_OK } else { OA_ERR_INVALID_ARG }
+}
+
+/// `get_channel_name` reply: copy as much of `name` as fits in `buf[..len]`, NUL-terminated,
+/// and return its full length.
+///
+/// # Safety
+/// `buf`, if non-null, must point to `len` writable bytes.
+pub unsafe fn write_channel_name(name: &str, buf: *mut c_char, len: usize) -> i32 {
+    if !buf.is_null() && len > 0 {
+        let n = name.len().min(len - 1);
+        std::ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, buf, n);
+        *buf.add(n) = 0;
+    }
+    name.len().min(i32::MAX as usize) as i32
+}
+
+/// `query_devices` reply: copy `list` into `buf` NUL-terminated and return `OA_OK`, or, when it
+/// does not fit in `len` bytes, leave `buf` an empty string and return the bytes it needs, NUL
+/// included. A list is never cut short, so a host never mistakes part of it for all of it.
+///
+/// # Safety
+/// `buf`, if non-null, must point to `len` writable bytes.
+pub unsafe fn write_device_list(list: &str, buf: *mut c_char, len: usize) -> i32 {
+    let need = list.len() + 1;
+    if buf.is_null() || len < need {
+        if !buf.is_null() && len > 0 { *buf = 0; }
+        return need.min(i32::MAX as usize) as i32;
+    }
+    std::ptr::copy_nonoverlapping(list.as_ptr() as *const c_char, buf, list.len());
+    *buf.add(list.len()) = 0;
+    OA_OK
+}
+
+/// `get_last_error` reply: same contract as [`write_channel_name`].
+///
+/// # Safety
+/// `buf`, if non-null, must point to `len` writable bytes.
+pub unsafe fn write_last_error(msg: &str, buf: *mut c_char, len: usize) -> i32 {
+    write_channel_name(msg, buf, len)
+}
+
+/// `get_option` reply: same contract as [`write_channel_name`].
+///
+/// # Safety
+/// `buf`, if non-null, must point to `len` writable bytes.
+pub unsafe fn write_option(value: &str, buf: *mut c_char, len: usize) -> i32 {
+    write_channel_name(value, buf, len)
+}
+
+/// A `set_option` or `get_option` argument as text, or `None` when it is null or not UTF-8.
+///
+/// # Safety
+/// `p`, if non-null, must point to a NUL-terminated string that outlives the returned borrow.
+pub unsafe fn option_arg<'a>(p: *const c_char) -> Option<&'a str> {
+    if p.is_null() { return None; }
+    CStr::from_ptr(p).to_str().ok()
+}
+
+/// The `key=value` pairs of `oa_create_params.options`, in the order given. A value may be
+/// double-quoted to hold commas; inside the quotes a backslash takes the next character as is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CreateOptions(Vec<(String, String)>);
+
+impl CreateOptions {
+    /// Parse an options string. Whitespace around keys and unquoted values is trimmed and empty
+    /// entries are skipped; an entry without `=`, an empty key or an unterminated quote is an error.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        let mut rest = s;
+        while !rest.trim().is_empty() {
+            let end = rest.find([',', '=']).unwrap_or(rest.len());
+            let key = rest[..end].trim();
+            match rest[end..].strip_prefix('=') {
+                Some(_) if key.is_empty() => return Err(format!("option with no key in {s:?}")),
+                Some(after) => {
+                    let (value, next) = Self::split_value(after)?;
+                    entries.push((key.to_string(), value));
+                    rest = next;
+                }
+                None if key.is_empty() => rest = &rest[end + 1..],
+                None => return Err(format!("option {key:?} has no value")),
+            }
+        }
+        Ok(Self(entries))
+    }
+
+    /// The value at the start of `s` and what follows its separating comma.
+    fn split_value(s: &str) -> Result<(String, &str), String> {
+        let s = s.trim_start();
+        let Some(quoted) = s.strip_prefix('"') else {
+            let (value, next) = s.split_once(',').unwrap_or((s, ""));
+            return Ok((value.trim_end().to_string(), next));
+        };
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() { Some((_, c)) => value.push(c), None => break },
+                '"' => {
+                    let after = quoted[i + 1..].trim_start();
+                    return match after.strip_prefix(',') {
+                        Some(next) => Ok((value, next)),
+                        None if after.is_empty() => Ok((value, after)),
+                        None => Err(format!("{after:?} follows the quoted value {value:?}")),
+                    };
+                }
+                c => value.push(c),
+            }
+        }
+        Err(format!("unterminated quote in {s:?}"))
+    }
+
+    /// The options in creation params: none if `struct_size` predates the field or it is null.
+    ///
+    /// # Safety
+    /// `p.options`, if covered and non-null, must point to a NUL-terminated string.
+    pub unsafe fn from_params(p: &oa_create_params) -> Result<Self, String> {
+        if !struct_has(p.struct_size, std::mem::offset_of!(oa_create_params, options), std::mem::size_of::<*const c_char>())
+            || p.options.is_null() {
+            return Ok(Self::default());
+        }
+        match CStr::from_ptr(p.options).to_str() {
+            Ok(s) => Self::parse(s),
+            Err(_) => Err("options are not UTF-8".into()),
+        }
+    }
+
+    /// Append `key=value`; a later entry for the same key overrides an earlier one.
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.push((key.into(), value.into()));
+    }
+
+    /// The value of the last entry for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The options string `parse` reads back, quoting values that need it.
+impl std::fmt::Display for CreateOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 { f.write_str(",")?; }
+            let plain = !value.contains([',', '"', '\\']) && value.trim() == value;
+            if plain {
+                write!(f, "{key}={value}")?;
+            } else {
+                write!(f, "{key}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The text behind a driver's `get_last_error`: the most recent failure, or empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LastError(String);
+
+impl LastError {
+    /// Replace the recorded failure with `msg`.
+    pub fn set(&mut self, msg: impl Into<String>) {
+        self.0 = msg.into();
+    }
+
+    /// Forget the recorded failure, so `get_last_error` reports an empty string.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The `get_last_error` reply, through [`write_last_error`].
+    ///
+    /// # Safety
+    /// As [`write_last_error`].
+    pub unsafe fn write(&self, buf: *mut c_char, len: usize) -> i32 {
+        write_last_error(&self.0, buf, len)
+    }
+}
+
+/// Label for channel `index` of a driver without hardware names: "Capture N" or "Playback N",
+/// counting from 1. `None` for an unknown direction.
+pub fn default_channel_name(direction: oa_direction, index: u32) -> Option<String> {
+    match direction {
+        oa_direction::OA_DIR_INPUT => Some(format!("Capture {}", index as u64 + 1)),
+        oa_direction::OA_DIR_OUTPUT => Some(format!("Playback {}", index as u64 + 1)),
+        _ => None,
+    }
+}
+
+/// `get_channel_name` for drivers without hardware names: [`default_channel_name`] for each
+/// channel of `cfg`.
+///
+/// # Safety
+/// As [`write_channel_name`].
+pub unsafe fn default_channel_name_for(cfg: &oa_stream_config, direction: oa_direction, index: u32, buf: *mut c_char, len: usize) -> i32 {
+    let count = match direction {
+        oa_direction::OA_DIR_INPUT => cfg.in_channels,
+        oa_direction::OA_DIR_OUTPUT => cfg.out_channels,
+        _ => return OA_ERR_INVALID_ARG,
+    };
+    match default_channel_name(direction, index) {
+        Some(name) if index < count as u32 => write_channel_name(&name, buf, len),
+        _ => OA_ERR_INVALID_ARG,
+    }
+}
+
+/// Lead time between the last `trigger_start` call and the shared start instant.
+pub const SYNC_START_LEAD: Duration = Duration::from_millis(5);
+
+/// Start `count` drivers so their devices begin within one sample period of each other: every
+/// driver is armed with its entry in `cfgs` first, then all are triggered for one
+/// `CLOCK_REALTIME` instant. `OA_ERR_UNSUPPORTED` if any driver lacks
+/// `arm_start`/`trigger_start`; on failure the drivers already armed are stopped again.
+///
+/// # Safety
+/// Unless `count` is 0, `drivers` and `cfgs` must each point at `count` elements; every non-null
+/// driver must be live.
+#[no_mangle]
+pub unsafe extern "C" fn openasio_start_synchronized(drivers: *const *mut oa_driver, cfgs: *const oa_stream_config, count: usize) -> oa_result {
+    if count == 0 { return OA_OK; }
+    if drivers.is_null() || cfgs.is_null() { return OA_ERR_INVALID_ARG; }
+    let drivers = std::slice::from_raw_parts(drivers, count);
+    let cfgs = std::slice::from_raw_parts(cfgs, count);
+    let mut entries = Vec::with_capacity(count);
+    for &drv in drivers {
+        if drv.is_null() { return OA_ERR_INVALID_ARG; }
+        let vt = &*(*drv).vt;
+        match (oa_vtable_field!(vt, arm_start), oa_vtable_field!(vt, trigger_start)) {
+            (Some(arm), Some(trigger)) => entries.push((drv, arm, trigger)),
+            _ => return OA_ERR_UNSUPPORTED,
+        }
+    }
+    let stop_armed = |n: usize| for &(drv, ..) in &entries[..n] {
+        if let Some(stop) = (*(*drv).vt).stop { stop(drv); }
+    };
+    for (i, (&(drv, arm, _), cfg)) in entries.iter().zip(cfgs).enumerate() {
+        let rc = arm(drv, cfg);
+        if rc < 0 { stop_armed(i); return rc; }
+    }
+    let start_ns = realtime_ns() + SYNC_START_LEAD.as_nanos() as u64;
+    for &(drv, _, trigger) in &entries {
+        let rc = trigger(drv, start_ns);
+        if rc < 0 { stop_armed(count); return rc; }
+    }
+    OA_OK
+}
+
+/// [`openasio_start_synchronized`] over slices: `OA_ERR_INVALID_ARG` unless there is one config
+/// per driver.
+///
+/// # Safety
+/// Every non-null pointer in `drivers` must be a live driver.
+pub unsafe fn start_synchronized(drivers: &[*mut oa_driver], cfgs: &[oa_stream_config]) -> oa_result {
+    if drivers.len() != cfgs.len() { return OA_ERR_INVALID_ARG; }
+    openasio_start_synchronized(drivers.as_ptr(), cfgs.as_ptr(), drivers.len())
+}
+
+/// Nanoseconds since the Unix epoch on `CLOCK_REALTIME`, the clock `trigger_start` is given in.
+pub fn realtime_ns() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Worker-side gate for drivers implementing `arm_start`/`trigger_start`: `start` opens it,
+/// `arm_start` closes it, and `trigger_start` sets the instant the worker may pass.
+pub struct StartGate(AtomicU64);
+
+impl StartGate {
+    const OPEN: u64 = 0;
+    const ARMED: u64 = u64::MAX;
+    pub const fn new() -> Self { Self(AtomicU64::new(Self::OPEN)) }
+    pub fn open(&self) { self.0.store(Self::OPEN, Ordering::Release) }
+    pub fn arm(&self) { self.0.store(Self::ARMED, Ordering::Release) }
+    /// Release an armed worker at `start_ns`. False if the gate was not armed.
+    pub fn trigger(&self, start_ns: u64) -> bool {
+        let at = start_ns.clamp(1, Self::ARMED - 1);
+        self.0.compare_exchange(Self::ARMED, at, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+    /// Block the worker until it may start. `Some(true)` for a synchronized start (the worker
+    /// should start its devices explicitly, now), `Some(false)` for a plain `start`, and `None`
+    /// if `running` was cleared while waiting.
+    pub fn wait(&self, running: &AtomicBool) -> Option<bool> {
+        loop {
+            if !running.load(Ordering::Acquire) { return None; }
+            match self.0.load(Ordering::Acquire) {
+                Self::OPEN => return Some(false),
+                Self::ARMED => std::thread::sleep(Duration::from_millis(1)),
+                at => {
+                    // Sleep most of the way, then spin: wakeups can be late by more than a sample.
+                    let left = at.saturating_sub(realtime_ns());
+                    if left > 2_000_000 { std::thread::sleep(Duration::from_nanos(left - 1_000_000)); continue; }
+                    while realtime_ns() < at { std::hint::spin_loop(); }
+                    return Some(true);
+                }
+            }
+        }
+    }
+}
+
+impl Default for StartGate {
+    fn default() -> Self { Self::new() }
+}
+
+/// Header of every driver instance: drivers put it first in their `#[repr(C)]` state and point
+/// `vt` at a table that outlives the instance, typically a `static`.
+#[repr(C)] #[derive(Debug)] pub struct oa_driver { pub vt: *const oa_driver_vtable }
+
+/// Mandatory export `openasio_driver_create`: validate `params`, build a driver in the `Created`
+/// phase and store it in `*out`. Returns `OA_OK`, or an error leaving `*out` untouched.
+pub type openasio_driver_create_fn = unsafe extern "C" fn(params:*const oa_create_params,out:*mut *mut oa_driver)->i32;
+/// Mandatory export `openasio_driver_destroy`: stop and free a driver from `openasio_driver_create`.
+/// Null is ignored. The host owns the driver and must destroy it exactly once, and must not use
+/// `driver` afterwards; debug builds of the ALSA drivers log and ignore a second destroy.
 pub type openasio_driver_destroy_fn = unsafe extern "C" fn(driver:*mut oa_driver);
+/// Optional export; see [`oa_driver_info`].
+pub type openasio_driver_get_info_fn = unsafe extern "C" fn(out:*mut oa_driver_info)->i32;
+/// Export every driver provides except those built against the first 1.0 SDK. See [`OA_ABI_VERSION`].
+pub type openasio_abi_version_fn = unsafe extern "C" fn()->u32;
+
+/// Drivers `openasio_driver_create` handed out and not yet destroyed; debug builds only.
+#[cfg(debug_assertions)]
+static LIVE_DRIVERS: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+/// Record a driver `openasio_driver_create` is handing out; see [`release_driver`].
+pub fn track_driver(driver: *mut oa_driver) {
+    #[cfg(debug_assertions)] LIVE_DRIVERS.lock().unwrap_or_else(|e| e.into_inner()).push(driver as usize);
+    #[cfg(not(debug_assertions))] let _ = driver;
+}
+
+/// Whether `openasio_driver_destroy` may free `driver`: false for null and, in debug builds, for
+/// a pointer [`track_driver`] never saw or one already released, which is logged. Release builds
+/// track nothing and trust the host to destroy once; a stale pointer whose address a newer driver
+/// reuses passes either way.
+pub fn release_driver(driver: *mut oa_driver) -> bool {
+    if driver.is_null() { return false; }
+    #[cfg(debug_assertions)] {
+        let mut live = LIVE_DRIVERS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(i) = live.iter().position(|&p| p == driver as usize) else {
+            log::error!("openasio_driver_destroy({driver:p}): not a live driver, ignored");
+            return false;
+        };
+        live.swap_remove(i);
+    }
+    true
+}
+
+/// A driver linked into the host at build time instead of loaded with dlopen. Driver crates
+/// implement it, behind their `static-link` feature, by forwarding to their entry points; hosts
+/// get a [`loader::DriverLib`] from it with [`loader::DriverLib::from_factory`].
+pub trait DriverFactory {
+    /// The driver's `openasio_driver_create`.
+    ///
+    /// # Safety
+    /// As for any `openasio_driver_create_fn`.
+    unsafe extern "C" fn create(params: *const oa_create_params, out: *mut *mut oa_driver) -> i32;
+    /// The driver's `openasio_driver_destroy`.
+    ///
+    /// # Safety
+    /// As for any `openasio_driver_destroy_fn`.
+    unsafe extern "C" fn destroy(driver: *mut oa_driver);
+    /// The driver's `openasio_driver_get_info`; `OA_ERR_UNSUPPORTED` for drivers without one.
+    ///
+    /// # Safety
+    /// As for any `openasio_driver_get_info_fn`.
+    unsafe extern "C" fn get_info(out: *mut oa_driver_info) -> i32 {
+        let _ = out;
+        OA_ERR_UNSUPPORTED
+    }
+}
+
+/// Define the `openasio_abi_version` export as this crate's [`OA_ABI_VERSION`]. Invoke once at
+/// the root of every driver crate; drivers linked statically skip it, as the host was built
+/// against the same ABI.
+#[macro_export]
+macro_rules! oa_export_abi_version {
+    () => {
+        #[no_mangle]
+        pub extern "C" fn openasio_abi_version() -> u32 { $crate::OA_ABI_VERSION }
+    };
+}
+
+/// The ABI types as bindgen reads them from the C header, with the `generated` feature. The crate
+/// keeps using the handwritten definitions; `tests/layout.rs` checks the two agree field for field.
+#[cfg(feature = "generated")]
+#[allow(non_camel_case_types, non_upper_case_globals, non_snake_case, dead_code, clippy::all)]
+pub mod generated {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
 
 pub mod loader {
-    use super::*; use libloading::{Library, Symbol};
-    pub struct DriverLib { pub lib: Library, pub create: openasio_driver_create_fn, pub destroy: openasio_driver_destroy_fn }
+    use super::*; use libloading::Symbol;
+    pub use libloading::{Error, Library};
+    use std::ffi::OsString; use std::path::{Path, PathBuf};
+    /// NUL-terminated names of the entry points every driver exports.
+    pub const CREATE_SYMBOL: &[u8] = b"openasio_driver_create\0";
+    pub const DESTROY_SYMBOL: &[u8] = b"openasio_driver_destroy\0";
+    /// Optional export filling an [`oa_driver_info`].
+    pub const GET_INFO_SYMBOL: &[u8] = b"openasio_driver_get_info\0";
+    /// Export reporting the ABI a library was built against; see [`OA_ABI_VERSION`].
+    pub const ABI_VERSION_SYMBOL: &[u8] = b"openasio_abi_version\0";
+    /// A driver's entry points. For a loaded library it owns the `Library`, so the resolved entry
+    /// points stay valid for as long as this value (and any driver created through it) is alive;
+    /// `lib` is `None` for a driver linked into the host.
+    pub struct DriverLib {
+        pub lib: Option<Library>,
+        pub create: openasio_driver_create_fn,
+        pub destroy: openasio_driver_destroy_fn,
+        pub get_info: Option<openasio_driver_get_info_fn>,
+    }
     impl DriverLib {
-        pub unsafe fn load(path:&str)->Result<Self,libloading::Error>{
-            let lib = Library::new(path)?;
+        /// The entry points of a driver linked into the host, without touching the dynamic loader.
+        pub fn from_factory<F: DriverFactory>() -> Self {
+            Self { lib: None, create: F::create, destroy: F::destroy, get_info: Some(F::get_info) }
+        }
+        /// When nothing is at `path` the platform's decorated names are tried too; see [`open`].
+        ///
+        /// # Safety
+        /// Loading a library runs its initializers; `path` must name a trusted OpenASIO driver.
+        pub unsafe fn load<P: AsRef<std::ffi::OsStr>>(path:P)->Result<Self,LoadError>{
+            Self::from_library(open(Path::new(path.as_ref()))?.0)
+        }
+        /// Wrap a library the caller has already opened (e.g. after verifying or pinning it).
+        /// Takes ownership of `lib` and resolves the same entry points as [`DriverLib::load`].
+        /// Libraries reporting a different ABI major version are refused; 1.0 libraries, which
+        /// predate `openasio_abi_version`, are accepted.
+        ///
+        /// # Safety
+        /// `lib` must be an OpenASIO driver: the resolved symbols are trusted to have the
+        /// `openasio_driver_create_fn`/`openasio_driver_destroy_fn` signatures.
+        pub unsafe fn from_library(lib: Library)->Result<Self,LoadError>{
+            check_abi(&lib)?;
             let create = {
-                let symbol: Symbol<openasio_driver_create_fn> = lib.get(b"openasio_driver_create\0")?;
+                let symbol: Symbol<openasio_driver_create_fn> = lib.get(CREATE_SYMBOL)?;
                 *symbol
             };
             let destroy = {
-                let symbol: Symbol<openasio_driver_destroy_fn> = lib.get(b"openasio_driver_destroy\0")?;
+                let symbol: Symbol<openasio_driver_destroy_fn> = lib.get(DESTROY_SYMBOL)?;
                 *symbol
             };
-            Ok(Self{lib,create,destroy})
+            let get_info = lib.get::<openasio_driver_get_info_fn>(GET_INFO_SYMBOL).ok().map(|symbol| *symbol);
+            Ok(Self{lib: Some(lib), create, destroy, get_info})
+        }
+        /// The driver's `openasio_driver_get_info` reply; `None` if it has none or the call fails.
+        pub fn info(&self) -> Option<DriverInfo> {
+            // SAFETY: the entry point was trusted as a driver's when it was resolved.
+            unsafe { self.get_info.and_then(|get| read_info(get)) }
+        }
+        /// The library's `openasio_abi_version`; `None` for a 1.0 driver that does not export it.
+        /// A linked driver was built against this crate, so it reports [`OA_ABI_VERSION`].
+        pub fn abi_version(&self) -> Option<u32> {
+            // SAFETY: the library was trusted as a driver when it was loaded.
+            self.lib.as_ref().map_or(Some(OA_ABI_VERSION), |lib| unsafe { abi_version(lib) })
+        }
+        /// Load the driver called `name` (e.g. `"alsa17h"`) from the first of [`search_dirs`]
+        /// that has it, returning where it was found.
+        ///
+        /// # Safety
+        /// As [`DriverLib::load`]; every search directory is trusted to hold only OpenASIO drivers.
+        pub unsafe fn load_default(name: &str)->Result<(Self,PathBuf),SearchError>{
+            Self::load_from(name, search_dirs())
         }
+        /// [`DriverLib::load_default`] over the caller's own directories, in order.
+        ///
+        /// # Safety
+        /// As [`DriverLib::load_default`].
+        pub unsafe fn load_from<I: IntoIterator<Item=PathBuf>>(name: &str, dirs: I)->Result<(Self,PathBuf),SearchError>{
+            let file = driver_filename(name);
+            let mut attempts = Vec::new();
+            for dir in dirs {
+                let path = dir.join(&file);
+                match Self::load(&path) { Ok(lib) => return Ok((lib, path)), Err(e) => attempts.push((path, e)) }
+            }
+            Err(SearchError{name: name.to_string(), attempts})
+        }
+    }
+
+    /// Call `lib`'s `openasio_abi_version`, if it exports one.
+    ///
+    /// # Safety
+    /// `lib` must be an OpenASIO driver, or the symbol may not have the expected signature.
+    pub unsafe fn abi_version(lib: &Library) -> Option<u32> {
+        lib.get::<openasio_abi_version_fn>(ABI_VERSION_SYMBOL).ok().map(|get| get())
+    }
+
+    unsafe fn check_abi(lib: &Library) -> Result<(), LoadError> {
+        match abi_version(lib) {
+            Some(found) if oa_abi_split(found).0 != OA_VERSION_MAJOR => Err(LoadError::AbiMismatch { found }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Why [`DriverLib::load`] refused a library.
+    #[derive(Debug)]
+    pub enum LoadError {
+        /// The library could not be opened or lacks an entry point.
+        Library(Error),
+        /// `openasio_abi_version` reported `found`, whose major differs from [`OA_VERSION_MAJOR`].
+        AbiMismatch { found: u32 },
+    }
+    impl From<Error> for LoadError { fn from(e: Error) -> Self { Self::Library(e) } }
+    impl std::fmt::Display for LoadError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Library(e) => e.fmt(f),
+                Self::AbiMismatch { found } => {
+                    let (major, minor) = oa_abi_split(*found);
+                    write!(f, "driver was built for OpenASIO ABI {major}.{minor}; this host speaks {OA_VERSION_MAJOR}.x")
+                }
+            }
+        }
+    }
+    impl std::error::Error for LoadError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self { Self::Library(e) => Some(e), Self::AbiMismatch { .. } => None }
+        }
+    }
+
+    /// File names to try for a library called `name`, in order: as given, with the platform's
+    /// prefix, with its suffix and with both (`foo`, `libfoo`, `foo.so`, `libfoo.so` on Linux;
+    /// `foo`, `foo.dll` on Windows). A decoration `name` already carries is not added again.
+    pub fn candidate_filenames(name: &str) -> Vec<String> {
+        use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+        let prefix = if name.starts_with(DLL_PREFIX) { "" } else { DLL_PREFIX };
+        let suffix = if name.ends_with(DLL_SUFFIX) { "" } else { DLL_SUFFIX };
+        let mut out = vec![name.to_string()];
+        for candidate in [format!("{prefix}{name}"), format!("{name}{suffix}"), format!("{prefix}{name}{suffix}")] {
+            if !out.contains(&candidate) { out.push(candidate); }
+        }
+        out
+    }
+
+    /// `path` with each of [`candidate_filenames`] for its file name, `path` itself first.
+    pub fn candidate_paths(path: &Path) -> Vec<PathBuf> {
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => candidate_filenames(name).into_iter().map(|c| path.with_file_name(c)).collect(),
+            None => vec![path.to_path_buf()],
+        }
+    }
+
+    /// Open the library at `path` or, when there is no such file, the first of [`candidate_paths`]
+    /// that opens, returning which one did. A file that exists is opened by its absolute path, so
+    /// the system's search order cannot substitute another library of the same name; on Windows
+    /// the driver's own DLLs are then looked up next to it first. Candidates with no file behind
+    /// them are left to that search. On failure the error is the one for `path` as given.
+    ///
+    /// # Safety
+    /// As [`DriverLib::load`].
+    pub unsafe fn open(path: &Path) -> Result<(Library, PathBuf), Error> {
+        let candidates = if path.exists() { vec![path.to_path_buf()] } else { candidate_paths(path) };
+        let mut first_err = None;
+        for candidate in candidates {
+            let result = if candidate.exists() { open_file(&std::path::absolute(&candidate).unwrap_or_else(|_| candidate.clone())) } else { Library::new(&candidate) };
+            match result {
+                Ok(lib) => return Ok((lib, candidate)),
+                Err(e) => { first_err.get_or_insert(e); }
+            }
+        }
+        Err(first_err.expect("at least one candidate"))
+    }
+
+    #[cfg(windows)]
+    unsafe fn open_file(path: &Path) -> Result<Library, Error> {
+        use libloading::os::windows;
+        windows::Library::load_with_flags(path, windows::LOAD_WITH_ALTERED_SEARCH_PATH).map(Library::from)
+    }
+
+    #[cfg(not(windows))]
+    unsafe fn open_file(path: &Path) -> Result<Library, Error> {
+        Library::new(path)
+    }
+
+    /// Platform library filename for a short driver name: `alsa17h` -> `libopenasio_driver_alsa17h.so`.
+    pub fn driver_filename(name: &str) -> OsString { libloading::library_filename(format!("openasio_driver_{}", name.replace('-', "_"))) }
+
+    /// Where [`DriverLib::load_default`] looks, highest precedence first: each entry of
+    /// `OPENASIO_DRIVER_PATH`, then the per-user and system driver directories.
+    pub fn search_dirs() -> Vec<PathBuf> { search_dirs_with(|k| std::env::var_os(k)) }
+
+    pub(crate) fn search_dirs_with(env: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = env("OPENASIO_DRIVER_PATH").map(|p| std::env::split_paths(&p).filter(|d| !d.as_os_str().is_empty()).collect()).unwrap_or_default();
+        let home = env("HOME").map(PathBuf::from);
+        if cfg!(windows) {
+            dirs.extend(env("LOCALAPPDATA").map(|d| Path::new(&d).join("OpenASIO").join("drivers")));
+            dirs.extend(env("ProgramFiles").map(|d| Path::new(&d).join("OpenASIO").join("drivers")));
+        } else if cfg!(target_os = "macos") {
+            dirs.extend(home.map(|h| h.join("Library/Audio/OpenASIO/Drivers")));
+            dirs.push("/Library/Audio/OpenASIO/Drivers".into());
+            dirs.push("/usr/local/lib/openasio".into());
+        } else {
+            let data = env("XDG_DATA_HOME").filter(|d| !d.is_empty()).map(PathBuf::from).or_else(|| home.map(|h| h.join(".local/share")));
+            dirs.extend(data.map(|d| d.join("openasio/drivers")));
+            dirs.push("/usr/local/lib/openasio".into());
+            dirs.push("/usr/lib/openasio".into());
+        }
+        dirs
+    }
+
+    /// What a library reports through `openasio_driver_get_info`.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct DriverInfo { pub name: String, pub vendor: String, pub version: (u32, u32, u32), pub api_version: (u32, u32) }
+
+    /// Call a library's `openasio_driver_get_info`.
+    unsafe fn read_info(get: openasio_driver_get_info_fn) -> Option<DriverInfo> {
+        let mut raw = oa_driver_info::default();
+        if get(&mut raw) != OA_OK { return None; }
+        let text = |s: &[c_char]| CStr::from_bytes_until_nul(std::slice::from_raw_parts(s.as_ptr() as *const u8, s.len())).map(|c| c.to_string_lossy().into_owned()).unwrap_or_default();
+        Some(DriverInfo {
+            name: text(&raw.name), vendor: text(&raw.vendor),
+            version: (raw.version_major, raw.version_minor, raw.version_patch), api_version: (raw.api_major, raw.api_minor),
+        })
+    }
+
+    /// A library [`enumerate`] found that exports at least one driver entry point.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct DriverEntry { pub path: PathBuf, pub has_create: bool, pub has_destroy: bool, pub info: Option<DriverInfo> }
+    impl DriverEntry {
+        /// Both entry points are present, so [`DriverLib::load`] will accept it.
+        pub fn is_loadable(&self) -> bool { self.has_create && self.has_destroy }
+    }
+
+    /// Scan `paths` for installed drivers. Directories are listed (not recursively) for files with
+    /// the platform's library suffix; other paths are probed as given. Files that fail to open, and
+    /// libraries exporting neither entry point, are skipped. Results are sorted by path.
+    ///
+    /// # Safety
+    /// Opening a library runs its initializers, and a present `openasio_driver_get_info` is called:
+    /// the scanned directories must hold trusted libraries only. Nothing else in them is executed;
+    /// on Unix they are opened with lazy binding.
+    pub unsafe fn enumerate(paths: &[&Path]) -> Vec<DriverEntry> {
+        let mut files = Vec::new();
+        for path in paths {
+            match std::fs::read_dir(path) {
+                Ok(dir) => files.extend(dir.flatten().map(|e| e.path()).filter(|p| p.is_file() && p.to_string_lossy().ends_with(std::env::consts::DLL_SUFFIX))),
+                Err(_) if path.is_file() => files.push(path.to_path_buf()),
+                Err(_) => {}
+            }
+        }
+        files.sort();
+        files.dedup();
+        files.into_iter().filter_map(|path| probe(&path).map(|(has_create, has_destroy, info)| DriverEntry { path, has_create, has_destroy, info })).collect()
+    }
+
+    unsafe fn probe(path: &Path) -> Option<(bool, bool, Option<DriverInfo>)> {
+        #[cfg(unix)]
+        let lib: Library = libloading::os::unix::Library::open(Some(path), libloading::os::unix::RTLD_LAZY | libloading::os::unix::RTLD_LOCAL).ok()?.into();
+        #[cfg(not(unix))]
+        let lib = Library::new(path).ok()?;
+        let has_create = lib.get::<*const c_void>(CREATE_SYMBOL).is_ok();
+        let has_destroy = lib.get::<*const c_void>(DESTROY_SYMBOL).is_ok();
+        if !has_create && !has_destroy { return None; }
+        let info = lib.get::<openasio_driver_get_info_fn>(GET_INFO_SYMBOL).ok().and_then(|get| read_info(*get));
+        Some((has_create, has_destroy, info))
+    }
+
+    /// [`DriverLib::load_default`] found no loadable driver; holds every path tried, in order.
+    #[derive(Debug)]
+    pub struct SearchError { pub name: String, pub attempts: Vec<(PathBuf, LoadError)> }
+    impl std::fmt::Display for SearchError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "no loadable OpenASIO driver `{}`", self.name)?;
+            for (path, e) in &self.attempts { write!(f, "\n  {}: {e}", path.display())?; }
+            Ok(())
+        }
+    }
+    impl std::error::Error for SearchError {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::{offset_of, size_of, MaybeUninit};
+
+    #[test]
+    fn compact_drops_disabled_channels() {
+        // Three frames of channels 0..4, sample value = 10 * frame + channel.
+        let mut buf: Vec<i32> = (0..3).flat_map(|f| (0..4).map(move |c| 10 * f + c)).collect();
+        assert_eq!(compact_interleaved(&mut buf, 3, 4, 0b1010), 2);
+        assert_eq!(buf[..6], [1, 3, 11, 13, 21, 23]);
+        let mut all = [1.0f32, 2.0, 3.0, 4.0];
+        assert_eq!(compact_interleaved(&mut all, 2, 2, oa_channel_mask::ALL.in_mask), 2);
+        assert_eq!(all, [1.0, 2.0, 3.0, 4.0]);
+        let cfg = oa_stream_config { in_channels: 2, out_channels: 2, ..Default::default() };
+        let masked = oa_channel_mask { in_mask: 0b01, ..oa_channel_mask::ALL }.apply(&cfg);
+        assert_eq!((masked.in_channels, masked.out_channels), (1, 2));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn open_errno_tells_busy_from_missing() {
+        assert_eq!(open_errno_result(libc::EBUSY), OA_ERR_BUSY);
+        assert_eq!(open_errno_result(libc::EAGAIN), OA_ERR_BUSY);
+        assert_eq!(open_errno_result(libc::ENOENT), OA_ERR_DEVICE);
+        assert_eq!(open_errno_result(libc::ENODEV), OA_ERR_DEVICE);
+        assert_eq!(open_errno_result(libc::EIO), OA_ERR_DEVICE);
+        assert_eq!(oa_strerror(OA_ERR_NOT_OPEN), "no device open");
+        assert_eq!(OaResult(OA_ERR_NOT_OPEN).to_string(), "err_not_open");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn release_driver_refuses_a_second_destroy() {
+        let mut drv = oa_driver { vt: std::ptr::null() };
+        let p: *mut oa_driver = &mut drv;
+        assert!(!release_driver(std::ptr::null_mut()));
+        assert!(!release_driver(p), "never tracked");
+        track_driver(p);
+        assert!(release_driver(p));
+        assert!(!release_driver(p));
+    }
+
+    #[test]
+    fn start_error_names_a_missing_device() {
+        assert_eq!(DriverPhase::Created.start_error(), OA_ERR_NOT_OPEN);
+        assert_eq!(DriverPhase::Running.start_error(), OA_ERR_STATE);
+        assert_eq!(DriverPhase::Destroyed.start_error(), OA_ERR_STATE);
+        assert!(!DriverPhase::Destroyed.can_start());
+    }
+
+    unsafe extern "C" fn caps(_drv: *mut oa_driver) -> u32 { OA_CAP_OUTPUT }
+
+    /// A vtable whose bytes past `struct_size` are garbage, as when a 1.0 driver's shorter table
+    /// is followed by unrelated memory.
+    fn truncated_vtable(struct_size: usize) -> Box<MaybeUninit<oa_driver_vtable>> {
+        let mut vt = Box::new(MaybeUninit::<oa_driver_vtable>::uninit());
+        let bytes = vt.as_mut_ptr() as *mut u8;
+        unsafe {
+            std::ptr::write_bytes(bytes, 0xA5, size_of::<oa_driver_vtable>());
+            std::ptr::write_bytes(bytes, 0, struct_size);
+            (bytes as *mut u32).write(struct_size as u32);
+            (bytes.add(offset_of!(oa_driver_vtable, get_caps)) as *mut Option<unsafe extern "C" fn(*mut oa_driver) -> u32>).write(Some(caps));
+        }
+        vt
+    }
+
+    #[test]
+    fn vtable_field_ignores_bytes_past_struct_size() {
+        let size = offset_of!(oa_driver_vtable, query_supported_configs);
+        let vt = truncated_vtable(size);
+        let vt = unsafe { vt.assume_init_ref() };
+        assert!(oa_vtable_field!(vt, get_caps).is_some());
+        assert!(oa_vtable_field!(vt, query_supported_configs).is_none());
+        assert!(oa_vtable_field!(vt, query_supported_rates).is_none());
+        assert!(oa_vtable_field!(vt, query_device_info).is_none());
+    }
+
+    #[test]
+    fn vtable_get_fn_stops_at_struct_size() {
+        let size = offset_of!(oa_driver_vtable, query_supported_configs);
+        let vt = truncated_vtable(size);
+        let vt = vt.as_ptr();
+        unsafe {
+            assert_eq!(vtable_get_fn(vt, offset_of!(oa_driver_vtable, get_caps)), Some(caps as *const ()));
+            assert_eq!(vtable_get_fn(vt, offset_of!(oa_driver_vtable, start)), None, "null entry");
+            assert_eq!(vtable_get_fn(vt, offset_of!(oa_driver_vtable, query_supported_configs)), None);
+            assert_eq!(vtable_get_fn(vt, offset_of!(oa_driver_vtable, query_device_info)), None);
+            assert_eq!(vtable_get_fn(vt, 0), None, "struct_size is not an entry");
+            assert_eq!(vtable_get_fn(vt, offset_of!(oa_driver_vtable, get_caps) + 1), None);
+            assert_eq!(vtable_get_fn(std::ptr::null(), offset_of!(oa_driver_vtable, get_caps)), None);
+        }
+    }
+
+    #[test]
+    fn vtable_field_needs_the_whole_slot() {
+        let size = offset_of!(oa_driver_vtable, get_channel_names) + size_of::<usize>() - 1;
+        let vt = truncated_vtable(size);
+        let vt = unsafe { vt.assume_init_ref() };
+        assert!(!vtable_has(vt, offset_of!(oa_driver_vtable, get_channel_names)));
+        assert!(oa_vtable_field!(vt, get_channel_names).is_none());
+    }
+
+    #[test]
+    fn vtable_field_reads_covered_entries() {
+        let vt = oa_driver_vtable { get_caps: Some(caps), ..oa_driver_vtable::empty() };
+        assert!(oa_vtable_field!(&vt, get_caps).is_some());
+        assert!(oa_vtable_field!(&vt, query_device_info).is_none());
+    }
+
+    unsafe extern "C" fn arm_ok(_: *mut oa_driver, _: *const oa_stream_config) -> i32 { OA_OK }
+    unsafe extern "C" fn arm_busy(_: *mut oa_driver, _: *const oa_stream_config) -> i32 { OA_ERR_BUSY }
+    static TRIGGERED: std::sync::Mutex<Vec<u64>> = std::sync::Mutex::new(Vec::new());
+    unsafe extern "C" fn trigger_record(_: *mut oa_driver, ns: u64) -> i32 { TRIGGERED.lock().unwrap().push(ns); OA_OK }
+    static STOPS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    unsafe extern "C" fn stop_count(_: *mut oa_driver) -> i32 { STOPS.fetch_add(1, Ordering::Relaxed); OA_OK }
+
+    fn sync_vtable(arm: Option<unsafe extern "C" fn(*mut oa_driver, *const oa_stream_config) -> i32>) -> oa_driver_vtable {
+        oa_driver_vtable { stop: Some(stop_count), arm_start: arm, trigger_start: Some(trigger_record), ..oa_driver_vtable::empty() }
+    }
+
+    #[test]
+    fn synchronized_start_triggers_one_instant() {
+        let vt = sync_vtable(Some(arm_ok));
+        let (mut a, mut b) = (oa_driver { vt: &vt }, oa_driver { vt: &vt });
+        let drivers = [&mut a as *mut oa_driver, &mut b];
+        let cfgs = [oa_stream_config::default(); 2];
+        let before = realtime_ns();
+        assert_eq!(unsafe { start_synchronized(&drivers, &cfgs) }, OA_OK);
+        let triggered = TRIGGERED.lock().unwrap();
+        assert_eq!(triggered.len(), 2);
+        assert_eq!(triggered[0], triggered[1]);
+        assert!(triggered[0] >= before + SYNC_START_LEAD.as_nanos() as u64);
+    }
+
+    #[test]
+    fn synchronized_start_rejects_drivers_without_it() {
+        // arm_busy would turn the result into OA_ERR_BUSY if anything were armed first.
+        let (vt, old) = (sync_vtable(Some(arm_busy)), sync_vtable(None));
+        let (mut a, mut b) = (oa_driver { vt: &vt }, oa_driver { vt: &old });
+        let drivers = [&mut a as *mut oa_driver, &mut b];
+        let cfgs = [oa_stream_config::default(); 2];
+        assert_eq!(unsafe { start_synchronized(&drivers, &cfgs) }, OA_ERR_UNSUPPORTED);
+        assert_eq!(unsafe { start_synchronized(&drivers, &cfgs[..1]) }, OA_ERR_INVALID_ARG);
+        assert_eq!(unsafe { start_synchronized(&[], &[]) }, OA_OK);
+        // The C export checks its pointers itself.
+        assert_eq!(unsafe { openasio_start_synchronized(std::ptr::null(), cfgs.as_ptr(), 2) }, OA_ERR_INVALID_ARG);
+        assert_eq!(unsafe { openasio_start_synchronized(drivers.as_ptr(), std::ptr::null(), 2) }, OA_ERR_INVALID_ARG);
+        assert_eq!(unsafe { openasio_start_synchronized(std::ptr::null(), std::ptr::null(), 0) }, OA_OK);
+    }
+
+    #[test]
+    fn synchronized_start_stops_armed_drivers_on_failure() {
+        let (ok, busy) = (sync_vtable(Some(arm_ok)), sync_vtable(Some(arm_busy)));
+        let (mut a, mut b) = (oa_driver { vt: &ok }, oa_driver { vt: &busy });
+        let drivers = [&mut a as *mut oa_driver, &mut b];
+        let cfgs = [oa_stream_config::default(); 2];
+        assert_eq!(unsafe { start_synchronized(&drivers, &cfgs) }, OA_ERR_BUSY);
+        assert_eq!(STOPS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn start_gate_states() {
+        let (gate, running) = (StartGate::new(), AtomicBool::new(true));
+        assert_eq!(gate.wait(&running), Some(false));
+        assert!(!gate.trigger(1), "only an armed gate can be triggered");
+        gate.arm();
+        assert!(gate.trigger(realtime_ns()));
+        assert_eq!(gate.wait(&running), Some(true));
+        gate.arm();
+        running.store(false, Ordering::Release);
+        assert_eq!(gate.wait(&running), None);
+    }
+
+    unsafe extern "C" fn silence(_user: *mut c_void, _in: *const c_void, _out: *mut c_void, _frames: u32, _time: *const oa_time_info, _cfg: *const oa_stream_config) -> oa_bool { OA_TRUE }
+
+    #[test]
+    fn create_params_validation() {
+        let host = oa_host_callbacks { process: Some(silence), ..Default::default() };
+        let full = oa_create_params::new(&host, std::ptr::null_mut());
+        unsafe {
+            assert_eq!(create_params_validate(&full), OA_OK);
+            let v1 = oa_create_params { struct_size: offset_of!(oa_create_params, log_cb) as u32, ..full };
+            assert_eq!(create_params_validate(&v1), OA_OK);
+            let short = oa_create_params { struct_size: offset_of!(oa_create_params, host_user) as u32, ..full };
+            assert_eq!(create_params_validate(&short), OA_ERR_INVALID_ARG);
+            // A newer host's bigger params are fine; a size no revision could have is not.
+            let newer = oa_create_params { struct_size: size_of::<oa_create_params>() as u32 + 16, ..full };
+            assert_eq!(create_params_validate(&newer), OA_OK);
+            let garbage = oa_create_params { struct_size: OA_MAX_STRUCT_SIZE + 1, ..full };
+            assert_eq!(create_params_validate(&garbage), OA_ERR_INVALID_ARG);
+            let no_host = oa_create_params { host: std::ptr::null(), ..full };
+            assert_eq!(create_params_validate(&no_host), OA_ERR_INVALID_ARG);
+            let tiny_host = oa_create_params { host_size: 8, ..full };
+            assert_eq!(create_params_validate(&tiny_host), OA_ERR_INVALID_ARG);
+            // Only process is required; the optional callbacks may all be null.
+            let no_process = oa_host_callbacks { reset_request: None, ..oa_host_callbacks::default() };
+            assert_eq!(create_params_validate(&oa_create_params { host: &no_process, ..full }), OA_ERR_INVALID_ARG);
+        }
+    }
+
+    #[test]
+    fn thread_hints_stop_at_struct_size() {
+        let host = oa_host_callbacks::default();
+        let full = oa_create_params { rt_priority: 80, cpu_affinity_mask: 0b10, ..oa_create_params::new(&host, std::ptr::null_mut()) };
+        assert_eq!(ThreadHints::from_params(&full), ThreadHints { rt_priority: 80, cpu_affinity_mask: 0b10 });
+        let old = oa_create_params { struct_size: offset_of!(oa_create_params, rt_priority) as u32, ..full };
+        assert_eq!(ThreadHints::from_params(&old), ThreadHints::default());
+    }
+
+    #[test]
+    fn thread_hints_take_priority_options() {
+        let mut hints = ThreadHints::default();
+        assert_eq!(hints.priority_option(), "default");
+        assert!(hints.set_priority_option("70"));
+        assert_eq!((hints.rt_priority, hints.priority_option().as_str()), (70, "70"));
+        for bad in ["100", "-1", "high", ""] { assert!(!hints.set_priority_option(bad), "{bad:?}"); }
+        assert_eq!(hints.rt_priority, 70);
+        assert!(hints.set_priority_option("default"));
+        assert_eq!(hints, ThreadHints::default());
+    }
+
+    #[test]
+    fn create_options_parse_quoted_values() {
+        let opts = CreateOptions::parse(r#" host = jack ,, device="hw:2,0",note="say \"hi\", C:\\x" ,period_count=3,"#).unwrap();
+        let pairs: Vec<_> = opts.iter().collect();
+        assert_eq!(pairs, [("host", "jack"), ("device", "hw:2,0"), ("note", r#"say "hi", C:\x"#), ("period_count", "3")]);
+        assert_eq!(CreateOptions::parse(&opts.to_string()), Ok(opts));
+        assert_eq!(CreateOptions::parse("device=a,device=b").unwrap().get("device"), Some("b"));
+        assert_eq!(CreateOptions::parse("  "), Ok(CreateOptions::default()));
+        for bad in ["host", "=jack", r#"device="hw:2"#, r#"device="hw:2"x"#] {
+            assert!(CreateOptions::parse(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn create_options_are_gated_by_struct_size() {
+        let host = oa_host_callbacks { process: Some(silence), ..Default::default() };
+        let text = c"host=alsa";
+        let full = oa_create_params { options: text.as_ptr(), ..oa_create_params::new(&host, std::ptr::null_mut()) };
+        unsafe {
+            assert_eq!(CreateOptions::from_params(&full).unwrap().get("host"), Some("alsa"));
+            let old = oa_create_params { struct_size: offset_of!(oa_create_params, options) as u32, ..full };
+            assert!(CreateOptions::from_params(&old).unwrap().is_empty());
+            let none = oa_create_params { options: std::ptr::null(), ..full };
+            assert!(CreateOptions::from_params(&none).unwrap().is_empty());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn thread_hints_degrade_without_privileges() {
+        // Pin to the first CPU this process may use; priority 80 needs privileges a test
+        // usually lacks, which must only be logged.
+        let allowed = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            assert_eq!(libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set), 0);
+            (0..64).find(|&cpu| libc::CPU_ISSET(cpu, &set)).unwrap()
+        };
+        let hints = ThreadHints { rt_priority: 80, cpu_affinity_mask: 1 << allowed };
+        let log = LogSink { cb: None, user: std::ptr::null_mut() };
+        let pinned = std::thread::spawn(move || unsafe {
+            hints.apply_to_current_thread(&log);
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set);
+            (0..64).filter(|&cpu| libc::CPU_ISSET(cpu, &set)).collect::<Vec<_>>()
+        }).join().unwrap();
+        assert_eq!(pinned, [allowed]);
+    }
+
+    unsafe extern "C" fn noop(_user: *mut c_void) {}
+
+    unsafe extern "C" fn record_xrun(user: *mut c_void, kind: oa_xrun_kind, count: u32) {
+        (*(user as *mut Vec<(oa_xrun_kind, u32)>)).push((kind, count));
+    }
+
+    #[test]
+    fn report_xrun_counts_and_notifies() {
+        let (under, over) = (AtomicU32::new(0), AtomicU32::new(0));
+        let mut seen: Vec<(oa_xrun_kind, u32)> = Vec::new();
+        let host = oa_host_callbacks { xrun: Some(record_xrun), ..Default::default() };
+        let user = &mut seen as *mut _ as *mut c_void;
+        unsafe {
+            report_xrun(&host, user, &under, OA_XRUN_UNDERRUN);
+            report_xrun(&host, user, &over, OA_XRUN_OVERRUN);
+            report_xrun(&host, user, &under, OA_XRUN_UNDERRUN);
+            // Without the callback the counter still moves.
+            report_xrun(&oa_host_callbacks::default(), user, &over, OA_XRUN_OVERRUN);
+        }
+        assert_eq!(seen, [(OA_XRUN_UNDERRUN, 1), (OA_XRUN_OVERRUN, 1), (OA_XRUN_UNDERRUN, 2)]);
+        assert_eq!((under.load(Ordering::Relaxed), over.load(Ordering::Relaxed)), (2, 2));
+    }
+
+    #[test]
+    fn host_callbacks_stop_at_host_size() {
+        let host = oa_host_callbacks { process: Some(silence), reset_request: Some(noop), device_list_changed: Some(noop), ..Default::default() };
+        let full = oa_create_params::new(&host, std::ptr::null_mut());
+        let got = unsafe { host_callbacks(&full) };
+        assert!(got.reset_request.is_some() && got.device_list_changed.is_some());
+        let v1_host = oa_create_params { host_size: OA_HOST_CALLBACKS_V1_SIZE as u32, ..full };
+        let got = unsafe { host_callbacks(&v1_host) };
+        assert!(got.reset_request.is_some() && got.device_list_changed.is_none());
+        // Params without `host_size` come from a 1.0 host, whose callbacks end at `reset_request`.
+        let v1_params = oa_create_params { struct_size: offset_of!(oa_create_params, host_size) as u32, host_size: 0, ..full };
+        assert_eq!(unsafe { create_params_validate(&v1_params) }, OA_OK);
+        let got = unsafe { host_callbacks(&v1_params) };
+        assert!(got.reset_request.is_some() && got.device_list_changed.is_none());
+    }
+
+    #[test]
+    fn stream_config_stops_at_config_size() {
+        let host = oa_host_callbacks { process: Some(silence), ..Default::default() };
+        let full = oa_create_params::new(&host, std::ptr::null_mut());
+        let v1_params = oa_create_params { struct_size: offset_of!(oa_create_params, config_size) as u32, config_size: 0, ..full };
+        assert_eq!(stream_config_size(&v1_params), OA_STREAM_CONFIG_V1_SIZE);
+        let too_small = oa_create_params { config_size: 4, ..full };
+        assert_eq!(unsafe { create_params_validate(&too_small) }, OA_ERR_INVALID_ARG);
+
+        // A 1.0 host's config ends at `layout`; whatever follows it in memory is not `exclusive`.
+        let mut mem = [OA_TRUE; size_of::<oa_stream_config>() / 4];
+        let cfg = oa_stream_config { exclusive: OA_FALSE, ..Default::default() };
+        unsafe {
+            write_stream_config(&cfg, mem.as_mut_ptr() as *mut oa_stream_config, OA_STREAM_CONFIG_V1_SIZE);
+            assert_eq!(*mem.last().unwrap(), OA_TRUE, "wrote past the host's config");
+            let back = read_stream_config(mem.as_ptr() as *const oa_stream_config, OA_STREAM_CONFIG_V1_SIZE);
+            assert_eq!(back, cfg);
+            let full_read = read_stream_config(mem.as_ptr() as *const oa_stream_config, stream_config_size(&full));
+            assert_eq!(full_read.exclusive, OA_TRUE);
+        }
+    }
+
+    #[test]
+    fn i32_full_scale_and_clipping() {
+        let mut out = [0i32; 5];
+        convert::f32_to_i32(&[1.0, -1.0, 0.0, 1.5, -7.0], &mut out);
+        assert_eq!(out, [i32::MAX, i32::MIN, 0, i32::MAX, i32::MIN]);
+        let mut back = [0f32; 2];
+        convert::i32_to_f32(&[i32::MIN, 1 << 30], &mut back);
+        assert_eq!(back, [-1.0, 0.5]);
+    }
+
+    #[test]
+    fn i24_full_scale_and_clipping() {
+        let mut out = [0u8; 15];
+        convert::f32_to_i24(&[1.0, -1.0, 0.0, 2.0, -2.0], &mut out);
+        assert_eq!(&out[0..3], &[0xFF, 0xFF, 0x7F]);
+        assert_eq!(&out[3..6], &[0x00, 0x00, 0x80]);
+        assert_eq!(&out[6..9], &[0, 0, 0]);
+        assert_eq!(&out[9..12], &[0xFF, 0xFF, 0x7F]);
+        assert_eq!(&out[12..15], &[0x00, 0x00, 0x80]);
+        let mut back = [0f32; 2];
+        convert::i24_to_f32(&out[3..9], &mut back);
+        assert_eq!(back, [-1.0, 0.0]);
+    }
+
+    #[test]
+    fn integer_round_trips() {
+        let src: Vec<f32> = (0..=200).map(|i| i as f32 / 100.0 - 1.0).collect();
+        let (mut i32s, mut i24s) = (vec![0i32; src.len()], vec![0u8; src.len() * 3]);
+        let (mut from32, mut from24) = (vec![0f32; src.len()], vec![0f32; src.len()]);
+        convert::f32_to_i32(&src, &mut i32s);
+        convert::i32_to_f32(&i32s, &mut from32);
+        convert::f32_to_i24(&src, &mut i24s);
+        convert::i24_to_f32(&i24s, &mut from24);
+        for ((s, a), b) in src.iter().zip(&from32).zip(&from24) {
+            assert!((s - a).abs() <= 1.0 / 2147483648.0 + f32::EPSILON, "i32: {s} -> {a}");
+            assert!((s - b).abs() <= 1.0 / 8388608.0, "i24: {s} -> {b}");
+        }
+    }
+
+    #[test]
+    fn f64_round_trips_through_f32() {
+        let src: Vec<f32> = (0..=200).map(|i| i as f32 / 100.0 - 1.0).chain([1.5, -7.0, f32::MIN_POSITIVE]).collect();
+        let (mut wide, mut back) = (vec![0f64; src.len()], vec![0f32; src.len()]);
+        convert::f32_to_f64(&src, &mut wide);
+        convert::f64_to_f32(&wide, &mut back);
+        // Widening is exact, and so is narrowing a value that came from an f32.
+        assert_eq!(back, src);
+        let mut narrowed = [0f32; 2];
+        convert::f64_to_f32(&[0.1, 2.0], &mut narrowed);
+        assert_eq!(narrowed, [0.1f32, 2.0]);
+        assert_eq!((OA_SAMPLE_F64.bytes_per_sample(), OA_SAMPLE_F64.to_string()), (8, "f64".to_string()));
+    }
+
+    #[test]
+    fn layout_changes_width_in_the_same_pass() {
+        // Two planes of three frames, with a spare sample of stride.
+        let planar = [0.25f64, 0.5, 0.75, 0.0, -0.25, -0.5, -0.75, 0.0];
+        let mut interleaved = [0f32; 6];
+        layout::interleave(&planar, 4, 2, &mut interleaved);
+        assert_eq!(interleaved, [0.25, -0.25, 0.5, -0.5, 0.75, -0.75]);
+        let mut back = [0f64; 8];
+        layout::deinterleave(&interleaved, 2, &mut back, 4);
+        assert_eq!(back, planar);
+    }
+
+    #[test]
+    fn f64_block_planes_are_f64_planes() {
+        let cfg = oa_stream_config { buffer_frames: 3, in_channels: 2, out_channels: 2, format: OA_SAMPLE_F64, layout: OA_BUF_NONINTERLEAVED, ..Default::default() };
+        let mut block = F64Block::new(&cfg);
+        block.load_input(&[0.25, -0.25, 0.5, -0.5, 0.75, -0.75], 2);
+        let (in_ptr, out_ptr) = block.host_ptrs();
+        unsafe {
+            let inputs = std::slice::from_raw_parts(in_ptr as *const *const f64, 2);
+            assert_eq!(std::slice::from_raw_parts(inputs[1], 3), [-0.25, -0.5, -0.75]);
+            let outputs = std::slice::from_raw_parts(out_ptr as *const *mut f64, 2);
+            for (c, &plane) in outputs.iter().enumerate() {
+                std::slice::from_raw_parts_mut(plane, 3).copy_from_slice(&[0.1 * c as f64, 0.2, 0.3]);
+            }
+        }
+        let mut device = [0f32; 6];
+        block.store_output(&mut device, 2);
+        assert_eq!(device, [0.0, 0.1, 0.2, 0.2, 0.3, 0.3]);
+
+        let mut interleaved = F64Block::new(&oa_stream_config { in_channels: 0, layout: OA_BUF_INTERLEAVED, ..cfg });
+        let (in_ptr, out_ptr) = interleaved.host_ptrs();
+        assert!(in_ptr.is_null());
+        unsafe { std::slice::from_raw_parts_mut(out_ptr as *mut f64, 6).fill(0.5) };
+        interleaved.store_output(&mut device, 2);
+        assert_eq!(device, [0.5; 6]);
+    }
+
+    #[test]
+    fn midi_events_hold_whole_messages() {
+        let note_on = oa_midi_event::new(7, &[0x90, 60, 100]).unwrap();
+        assert_eq!((note_on.sample_offset, note_on.bytes()), (7, &[0x90, 60, 100][..]));
+        assert_eq!(oa_midi_event::new(0, &[0xC3, 5]).unwrap().bytes(), [0xC3, 5]);
+        assert_eq!(oa_midi_event::new(0, &[0xF8]).unwrap().data, [0xF8, 0, 0]);
+        // Truncated, overlong, running status, system exclusive, undefined.
+        for bad in [&[0x90, 60][..], &[0xC0, 1, 2], &[60, 100], &[0xF0, 1, 0xF7], &[0xF4], &[]] {
+            assert_eq!(oa_midi_event::new(0, bad), None, "{bad:x?}");
+        }
+        let lying = oa_midi_event { size: 9, ..note_on };
+        assert_eq!(lying.bytes().len(), 3);
+    }
+
+    #[test]
+    fn caps_keep_their_bits() {
+        let bits = [
+            (OaCaps::OUTPUT, 1 << 0), (OaCaps::INPUT, 1 << 1), (OaCaps::FULL_DUPLEX, 1 << 2),
+            (OaCaps::SET_SAMPLERATE, 1 << 3), (OaCaps::SET_BUFFRAMES, 1 << 4), (OaCaps::CLOCK_SELECT, 1 << 5),
+            (OaCaps::LOOPBACK, 1 << 6), (OaCaps::SAMPLE_POSITION, 1 << 7), (OaCaps::CHANNEL_MASK, 1 << 8),
+            (OaCaps::EXCLUSIVE, 1 << 9), (OaCaps::MIDI, 1 << 10), (OaCaps::MMAP, 1 << 11),
+        ];
+        let mut all = OaCaps::empty();
+        for (flag, bit) in bits {
+            assert_eq!(flag.bits(), bit);
+            assert!(!all.contains(flag), "{flag:?} collides");
+            all |= flag;
+        }
+        assert_eq!(OaCaps::NAMED.iter().fold(OaCaps::empty(), |acc, (flag, _)| acc | *flag), all);
+        assert_eq!(all & (OaCaps::RESERVED | OaCaps::VENDOR), OaCaps::empty());
+        assert_eq!((all | OaCaps::RESERVED | OaCaps::VENDOR).bits(), u32::MAX);
+        let caps = OaCaps::OUTPUT.union(OaCaps::INPUT) | OaCaps::from_bits_retain(1 << 24);
+        assert_eq!(u32::from(caps), 0x0100_0003);
+        assert_eq!(format!("{caps:?}"), "OUTPUT | INPUT | 0x1000000");
+        assert_eq!(format!("{:?}", OaCaps::empty()), "0x0");
+    }
+
+    #[test]
+    fn stream_config_check_table() {
+        let ok = oa_stream_config::default();
+        let cases = [
+            ("default", ok, OA_OK),
+            ("input only", oa_stream_config { out_channels: 0, ..ok }, OA_OK),
+            ("odd buffer", oa_stream_config { buffer_frames: 100, ..ok }, OA_OK),
+            ("lowest rate", oa_stream_config { sample_rate: OA_MIN_SAMPLE_RATE, ..ok }, OA_OK),
+            ("highest rate", oa_stream_config { sample_rate: OA_MAX_SAMPLE_RATE, ..ok }, OA_OK),
+            ("all channels", oa_stream_config { in_channels: OA_MAX_CHANNELS, out_channels: OA_MAX_CHANNELS, ..ok }, OA_OK),
+            ("zero buffer", oa_stream_config { buffer_frames: 0, ..ok }, OA_ERR_INVALID_ARG),
+            ("zero rate", oa_stream_config { sample_rate: 0, ..ok }, OA_ERR_INVALID_ARG),
+            ("rate too low", oa_stream_config { sample_rate: OA_MIN_SAMPLE_RATE - 1, ..ok }, OA_ERR_INVALID_ARG),
+            ("rate too high", oa_stream_config { sample_rate: OA_MAX_SAMPLE_RATE + 1, ..ok }, OA_ERR_INVALID_ARG),
+            ("no channels", oa_stream_config { in_channels: 0, out_channels: 0, ..ok }, OA_ERR_INVALID_ARG),
+            ("too many inputs", oa_stream_config { in_channels: OA_MAX_CHANNELS + 1, ..ok }, OA_ERR_INVALID_ARG),
+            ("too many outputs", oa_stream_config { out_channels: OA_MAX_CHANNELS + 1, ..ok }, OA_ERR_INVALID_ARG),
+            ("block overflows", oa_stream_config { buffer_frames: u32::MAX, ..ok }, OA_ERR_INVALID_ARG),
+            ("unknown format", oa_stream_config { format: oa_sample_format(99), ..ok }, OA_ERR_INVALID_ARG),
+            ("unknown layout", oa_stream_config { layout: oa_buffer_layout(7), ..ok }, OA_ERR_INVALID_ARG),
+        ];
+        for (what, cfg, want) in cases {
+            assert_eq!(oa_stream_config_check(&cfg), want, "{what}");
+        }
+    }
+
+    #[test]
+    fn channel_counts_fuzz() {
+        // xorshift64: deterministic, so a failure reproduces.
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || { seed ^= seed << 13; seed ^= seed >> 7; seed ^= seed << 17; seed };
+        for _ in 0..100_000 {
+            let r = next();
+            let cfg = oa_stream_config {
+                in_channels: r as u16 % 80, out_channels: (r >> 16) as u16 % 80,
+                buffer_frames: if r >> 63 == 1 { (r >> 32) as u32 } else { (r >> 32) as u32 % 8192 },
+                ..Default::default()
+            };
+            let min_out = (r >> 62) as u16 & 1;
+            let ok = validate_channel_counts(&cfg, min_out) == OA_OK;
+            let widest = cfg.in_channels.max(cfg.out_channels) as u64;
+            let expect = cfg.out_channels >= min_out && widest <= OA_MAX_CHANNELS as u64 && cfg.buffer_frames as u64 * widest <= u32::MAX as u64;
+            assert_eq!(ok, expect, "{cfg:?} min_out={min_out}");
+        }
+        assert_eq!(validate_channel_counts(&oa_stream_config { out_channels: 0, ..Default::default() }, 1), OA_ERR_INVALID_ARG);
+        assert_eq!(validate_channel_counts(&oa_stream_config { buffer_frames: u32::MAX / 2 + 1, ..Default::default() }, 1), OA_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn constructors_fill_every_field() {
+        let cfg = oa_stream_config::default();
+        assert_eq!((cfg.sample_rate, cfg.buffer_frames, cfg.in_channels, cfg.out_channels), (48000, 128, 2, 2));
+        assert_eq!((cfg.format, cfg.layout), (OA_SAMPLE_F32, OA_BUF_INTERLEAVED));
+        assert_eq!(validate_stream_config(&cfg), OA_OK);
+        assert_ne!(validate_stream_config(&oa_stream_config::zeroed()), OA_OK);
+        assert_eq!(oa_time_info::zeroed(), oa_time_info::default());
+        let vt = oa_driver_vtable::empty();
+        assert_eq!(vt.struct_size as usize, size_of::<oa_driver_vtable>());
+        assert!(vt.get_caps.is_none() && vt.set_channel_gain.is_none());
+        assert!(oa_host_callbacks::empty().process.is_none());
+    }
+
+    #[test]
+    fn unknown_enum_values_are_representable() {
+        let format = oa_sample_format(7);
+        assert!(!format.is_known() && oa_sample_format::OA_SAMPLE_I24.is_known());
+        assert_eq!(format!("{format:?} {format}"), "oa_sample_format(7) unknown format 7");
+        assert_eq!(format!("{:?}", oa_buffer_layout::OA_BUF_NONINTERLEAVED), "OA_BUF_NONINTERLEAVED");
+        assert_eq!((format.bytes_per_sample(), oa_format_bit(oa_sample_format(-1)), oa_format_bit(oa_sample_format(40))), (0, 0, 0));
+        let cfg = oa_stream_config { layout: oa_buffer_layout(0), ..Default::default() };
+        assert_eq!(validate_stream_config(&cfg), OA_ERR_INVALID_ARG);
+        assert!(!oa_xrun_kind(0).is_known() && !oa_direction(2).is_known());
+        assert_eq!((oa_direction::OA_DIR_INPUT.as_u32(), oa_direction::OA_DIR_OUTPUT.as_u32()), (OA_DIR_INPUT, OA_DIR_OUTPUT));
+    }
+
+    #[test]
+    fn channel_gains_clamp_and_scale() {
+        let gains = ChannelGains::default();
+        assert_eq!(gains.set(1, 2.0), OA_OK);
+        assert_eq!(gains.set(2, 9.0), OA_OK);
+        assert_eq!(gains.get(2), OA_MAX_CHANNEL_GAIN);
+        assert_eq!(gains.set(0, -1.0), OA_OK);
+        assert_eq!(gains.get(0), 0.0);
+        assert_eq!(gains.set(OA_MAX_CHANNELS as u32, 1.0), OA_ERR_INVALID_ARG);
+        assert_eq!(gains.set(0, f32::NAN), OA_ERR_INVALID_ARG);
+        let mut buf = [0.25f32; 8];
+        gains.apply_interleaved(&mut buf, 4);
+        assert_eq!(buf, [0.0, 0.5, 1.0, 0.25, 0.0, 0.5, 1.0, 0.25]);
+        assert_eq!(set_channel_gain(&gains, &gains, 7, 0, 1.0), OA_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn default_channel_names_cover_the_config() {
+        let cfg = oa_stream_config { in_channels: 1, out_channels: 2, ..Default::default() };
+        let mut buf = [0 as c_char; 4];
+        let rc = unsafe { default_channel_name_for(&cfg, oa_direction::OA_DIR_OUTPUT, 1, buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(rc, "Playback 2".len() as i32);
+        assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_bytes(), b"Pla");
+        assert_eq!(unsafe { default_channel_name_for(&cfg, oa_direction::OA_DIR_INPUT, 1, buf.as_mut_ptr(), buf.len()) }, OA_ERR_INVALID_ARG);
+        assert_eq!(unsafe { write_channel_name("Capture 1", buf.as_mut_ptr(), 0) }, 9);
+        assert_eq!(buf[0] as u8, b'P', "len 0 writes nothing");
+    }
+
+    #[test]
+    fn device_list_is_sized_rather_than_cut() {
+        let mut buf = [b'?' as c_char; 4];
+        assert_eq!(unsafe { write_device_list("hw:0,0\n", buf.as_mut_ptr(), buf.len()) }, 8);
+        assert_eq!(buf[0], 0, "a short buffer gets an empty list");
+        assert_eq!(unsafe { write_device_list("hw:0,0\n", std::ptr::null_mut(), 0) }, 8);
+        let mut buf = [b'?' as c_char; 8];
+        assert_eq!(unsafe { write_device_list("hw:0,0\n", buf.as_mut_ptr(), buf.len()) }, OA_OK);
+        assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_bytes(), b"hw:0,0\n");
+    }
+
+    #[test]
+    fn clock_source_name_is_truncated_and_terminated() {
+        let long = "x".repeat(OA_CLOCK_SOURCE_NAME_LEN * 2);
+        let src = oa_clock_source::new(3, &long, true);
+        assert_eq!(src.name[OA_CLOCK_SOURCE_NAME_LEN - 1], 0);
+        assert_eq!(src.name[OA_CLOCK_SOURCE_NAME_LEN - 2] as u8, b'x');
+        let mut buf = [oa_clock_source::default(); 2];
+        assert_eq!(unsafe { write_clock_sources(&[src, oa_clock_source::internal()], buf.as_mut_ptr(), 1) }, 2);
+        assert_eq!(buf[0].index, 0, "short buffer is left untouched");
+        assert_eq!(unsafe { write_clock_sources(&[src, oa_clock_source::internal()], buf.as_mut_ptr(), 2) }, 2);
+        assert_eq!(buf[0].index, 3);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn search_dirs_precedence() {
+        use std::path::PathBuf;
+        let env = |k: &str| match k { "OPENASIO_DRIVER_PATH" => Some("/opt/a::/opt/b".into()), "HOME" => Some("/home/u".into()), _ => None };
+        let dirs = loader::search_dirs_with(env);
+        let want: Vec<PathBuf> = ["/opt/a", "/opt/b", "/home/u/.local/share/openasio/drivers", "/usr/local/lib/openasio", "/usr/lib/openasio"].iter().map(PathBuf::from).collect();
+        assert_eq!(dirs, want);
+        let dirs = loader::search_dirs_with(|k| (k == "XDG_DATA_HOME").then(|| "/data".into()));
+        assert_eq!(dirs[0], PathBuf::from("/data/openasio/drivers"));
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn candidate_filenames_add_what_is_missing() {
+        assert_eq!(loader::candidate_filenames("foo"), ["foo", "libfoo", "foo.so", "libfoo.so"]);
+        assert_eq!(loader::candidate_filenames("libfoo"), ["libfoo", "libfoo.so"]);
+        assert_eq!(loader::candidate_filenames("foo.so"), ["foo.so", "libfoo.so"]);
+        assert_eq!(loader::candidate_filenames("libfoo.so"), ["libfoo.so"]);
+        let paths = loader::candidate_paths(std::path::Path::new("/opt/drivers/foo"));
+        assert_eq!(paths.last().map(|p| p.as_path()), Some(std::path::Path::new("/opt/drivers/libfoo.so")));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn candidate_filenames_add_what_is_missing() {
+        assert_eq!(loader::candidate_filenames("foo"), ["foo", "libfoo", "foo.dylib", "libfoo.dylib"]);
+        assert_eq!(loader::candidate_filenames("libfoo.dylib"), ["libfoo.dylib"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn candidate_filenames_add_what_is_missing() {
+        assert_eq!(loader::candidate_filenames("foo"), ["foo", "foo.dll"]);
+        assert_eq!(loader::candidate_filenames("foo.dll"), ["foo.dll"]);
+    }
+
+    #[test]
+    fn load_from_reports_every_candidate() {
+        let dirs: Vec<std::path::PathBuf> = vec!["/nonexistent/one".into(), "/nonexistent/two".into()];
+        let err = match unsafe { loader::DriverLib::load_from("nope", dirs.clone()) } { Err(e) => e, Ok(_) => panic!("loaded a driver from nowhere") };
+        let file = loader::driver_filename("nope");
+        let tried: Vec<_> = err.attempts.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(tried, dirs.iter().map(|d| d.join(&file)).collect::<Vec<_>>());
+        let msg = err.to_string();
+        assert!(msg.contains("/nonexistent/one") && msg.contains("/nonexistent/two"), "{msg}");
     }
 }
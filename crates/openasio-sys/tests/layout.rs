@@ -0,0 +1,87 @@
+//! Layout of every public ABI struct. Each field must sit where a C compiler puts it, in header
+//! order; with the `generated` feature each struct must also match bindgen's reading of the header
+//! in size, alignment and every field offset.
+use openasio_sys as sys;
+use std::mem::{align_of, offset_of, size_of};
+
+/// Size and alignment of the field `get` reads.
+fn field<T, F>(_get: impl Fn(&T) -> &F) -> (usize, usize) {
+    (size_of::<F>(), align_of::<F>())
+}
+
+/// Check `fields` (name, offset, size, alignment) against the C layout rules: each field at the
+/// first suitably aligned offset after the previous one, the struct padded to its largest
+/// alignment.
+fn assert_c_layout(ty: &str, size: usize, align: usize, fields: &[(&str, usize, usize, usize)]) {
+    let (mut end, mut max_align) = (0usize, 1usize);
+    for &(name, offset, field_size, field_align) in fields {
+        assert_eq!(offset, end.next_multiple_of(field_align), "{ty}.{name} is not where C puts it");
+        end = offset + field_size;
+        max_align = max_align.max(field_align);
+    }
+    assert_eq!(align, max_align, "{ty} alignment");
+    assert_eq!(size, end.next_multiple_of(max_align), "{ty} has fields missing from this list");
+}
+
+macro_rules! layout {
+    ($($ty:ident { $($field:ident),* $(,)? })*) => {$(
+        #[test]
+        fn $ty() {
+            let fields = [$({
+                let (size, align) = field(|t: &sys::$ty| &t.$field);
+                (stringify!($field), offset_of!(sys::$ty, $field), size, align)
+            }),*];
+            assert_c_layout(stringify!($ty), size_of::<sys::$ty>(), align_of::<sys::$ty>(), &fields);
+            #[cfg(feature = "generated")]
+            {
+                assert_eq!(size_of::<sys::$ty>(), size_of::<sys::generated::$ty>(), concat!(stringify!($ty), " size"));
+                assert_eq!(align_of::<sys::$ty>(), align_of::<sys::generated::$ty>(), concat!(stringify!($ty), " alignment"));
+                $(assert_eq!(
+                    offset_of!(sys::$ty, $field),
+                    offset_of!(sys::generated::$ty, $field),
+                    concat!(stringify!($ty), ".", stringify!($field)),
+                );)*
+            }
+        }
+    )*};
+}
+
+layout! {
+    oa_stream_config { sample_rate, buffer_frames, in_channels, out_channels, format, layout, exclusive }
+    oa_time_info { host_time_ns, device_time_ns, underruns, overruns, near_xrun, sample_position }
+    oa_midi_event { sample_offset, size, data }
+    oa_host_callbacks { process, latency_changed, reset_request, device_list_changed, xrun, midi_in }
+    oa_supported_configs { sample_rates, sample_rate_count, min_buffer_frames, max_buffer_frames, formats }
+    oa_device_info {
+        struct_size, id, description, max_in_channels, max_out_channels, min_buffer_frames,
+        max_buffer_frames,
+    }
+    oa_create_params {
+        struct_size, host, host_user, log_cb, log_user, host_size, rt_priority, cpu_affinity_mask,
+        config_size, options,
+    }
+    oa_driver { vt }
+    oa_stream_status {
+        struct_size, running, actual_sample_rate, actual_buffer_frames, frames_processed,
+        underruns, overruns, near_xrun_count, input_queued_frames, max_callback_ns,
+    }
+    oa_clock_source { index, name, current }
+    oa_config_range {
+        min_rate, max_rate, min_channels_in, max_channels_in, min_channels_out, max_channels_out,
+        formats_mask, min_buffer_frames, max_buffer_frames,
+    }
+    oa_channel_mask { in_mask, out_mask }
+    oa_driver_vtable {
+        struct_size, get_caps, query_devices, open_device, close_device, get_default_config, start,
+        stop, get_latency, set_sample_rate, set_buffer_frames, query_supported_configs,
+        get_channel_names, query_supported_rates, enumerate_clock_sources, select_clock_source,
+        query_device_info, arm_start, trigger_start, get_status, query_clock_sources,
+        set_clock_source, enable_dc_filter, get_channel_name, set_channel_gain, pause, resume,
+        abort, get_last_error, query_config_ranges, set_channel_mask, send_midi,
+        show_control_panel, set_option, get_option,
+    }
+    oa_driver_info {
+        struct_size, name, vendor, version_major, version_minor, version_patch, api_major,
+        api_minor,
+    }
+}
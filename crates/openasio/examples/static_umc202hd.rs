@@ -0,0 +1,60 @@
+//! Play a sine tone through the UMC202HD driver linked into the binary, with no driver library
+//! to load. For deployments that cannot dlopen.
+//!
+//! Usage: `cargo run -p openasio --features static-umc202hd --example static_umc202hd [device] [seconds]`.
+use anyhow::{bail, Context, Result};
+use openasio::{linked::Umc202hd, Driver, HostProcess, StopReason, StreamConfig};
+use std::f32::consts::TAU;
+use std::os::raw::c_void;
+use std::time::{Duration, Instant};
+
+struct Tone {
+    phase: f32,
+}
+
+impl HostProcess for Tone {
+    fn process(&mut self, _inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool {
+        let step = 440.0 * TAU / cfg.sample_rate as f32;
+        let out_ch = cfg.out_channels as usize;
+        for f in 0..frames as usize {
+            let s = 0.2 * self.phase.sin();
+            self.phase = (self.phase + step) % TAU;
+            for c in 0..out_ch {
+                unsafe {
+                    if cfg.interleaved {
+                        *(outputs as *mut f32).add(f * out_ch + c) = s;
+                    } else {
+                        *(*(outputs as *const *mut f32).add(c)).add(f) = s;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let device = args.next();
+    let seconds: u64 = match args.next() {
+        Some(s) => s.parse().with_context(|| format!("bad duration {s:?}"))?,
+        None => 3,
+    };
+    let cfg = StreamConfig { in_channels: 0, out_channels: 2, ..StreamConfig::default() };
+
+    let mut driver = Driver::static_load::<Umc202hd>(Box::new(Tone { phase: 0.0 }), cfg, cfg.interleaved)?;
+    driver.open_by_name(device.as_deref())?;
+    let stopped = driver.stopped_notifier();
+    let stream = driver.start_stream(cfg)?;
+    let until = Instant::now() + Duration::from_secs(seconds);
+    while Instant::now() < until {
+        match stopped.wait(Duration::from_millis(100)) {
+            Some(StopReason::Panic(msg)) => bail!("audio callback panicked: {msg}"),
+            Some(StopReason::InvalidConfig { format, layout }) => bail!("driver sent format {format}, layout {layout}"),
+            Some(reason) => bail!("stream stopped early: {reason:?}"),
+            None => {}
+        }
+    }
+    stream.stop();
+    Ok(())
+}
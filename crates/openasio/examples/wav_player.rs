@@ -0,0 +1,98 @@
+//! Play a WAV file once through an OpenASIO driver, then exit when the stream reports it stopped.
+//!
+//! Usage: `wav_player <driver.so> <file.wav> [device]`. Supports 16-bit PCM and 32-bit float files.
+use anyhow::{anyhow, bail, Context, Result};
+use openasio::{Driver, HostProcess, StopReason, StreamConfig};
+use std::os::raw::c_void;
+use std::time::Duration;
+
+struct Wav {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>, // interleaved
+}
+
+fn read_wav(path: &str) -> Result<Wav> {
+    let data = std::fs::read(path).with_context(|| format!("reading {path}"))?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        bail!("{path} is not a RIFF/WAVE file");
+    }
+    let (mut fmt, mut body) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let chunk = data.get(pos + 8..pos + 8 + len).ok_or_else(|| anyhow!("truncated chunk"))?;
+        match id {
+            b"fmt " => fmt = Some(chunk),
+            b"data" => body = Some(chunk),
+            _ => {}
+        }
+        pos += 8 + len + (len & 1);
+    }
+    let fmt = fmt.ok_or_else(|| anyhow!("missing fmt chunk"))?;
+    let body = body.ok_or_else(|| anyhow!("missing data chunk"))?;
+    let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+    let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+    let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().unwrap());
+    let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+    let samples = match (tag, bits) {
+        (1, 16) => body.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0).collect(),
+        (3, 32) => body.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+        _ => bail!("unsupported WAV encoding (format {tag}, {bits} bits)"),
+    };
+    Ok(Wav { sample_rate, channels, samples })
+}
+
+struct WavPlayer {
+    wav: Wav,
+    pos: usize, // frame index
+}
+
+impl HostProcess for WavPlayer {
+    fn process(&mut self, _inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool {
+        let (frames, out_ch, src_ch) = (frames as usize, cfg.out_channels as usize, self.wav.channels as usize);
+        let total = self.wav.samples.len() / src_ch;
+        for f in 0..frames {
+            let frame = self.pos + f;
+            for c in 0..out_ch {
+                let s = if frame < total { self.wav.samples[frame * src_ch + c % src_ch] } else { 0.0 };
+                unsafe {
+                    if cfg.interleaved {
+                        *(outputs as *mut f32).add(f * out_ch + c) = s;
+                    } else {
+                        *(*(outputs as *const *mut f32).add(c)).add(f) = s;
+                    }
+                }
+            }
+        }
+        self.pos += frames;
+        self.pos < total
+    }
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (driver_path, wav_path) = match (args.next(), args.next()) {
+        (Some(d), Some(w)) => (d, w),
+        _ => bail!("usage: wav_player <driver> <file.wav> [device]"),
+    };
+    let device = args.next();
+    let wav = read_wav(&wav_path)?;
+    let cfg = StreamConfig { sample_rate: wav.sample_rate, in_channels: 0, out_channels: 2, ..StreamConfig::default() };
+    let player = WavPlayer { wav, pos: 0 };
+
+    let mut driver = Driver::load(&driver_path, Box::new(player), cfg, cfg.interleaved)?;
+    driver.open_by_name(device.as_deref())?;
+    let stopped = driver.stopped_notifier();
+    let _stream = driver.start_stream(cfg)?;
+    loop {
+        match stopped.wait(Duration::from_secs(1)) {
+            Some(StopReason::HostRequested) => { println!("finished {wav_path}"); return Ok(()); }
+            Some(StopReason::Panic(msg)) => bail!("audio callback panicked: {msg}"),
+            Some(StopReason::Api) => return Ok(()),
+            Some(StopReason::InvalidConfig { format, layout }) => bail!("driver sent format {format}, layout {layout}"),
+            None => {}
+        }
+    }
+}
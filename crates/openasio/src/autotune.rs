@@ -0,0 +1,99 @@
+//! Buffer-size search driven by the xrun counters drivers report in `oa_time_info`.
+use crate::{Driver, Event, StreamConfig};
+use anyhow::{anyhow, Result};
+use openasio_sys as sys;
+use std::time::{Duration, Instant};
+
+/// Finds the smallest buffer size a machine sustains without xruns.
+///
+/// [`AutoTune::run`] starts at `min_frames` and doubles the buffer each time a measurement window
+/// sees an xrun. [`AutoTune::monitor`] keeps a stream running and bumps the buffer whenever
+/// `xrun_limit` xruns accumulate within one window, announcing each change as
+/// [`Event::BufferFramesChanged`].
+pub struct AutoTune {
+    min_frames: u32,
+    max_frames: u32,
+    window: Duration,
+    xrun_limit: u32,
+    result: Option<u32>,
+}
+
+impl AutoTune {
+    pub fn new(min_frames: u32) -> Self {
+        Self { min_frames: min_frames.max(1), max_frames: 8192, window: Duration::from_secs(2), xrun_limit: 3, result: None }
+    }
+    pub fn max_frames(mut self, frames: u32) -> Self { self.max_frames = frames; self }
+    /// How long each buffer size is observed.
+    pub fn window(mut self, window: Duration) -> Self { self.window = window; self }
+    /// Xruns within one window that make [`AutoTune::monitor`] grow the buffer.
+    pub fn xrun_limit(mut self, xruns: u32) -> Self { self.xrun_limit = xruns.max(1); self }
+    /// Buffer size chosen by the last [`AutoTune::run`] or [`AutoTune::monitor`].
+    pub fn result(&self) -> Option<u32> { self.result }
+
+    /// Measurement phase: restart the stream at increasing buffer sizes until a full window passes
+    /// without xruns. The stream is stopped afterwards.
+    pub fn run(&mut self, driver: &mut Driver, cfg: StreamConfig) -> Result<u32> {
+        check_caps(driver)?;
+        let mut frames = self.min_frames;
+        loop {
+            let stream = driver.start_stream(StreamConfig { buffer_frames: frames, ..cfg })?;
+            let before = stream.xrun_stats();
+            std::thread::sleep(self.window);
+            let after = stream.xrun_stats();
+            stream.stop();
+            if after == before {
+                self.result = Some(frames);
+                return Ok(frames);
+            }
+            log::debug!("autotune: xruns at {frames} frames");
+            frames = self.next_size(frames)?;
+        }
+    }
+
+    /// Continuous mode: stream at `cfg` until `keep_running` returns false, restarting with a
+    /// larger buffer after repeated xruns.
+    pub fn monitor(&mut self, driver: &mut Driver, cfg: StreamConfig, mut keep_running: impl FnMut() -> bool) -> Result<()> {
+        check_caps(driver)?;
+        let mut frames = cfg.buffer_frames.max(self.min_frames);
+        self.result = Some(frames);
+        'restart: loop {
+            let stream = driver.restart_stream(StreamConfig { buffer_frames: frames, ..cfg })?;
+            let mut window_start = Instant::now();
+            let mut base = stream.xrun_stats();
+            while keep_running() {
+                std::thread::sleep(self.window.min(Duration::from_millis(100)));
+                let now = stream.xrun_stats();
+                let xruns = now.underruns.wrapping_sub(base.underruns) + now.overruns.wrapping_sub(base.overruns);
+                if xruns >= self.xrun_limit {
+                    stream.stop();
+                    let next = self.next_size(frames)?;
+                    log::warn!("autotune: {xruns} xruns at {frames} frames, switching to {next}");
+                    driver.emit(Event::BufferFramesChanged { from: frames, to: next });
+                    frames = next;
+                    self.result = Some(frames);
+                    continue 'restart;
+                }
+                if window_start.elapsed() >= self.window {
+                    window_start = Instant::now();
+                    base = now;
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    fn next_size(&self, frames: u32) -> Result<u32> {
+        let next = frames.saturating_mul(2);
+        if next > self.max_frames {
+            return Err(anyhow!("xruns persist up to {} frames", self.max_frames));
+        }
+        Ok(next)
+    }
+}
+
+fn check_caps(driver: &Driver) -> Result<()> {
+    if !driver.caps().contains(sys::OaCaps::SET_BUFFRAMES) {
+        return Err(anyhow!("driver does not advertise OA_CAP_SET_BUFFRAMES"));
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Type-state driver lifecycle: [`DriverBuilder`] → [`OpenedDriver`] → [`RunningDriver`].
+use crate::{Driver, HostProcess, StreamConfig, XrunStats};
+use anyhow::Result;
+use openasio_sys as sys;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+enum Source {
+    Path(PathBuf),
+    Library(sys::loader::Library),
+}
+
+/// Collects creation parameters for a driver. [`DriverBuilder::open`] yields an [`OpenedDriver`],
+/// which is the only way to reach a [`RunningDriver`], so streaming without an open device does
+/// not compile.
+pub struct DriverBuilder {
+    source: Source,
+    host: Box<dyn HostProcess>,
+    config: StreamConfig,
+    hints: sys::ThreadHints,
+    options: sys::CreateOptions,
+}
+
+impl DriverBuilder {
+    pub fn new(path: impl Into<PathBuf>, host: Box<dyn HostProcess>) -> Self {
+        Self { source: Source::Path(path.into()), host, config: StreamConfig::default(), hints: sys::ThreadHints::default(), options: sys::CreateOptions::default() }
+    }
+    /// Build from a library the application loaded itself (see [`Driver::load_from_library`]).
+    pub fn from_library(lib: sys::loader::Library, host: Box<dyn HostProcess>) -> Self {
+        Self { source: Source::Library(lib), host, config: StreamConfig::default(), hints: sys::ThreadHints::default(), options: sys::CreateOptions::default() }
+    }
+    /// Stream configuration the driver is created with. Defaults to [`StreamConfig::default`].
+    pub fn config(mut self, cfg: StreamConfig) -> Self { self.config = cfg; self }
+    /// Ask for the driver's audio thread to run `SCHED_FIFO` at `priority` (1..=99), or with
+    /// normal scheduling for 0. Without privileges drivers log a warning and keep their default.
+    pub fn rt_priority(mut self, priority: i32) -> Self { self.hints.rt_priority = priority; self }
+    /// Restrict the driver's audio thread to the CPUs set in `mask`, bit n for CPU n.
+    pub fn cpu_affinity(mut self, mask: u64) -> Self { self.hints.cpu_affinity_mask = mask; self }
+    /// Pass a backend hint in `oa_create_params.options`, e.g. `option("host", "jack")` for the
+    /// cpal driver or `option("device", "hw:2,0")` for the ALSA ones. Drivers ignore keys they
+    /// do not know; a later value for the same key wins.
+    pub fn option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self { self.options.push(key, value); self }
+    /// Create the driver without opening a device.
+    pub fn build(self) -> Result<Driver> {
+        let interleaved = self.config.interleaved;
+        match self.source {
+            Source::Path(path) => Driver::load_with_hints(&path, self.host, self.config, interleaved, self.hints, &self.options),
+            Source::Library(lib) => Driver::load_from_library_with_hints(lib, self.host, self.config, interleaved, self.hints, &self.options),
+        }
+    }
+    /// Create the driver and open `device` (`None` for the driver's default).
+    pub fn open(self, device: Option<&str>) -> Result<OpenedDriver> {
+        let mut driver = self.build()?;
+        driver.open_by_name(device)?;
+        Ok(OpenedDriver { driver })
+    }
+}
+
+/// A driver with an open device, ready to stream.
+pub struct OpenedDriver {
+    driver: Driver,
+}
+
+impl OpenedDriver {
+    pub fn start(mut self, cfg: StreamConfig) -> Result<RunningDriver> {
+        self.driver.apply_config(cfg);
+        self.driver.start_raw()?;
+        Ok(RunningDriver { driver: self.driver })
+    }
+    pub fn into_inner(self) -> Driver { self.driver }
+}
+
+impl Deref for OpenedDriver {
+    type Target = Driver;
+    fn deref(&self) -> &Driver { &self.driver }
+}
+
+/// A streaming driver. Dropping it stops the stream and closes the device.
+pub struct RunningDriver {
+    driver: Driver,
+}
+
+impl RunningDriver {
+    pub fn stop(mut self) -> OpenedDriver {
+        self.driver.stop_raw();
+        OpenedDriver { driver: self.driver }
+    }
+    /// Like [`RunningDriver::stop`], but discards queued audio; see [`Driver::abort`].
+    pub fn abort(mut self) -> OpenedDriver {
+        self.driver.abort();
+        OpenedDriver { driver: self.driver }
+    }
+    pub fn xrun_stats(&self) -> XrunStats { self.driver.xrun_stats() }
+    /// False once the process callback returned `false` or the driver requested a reset.
+    pub fn is_alive(&self) -> bool { self.driver.is_alive() }
+}
+
+impl Deref for RunningDriver {
+    type Target = Driver;
+    fn deref(&self) -> &Driver { &self.driver }
+}
@@ -0,0 +1,84 @@
+//! Typed view of the `oa_result` codes drivers return.
+use openasio_sys as sys;
+use std::path::PathBuf;
+
+/// A negative `oa_result` from a driver call. Errors returned by [`crate::Driver`] carry one of
+/// these as their source; recover it with `err.downcast_ref::<DriverError>()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum DriverError {
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_GENERIC))]
+    Generic,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_UNSUPPORTED))]
+    Unsupported,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_INVALID_ARG))]
+    InvalidArg,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_DEVICE))]
+    Device,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_BACKEND))]
+    Backend,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_STATE))]
+    State,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_BUSY))]
+    Busy,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_TIMEOUT))]
+    Timeout,
+    #[error("{}", sys::oa_strerror(sys::OA_ERR_NOT_OPEN))]
+    NotOpen,
+    #[error("{} ({0})", sys::oa_strerror(*.0))]
+    Unknown(i32),
+}
+
+impl DriverError {
+    pub fn from_code(rc: i32) -> Self {
+        match rc {
+            sys::OA_ERR_GENERIC => Self::Generic,
+            sys::OA_ERR_UNSUPPORTED => Self::Unsupported,
+            sys::OA_ERR_INVALID_ARG => Self::InvalidArg,
+            sys::OA_ERR_DEVICE => Self::Device,
+            sys::OA_ERR_BACKEND => Self::Backend,
+            sys::OA_ERR_STATE => Self::State,
+            sys::OA_ERR_BUSY => Self::Busy,
+            sys::OA_ERR_TIMEOUT => Self::Timeout,
+            sys::OA_ERR_NOT_OPEN => Self::NotOpen,
+            other => Self::Unknown(other),
+        }
+    }
+    pub fn code(self) -> i32 {
+        match self {
+            Self::Generic => sys::OA_ERR_GENERIC,
+            Self::Unsupported => sys::OA_ERR_UNSUPPORTED,
+            Self::InvalidArg => sys::OA_ERR_INVALID_ARG,
+            Self::Device => sys::OA_ERR_DEVICE,
+            Self::Backend => sys::OA_ERR_BACKEND,
+            Self::State => sys::OA_ERR_STATE,
+            Self::Busy => sys::OA_ERR_BUSY,
+            Self::Timeout => sys::OA_ERR_TIMEOUT,
+            Self::NotOpen => sys::OA_ERR_NOT_OPEN,
+            Self::Unknown(rc) => rc,
+        }
+    }
+}
+
+/// `Ok` for non-negative results, otherwise a [`DriverError`] with `op` as context.
+pub(crate) fn check(op: &str, rc: i32) -> anyhow::Result<()> {
+    if rc >= 0 { return Ok(()); }
+    Err(anyhow::Error::new(DriverError::from_code(rc)).context(format!("{op} returned {}", sys::OaResult(rc))))
+}
+
+/// Why [`crate::Driver::load`] could not produce a driver library.
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    /// None of the candidate paths could be opened; `source` is the error for the path as given.
+    #[error("could not load driver library (tried {})", display_paths(.candidates))]
+    NotFound { candidates: Vec<PathBuf>, #[source] source: sys::loader::Error },
+    #[error("{} does not export `{symbol}`", .path.display())]
+    MissingSymbol { path: PathBuf, symbol: &'static str, #[source] source: sys::loader::Error },
+    /// `openasio_abi_version` reported `found`, whose major version this host cannot drive.
+    #[error("{} was built for OpenASIO ABI {}.{}; this host speaks {}.x", .path.display(), sys::oa_abi_split(*.found).0, sys::oa_abi_split(*.found).1, sys::OA_VERSION_MAJOR)]
+    AbiMismatch { path: PathBuf, found: u32 },
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+}
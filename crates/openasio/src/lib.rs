@@ -2,8 +2,50 @@
 use anyhow::{anyhow, Context, Result};
 use openasio_sys as sys;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod autotune;
+mod builder;
+mod error;
+mod stats;
+mod xruns;
+pub use autotune::AutoTune;
+pub use builder::{DriverBuilder, OpenedDriver, RunningDriver};
+pub use error::{DriverError, LoadError};
+pub use stats::Stats;
+pub use xruns::XrunReceiver;
+pub use sys::loader::DriverInfo;
+pub use sys::DriverFactory;
+
+/// Drivers linked into this build by the `static-*` features, for [`Driver::static_load`].
+#[cfg(feature = "static-link")]
+pub mod linked {
+    #[cfg(feature = "static-alsa17h")]
+    pub use openasio_driver_alsa17h::Alsa17h;
+    #[cfg(feature = "static-cpal")]
+    pub use openasio_driver_cpal::Cpal;
+    #[cfg(feature = "static-umc202hd")]
+    pub use openasio_driver_umc202hd::Umc202hd;
+}
+
+/// A 1.0 vtable entry through `oa_vtable_field!`, failing with [`DriverError::Unsupported`]
+/// rather than panicking when the driver left it null.
+macro_rules! required {
+    ($vt:expr, $field:ident) => {
+        sys::oa_vtable_field!($vt, $field)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context(concat!("driver has no ", stringify!($field))))
+    };
+}
+
+const EVENT_QUEUE_LEN: usize = 64;
+/// Events [`Driver::xrun_receiver`] holds; newer ones overwrite the oldest.
+pub const XRUN_QUEUE_LEN: usize = 64;
 
 #[derive(Clone, Copy, Debug)]
 pub struct StreamConfig {
@@ -12,22 +54,553 @@ pub struct StreamConfig {
     pub in_channels: u16,
     pub out_channels: u16,
     pub interleaved: bool,
+    /// Sample type of the buffers handed to `process`; check it against
+    /// [`SupportedConfigs::supports_format`]. Conventions are on [`sys::oa_sample_format`].
+    pub format: sys::oa_sample_format,
+    /// Frames of silence to output after each start before the host's `process` is first called,
+    /// so the device's first periods never carry stale data. Rounded up to whole callbacks.
+    pub priming_frames: u32,
+    /// Device channels to carry; `None` carries all of them. Only drivers for which
+    /// [`Driver::supports_channel_mask`] holds apply it: `process` then gets the enabled channels
+    /// packed in order and a config with their counts. Other drivers ignore it.
+    pub channel_mask: Option<ChannelMask>,
+    /// Require exclusive access to the hardware, bypassing sound servers and software mixers.
+    /// Drivers reporting `OA_CAP_EXCLUSIVE` fail the start with [`DriverError::Device`] when the
+    /// device is shared; [`Driver::last_error`] then starts with [`sys::OA_EXCLUSIVE_UNAVAILABLE`].
+    /// Other drivers ignore it.
+    pub exclusive: bool,
+}
+
+/// Channels enabled for a stream, bit n for device channel n.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelMask {
+    pub inputs: u64,
+    pub outputs: u64,
+}
+
+impl From<ChannelMask> for sys::oa_channel_mask {
+    fn from(m: ChannelMask) -> Self { sys::oa_channel_mask { in_mask: m.inputs, out_mask: m.outputs } }
+}
+
+impl Default for StreamConfig {
+    /// [`sys::oa_stream_config::default`]: 48 kHz, 128 frames, stereo in and out, interleaved f32.
+    fn default() -> Self { Self::from_raw(&sys::oa_stream_config::default(), 0) }
+}
+
+impl StreamConfig {
+    /// Unvalidated view of a driver config; used on the RT path, where nothing may fail or log.
+    /// Reads only the 1.0 fields, which every driver's config has.
+    fn from_raw(c: &sys::oa_stream_config, priming_frames: u32) -> Self {
+        Self {
+            sample_rate: c.sample_rate, buffer_frames: c.buffer_frames,
+            in_channels: c.in_channels, out_channels: c.out_channels,
+            interleaved: matches!(c.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED),
+            format: c.format,
+            priming_frames,
+            channel_mask: None,
+            exclusive: false,
+        }
+    }
+}
+
+impl From<&StreamConfig> for sys::oa_stream_config {
+    fn from(c: &StreamConfig) -> Self {
+        sys::oa_stream_config {
+            sample_rate: c.sample_rate,
+            buffer_frames: c.buffer_frames,
+            in_channels: c.in_channels,
+            out_channels: c.out_channels,
+            format: c.format,
+            layout: if c.interleaved { sys::oa_buffer_layout::OA_BUF_INTERLEAVED } else { sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED },
+            exclusive: if c.exclusive { sys::OA_TRUE } else { sys::OA_FALSE },
+        }
+    }
+}
+
+impl From<StreamConfig> for sys::oa_stream_config {
+    fn from(c: StreamConfig) -> Self { (&c).into() }
+}
+
+impl TryFrom<sys::oa_stream_config> for StreamConfig {
+    type Error = DriverError;
+    /// Validates with [`sys::oa_stream_config_check`].
+    fn try_from(c: sys::oa_stream_config) -> std::result::Result<Self, DriverError> {
+        let rc = sys::oa_stream_config_check(&c);
+        if rc != sys::OA_OK { return Err(DriverError::from_code(rc)); }
+        Ok(Self { exclusive: c.exclusive != sys::OA_FALSE, ..Self::from_raw(&c, 0) })
+    }
+}
+
+/// Rates, buffer range and formats reported by `query_supported_configs`.
+#[derive(Clone, Debug)]
+pub struct SupportedConfigs {
+    pub sample_rates: Vec<u32>,
+    pub min_buffer_frames: u32,
+    pub max_buffer_frames: u32,
+    pub formats: u32,
+}
+
+impl SupportedConfigs {
+    pub fn supports_format(&self, fmt: sys::oa_sample_format) -> bool { self.formats & sys::oa_format_bit(fmt) != 0 }
+}
+
+/// One combination of settings the open device accepts, from [`Driver::config_ranges`]. Every
+/// pairing of values within the ranges works.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigRange {
+    pub sample_rates: std::ops::RangeInclusive<u32>,
+    pub in_channels: std::ops::RangeInclusive<u32>,
+    pub out_channels: std::ops::RangeInclusive<u32>,
+    pub buffer_frames: std::ops::RangeInclusive<u32>,
+    pub formats: u32,
+}
+
+impl ConfigRange {
+    pub fn supports_format(&self, fmt: sys::oa_sample_format) -> bool { self.formats & sys::oa_format_bit(fmt) != 0 }
+    /// Whether `cfg`'s rate, channel counts, buffer size and format all fall in this range.
+    pub fn contains(&self, cfg: &StreamConfig) -> bool {
+        self.sample_rates.contains(&cfg.sample_rate)
+            && self.in_channels.contains(&cfg.in_channels.into())
+            && self.out_channels.contains(&cfg.out_channels.into())
+            && self.buffer_frames.contains(&cfg.buffer_frames)
+            && self.supports_format(cfg.format)
+    }
+}
+
+impl From<&sys::oa_config_range> for ConfigRange {
+    fn from(r: &sys::oa_config_range) -> Self {
+        Self {
+            sample_rates: r.min_rate..=r.max_rate,
+            in_channels: r.min_channels_in..=r.max_channels_in,
+            out_channels: r.min_channels_out..=r.max_channels_out,
+            buffer_frames: r.min_buffer_frames..=r.max_buffer_frames,
+            formats: r.formats_mask,
+        }
+    }
+}
+
+/// A device as described by [`Driver::device_info`], before it is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Name to pass to [`Driver::open_by_name`].
+    pub id: String,
+    pub description: String,
+    pub max_in_channels: u32,
+    pub max_out_channels: u32,
+    pub min_buffer_frames: u32,
+    pub max_buffer_frames: u32,
+}
+
+/// A clock the device can sync to, from [`Driver::clock_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockSource {
+    /// What [`Driver::set_clock_source`] takes.
+    pub index: u32,
+    pub name: String,
+    /// The device is syncing to it. Always false from drivers that only list names.
+    pub current: bool,
+}
+
+/// Live stream state as reported by [`Driver::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamStatus {
+    pub running: bool,
+    pub sample_rate: u32,
+    pub buffer_frames: u32,
+    /// Frames the driver has run through the host since the stream started.
+    pub frames_processed: u64,
+    pub underruns: u32,
+    pub overruns: u32,
+    /// Blocks that reached the device with its buffer nearly empty; see [`Stats::near_xruns`].
+    pub near_xrun_count: u32,
+    /// Captured frames queued for the next `process` calls, from drivers that buffer input.
+    pub input_queued_frames: u32,
+    /// Longest `process` call the driver timed since the stream started; 0 from drivers that don't.
+    pub max_callback_ns: u64,
 }
 
 pub trait HostProcess: Send {
     /// Called on the driver's RT thread. Must be RT-safe.
     fn process(&mut self, inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig) -> bool;
+    /// What the wrapper actually calls for each block. Override it instead of `process` (leaving
+    /// that a stub) to see the block's [`TimeInfo`]; the default forwards to `process`.
+    fn process_with_time(&mut self, inputs: *const c_void, outputs: *mut c_void, frames: u32, cfg: &StreamConfig, _time: &TimeInfo) -> bool {
+        self.process(inputs, outputs, frames, cfg)
+    }
+    /// Called once on the driver's RT thread, right before the first `process` call.
+    /// A good place for `rt::enable_flush_denormals()` and similar per-thread setup.
+    fn on_audio_thread_start(&mut self) {}
+    /// The driver asked the host to reset (e.g. its device was unplugged). Streaming has stopped;
+    /// restart it once [`Event::LatencyChanged`] reports the device is back.
+    fn reconnect_hint(&mut self) {}
+    /// Called on the RT thread when the driver reports an xrun, through its `xrun` callback or a
+    /// grown `underruns`/`overruns` counter; `total` is the new cumulative count. Must be cheap:
+    /// no locks, allocation or I/O.
+    fn on_xrun(&mut self, _kind: XrunKind, _total: u32) {}
+    /// Called from a non-RT thread when restarting or reconfiguring the stream fails inside an
+    /// automatic recovery path such as [`AutoTune::monitor`].
+    fn on_stream_error(&mut self, _msg: &str) {}
+    /// Called on the RT thread, right before the next `process`, after the devices
+    /// [`Driver::enumerate_devices`] reports have changed, e.g. an interface was plugged in.
+    /// Drivers signal that from their own threads, so a host that needs it while no stream runs
+    /// listens for [`Event::DevicesChanged`] instead. Must be cheap: no locks, allocation or I/O.
+    fn devices_changed(&mut self) {}
+    /// Called on the RT thread right before `process` with the MIDI the driver received during
+    /// the previous block, each event placed by its [`MidiEvent::sample_offset`] in the coming
+    /// one. Only drivers reporting `OA_CAP_MIDI` call it. Must be RT-safe.
+    fn midi_in(&mut self, _events: &[MidiEvent]) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XrunKind {
+    Underrun,
+    Overrun,
+    /// One `process` call ran past the limit set with the driver's `callback_threshold_ns`
+    /// option; see [`Stats::max_callback_ns`] for how long the worst one took.
+    SlowCallback,
+}
+
+/// Timing of one block, passed to [`HostProcess::process_with_time`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeInfo {
+    /// Host monotonic time the driver took for this block, in nanoseconds since the driver's origin.
+    pub host_time_ns: u64,
+    /// Device clock, or 0 when the driver has none.
+    pub device_time_ns: u64,
+    /// Frames the driver has passed to the host since the stream started, before this block,
+    /// priming included. From drivers without `OA_CAP_SAMPLE_POSITION` the wrapper counts them.
+    pub sample_position: u64,
+}
+
+/// One xrun, delivered through [`Driver::xrun_receiver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XrunEvent {
+    pub xrun_type: XrunKind,
+    /// When the host learned of it, in nanoseconds since the Unix epoch.
+    pub timestamp_ns: u64,
+    /// The driver's cumulative count for `xrun_type` in this stream.
+    pub total_count: u64,
+}
+
+/// One short MIDI message (no system exclusive), for [`HostProcess::midi_in`] and
+/// [`Driver::send_midi`].
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MidiEvent(sys::oa_midi_event);
+
+impl MidiEvent {
+    /// `bytes` at `sample_offset` frames into the block, or `None` unless it is one complete
+    /// message with a status byte.
+    pub fn new(sample_offset: u32, bytes: &[u8]) -> Option<Self> {
+        sys::oa_midi_event::new(sample_offset, bytes).map(Self)
+    }
+    pub fn sample_offset(&self) -> u32 { self.0.sample_offset }
+    pub fn bytes(&self) -> &[u8] { self.0.bytes() }
+}
+
+/// Helpers for preparing the audio thread and process.
+pub mod rt {
+    /// Set flush-to-zero and denormals-are-zero for the calling thread (no-op on other architectures).
+    pub fn enable_flush_denormals() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        unsafe {
+            const FTZ_DAZ: u32 = (1 << 15) | (1 << 6);
+            let mut csr: u32 = 0;
+            std::arch::asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack));
+            csr |= FTZ_DAZ;
+            std::arch::asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack));
+        }
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            const FZ: u64 = 1 << 24;
+            let mut fpcr: u64;
+            std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack));
+            fpcr |= FZ;
+            std::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack));
+        }
+    }
+
+    /// Lock current and future pages of the process into RAM so the audio path never page-faults.
+    #[cfg(target_os = "linux")]
+    pub fn lock_memory() -> anyhow::Result<()> {
+        let rc = unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) };
+        if rc != 0 { return Err(anyhow::anyhow!("mlockall: {}", std::io::Error::last_os_error())); }
+        Ok(())
+    }
+}
+
+/// Notifications delivered through [`Driver::subscribe_events`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// [`AutoTune`] restarted the stream with a larger buffer after repeated xruns.
+    BufferFramesChanged { from: u32, to: u32 },
+    /// The driver's device list changed; call [`Driver::enumerate_devices`] again.
+    DevicesChanged,
+    /// The driver reported an xrun; `total` is its cumulative count for `kind` in this stream.
+    Xrun { kind: XrunKind, total: u32 },
+    /// The driver's latency changed to `input`/`output` frames. After a
+    /// [`HostProcess::reconnect_hint`] this is the sign its device is back.
+    LatencyChanged { input: u32, output: u32 },
+}
+
+/// Why a stream stopped, as reported through [`StoppedNotifier`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// [`HostProcess::process`] returned `false`.
+    HostRequested,
+    /// [`HostProcess::process`] panicked; the payload message if it was a string.
+    Panic(String),
+    /// The stream was stopped through the API (`Stream::stop`, drop, `RunningDriver::stop`).
+    Api,
+    /// The driver called `process` with a format or layout outside the ABI; the raw values.
+    InvalidConfig { format: i32, layout: i32 },
+}
+
+/// Wakes a non-RT thread when the stream stops. Obtained from [`Driver::stopped_notifier`].
+pub struct StoppedNotifier {
+    rx: Receiver<StopReason>,
+}
+
+impl StoppedNotifier {
+    /// Block until the stream stops or `timeout` elapses (`None`).
+    pub fn wait(&self, timeout: Duration) -> Option<StopReason> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+    pub fn try_wait(&self) -> Option<StopReason> { self.rx.try_recv().ok() }
+}
+
+/// Cumulative xrun counters as last reported by the driver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct XrunStats {
+    pub underruns: u32,
+    pub overruns: u32,
 }
 
 struct HostThunk {
     inner: Box<dyn HostProcess>,
     cfg: sys::oa_stream_config,
+    thread_started: bool,
+    priming_frames: u32,
+    primed_frames: u32,
+    channel_mask: Option<ChannelMask>, // forwarded by the next start
+    alive: AtomicBool,
+    devices_changed: AtomicBool, // set from a driver thread, handed to `inner` on the RT thread
+    underruns: AtomicU32,
+    overruns: AtomicU32,
+    slow_callbacks: AtomicU32,
+    near_xruns: u32,
+    reads_near_xrun: bool, // the driver's oa_time_info has `near_xrun`
+    reads_sample_position: bool, // the driver reports OA_CAP_SAMPLE_POSITION
+    frames_seen: u64, // fallback sample position: frames through `cb_process` since start
+    events: Option<SyncSender<Event>>,
+    xruns: Option<Arc<xruns::XrunQueue>>,
+    stopped: Option<SyncSender<StopReason>>,
+    stats: Stats,
+    muted: bool, // set while probing: output silence without calling `inner`
+    #[cfg(feature = "tracing")]
+    trace: TraceSampler,
+}
+
+/// Emits one `trace!` per `every` process callbacks (0 disables).
+#[cfg(feature = "tracing")]
+struct TraceSampler {
+    every: u32,
+    blocks: u32,
+}
+
+#[cfg(feature = "tracing")]
+const DEFAULT_TRACE_EVERY: u32 = 1000;
+
+/// Run a vtable call inside a `vtable` span that records its return code.
+#[cfg(feature = "tracing")]
+fn traced<R: Copy + Into<i64>>(op: &'static str, call: impl FnOnce() -> R) -> R {
+    let span = tracing::debug_span!("vtable", op, rc = tracing::field::Empty);
+    let _enter = span.enter();
+    let rc = call();
+    span.record("rc", rc.into());
+    rc
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+fn traced<R>(_op: &'static str, call: impl FnOnce() -> R) -> R { call() }
+
+/// Size-then-fill negotiation for `query_supported_rates`, repeated if the list grows between calls.
+unsafe fn query_rates(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver) -> Result<Vec<u32>> {
+    let query = sys::oa_vtable_field!(vt, query_supported_rates)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("query_supported_rates not provided by driver"))?;
+    let mut rates = Vec::new();
+    loop {
+        let rc = traced("query_supported_rates", || query(drv, rates.as_mut_ptr(), rates.len()));
+        error::check("query_supported_rates", rc)?;
+        let n = rc as usize;
+        if n <= rates.len() { rates.truncate(n); return Ok(rates); }
+        rates.resize(n, 0);
+    }
+}
+
+/// Buffer handed to drivers that cannot size their device list.
+const LEGACY_DEVICE_LIST_LEN: usize = 16 * 1024;
+
+/// Size-then-fill negotiation for `query_config_ranges`, like [`query_rates`].
+unsafe fn query_config_ranges(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver) -> Result<Vec<ConfigRange>> {
+    let query = sys::oa_vtable_field!(vt, query_config_ranges)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("query_config_ranges not provided by driver"))?;
+    let mut ranges = Vec::new();
+    loop {
+        let rc = traced("query_config_ranges", || query(drv, ranges.as_mut_ptr(), ranges.len()));
+        error::check("query_config_ranges", rc)?;
+        let n = rc as usize;
+        if n <= ranges.len() { ranges.truncate(n); return Ok(ranges.iter().map(ConfigRange::from).collect()); }
+        ranges.resize(n, sys::oa_config_range::default());
+    }
+}
+
+/// `query_clock_sources`, or the names from `enumerate_clock_sources` for drivers predating it.
+unsafe fn query_clock_sources(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver) -> Result<Vec<ClockSource>> {
+    let Some(query) = sys::oa_vtable_field!(vt, query_clock_sources) else {
+        let names = query_clock_names(vt, drv)?;
+        return Ok(names.into_iter().enumerate().map(|(i, name)| ClockSource { index: i as u32, name, current: false }).collect());
+    };
+    let mut sources = Vec::new();
+    loop {
+        let rc = traced("query_clock_sources", || query(drv, sources.as_mut_ptr(), sources.len()));
+        error::check("query_clock_sources", rc)?;
+        let n = rc as usize;
+        if n <= sources.len() { sources.truncate(n); break; }
+        sources.resize(n, sys::oa_clock_source::default());
+    }
+    let text = |s: &[c_char]| CStr::from_bytes_until_nul(std::slice::from_raw_parts(s.as_ptr() as *const u8, s.len()))
+        .map(|c| c.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok(sources.iter().map(|s| ClockSource { index: s.index, name: text(&s.name), current: s.current != sys::OA_FALSE }).collect())
+}
+
+/// `get_channel_name` for index 0, 1, ... until the driver answers `OA_ERR_INVALID_ARG`.
+unsafe fn query_channel_names(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver, direction: sys::oa_direction) -> Result<Vec<String>> {
+    let get = sys::oa_vtable_field!(vt, get_channel_name)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("get_channel_name not provided by driver"))?;
+    let mut names = Vec::new();
+    let mut buf = vec![0u8; 64];
+    // The cap only guards against a driver that never reports the end.
+    for index in 0..sys::OA_MAX_CHANNELS as u32 {
+        let mut rc = traced("get_channel_name", || get(drv, direction, index, buf.as_mut_ptr() as *mut c_char, buf.len()));
+        if rc == sys::OA_ERR_INVALID_ARG { break; }
+        if rc >= 0 && rc as usize >= buf.len() {
+            buf.resize(rc as usize + 1, 0);
+            rc = traced("get_channel_name", || get(drv, direction, index, buf.as_mut_ptr() as *mut c_char, buf.len()));
+        }
+        error::check("get_channel_name", rc)?;
+        names.push(CStr::from_bytes_until_nul(&buf).map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
+    }
+    Ok(names)
+}
+
+unsafe fn query_clock_names(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver) -> Result<Vec<String>> {
+    let enumerate = sys::oa_vtable_field!(vt, enumerate_clock_sources)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("enumerate_clock_sources not provided by driver"))?;
+    let mut buf = vec![0u8; 1024];
+    loop {
+        let rc = traced("enumerate_clock_sources", || enumerate(drv, buf.as_mut_ptr() as *mut c_char, buf.len()));
+        error::check("enumerate_clock_sources", rc)?;
+        let list = CStr::from_bytes_until_nul(&buf).map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let names: Vec<String> = list.split('\n').filter(|s| !s.is_empty()).map(str::to_owned).collect();
+        // A full buffer means the list may have been cut; retry with more room.
+        if names.len() >= rc as usize || list.len() + 1 < buf.len() { return Ok(names); }
+        buf.resize(buf.len() * 2, 0);
+    }
+}
+
+unsafe fn query_device_info(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver, name: Option<&str>) -> Result<DeviceInfo> {
+    let query = sys::oa_vtable_field!(vt, query_device_info)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("query_device_info not provided by driver"))?;
+    let cname = name.map(CString::new).transpose().context("device name contains NUL")?;
+    let mut info = sys::oa_device_info::default();
+    let rc = traced("query_device_info", || query(drv, cname.as_ref().map_or(std::ptr::null(), |c| c.as_ptr()), &mut info));
+    error::check("query_device_info", rc).with_context(|| format!("device {name:?}"))?;
+    let text = |s: &[c_char]| unsafe { CStr::from_ptr(s.as_ptr()) }.to_string_lossy().into_owned();
+    Ok(DeviceInfo {
+        id: text(&info.id), description: text(&info.description),
+        max_in_channels: info.max_in_channels, max_out_channels: info.max_out_channels,
+        min_buffer_frames: info.min_buffer_frames, max_buffer_frames: info.max_buffer_frames,
+    })
+}
+
+unsafe fn query_status(vt: &sys::oa_driver_vtable, drv: *mut sys::oa_driver) -> Result<StreamStatus> {
+    let get = sys::oa_vtable_field!(vt, get_status)
+        .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("get_status not provided by driver"))?;
+    let mut status = sys::oa_stream_status::default();
+    let rc = traced("get_status", || get(drv, &mut status));
+    error::check("get_status", rc)?;
+    Ok(StreamStatus {
+        running: status.running != sys::OA_FALSE, sample_rate: status.actual_sample_rate, buffer_frames: status.actual_buffer_frames,
+        frames_processed: status.frames_processed, underruns: status.underruns, overruns: status.overruns,
+        near_xrun_count: status.near_xrun_count, input_queued_frames: status.input_queued_frames,
+        max_callback_ns: status.max_callback_ns,
+    })
+}
+
+impl HostThunk {
+    /// Mark the stream dead and report `reason` the first time only. Never blocks.
+    fn signal_stop(&self, reason: StopReason) {
+        if !self.alive.swap(false, Ordering::AcqRel) { return; }
+        if let Some(tx) = &self.stopped { let _ = tx.try_send(reason); }
+    }
+    fn emit(&self, ev: Event) {
+        if let Some(tx) = &self.events { let _ = tx.try_send(ev); }
+    }
+    /// Report a driver xrun total once: drivers with an `xrun` callback pass the same count again
+    /// in the next `oa_time_info`.
+    fn note_xrun(&mut self, kind: XrunKind, total: u32) {
+        let counter = match kind { XrunKind::Underrun => &self.underruns, XrunKind::Overrun => &self.overruns, XrunKind::SlowCallback => &self.slow_callbacks };
+        let prev = counter.fetch_max(total, Ordering::Relaxed);
+        if total <= prev { return; }
+        match kind {
+            XrunKind::Underrun => self.stats.add_underruns(total - prev),
+            XrunKind::Overrun => self.stats.add_overruns(total - prev),
+            XrunKind::SlowCallback => self.stats.add_slow_callbacks(total - prev),
+        }
+        self.inner.on_xrun(kind, total);
+        self.emit(Event::Xrun { kind, total });
+        if let Some(queue) = &self.xruns {
+            queue.force_push(XrunEvent { xrun_type: kind, timestamp_ns: sys::realtime_ns(), total_count: total.into() });
+        }
+    }
+}
+
+/// Host-side mirror of the driver lifecycle, so out-of-order calls fail before reaching the driver.
+struct PhaseGuard {
+    phase: sys::DriverPhase,
+    paused: Option<Pause>,
+}
+
+/// How a paused stream was paused, so `resume` undoes the same thing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pause {
+    /// Through the driver's `pause`; the driver is still `Running`.
+    Driver,
+    /// The driver has no `pause`, so it was stopped and `resume` starts it again.
+    Stopped,
+}
+
+impl PhaseGuard {
+    fn check(&self, ok: bool, op: &str) -> Result<()> {
+        if ok { return Ok(()); }
+        Err(anyhow::Error::new(DriverError::State).context(format!("{op} called while {:?}", self.phase)))
+    }
+    /// [`PhaseGuard::check`] for calls that need a stopped, open device; before `open_device`
+    /// they fail with [`DriverError::NotOpen`].
+    fn check_start(&self, op: &str) -> Result<()> {
+        if self.phase.can_start() { return Ok(()); }
+        Err(anyhow::Error::new(DriverError::from_code(self.phase.start_error())).context(format!("{op} called while {:?}", self.phase)))
+    }
 }
 
 pub struct Driver {
     _lib: sys::loader::DriverLib,
     drv: NonNull<sys::oa_driver>,
     _host_thunk: Box<HostThunk>,
+    phase: PhaseGuard,
 }
 
 unsafe extern "C" fn cb_process(
@@ -35,84 +608,1463 @@ unsafe extern "C" fn cb_process(
     in_ptr: *const c_void,
     out_ptr: *mut c_void,
     frames: u32,
-    _time: *const sys::oa_time_info,
+    time: *const sys::oa_time_info,
     cfg: *const sys::oa_stream_config,
 ) -> i32 {
     let ctx = &mut *(user as *mut HostThunk);
-    let cfg_rust = StreamConfig {
-        sample_rate: (*cfg).sample_rate,
-        buffer_frames: (*cfg).buffer_frames,
-        in_channels: (*cfg).in_channels,
-        out_channels: (*cfg).out_channels,
-        interleaved: matches!((*cfg).layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED),
+    // Without a known format and layout the buffers cannot even be silenced.
+    if !(*cfg).format.is_known() || !(*cfg).layout.is_known() {
+        ctx.signal_stop(StopReason::InvalidConfig { format: (*cfg).format.0, layout: (*cfg).layout.0 });
+        return sys::OA_FALSE;
+    }
+    let mut timing = TimeInfo { sample_position: ctx.frames_seen, ..Default::default() };
+    ctx.frames_seen += u64::from(frames);
+    if ctx.muted {
+        write_silence(out_ptr, frames, &*cfg);
+        return sys::OA_TRUE;
+    }
+    if !time.is_null() {
+        timing.host_time_ns = (*time).host_time_ns;
+        timing.device_time_ns = (*time).device_time_ns;
+        if ctx.reads_sample_position { timing.sample_position = (*time).sample_position; }
+        ctx.note_xrun(XrunKind::Underrun, (*time).underruns);
+        ctx.note_xrun(XrunKind::Overrun, (*time).overruns);
+        if ctx.reads_near_xrun && (*time).near_xrun > ctx.near_xruns {
+            ctx.stats.add_near_xruns((*time).near_xrun - ctx.near_xruns);
+            ctx.near_xruns = (*time).near_xrun;
+        }
+    }
+    if ctx.primed_frames < ctx.priming_frames {
+        ctx.primed_frames = ctx.primed_frames.saturating_add(frames);
+        write_silence(out_ptr, frames, &*cfg);
+        return sys::OA_TRUE;
+    }
+    if !ctx.alive.load(Ordering::Acquire) {
+        write_silence(out_ptr, frames, &*cfg);
+        return sys::OA_FALSE;
+    }
+    if !ctx.thread_started {
+        ctx.thread_started = true;
+        ctx.inner.on_audio_thread_start();
+    }
+    if ctx.devices_changed.swap(false, Ordering::Acquire) { ctx.inner.devices_changed(); }
+    #[cfg(feature = "tracing")]
+    {
+        let t = &mut ctx.trace;
+        t.blocks = t.blocks.wrapping_add(1);
+        if t.every != 0 && t.blocks.is_multiple_of(t.every) {
+            tracing::trace!(frames, underruns = ctx.underruns.load(Ordering::Relaxed), overruns = ctx.overruns.load(Ordering::Relaxed), "process");
+        }
+    }
+    let cfg_rust = StreamConfig::from_raw(&*cfg, ctx.priming_frames);
+    let inner = &mut ctx.inner;
+    let began = Instant::now();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| inner.process_with_time(in_ptr, out_ptr, frames, &cfg_rust, &timing)));
+    ctx.stats.record_callback(began.elapsed());
+    match result {
+        Ok(true) => sys::OA_TRUE,
+        Ok(false) => {
+            ctx.signal_stop(StopReason::HostRequested);
+            sys::OA_FALSE
+        }
+        Err(payload) => {
+            let msg = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            write_silence(out_ptr, frames, &*cfg);
+            ctx.signal_stop(StopReason::Panic(msg));
+            sys::OA_FALSE
+        }
+    }
+}
+fn open_library(path: &Path) -> std::result::Result<(sys::loader::Library, PathBuf), LoadError> {
+    unsafe { sys::loader::open(path) }.map_err(|source| {
+        let candidates = if path.exists() { vec![path.to_path_buf()] } else { sys::loader::candidate_paths(path) };
+        LoadError::NotFound { candidates, source }
+    })
+}
+
+fn check_symbols(lib: &sys::loader::Library, path: &Path) -> std::result::Result<(), LoadError> {
+    for (symbol, raw) in [("openasio_driver_create", sys::loader::CREATE_SYMBOL), ("openasio_driver_destroy", sys::loader::DESTROY_SYMBOL)] {
+        if let Err(source) = unsafe { lib.get::<*const c_void>(raw) } {
+            return Err(LoadError::MissingSymbol { path: path.to_path_buf(), symbol, source });
+        }
+    }
+    Ok(())
+}
+
+fn check_abi(lib: &sys::loader::Library, path: &Path) -> std::result::Result<(), LoadError> {
+    match unsafe { sys::loader::abi_version(lib) } {
+        Some(found) if sys::oa_abi_split(found).0 != sys::OA_VERSION_MAJOR => Err(LoadError::AbiMismatch { path: path.to_path_buf(), found }),
+        _ => Ok(()),
+    }
+}
+
+/// Zero `frames` of output in any format and either layout (non-interleaved `out` is an array of channel pointers).
+unsafe fn write_silence(out: *mut c_void, frames: u32, cfg: &sys::oa_stream_config) {
+    if out.is_null() { return; }
+    let (frames, ch, bytes) = (frames as usize, cfg.out_channels as usize, cfg.format.bytes_per_sample());
+    if matches!(cfg.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED) {
+        std::ptr::write_bytes(out as *mut u8, 0, frames * ch * bytes);
+    } else {
+        for &plane in std::slice::from_raw_parts(out as *const *mut u8, ch) {
+            if !plane.is_null() { std::ptr::write_bytes(plane, 0, frames * bytes); }
+        }
+    }
+}
+
+/// Drivers call it from their reconnect thread, so like `cb_device_list_changed` it only emits.
+unsafe extern "C" fn cb_latency_changed(user: *mut c_void, input: u32, output: u32) {
+    let ctx = &*(user as *const HostThunk);
+    ctx.emit(Event::LatencyChanged { input, output });
+}
+/// Default driver log sink: forwards driver messages to the `log` crate under `openasio::driver`.
+unsafe extern "C" fn cb_log(_user: *mut c_void, level: u32, msg: *const c_char) {
+    if msg.is_null() { return; }
+    let level = match level {
+        sys::OA_LOG_ERROR => log::Level::Error,
+        sys::OA_LOG_WARN => log::Level::Warn,
+        sys::OA_LOG_INFO => log::Level::Info,
+        sys::OA_LOG_DEBUG => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+    log::log!(target: "openasio::driver", level, "{}", CStr::from_ptr(msg).to_string_lossy());
+}
+unsafe extern "C" fn cb_reset_request(user: *mut c_void) {
+    let ctx = &mut *(user as *mut HostThunk);
+    ctx.alive.store(false, Ordering::Release);
+    ctx.inner.reconnect_hint();
+}
+unsafe extern "C" fn cb_xrun(user: *mut c_void, kind: sys::oa_xrun_kind, count: u32) {
+    let ctx = &mut *(user as *mut HostThunk);
+    let kind = match kind {
+        sys::oa_xrun_kind::OA_XRUN_UNDERRUN => XrunKind::Underrun,
+        sys::oa_xrun_kind::OA_XRUN_OVERRUN => XrunKind::Overrun,
+        sys::oa_xrun_kind::OA_XRUN_CALLBACK => XrunKind::SlowCallback,
+        _ => return,
     };
-    if ctx.inner.process(in_ptr, out_ptr, frames, &cfg_rust) { sys::OA_TRUE } else { sys::OA_FALSE }
+    ctx.note_xrun(kind, count);
+}
+unsafe extern "C" fn cb_midi_in(user: *mut c_void, events: *const sys::oa_midi_event, count: u32) {
+    if events.is_null() || count == 0 { return; }
+    let ctx = &mut *(user as *mut HostThunk);
+    // MidiEvent is a transparent wrapper, so the driver's array is used in place.
+    ctx.inner.midi_in(std::slice::from_raw_parts(events as *const MidiEvent, count as usize));
+}
+/// Runs on a driver thread while `process` may be running, so it only touches the thunk's
+/// thread-safe parts and leaves `inner` to the RT thread.
+unsafe extern "C" fn cb_device_list_changed(user: *mut c_void) {
+    let ctx = &*(user as *const HostThunk);
+    ctx.devices_changed.store(true, Ordering::Release);
+    ctx.emit(Event::DevicesChanged);
 }
-unsafe extern "C" fn cb_latency_changed(_user: *mut c_void, _in: u32, _out: u32) {}
-unsafe extern "C" fn cb_reset_request(_user: *mut c_void) {}
 
 impl Driver {
-    pub fn load(path: &str, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool) -> Result<Self> {
+    /// Load a driver library. If there is no file at `path`, the platform's decorated names are
+    /// tried too (`libfoo.so`, `foo.dll`, ...; see [`sys::loader::candidate_filenames`]).
+    /// Failures carry a [`LoadError`].
+    pub fn load(path: impl AsRef<Path>, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool) -> Result<Self> {
+        Self::load_with_hints(path.as_ref(), host, default_cfg, interleaved, sys::ThreadHints::default(), &sys::CreateOptions::default())
+    }
+    pub(crate) fn load_with_hints(path: &Path, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool, hints: sys::ThreadHints, options: &sys::CreateOptions) -> Result<Self> {
+        let (lib, loaded_from) = open_library(path)?;
+        check_symbols(&lib, &loaded_from)?;
+        check_abi(&lib, &loaded_from)?;
+        let lib = unsafe { sys::loader::DriverLib::from_library(lib) }.context("resolving OpenASIO entry points")?;
+        log::info!("loaded OpenASIO driver from {}", loaded_from.display());
+        Self::create(lib, host, default_cfg, interleaved, hints, options)
+    }
+    /// Create a driver from a library the application loaded itself. The `Library` is moved
+    /// into the returned `Driver` and stays loaded until the driver is dropped.
+    pub fn load_from_library(lib: sys::loader::Library, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool) -> Result<Self> {
+        Self::load_from_library_with_hints(lib, host, default_cfg, interleaved, sys::ThreadHints::default(), &sys::CreateOptions::default())
+    }
+    pub(crate) fn load_from_library_with_hints(lib: sys::loader::Library, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool, hints: sys::ThreadHints, options: &sys::CreateOptions) -> Result<Self> {
+        let lib = unsafe { sys::loader::DriverLib::from_library(lib) }.context("resolving OpenASIO entry points")?;
+        Self::create(lib, host, default_cfg, interleaved, hints, options)
+    }
+    /// Create a driver linked into the host, e.g. one of [`linked`], calling its entry points
+    /// directly instead of going through dlopen.
+    #[cfg(feature = "static-link")]
+    pub fn static_load<F: DriverFactory>(host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool) -> Result<Self> {
+        log::info!("using statically linked OpenASIO driver {}", std::any::type_name::<F>());
+        Self::create(sys::loader::DriverLib::from_factory::<F>(), host, default_cfg, interleaved, sys::ThreadHints::default(), &sys::CreateOptions::default())
+    }
+    /// `options` reach the driver as `oa_create_params.options`, or null when there are none.
+    fn create(lib: sys::loader::DriverLib, host: Box<dyn HostProcess>, default_cfg: StreamConfig, interleaved: bool, hints: sys::ThreadHints, options: &sys::CreateOptions) -> Result<Self> {
+        let options = if options.is_empty() { None } else { Some(CString::new(options.to_string()).context("create options contain a NUL byte")?) };
         unsafe {
-            let lib = sys::loader::DriverLib::load(path).with_context(|| format!("dlopen({path})"))?;
             let mut drv_ptr: *mut sys::oa_driver = std::ptr::null_mut();
-            let callbacks = sys::oa_host_callbacks { process: Some(cb_process), latency_changed: Some(cb_latency_changed), reset_request: Some(cb_reset_request) };
+            let callbacks = sys::oa_host_callbacks { process: Some(cb_process), latency_changed: Some(cb_latency_changed), reset_request: Some(cb_reset_request), device_list_changed: Some(cb_device_list_changed), xrun: Some(cb_xrun), midi_in: Some(cb_midi_in) };
             let mut host_thunk = Box::new(HostThunk{
                 inner: host,
-                cfg: sys::oa_stream_config{
-                    sample_rate: default_cfg.sample_rate,
-                    buffer_frames: default_cfg.buffer_frames,
-                    in_channels: default_cfg.in_channels,
-                    out_channels: default_cfg.out_channels,
-                    format: sys::oa_sample_format::OA_SAMPLE_F32,
-                    layout: if interleaved { sys::oa_buffer_layout::OA_BUF_INTERLEAVED } else { sys::oa_buffer_layout::OA_BUF_NONINTERLEAVED },
-                },
+                cfg: StreamConfig { interleaved, ..default_cfg }.into(),
+                thread_started: false,
+                priming_frames: default_cfg.priming_frames,
+                primed_frames: 0,
+                channel_mask: None,
+                alive: AtomicBool::new(false),
+                devices_changed: AtomicBool::new(false),
+                underruns: AtomicU32::new(0),
+                slow_callbacks: AtomicU32::new(0),
+                overruns: AtomicU32::new(0),
+                near_xruns: 0,
+                reads_near_xrun: false,
+                reads_sample_position: false,
+                frames_seen: 0,
+                events: None,
+                xruns: None,
+                stopped: None,
+                stats: Stats::default(),
+                muted: false,
+                #[cfg(feature = "tracing")]
+                trace: TraceSampler { every: DEFAULT_TRACE_EVERY, blocks: 0 },
             });
-            let params = sys::oa_create_params{ struct_size: std::mem::size_of::<sys::oa_create_params>() as u32, host: &callbacks, host_user: (&mut *host_thunk) as *mut _ as *mut c_void };
+            let params = sys::oa_create_params { log_cb: Some(cb_log), rt_priority: hints.rt_priority, cpu_affinity_mask: hints.cpu_affinity_mask, options: options.as_ref().map_or(std::ptr::null(), |o| o.as_ptr()), ..sys::oa_create_params::new(&callbacks, (&mut *host_thunk) as *mut _ as *mut c_void) };
             let rc = (lib.create)(&params as *const _, &mut drv_ptr as *mut _);
-            if rc < 0 || drv_ptr.is_null(){ return Err(anyhow!("openasio_driver_create rc={rc}")); }
-            Ok(Self{ _lib: lib, drv: NonNull::new(drv_ptr).unwrap(), _host_thunk: host_thunk })
+            error::check("openasio_driver_create", rc)?;
+            if drv_ptr.is_null() { return Err(anyhow!("openasio_driver_create returned a null driver")); }
+            let vt_size = (*(*drv_ptr).vt).struct_size;
+            if !sys::struct_has(vt_size, 0, sys::OA_VTABLE_V1_SIZE) {
+                (lib.destroy)(drv_ptr);
+                return Err(anyhow::Error::new(DriverError::Unsupported).context(format!("driver vtable is {vt_size} bytes, shorter than the 1.0 table")));
+            }
+            host_thunk.reads_near_xrun = sys::time_info_has_near_xrun(&*(*drv_ptr).vt);
+            let caps = sys::OaCaps::from(sys::oa_vtable_field!(&*(*drv_ptr).vt, get_caps).map_or(0, |get_caps| get_caps(drv_ptr)));
+            host_thunk.reads_sample_position = caps.contains(sys::OaCaps::SAMPLE_POSITION);
+            Ok(Self{ _lib: lib, drv: NonNull::new(drv_ptr).unwrap(), _host_thunk: host_thunk, phase: PhaseGuard { phase: sys::DriverPhase::Created, paused: None } })
         }
     }
-    pub fn caps(&self) -> u32 {
-        unsafe { let vt = &*(*self.drv.as_ptr()).vt; (vt.get_caps.unwrap())(self.drv.as_ptr()) }
+    /// Whether [`StreamConfig::channel_mask`] is applied: the driver reports
+    /// `OA_CAP_CHANNEL_MASK` and has the `set_channel_mask` entry.
+    pub fn supports_channel_mask(&self) -> bool {
+        self.caps().contains(sys::OaCaps::CHANNEL_MASK) && sys::oa_vtable_field!(self.vtable(), set_channel_mask).is_some()
+    }
+    /// What the driver's `get_caps` reports; empty for a driver without it.
+    pub fn caps(&self) -> sys::OaCaps {
+        let bits = sys::oa_vtable_field!(self.vtable(), get_caps).map_or(0, |get_caps| traced("get_caps", || unsafe { get_caps(self.drv.as_ptr()) }));
+        sys::OaCaps::from(bits)
     }
+    /// The names `query_devices` lists. The list is sized with an empty buffer first and asked
+    /// for again until it fits, so a long or growing list is never cut short.
     pub fn enumerate_devices(&self) -> Result<Vec<String>> {
         unsafe {
             let vt = &*(*self.drv.as_ptr()).vt;
-            let mut buf = vec![0u8; 16*1024];
-            let rc = (vt.query_devices.unwrap())(self.drv.as_ptr(), buf.as_mut_ptr() as *mut i8, buf.len());
-            if rc < 0 { return Err(anyhow!("query_devices rc={rc}")); }
-            let list = CStr::from_ptr(buf.as_ptr() as *const i8).to_string_lossy().to_string();
-            Ok(list.lines().map(|s| s.to_string()).collect())
+            let query_devices = required!(vt, query_devices)?;
+            let mut buf = Vec::new();
+            loop {
+                let rc = traced("query_devices", || query_devices(self.drv.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len()));
+                error::check("query_devices", rc)?;
+                match rc as usize {
+                    // Drivers from before the sizing contract answer an empty buffer with OA_OK.
+                    0 if buf.is_empty() => buf.resize(LEGACY_DEVICE_LIST_LEN, 0),
+                    0 => break,
+                    need => buf.resize(need, 0),
+                }
+            }
+            let list = CStr::from_bytes_until_nul(&buf).context("query_devices reply is not terminated")?.to_string_lossy().into_owned();
+            let devices: Vec<String> = list.lines().map(|s| s.to_string()).collect();
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?devices, "enumerated devices");
+            Ok(devices)
         }
     }
     pub fn open_default(&mut self) -> Result<()> { self.open_by_name(None) }
     pub fn open_by_name(&mut self, name: Option<&str>) -> Result<()> {
+        self.phase.check(self.phase.phase == sys::DriverPhase::Created, "open_device")?;
         unsafe {
             let vt = &*(*self.drv.as_ptr()).vt;
-            let c = name.map(|s| CString::new(s).unwrap());
+            let c = name.map(CString::new).transpose()
+                .map_err(|_| anyhow::Error::new(DriverError::InvalidArg).context("device name contains NUL"))?;
             let ptr = c.as_ref().map(|c| c.as_ptr()).unwrap_or(std::ptr::null());
-            let rc = (vt.open_device.unwrap())(self.drv.as_ptr(), ptr);
-            if rc < 0 { return Err(anyhow!("open_device rc={rc}")); }
+            let open_device = required!(vt, open_device)?;
+            let rc = traced("open_device", || open_device(self.drv.as_ptr(), ptr));
+            self.check("open_device", rc)?;
+            self.phase.phase = sys::DriverPhase::Opened;
             Ok(())
         }
     }
+    /// Open `preferred`, falling back to the first enumerated device and then to `"default"`.
+    /// Returns the name that opened; each fallback is logged at warn level.
+    pub fn open_with_fallback(&mut self, preferred: Option<&str>) -> Result<String> {
+        self.phase.check(self.phase.phase == sys::DriverPhase::Created, "open_device")?;
+        let first = match self.enumerate_devices() {
+            Ok(devices) => devices.into_iter().next(),
+            Err(e) => { log::warn!("open_with_fallback: cannot enumerate devices: {e:#}"); None }
+        };
+        let mut candidates: Vec<String> = Vec::new();
+        for name in [preferred.map(str::to_owned), first, Some("default".to_owned())].into_iter().flatten() {
+            if !candidates.contains(&name) { candidates.push(name); }
+        }
+        let mut last = None;
+        for name in candidates {
+            if let Some(e) = &last { log::warn!("open_with_fallback: {e:#}; trying {name:?}"); }
+            match self.open_by_name(Some(&name)) {
+                Ok(()) => return Ok(name),
+                Err(e) => last = Some(e.context(format!("opening {name:?}"))),
+            }
+        }
+        Err(last.expect("\"default\" is always a candidate"))
+    }
     pub fn default_config(&self) -> Result<StreamConfig> {
         unsafe {
             let vt = &*(*self.drv.as_ptr()).vt;
-            let mut c = std::mem::MaybeUninit::<sys::oa_stream_config>::uninit();
-            let rc = (vt.get_default_config.unwrap())(self.drv.as_ptr(), c.as_mut_ptr());
-            if rc < 0 { return Err(anyhow!("get_default_config rc={rc}")); }
+            let mut c = sys::oa_stream_config::zeroed();
+            let get_default_config = required!(vt, get_default_config)?;
+            let rc = traced("get_default_config", || get_default_config(self.drv.as_ptr(), &mut c));
+            error::check("get_default_config", rc)?;
+            if !c.format.is_known() || !c.layout.is_known() {
+                return Err(anyhow::Error::new(DriverError::InvalidArg)
+                    .context(format!("driver default config has {} and {}", c.format, c.layout)));
+            }
+            Ok(StreamConfig::from_raw(&c, 0))
+        }
+    }
+    fn vtable(&self) -> &sys::oa_driver_vtable { unsafe { &*(*self.drv.as_ptr()).vt } }
+    pub fn supported_configs(&self) -> Result<SupportedConfigs> {
+        unsafe {
+            let query = sys::oa_vtable_field!(self.vtable(), query_supported_configs).ok_or_else(|| anyhow!("query_supported_configs not supported by driver"))?;
+            let mut c = std::mem::MaybeUninit::<sys::oa_supported_configs>::zeroed();
+            let rc = traced("query_supported_configs", || query(self.drv.as_ptr(), c.as_mut_ptr()));
+            error::check("query_supported_configs", rc)?;
             let c = c.assume_init();
-            Ok(StreamConfig{
-                sample_rate: c.sample_rate, buffer_frames: c.buffer_frames,
-                in_channels: c.in_channels, out_channels: c.out_channels,
-                interleaved: matches!(c.layout, sys::oa_buffer_layout::OA_BUF_INTERLEAVED),
-            })
+            let sample_rates = if c.sample_rates.is_null() { Vec::new() } else { std::slice::from_raw_parts(c.sample_rates, c.sample_rate_count as usize).to_vec() };
+            Ok(SupportedConfigs{ sample_rates, min_buffer_frames: c.min_buffer_frames, max_buffer_frames: c.max_buffer_frames, formats: c.formats })
+        }
+    }
+    /// Which of `candidates` the open device accepts. Uses `query_supported_configs` when the
+    /// driver has it; otherwise each rate is tried with a short silent start/stop (the host's
+    /// `process` is not called), so this briefly opens the device. Must be called while stopped;
+    /// the previous stream config is restored afterwards.
+    pub fn probe_sample_rates(&mut self, candidates: &[u32]) -> Result<Vec<u32>> {
+        if sys::oa_vtable_field!(self.vtable(), query_supported_configs).is_some() {
+            let supported = self.supported_configs()?;
+            return Ok(candidates.iter().copied().filter(|r| supported.sample_rates.contains(r)).collect());
+        }
+        self.phase.check_start("probe_sample_rates")?;
+        let saved = self._host_thunk.cfg;
+        let notifier = self._host_thunk.stopped.take();
+        self._host_thunk.muted = true;
+        let mut accepted = Vec::new();
+        for &rate in candidates {
+            self._host_thunk.cfg = sys::oa_stream_config { sample_rate: rate, ..saved };
+            match self.start_raw() {
+                Ok(()) => { self.stop_raw(); accepted.push(rate); }
+                Err(e) => log::debug!("probe: {rate} Hz rejected: {e:#}"),
+            }
         }
+        self._host_thunk.muted = false;
+        self._host_thunk.stopped = notifier;
+        self._host_thunk.cfg = saved;
+        Ok(accepted)
+    }
+    /// Sample rates the open device accepts, via `query_supported_rates`. Drivers built before
+    /// that entry existed yield [`DriverError::Unsupported`].
+    pub fn supported_sample_rates(&self) -> Result<Vec<u32>> {
+        unsafe { query_rates(self.vtable(), self.drv.as_ptr()) }
+    }
+    /// Every combination of rate, channel counts, buffer size and format the open device accepts,
+    /// via `query_config_ranges`; [`Driver::supported_configs`] only has the rates and buffer
+    /// range. Drivers without that entry yield [`DriverError::Unsupported`].
+    pub fn config_ranges(&self) -> Result<Vec<ConfigRange>> {
+        unsafe { query_config_ranges(self.vtable(), self.drv.as_ptr()) }
+    }
+    /// Whether the driver is actually streaming, at what rate and period, and how far it got.
+    /// Drivers without `get_status` yield [`DriverError::Unsupported`].
+    pub fn status(&self) -> Result<StreamStatus> {
+        unsafe { query_status(self.vtable(), self.drv.as_ptr()) }
+    }
+    /// Name, vendor and version the library reports through `openasio_driver_get_info`.
+    /// Libraries without that export yield [`DriverError::Unsupported`].
+    pub fn info(&self) -> Result<DriverInfo> {
+        self._lib.info().ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("openasio_driver_get_info not exported by driver"))
+    }
+    /// The library's `openasio_abi_version` as `(major, minor)`; `None` for 1.0 drivers that
+    /// predate the export.
+    pub fn abi_version(&self) -> Option<(u32, u32)> { self._lib.abi_version().map(sys::oa_abi_split) }
+    /// Channel counts and buffer range of device `name` (`None` = the open or default device).
+    pub fn device_info(&self, name: Option<&str>) -> Result<DeviceInfo> {
+        unsafe { query_device_info(self.vtable(), self.drv.as_ptr(), name) }
+    }
+    /// Clock sources the device can sync to (internal, S/PDIF, word clock, ...). Drivers with
+    /// `query_clock_sources` always list at least their internal clock; older ones fall back to
+    /// `enumerate_clock_sources`, and without either yield [`DriverError::Unsupported`].
+    pub fn clock_sources(&self) -> Result<Vec<ClockSource>> {
+        unsafe { query_clock_sources(self.vtable(), self.drv.as_ptr()) }
+    }
+    /// Sync to the source with `index` from [`Driver::clock_sources`]. Must be called while
+    /// stopped. Drivers without `set_clock_source` are driven through `select_clock_source`.
+    pub fn set_clock_source(&mut self, index: u32) -> Result<()> {
+        self.phase.check(self.phase.phase != sys::DriverPhase::Running, "set_clock_source")?;
+        let Some(set) = sys::oa_vtable_field!(self.vtable(), set_clock_source) else {
+            let name = self.clock_sources()?.into_iter().find(|s| s.index == index).map(|s| s.name)
+                .ok_or_else(|| anyhow::Error::new(DriverError::InvalidArg).context(format!("no clock source #{index}")))?;
+            return self.select_clock_source(&name);
+        };
+        let rc = traced("set_clock_source", || unsafe { set(self.drv.as_ptr(), index) });
+        error::check("set_clock_source", rc)
+    }
+    /// Sync to `name`, one of [`Driver::clock_sources`]. Must be called while stopped.
+    pub fn select_clock_source(&mut self, name: &str) -> Result<()> {
+        self.phase.check(self.phase.phase != sys::DriverPhase::Running, "select_clock_source")?;
+        let select = sys::oa_vtable_field!(self.vtable(), select_clock_source)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("select_clock_source not provided by driver"))?;
+        let name = CString::new(name).context("clock source name contains NUL")?;
+        let rc = traced("select_clock_source", || unsafe { select(self.drv.as_ptr(), name.as_ptr()) });
+        error::check("select_clock_source", rc)
+    }
+    /// Turn the driver's capture DC blocker on or off. Allowed while running; drivers without one
+    /// yield [`DriverError::Unsupported`].
+    pub fn enable_dc_filter(&mut self, enable: bool) -> Result<()> {
+        let set = sys::oa_vtable_field!(self.vtable(), enable_dc_filter)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("enable_dc_filter not provided by driver"))?;
+        let rc = traced("enable_dc_filter", || unsafe { set(self.drv.as_ptr(), enable as sys::oa_bool) });
+        error::check("enable_dc_filter", rc)
+    }
+    /// Linear trim gain for input channel `ch`, applied by the driver from the next period.
+    /// Clamped to [0.0, 4.0]; allowed while running.
+    pub fn set_input_gain(&self, ch: usize, gain: f32) -> Result<()> { self.set_channel_gain(sys::oa_direction::OA_DIR_INPUT, ch, gain) }
+    /// Linear trim gain for output channel `ch`; see [`Driver::set_input_gain`].
+    pub fn set_output_gain(&self, ch: usize, gain: f32) -> Result<()> { self.set_channel_gain(sys::oa_direction::OA_DIR_OUTPUT, ch, gain) }
+    fn set_channel_gain(&self, direction: sys::oa_direction, ch: usize, gain: f32) -> Result<()> {
+        let set = sys::oa_vtable_field!(self.vtable(), set_channel_gain)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("set_channel_gain not provided by driver"))?;
+        let ch = u32::try_from(ch).map_err(|_| anyhow::Error::new(DriverError::InvalidArg).context("channel index out of range"))?;
+        let rc = traced("set_channel_gain", || unsafe { set(self.drv.as_ptr(), direction.as_u32(), ch, gain) });
+        error::check("set_channel_gain", rc)
+    }
+    /// Queue `events` for the device's MIDI output, each due `sample_offset` frames from now at
+    /// the stream's rate (at once when stopped). Fails with [`DriverError::Unsupported`] unless
+    /// the driver reports `OA_CAP_MIDI`. Does not block on the hardware, so `process` may call it.
+    pub fn send_midi(&self, events: &[MidiEvent]) -> Result<()> {
+        let send = sys::oa_vtable_field!(self.vtable(), send_midi)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("send_midi not provided by driver"))?;
+        let count = u32::try_from(events.len()).map_err(|_| anyhow::Error::new(DriverError::InvalidArg).context("too many MIDI events"))?;
+        let rc = traced("send_midi", || unsafe { send(self.drv.as_ptr(), events.as_ptr() as *const sys::oa_midi_event, count) });
+        error::check("send_midi", rc)
+    }
+    /// Show the driver's own settings window, like an ASIO control panel. Drivers without one,
+    /// which is most on Linux, yield [`DriverError::Unsupported`]; see [`Driver::set_option`].
+    pub fn show_control_panel(&mut self) -> Result<()> {
+        let show = sys::oa_vtable_field!(self.vtable(), show_control_panel)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("show_control_panel not provided by driver"))?;
+        let rc = traced("show_control_panel", || unsafe { show(self.drv.as_ptr()) });
+        self.check("show_control_panel", rc)
+    }
+    /// Set a driver-specific setting, e.g. `("period_count", "4")` on the ALSA drivers. Settings
+    /// that shape the stream take effect from the next start. Keys and values the driver does
+    /// not take yield [`DriverError::Unsupported`].
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<()> {
+        let set = sys::oa_vtable_field!(self.vtable(), set_option)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("set_option not provided by driver"))?;
+        let key = CString::new(key).context("option key contains NUL")?;
+        let value = CString::new(value).context("option value contains NUL")?;
+        let rc = traced("set_option", || unsafe { set(self.drv.as_ptr(), key.as_ptr(), value.as_ptr()) });
+        self.check("set_option", rc)
+    }
+    /// Ask for the device's DMA ring to be mapped from the next start (`access=mmap`), or go back
+    /// to read/write access. Drivers without [`sys::OaCaps::MMAP`] yield
+    /// [`DriverError::Unsupported`]; devices that cannot be mapped fall back to read/write with a warning.
+    pub fn request_mmap(&mut self, enabled: bool) -> Result<()> {
+        if !self.caps().contains(sys::OaCaps::MMAP) { return Err(anyhow::Error::new(DriverError::Unsupported).context("driver lacks OA_CAP_MMAP")); }
+        self.set_option("access", if enabled { "mmap" } else { "rw" })
+    }
+    /// The current value of a driver-specific setting; see [`Driver::set_option`].
+    pub fn option(&self, key: &str) -> Result<String> {
+        let get = sys::oa_vtable_field!(self.vtable(), get_option)
+            .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("get_option not provided by driver"))?;
+        let key = CString::new(key).context("option key contains NUL")?;
+        let mut buf = vec![0u8; 64];
+        let mut rc = traced("get_option", || unsafe { get(self.drv.as_ptr(), key.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len()) });
+        if rc >= 0 && rc as usize >= buf.len() {
+            buf.resize(rc as usize + 1, 0);
+            rc = traced("get_option", || unsafe { get(self.drv.as_ptr(), key.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len()) });
+        }
+        error::check("get_option", rc)?;
+        let value = CStr::from_bytes_until_nul(&buf).context("get_option reply is not terminated")?;
+        Ok(value.to_string_lossy().into_owned())
+    }
+    /// Names of the configured input channels. Channels the driver does not name (or all of
+    /// them, if it lacks `get_channel_names`) are reported as `"Ch N"`.
+    pub fn input_channel_names(&self) -> Result<Vec<String>> {
+        self.labelled_channel_names(sys::oa_direction::OA_DIR_INPUT, self._host_thunk.cfg.in_channels as usize)
+    }
+    /// Names of the configured output channels; see [`Driver::input_channel_names`].
+    pub fn output_channel_names(&self) -> Result<Vec<String>> {
+        self.labelled_channel_names(sys::oa_direction::OA_DIR_OUTPUT, self._host_thunk.cfg.out_channels as usize)
+    }
+    /// Every channel the driver names in `direction`, e.g. for a routing UI. Needs
+    /// `get_channel_name`; drivers without it yield [`DriverError::Unsupported`].
+    pub fn channel_names(&self, direction: sys::oa_direction) -> Result<Vec<String>> {
+        unsafe { query_channel_names(self.vtable(), self.drv.as_ptr(), direction) }
+    }
+    fn labelled_channel_names(&self, direction: sys::oa_direction, count: usize) -> Result<Vec<String>> {
+        let mut names = Vec::with_capacity(count);
+        if let Some(get) = sys::oa_vtable_field!(self.vtable(), get_channel_names) {
+            let mut ptrs = vec![std::ptr::null::<c_char>(); count];
+            let rc = traced("get_channel_names", || unsafe { get(self.drv.as_ptr(), direction.as_u32(), ptrs.as_mut_ptr(), count as u32) });
+            error::check("get_channel_names", rc)?;
+            let written = (rc as usize).min(count);
+            names.extend(ptrs[..written].iter().map(|&p| {
+                if p.is_null() { String::new() } else { unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned() }
+            }));
+        }
+        for (i, name) in names.iter_mut().enumerate() { if name.is_empty() { *name = format!("Ch {}", i + 1); } }
+        names.extend((names.len()..count).map(|i| format!("Ch {}", i + 1)));
+        Ok(names)
+    }
+    /// The driver's description of its most recent failure, e.g. which hardware parameter the
+    /// device rejected when `start` failed. `None` if nothing has failed or the driver lacks
+    /// `get_last_error`. Errors from opening and starting already carry it as context.
+    pub fn last_error(&self) -> Option<String> {
+        let get = sys::oa_vtable_field!(self.vtable(), get_last_error)?;
+        let mut buf = vec![0u8; 256];
+        let mut rc = traced("get_last_error", || unsafe { get(self.drv.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len()) });
+        if rc >= 0 && rc as usize >= buf.len() {
+            buf.resize(rc as usize + 1, 0);
+            rc = traced("get_last_error", || unsafe { get(self.drv.as_ptr(), buf.as_mut_ptr() as *mut c_char, buf.len()) });
+        }
+        if rc <= 0 { return None; }
+        CStr::from_bytes_until_nul(&buf).ok().map(|s| s.to_string_lossy().into_owned())
+    }
+    /// [`error::check`], with the driver's [`Driver::last_error`] as outer context when it has one.
+    fn check(&self, op: &str, rc: i32) -> Result<()> {
+        error::check(op, rc).map_err(|e| match self.last_error() { Some(msg) => e.context(msg), None => e })
+    }
+    /// Emit a `trace!` every `blocks` process callbacks (default 1000; 0 disables). Call while stopped.
+    #[cfg(feature = "tracing")]
+    pub fn set_trace_sampling(&mut self, blocks: u32) {
+        self._host_thunk.trace = TraceSampler { every: blocks, blocks: 0 };
+    }
+    /// Receive driver events. Replaces any previous subscriber; events are dropped while the queue is full.
+    pub fn subscribe_events(&mut self) -> Receiver<Event> {
+        let (tx, rx) = sync_channel(EVENT_QUEUE_LEN);
+        self._host_thunk.events = Some(tx);
+        rx
+    }
+    /// Receive each xrun as it is reported. Replaces any previous receiver; the latest
+    /// [`XRUN_QUEUE_LEN`] events are kept, older ones are dropped if it is not drained in time (the
+    /// `total_count` of later events still accounts for them).
+    pub fn xrun_receiver(&mut self) -> XrunReceiver {
+        let queue = xruns::XrunQueue::new(XRUN_QUEUE_LEN);
+        self._host_thunk.xruns = Some(queue.clone());
+        XrunReceiver::new(queue)
+    }
+    /// Wake a waiting thread when the stream stops. Replaces any previous notifier.
+    pub fn stopped_notifier(&mut self) -> StoppedNotifier {
+        let (tx, rx) = sync_channel(EVENT_QUEUE_LEN);
+        self._host_thunk.stopped = Some(tx);
+        StoppedNotifier { rx }
+    }
+    /// Only call while stopped: the host is otherwise owned by the RT thread.
+    fn report_stream_error(&mut self, msg: &str) {
+        log::error!("{msg}");
+        self._host_thunk.inner.on_stream_error(msg);
+    }
+    fn emit(&self, ev: Event) { self._host_thunk.emit(ev) }
+    /// Statistics the audio thread keeps updated, readable from any thread.
+    pub fn stats_handle(&self) -> Stats { self._host_thunk.stats.clone() }
+    pub fn latency(&self) -> Result<(u32, u32)> {
+        unsafe {
+            let vt = &*(*self.drv.as_ptr()).vt;
+            let (mut in_lat, mut out_lat) = (0u32, 0u32);
+            let get_latency = required!(vt, get_latency)?;
+            let rc = traced("get_latency", || get_latency(self.drv.as_ptr(), &mut in_lat, &mut out_lat));
+            error::check("get_latency", rc)?;
+            Ok((in_lat, out_lat))
+        }
+    }
+    /// Start streaming with `cfg`. The returned guard stops the stream when dropped and borrows the
+    /// driver mutably, so it cannot be reopened while audio is running.
+    pub fn start_stream(&mut self, cfg: StreamConfig) -> Result<Stream<'_>> {
+        self.apply_config(cfg);
+        self.start_raw()?;
+        Ok(Stream { driver: self, stopped: false })
+    }
+    /// `start_stream` for automatic recovery paths: failures are also reported to the host.
+    pub(crate) fn restart_stream(&mut self, cfg: StreamConfig) -> Result<Stream<'_>> {
+        self.apply_config(cfg);
+        if let Err(e) = self.start_raw() {
+            self.report_stream_error(&format!("restarting stream at {} frames: {e:#}", cfg.buffer_frames));
+            return Err(e);
+        }
+        Ok(Stream { driver: self, stopped: false })
+    }
+    fn apply_config(&mut self, cfg: StreamConfig) {
+        let thunk = &mut self._host_thunk;
+        thunk.cfg = cfg.into();
+        thunk.priming_frames = cfg.priming_frames;
+        thunk.channel_mask = cfg.channel_mask;
+    }
+    fn start_raw(&mut self) -> Result<()> {
+        self.phase.check_start("start")?;
+        check_config(&self._host_thunk.cfg)?;
+        unsafe {
+            let vt = &*(*self.drv.as_ptr()).vt;
+            if self.supports_channel_mask() {
+                let set_channel_mask = sys::oa_vtable_field!(vt, set_channel_mask).unwrap();
+                let mask = self._host_thunk.channel_mask.map(sys::oa_channel_mask::from);
+                let ptr = mask.as_ref().map_or(std::ptr::null(), |m| m as *const _);
+                let rc = traced("set_channel_mask", || set_channel_mask(self.drv.as_ptr(), ptr));
+                error::check("set_channel_mask", rc)?;
+            }
+            self.arm_thunk();
+            let start = required!(vt, start)?;
+            let rc = traced("start", || start(self.drv.as_ptr(), &self._host_thunk.cfg as *const _));
+            if rc < 0 { self._host_thunk.alive.store(false, Ordering::Release); }
+            self.check("start", rc)?;
+            self.phase.phase = sys::DriverPhase::Running;
+            self.phase.paused = None;
+            Ok(())
+        }
+    }
+    /// Stop `process` calls but keep the device configured, so [`Driver::resume`] carries on
+    /// quickly and `sample_position` continues where it stopped. Drivers without `pause` are
+    /// stopped instead and `resume` starts them again with the same config, which renegotiates
+    /// the device and restarts the stream's counters. Pausing a paused stream does nothing; one
+    /// that is not running fails with [`DriverError::State`].
+    pub fn pause(&mut self) -> Result<()> {
+        if self.phase.paused.is_some() { return Ok(()); }
+        self.phase.check(self.phase.phase == sys::DriverPhase::Running, "pause")?;
+        if let Some(pause) = sys::oa_vtable_field!(self.vtable(), pause) {
+            let rc = traced("pause", || unsafe { pause(self.drv.as_ptr()) });
+            if rc != sys::OA_ERR_UNSUPPORTED {
+                error::check("pause", rc)?;
+                self.phase.paused = Some(Pause::Driver);
+                return Ok(());
+            }
+        }
+        let vt = self.vtable();
+        let stop = required!(vt, stop)?;
+        let rc = traced("stop", || unsafe { stop(self.drv.as_ptr()) });
+        error::check("stop", rc)?;
+        self.phase.phase = sys::DriverPhase::Configured;
+        self.phase.paused = Some(Pause::Stopped);
+        Ok(())
+    }
+    /// Undo [`Driver::pause`]. Resuming a running stream does nothing; one that is neither running
+    /// nor paused fails with [`DriverError::State`].
+    pub fn resume(&mut self) -> Result<()> {
+        match self.phase.paused {
+            None => self.phase.check(self.phase.phase == sys::DriverPhase::Running, "resume"),
+            Some(Pause::Driver) => {
+                let resume = sys::oa_vtable_field!(self.vtable(), resume)
+                    .ok_or_else(|| anyhow::Error::new(DriverError::Unsupported).context("driver has pause but no resume"))?;
+                let rc = traced("resume", || unsafe { resume(self.drv.as_ptr()) });
+                error::check("resume", rc)?;
+                self.phase.paused = None;
+                Ok(())
+            }
+            Some(Pause::Stopped) => self.start_raw(),
+        }
+    }
+    /// Stop at once, discarding the audio the driver still has queued (a panic button), where
+    /// stopping waits for it to play out. Drivers without `abort` are stopped normally. Does
+    /// nothing unless running.
+    pub fn abort(&mut self) {
+        if self.phase.paused.take() == Some(Pause::Stopped) { self._host_thunk.signal_stop(StopReason::Api); return; }
+        if self.phase.phase != sys::DriverPhase::Running { return; }
+        let rc = match sys::oa_vtable_field!(self.vtable(), abort) {
+            Some(abort) => traced("abort", || unsafe { abort(self.drv.as_ptr()) }),
+            None => sys::OA_ERR_UNSUPPORTED,
+        };
+        if rc == sys::OA_ERR_UNSUPPORTED {
+            if let Some(stop) = sys::oa_vtable_field!(self.vtable(), stop) {
+                let _ = traced("stop", || unsafe { stop(self.drv.as_ptr()) });
+            }
+        }
+        self._host_thunk.signal_stop(StopReason::Api);
+        self.phase.phase = sys::DriverPhase::Configured;
+    }
+    /// Reset per-stream host state right before the driver starts calling `process`.
+    fn arm_thunk(&mut self) {
+        self._host_thunk.alive.store(true, Ordering::Release);
+        self._host_thunk.primed_frames = 0;
+        self._host_thunk.underruns.store(0, Ordering::Relaxed);
+        self._host_thunk.overruns.store(0, Ordering::Relaxed);
+        self._host_thunk.slow_callbacks.store(0, Ordering::Relaxed);
+        self._host_thunk.near_xruns = 0;
+        self._host_thunk.frames_seen = 0;
+    }
+    fn stop_raw(&mut self) {
+        // Paused by stopping: the driver is stopped already.
+        if self.phase.paused.take() == Some(Pause::Stopped) { self._host_thunk.signal_stop(StopReason::Api); return; }
+        if self.phase.phase != sys::DriverPhase::Running { return; }
+        if let Some(stop) = sys::oa_vtable_field!(self.vtable(), stop) { let _ = traced("stop", || unsafe { stop(self.drv.as_ptr()) }); }
+        self._host_thunk.signal_stop(StopReason::Api);
+        self.phase.phase = sys::DriverPhase::Configured;
+    }
+    fn xrun_stats(&self) -> XrunStats {
+        XrunStats {
+            underruns: self._host_thunk.underruns.load(Ordering::Relaxed),
+            overruns: self._host_thunk.overruns.load(Ordering::Relaxed),
+        }
+    }
+    fn is_alive(&self) -> bool { self._host_thunk.alive.load(Ordering::Acquire) }
+    #[deprecated(note = "use start_stream, which stops the stream when the guard is dropped")]
+    pub fn start(&mut self) -> Result<()> { self.start_raw() }
+    #[deprecated(note = "use start_stream, which stops the stream when the guard is dropped")]
+    pub fn stop(&mut self) { self.stop_raw() }
+}
+
+/// A running stream. Dropping it stops the driver.
+pub struct Stream<'a> {
+    driver: &'a mut Driver,
+    stopped: bool,
+}
+
+impl Stream<'_> {
+    /// Stop explicitly; equivalent to dropping the guard.
+    pub fn stop(mut self) {
+        self.stopped = true;
+        self.driver.stop_raw();
+    }
+    /// See [`Driver::abort`].
+    pub fn abort(mut self) {
+        self.stopped = true;
+        self.driver.abort();
+    }
+    pub fn latency(&self) -> Result<(u32, u32)> { self.driver.latency() }
+    pub fn xrun_stats(&self) -> XrunStats { self.driver.xrun_stats() }
+    pub fn status(&self) -> Result<StreamStatus> { self.driver.status() }
+    pub fn set_input_gain(&self, ch: usize, gain: f32) -> Result<()> { self.driver.set_input_gain(ch, gain) }
+    pub fn set_output_gain(&self, ch: usize, gain: f32) -> Result<()> { self.driver.set_output_gain(ch, gain) }
+    /// See [`Driver::send_midi`].
+    pub fn send_midi(&self, events: &[MidiEvent]) -> Result<()> { self.driver.send_midi(events) }
+    /// See [`Driver::pause`].
+    pub fn pause(&mut self) -> Result<()> { self.driver.pause() }
+    /// See [`Driver::resume`].
+    pub fn resume(&mut self) -> Result<()> { self.driver.resume() }
+    /// False once the process callback returned `false` or the driver requested a reset.
+    pub fn is_alive(&self) -> bool { self.driver.is_alive() }
+}
+
+impl Drop for Stream<'_> {
+    fn drop(&mut self) {
+        if !self.stopped { self.driver.stop_raw(); }
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        self.stop_raw();
+        if let Some(close_device) = sys::oa_vtable_field!(self.vtable(), close_device) { let _ = traced("close_device", || unsafe { close_device(self.drv.as_ptr()) }); }
+    }
+}
+
+/// [`sys::oa_stream_config_check`] on a config about to be started, so every driver turns away the
+/// same configs without being called.
+fn check_config(cfg: &sys::oa_stream_config) -> Result<()> {
+    let rc = sys::oa_stream_config_check(cfg);
+    if rc == sys::OA_OK { return Ok(()); }
+    Err(anyhow::Error::new(DriverError::from_code(rc)).context(format!(
+        "invalid stream config: {} Hz, {} frames, {} in, {} out", cfg.sample_rate, cfg.buffer_frames, cfg.in_channels, cfg.out_channels)))
+}
+
+/// Start several drivers so their devices begin within one sample period of each other, each with
+/// the config it was last started with (or its load-time default). Every driver must implement
+/// `arm_start`/`trigger_start`; otherwise this fails with [`DriverError::Unsupported`] and none
+/// is started. The streams run until each driver is dropped.
+pub fn start_synchronized(drivers: &mut [&mut Driver]) -> Result<()> {
+    for d in drivers.iter() {
+        d.phase.check_start("start_synchronized")?;
+        check_config(&d._host_thunk.cfg)?;
+    }
+    let ptrs: Vec<*mut sys::oa_driver> = drivers.iter().map(|d| d.drv.as_ptr()).collect();
+    let cfgs: Vec<sys::oa_stream_config> = drivers.iter().map(|d| d._host_thunk.cfg).collect();
+    for d in drivers.iter_mut() { d.arm_thunk(); }
+    let rc = traced("start_synchronized", || unsafe { sys::start_synchronized(&ptrs, &cfgs) });
+    if rc < 0 {
+        for d in drivers.iter_mut() { d._host_thunk.alive.store(false, Ordering::Release); }
+    }
+    error::check("start_synchronized", rc)?;
+    for d in drivers.iter_mut() { d.phase.phase = sys::DriverPhase::Running; d.phase.paused = None; }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const RATES: [u32; 3] = [44100, 48000, 96000];
+
+    unsafe extern "C" fn fake_rates(_drv: *mut sys::oa_driver, buf: *mut u32, len: usize) -> i32 {
+        sys::write_supported_rates(&RATES, buf, len)
+    }
+
+    unsafe extern "C" fn fake_device_info(_drv: *mut sys::oa_driver, name: *const c_char, out: *mut sys::oa_device_info) -> i32 {
+        if !name.is_null() && CStr::from_ptr(name).to_bytes() != b"hw:1" { return sys::OA_ERR_DEVICE; }
+        let mut info = sys::oa_device_info { max_in_channels: 2, max_out_channels: 4, min_buffer_frames: 16, max_buffer_frames: 4096, ..Default::default() };
+        info.set_strings("hw:1", "Test Card");
+        sys::write_device_info(&info, out)
+    }
+
+    unsafe extern "C" fn fake_clock_names(_drv: *mut sys::oa_driver, buf: *mut c_char, len: usize) -> i32 {
+        let list = b"Internal\nS/PDIF\0";
+        if len < list.len() { return sys::OA_ERR_INVALID_ARG; }
+        std::ptr::copy_nonoverlapping(list.as_ptr() as *const c_char, buf, list.len());
+        2
+    }
+
+    unsafe extern "C" fn fake_clock_sources(_drv: *mut sys::oa_driver, buf: *mut sys::oa_clock_source, len: usize) -> i32 {
+        let sources = [sys::oa_clock_source::new(0, "Internal", false), sys::oa_clock_source::new(1, "S/PDIF", true)];
+        sys::write_clock_sources(&sources, buf, len)
+    }
+
+    const CHANNELS: [&str; 2] = ["Input 1 (Mic/Line)", "A rather long name for the second input"];
+
+    unsafe extern "C" fn fake_channel_name(_drv: *mut sys::oa_driver, direction: sys::oa_direction, index: u32, buf: *mut c_char, len: usize) -> i32 {
+        match (direction, CHANNELS.get(index as usize)) {
+            (sys::oa_direction::OA_DIR_INPUT, Some(name)) => sys::write_channel_name(name, buf, len),
+            _ => sys::OA_ERR_INVALID_ARG,
+        }
+    }
+
+    unsafe extern "C" fn fake_config_ranges(_drv: *mut sys::oa_driver, buf: *mut sys::oa_config_range, len: usize) -> i32 {
+        let f32_only = sys::oa_format_bit(sys::oa_sample_format::OA_SAMPLE_F32);
+        let ranges = RATES.map(|rate| sys::oa_config_range {
+            min_rate: rate, max_rate: rate, max_channels_in: 2, min_channels_out: 1, max_channels_out: 2,
+            formats_mask: f32_only, min_buffer_frames: 64, max_buffer_frames: 1024, ..Default::default()
+        });
+        sys::write_config_ranges(&ranges, buf, len)
+    }
+
+    fn vtable(struct_size: usize) -> sys::oa_driver_vtable {
+        sys::oa_driver_vtable {
+            struct_size: struct_size as u32,
+            query_supported_rates: Some(fake_rates),
+            query_device_info: Some(fake_device_info),
+            enumerate_clock_sources: Some(fake_clock_names),
+            query_clock_sources: Some(fake_clock_sources),
+            get_channel_name: Some(fake_channel_name),
+            query_config_ranges: Some(fake_config_ranges),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn config_ranges_negotiates_buffer_size() {
+        let vt = vtable(std::mem::size_of::<sys::oa_driver_vtable>());
+        let mut drv = sys::oa_driver { vt: &vt };
+        let ranges = unsafe { query_config_ranges(&vt, &mut drv) }.unwrap();
+        assert_eq!(ranges.iter().map(|r| *r.sample_rates.start()).collect::<Vec<_>>(), RATES);
+        let cfg = StreamConfig { sample_rate: 48000, buffer_frames: 256, in_channels: 0, ..Default::default() };
+        assert!(ranges.iter().any(|r| r.contains(&cfg)));
+        assert!(!ranges.iter().any(|r| r.contains(&StreamConfig { buffer_frames: 2048, ..cfg })));
+        assert!(!ranges.iter().any(|r| r.contains(&StreamConfig { format: sys::oa_sample_format::OA_SAMPLE_I16, ..cfg })));
+    }
+
+    thread_local! {
+        /// `(len, result)` of each `recorded_rates` call on this thread.
+        static RATE_QUERIES: std::cell::RefCell<Vec<(usize, i32)>> = Default::default();
+    }
+
+    unsafe extern "C" fn recorded_rates(drv: *mut sys::oa_driver, buf: *mut u32, len: usize) -> i32 {
+        let rc = fake_rates(drv, buf, len);
+        RATE_QUERIES.with(|q| q.borrow_mut().push((len, rc)));
+        rc
+    }
+
+    #[test]
+    fn supported_rates_retry_with_the_required_count() {
+        let vt = sys::oa_driver_vtable { query_supported_rates: Some(recorded_rates), ..vtable(std::mem::size_of::<sys::oa_driver_vtable>()) };
+        let mut drv = sys::oa_driver { vt: &vt };
+        let rates = unsafe { query_rates(&vt, &mut drv) }.unwrap();
+        assert_eq!(rates, RATES);
+        // The first buffer is too short for the list and the driver answers with the count it
+        // needs; the retry passes exactly that many and gets them all.
+        let n = RATES.len();
+        assert_eq!(RATE_QUERIES.with(|q| q.take()), [(0, n as i32), (n, n as i32)]);
+    }
+
+    #[test]
+    fn supported_rates_short_buffer_reports_required_count() {
+        let mut buf = [0u32; 2];
+        let rc = unsafe { fake_rates(std::ptr::null_mut(), buf.as_mut_ptr(), buf.len()) };
+        assert_eq!(rc, RATES.len() as i32);
+        assert_eq!(buf, [0, 0]);
+    }
+
+    #[test]
+    fn supported_rates_unsupported_for_old_vtable() {
+        let vt = vtable(std::mem::offset_of!(sys::oa_driver_vtable, query_supported_rates));
+        let mut drv = sys::oa_driver { vt: &vt };
+        let err = unsafe { query_rates(&vt, &mut drv) }.unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn device_info_reads_all_fields() {
+        let vt = vtable(std::mem::size_of::<sys::oa_driver_vtable>());
+        let mut drv = sys::oa_driver { vt: &vt };
+        let info = unsafe { query_device_info(&vt, &mut drv, Some("hw:1")) }.unwrap();
+        let expected = DeviceInfo {
+            id: "hw:1".into(), description: "Test Card".into(),
+            max_in_channels: 2, max_out_channels: 4, min_buffer_frames: 16, max_buffer_frames: 4096,
+        };
+        assert_eq!(info, expected);
+    }
+
+    #[test]
+    fn device_info_unknown_name_is_device_error() {
+        let vt = vtable(std::mem::size_of::<sys::oa_driver_vtable>());
+        let mut drv = sys::oa_driver { vt: &vt };
+        let err = unsafe { query_device_info(&vt, &mut drv, Some("hw:9")) }.unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Device));
+    }
+
+    #[test]
+    fn device_info_stops_at_host_struct_size() {
+        let mut info = sys::oa_device_info::default();
+        info.set_strings("hw:1", "Test Card");
+        info.max_in_channels = 8;
+        let mut out = sys::oa_device_info { struct_size: std::mem::offset_of!(sys::oa_device_info, max_in_channels) as u32, max_in_channels: 99, ..Default::default() };
+        assert_eq!(unsafe { sys::write_device_info(&info, &mut out) }, sys::OA_OK);
+        assert_eq!(out.id, info.id);
+        assert_eq!(out.max_in_channels, 99);
+        assert_eq!(out.struct_size, std::mem::offset_of!(sys::oa_device_info, max_in_channels) as u32);
+    }
+
+    /// In-process driver that keeps the host's callbacks so a test can fire them.
+    #[repr(C)]
+    struct StubDriver { base: sys::oa_driver, host: sys::oa_host_callbacks, host_user: *mut c_void, starts: u32, stops: u32, options: sys::CreateOptions }
+
+    unsafe extern "C" fn stub_ok(_drv: *mut sys::oa_driver) -> i32 { sys::OA_OK }
+    unsafe extern "C" fn stub_start(drv: *mut sys::oa_driver, _cfg: *const sys::oa_stream_config) -> i32 { (*(drv as *mut StubDriver)).starts += 1; sys::OA_OK }
+    unsafe extern "C" fn stub_stop(drv: *mut sys::oa_driver) -> i32 { (*(drv as *mut StubDriver)).stops += 1; sys::OA_OK }
+
+    /// A default config from a driver built against some other ABI: the layout is not one of ours.
+    unsafe extern "C" fn stub_default_config(_drv: *mut sys::oa_driver, out: *mut sys::oa_stream_config) -> i32 {
+        *out = sys::oa_stream_config { layout: sys::oa_buffer_layout(7), ..Default::default() };
+        sys::OA_OK
+    }
+
+    unsafe extern "C" fn stub_create(params: *const sys::oa_create_params, out: *mut *mut sys::oa_driver) -> i32 {
+        let vt = Box::leak(Box::new(sys::oa_driver_vtable {
+            start: Some(stub_start), stop: Some(stub_stop), close_device: Some(stub_ok), get_default_config: Some(stub_default_config),
+            ..sys::oa_driver_vtable::empty()
+        }));
+        let p = &*params;
+        let Ok(options) = sys::CreateOptions::from_params(p) else { return sys::OA_ERR_INVALID_ARG };
+        let stub = Box::new(StubDriver { base: sys::oa_driver { vt }, host: sys::host_callbacks(p), host_user: p.host_user, starts: 0, stops: 0, options });
+        *out = Box::into_raw(stub) as *mut sys::oa_driver;
+        sys::OA_OK
+    }
+
+    unsafe extern "C" fn stub_reject_rate(_drv: *mut sys::oa_driver, cfg: *const sys::oa_stream_config) -> i32 {
+        if (*cfg).sample_rate > 192_000 { sys::OA_ERR_UNSUPPORTED } else { sys::OA_OK }
+    }
+    unsafe extern "C" fn stub_rate_error(_drv: *mut sys::oa_driver, buf: *mut c_char, len: usize) -> i32 {
+        sys::write_last_error("sample rate 384000 not supported", buf, len)
+    }
+
+    /// Takes only `period_count`, and reports a value too long for a first guess at the buffer.
+    unsafe extern "C" fn stub_set_option(_drv: *mut sys::oa_driver, key: *const c_char, _value: *const c_char) -> i32 {
+        if CStr::from_ptr(key) == c"period_count" { sys::OA_OK } else { sys::OA_ERR_UNSUPPORTED }
+    }
+    unsafe extern "C" fn stub_get_option(_drv: *mut sys::oa_driver, key: *const c_char, buf: *mut c_char, len: usize) -> i32 {
+        if CStr::from_ptr(key) != c"period_count" { return sys::OA_ERR_UNSUPPORTED; }
+        sys::write_option(&"4".repeat(100), buf, len)
+    }
+
+    /// Lists more devices than fit in any guess, under the sizing contract.
+    unsafe extern "C" fn stub_query_devices(_drv: *mut sys::oa_driver, buf: *mut c_char, len: usize) -> i32 {
+        sys::write_device_list(&stub_device_names().join("\n"), buf, len)
+    }
+    /// A driver from before the sizing contract: truncates, and returns OA_OK regardless.
+    unsafe extern "C" fn stub_legacy_query_devices(_drv: *mut sys::oa_driver, buf: *mut c_char, len: usize) -> i32 {
+        let list = stub_device_names()[..3].join("\n");
+        let n = list.len().min(len.saturating_sub(1));
+        if n > 0 { std::ptr::copy_nonoverlapping(list.as_ptr() as *const c_char, buf, n); }
+        if len > 0 { *buf.add(n) = 0; }
+        sys::OA_OK
+    }
+    fn stub_device_names() -> Vec<String> { (0..2000).map(|i| format!("hw:{i},0")).collect() }
+
+    unsafe extern "C" fn stub_destroy(drv: *mut sys::oa_driver) { drop(Box::from_raw(drv as *mut StubDriver)); }
+
+    fn stub_driver(host: Box<dyn HostProcess>) -> Driver {
+        stub_driver_with_options(host, &sys::CreateOptions::default())
+    }
+
+    fn stub_driver_with_options(host: Box<dyn HostProcess>, options: &sys::CreateOptions) -> Driver {
+        #[cfg(unix)]
+        let this = libloading::os::unix::Library::this();
+        #[cfg(windows)]
+        let this = libloading::os::windows::Library::this().unwrap();
+        let lib = sys::loader::DriverLib { lib: Some(this.into()), create: stub_create, destroy: stub_destroy, get_info: None };
+        Driver::create(lib, host, StreamConfig::default(), true, sys::ThreadHints::default(), options).unwrap()
+    }
+
+    #[test]
+    fn create_options_reach_the_driver_intact() {
+        let mut options = sys::CreateOptions::default();
+        options.push("host", "jack");
+        options.push("device", r#"hw:2,0 "rear""#);
+        let driver = stub_driver_with_options(Box::new(Plugged(Arc::default())), &options);
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        assert_eq!(stub.options, options);
+        let driver = stub_driver(Box::new(Plugged(Arc::default())));
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        assert!(stub.options.is_empty());
+    }
+
+    #[test]
+    fn nul_in_a_device_name_is_an_invalid_arg() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        let err = driver.open_by_name(Some("hw:0\0,0")).unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::InvalidArg));
+        assert_eq!(driver.phase.phase, sys::DriverPhase::Created);
+    }
+
+    #[test]
+    fn pause_without_driver_support_stops_and_restarts() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        let stopped = driver.stopped_notifier();
+        let drv = driver.drv.as_ptr() as *const StubDriver;
+        let counts = || unsafe { ((*drv).starts, (*drv).stops) };
+        let mut stream = driver.start_stream(StreamConfig::default()).unwrap();
+        stream.pause().unwrap();
+        stream.pause().unwrap();
+        assert_eq!(counts(), (1, 1));
+        assert_eq!(stopped.try_wait(), None, "a pause is not the end of the stream");
+        stream.resume().unwrap();
+        stream.resume().unwrap();
+        assert_eq!(counts(), (2, 1));
+        stream.pause().unwrap();
+        drop(stream);
+        assert_eq!(counts(), (2, 2));
+        assert_eq!(stopped.try_wait(), Some(StopReason::Api));
+        assert!(driver.resume().is_err_and(|e| e.downcast_ref() == Some(&DriverError::State)));
+    }
+
+    #[test]
+    fn abort_without_driver_support_stops() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        let stopped = driver.stopped_notifier();
+        let drv = driver.drv.as_ptr() as *const StubDriver;
+        driver.start_stream(StreamConfig::default()).unwrap().abort();
+        assert_eq!(unsafe { ((*drv).starts, (*drv).stops) }, (1, 1));
+        assert_eq!(stopped.try_wait(), Some(StopReason::Api));
+        driver.abort();
+        assert_eq!(unsafe { (*drv).stops }, 1, "abort while stopped reached the driver");
+    }
+
+    #[test]
+    fn failed_start_carries_the_driver_error_text() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        unsafe {
+            let vt = (*driver.drv.as_ptr()).vt as *mut sys::oa_driver_vtable;
+            (*vt).start = Some(stub_reject_rate);
+            (*vt).get_last_error = Some(stub_rate_error);
+        }
+        let err = driver.start_stream(StreamConfig { sample_rate: 384_000, ..Default::default() }).err().unwrap();
+        assert_eq!(err.downcast_ref(), Some(&DriverError::Unsupported));
+        assert!(err.to_string().contains("384000"), "{err:#}");
+        assert!(format!("{err:#}").contains("start returned"), "{err:#}");
+    }
+
+    #[test]
+    fn invalid_config_never_reaches_the_driver() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        let drv = driver.drv.as_ptr() as *const StubDriver;
+        for cfg in [
+            StreamConfig { buffer_frames: 0, ..Default::default() },
+            StreamConfig { sample_rate: sys::OA_MAX_SAMPLE_RATE + 1, ..Default::default() },
+            StreamConfig { in_channels: 0, out_channels: 0, ..Default::default() },
+        ] {
+            let err = driver.start_stream(cfg).err().unwrap();
+            assert_eq!(err.downcast_ref(), Some(&DriverError::InvalidArg), "{cfg:?}");
+        }
+        assert_eq!(unsafe { (*drv).starts }, 0);
+    }
+
+    #[test]
+    fn channel_mask_is_ignored_without_driver_support() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        assert!(!driver.supports_channel_mask());
+        let drv = driver.drv.as_ptr() as *const StubDriver;
+        let cfg = StreamConfig { channel_mask: Some(ChannelMask { inputs: 0b01, outputs: 0b11 }), ..Default::default() };
+        driver.start_stream(cfg).unwrap().stop();
+        assert_eq!(unsafe { ((*drv).starts, (*drv).stops) }, (1, 1));
+    }
+
+    #[test]
+    fn default_config_is_the_sys_default() {
+        assert_eq!(sys::oa_stream_config::from(StreamConfig::default()), sys::oa_stream_config::default());
+    }
+
+    #[test]
+    fn exclusive_survives_the_sys_round_trip() {
+        let cfg = StreamConfig { exclusive: true, ..Default::default() };
+        let raw = sys::oa_stream_config::from(cfg);
+        assert_eq!(raw.exclusive, sys::OA_TRUE);
+        assert!(StreamConfig::try_from(raw).unwrap().exclusive);
+        assert!(!StreamConfig::from_raw(&raw, 0).exclusive, "process-time configs may be 1.0 structs");
+    }
+
+    struct Plugged(Arc<AtomicU32>);
+
+    impl HostProcess for Plugged {
+        fn process(&mut self, _in: *const c_void, _out: *mut c_void, _frames: u32, _cfg: &StreamConfig) -> bool { true }
+        fn devices_changed(&mut self) { self.0.fetch_add(1, Ordering::Relaxed); }
+    }
+
+    #[test]
+    fn device_list_changed_reaches_host_and_events() {
+        let seen = Arc::new(AtomicU32::new(0));
+        let mut driver = stub_driver(Box::new(Plugged(seen.clone())));
+        let events = driver.subscribe_events();
+        let stub = driver.drv.as_ptr() as usize;
+        // Drivers fire it from their own watcher thread.
+        std::thread::spawn(move || unsafe {
+            let stub = &*(stub as *const StubDriver);
+            (stub.host.device_list_changed.unwrap())(stub.host_user);
+        }).join().unwrap();
+        assert_eq!(events.try_recv(), Ok(Event::DevicesChanged));
+        assert_eq!(seen.load(Ordering::Relaxed), 0, "the hook waits for the RT thread");
+        driver._host_thunk.alive.store(true, Ordering::Release);
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let cfg = sys::oa_stream_config { out_channels: 0, ..Default::default() };
+        let process = || unsafe { (stub.host.process.unwrap())(stub.host_user, std::ptr::null(), std::ptr::null_mut(), 64, std::ptr::null(), &cfg) };
+        process();
+        process();
+        assert_eq!(seen.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn latency_change_is_an_event() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::new(AtomicU32::new(0)))));
+        let events = driver.subscribe_events();
+        let stub = driver.drv.as_ptr() as usize;
+        // umc202hd reports a reconnected device from its reconnect thread.
+        std::thread::spawn(move || unsafe {
+            let stub = &*(stub as *const StubDriver);
+            (stub.host.latency_changed.unwrap())(stub.host_user, 128, 256);
+        }).join().unwrap();
+        assert_eq!(events.try_recv(), Ok(Event::LatencyChanged { input: 128, output: 256 }));
+    }
+
+    struct XrunLog(Arc<std::sync::Mutex<Vec<(XrunKind, u32)>>>);
+
+    impl HostProcess for XrunLog {
+        fn process(&mut self, _in: *const c_void, _out: *mut c_void, _frames: u32, _cfg: &StreamConfig) -> bool { true }
+        fn on_xrun(&mut self, kind: XrunKind, total: u32) { self.0.lock().unwrap().push((kind, total)); }
+    }
+
+    #[test]
+    fn xrun_callback_reaches_host_once() {
+        use sys::{OA_XRUN_CALLBACK, OA_XRUN_OVERRUN, OA_XRUN_UNDERRUN};
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut driver = stub_driver(Box::new(XrunLog(seen.clone())));
+        let events = driver.subscribe_events();
+        let xruns = driver.xrun_receiver();
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let (under, over, slow) = (AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0));
+        unsafe {
+            sys::report_xrun(&stub.host, stub.host_user, &under, OA_XRUN_UNDERRUN);
+            sys::report_xrun(&stub.host, stub.host_user, &over, OA_XRUN_OVERRUN);
+            sys::report_xrun(&stub.host, stub.host_user, &under, OA_XRUN_UNDERRUN);
+            sys::report_xrun(&stub.host, stub.host_user, &slow, OA_XRUN_CALLBACK);
+            // The next process call carries the same totals, which must not be reported again.
+            let time = sys::oa_time_info { underruns: 2, overruns: 1, ..Default::default() };
+            let cfg = sys::oa_stream_config { out_channels: 0, ..Default::default() };
+            (stub.host.process.unwrap())(stub.host_user, std::ptr::null(), std::ptr::null_mut(), 64, &time, &cfg);
+        }
+        let expected = [(XrunKind::Underrun, 1), (XrunKind::Overrun, 1), (XrunKind::Underrun, 2), (XrunKind::SlowCallback, 1)];
+        assert_eq!(*seen.lock().unwrap(), expected);
+        let got: Vec<Event> = events.try_iter().collect();
+        assert_eq!(got, expected.map(|(kind, total)| Event::Xrun { kind, total }));
+        let got: Vec<(XrunKind, u64)> = xruns.try_iter().map(|e| (e.xrun_type, e.total_count)).collect();
+        assert_eq!(got, expected.map(|(kind, total)| (kind, u64::from(total))));
+        assert_eq!((driver.stats_handle().underruns(), driver.stats_handle().overruns()), (2, 1));
+        assert_eq!(driver.stats_handle().slow_callbacks(), 1);
+    }
+
+    struct MidiLog(Arc<std::sync::Mutex<Vec<MidiEvent>>>);
+
+    impl HostProcess for MidiLog {
+        fn process(&mut self, _in: *const c_void, _out: *mut c_void, _frames: u32, _cfg: &StreamConfig) -> bool { true }
+        fn midi_in(&mut self, events: &[MidiEvent]) { self.0.lock().unwrap().extend_from_slice(events); }
+    }
+
+    #[test]
+    fn midi_in_reaches_host_and_send_midi_needs_the_entry() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let driver = stub_driver(Box::new(MidiLog(seen.clone())));
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let events = [MidiEvent::new(3, &[0x90, 60, 100]).unwrap(), MidiEvent::new(9, &[0xF8]).unwrap()];
+        unsafe {
+            let midi_in = stub.host.midi_in.unwrap();
+            midi_in(stub.host_user, events.as_ptr() as *const sys::oa_midi_event, events.len() as u32);
+            midi_in(stub.host_user, std::ptr::null(), 0);
+        }
+        assert_eq!(*seen.lock().unwrap(), events);
+        assert_eq!(events[0].sample_offset(), 3);
+        let err = driver.send_midi(&events).unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn options_round_trip_through_the_driver() {
+        let mut driver = stub_driver(Box::new(Plugged(Arc::default())));
+        let err = driver.set_option("period_count", "4").unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&DriverError::Unsupported));
+        let err = driver.show_control_panel().unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&DriverError::Unsupported));
+        unsafe {
+            let vt = (*driver.drv.as_ptr()).vt as *mut sys::oa_driver_vtable;
+            (*vt).set_option = Some(stub_set_option);
+            (*vt).get_option = Some(stub_get_option);
+        }
+        driver.set_option("period_count", "4").unwrap();
+        assert_eq!(driver.option("period_count").unwrap(), "4".repeat(100));
+        let err = driver.set_option("colour", "blue").unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&DriverError::Unsupported));
+        assert!(driver.option("colour").is_err());
+        assert!(driver.set_option("period\0count", "4").is_err());
+        // The stub does not advertise OA_CAP_MMAP, so the request never reaches set_option.
+        let err = driver.request_mmap(true).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn xrun_receiver_keeps_the_latest_queue_len_events() {
+        let mut driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let rx = driver.xrun_receiver();
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let under = AtomicU32::new(0);
+        let before = sys::realtime_ns();
+        for _ in 0..XRUN_QUEUE_LEN + 10 {
+            unsafe { sys::report_xrun(&stub.host, stub.host_user, &under, sys::oa_xrun_kind::OA_XRUN_UNDERRUN) };
+        }
+        let got: Vec<XrunEvent> = rx.try_iter().collect();
+        assert_eq!(got.len(), XRUN_QUEUE_LEN);
+        assert!(got.iter().all(|e| e.xrun_type == XrunKind::Underrun && e.timestamp_ns >= before));
+        // The first 10 were overwritten; the newest is last.
+        let totals: Vec<u64> = got.iter().map(|e| e.total_count).collect();
+        assert_eq!(totals, (11..=XRUN_QUEUE_LEN as u64 + 10).collect::<Vec<_>>());
+        assert_eq!(rx.try_recv(), None);
+        unsafe { sys::report_xrun(&stub.host, stub.host_user, &under, sys::oa_xrun_kind::OA_XRUN_UNDERRUN) };
+        assert_eq!(rx.try_recv().unwrap().total_count, XRUN_QUEUE_LEN as u64 + 11);
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[test]
+    fn xrun_receiver_reads_in_order_while_the_ring_laps() {
+        let queue = xruns::XrunQueue::new(XRUN_QUEUE_LEN);
+        let rx = XrunReceiver::new(queue.clone());
+        let writer = std::thread::spawn(move || {
+            for total in 1..=20_000 {
+                queue.force_push(XrunEvent { xrun_type: XrunKind::Overrun, timestamp_ns: total, total_count: total });
+            }
+        });
+        let mut last = 0;
+        while !writer.is_finished() || last < 20_000 {
+            for ev in rx.try_iter() {
+                assert!(ev.total_count > last, "{} after {last}", ev.total_count);
+                assert_eq!(ev.timestamp_ns, ev.total_count, "torn read");
+                last = ev.total_count;
+            }
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn unknown_layout_is_reported_not_trusted() {
+        let mut driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let err = driver.default_config().unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::InvalidArg));
+        assert!(format!("{err:#}").contains("unknown layout 7"), "{err:#}");
+
+        let stopped = driver.stopped_notifier();
+        driver.arm_thunk();
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let cfg = sys::oa_stream_config { layout: sys::oa_buffer_layout(7), ..Default::default() };
+        let mut out = [1.0f32; 128];
+        let time = sys::oa_time_info::default();
+        let rc = unsafe { (stub.host.process.unwrap())(stub.host_user, std::ptr::null(), out.as_mut_ptr() as *mut c_void, 64, &time, &cfg) };
+        assert_eq!(rc, sys::OA_FALSE);
+        assert_eq!(stopped.try_wait(), Some(StopReason::InvalidConfig { format: 1, layout: 7 }));
+        // Unknown xrun kinds are dropped rather than misreported.
+        unsafe { (stub.host.xrun.unwrap())(stub.host_user, sys::oa_xrun_kind(9), 1) };
+        assert_eq!(driver.xrun_stats().underruns + driver.xrun_stats().overruns, 0);
+    }
+
+    #[test]
+    fn near_xruns_reach_stats() {
+        let mut driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let stats = driver.stats_handle();
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let cfg = sys::oa_stream_config { out_channels: 0, ..Default::default() };
+        let process = |near_xrun| unsafe {
+            let time = sys::oa_time_info { near_xrun, ..Default::default() };
+            (stub.host.process.unwrap())(stub.host_user, std::ptr::null(), std::ptr::null_mut(), 64, &time, &cfg);
+        };
+        process(3);
+        process(3);
+        process(5);
+        assert_eq!(stats.near_xruns(), 5);
+        // A 1.0 driver's shorter oa_time_info has no near_xrun to read.
+        driver._host_thunk.reads_near_xrun = false;
+        process(9);
+        assert_eq!(stats.near_xruns(), 5);
+    }
+
+    struct Positions(Arc<std::sync::Mutex<Vec<u64>>>);
+
+    impl HostProcess for Positions {
+        fn process(&mut self, _in: *const c_void, _out: *mut c_void, _frames: u32, _cfg: &StreamConfig) -> bool { true }
+        fn process_with_time(&mut self, _in: *const c_void, _out: *mut c_void, _frames: u32, _cfg: &StreamConfig, time: &TimeInfo) -> bool {
+            self.0.lock().unwrap().push(time.sample_position);
+            true
+        }
+    }
+
+    #[test]
+    fn sample_position_is_counted_for_drivers_without_it() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut driver = stub_driver(Box::new(Positions(seen.clone())));
+        assert!(!driver._host_thunk.reads_sample_position);
+        let stub = unsafe { &*(driver.drv.as_ptr() as *const StubDriver) };
+        let cfg = sys::oa_stream_config { out_channels: 0, ..Default::default() };
+        // Whatever an old driver leaves there is not read.
+        let time = sys::oa_time_info { sample_position: 999, ..Default::default() };
+        let process = |frames| unsafe { (stub.host.process.unwrap())(stub.host_user, std::ptr::null(), std::ptr::null_mut(), frames, &time, &cfg) };
+        driver.arm_thunk();
+        process(64);
+        process(64);
+        process(32);
+        driver.arm_thunk();
+        process(64);
+        assert_eq!(*seen.lock().unwrap(), [0, 64, 128, 0]);
+        driver._host_thunk.reads_sample_position = true;
+        process(64);
+        assert_eq!(seen.lock().unwrap().last(), Some(&999));
+    }
+
+    #[test]
+    fn start_before_open_is_not_open() {
+        let mut driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let err = driver.start_stream(StreamConfig::default()).err().unwrap();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::NotOpen));
+        driver.phase.phase = sys::DriverPhase::Opened;
+        driver.start_stream(StreamConfig::default()).unwrap();
+    }
+
+    #[test]
+    fn null_required_entry_is_unsupported() {
+        // The stub leaves query_devices and get_latency null.
+        let driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let err = driver.enumerate_devices().unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+        let err = driver.latency().unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn enumerate_devices_sizes_the_list_before_reading_it() {
+        let driver = stub_driver(Box::new(XrunLog(Default::default())));
+        unsafe {
+            let vt = (*driver.drv.as_ptr()).vt as *mut sys::oa_driver_vtable;
+            (*vt).query_devices = Some(stub_query_devices);
+        }
+        assert_eq!(driver.enumerate_devices().unwrap(), stub_device_names());
+        unsafe {
+            let vt = (*driver.drv.as_ptr()).vt as *mut sys::oa_driver_vtable;
+            (*vt).query_devices = Some(stub_legacy_query_devices);
+        }
+        assert_eq!(driver.enumerate_devices().unwrap(), stub_device_names()[..3]);
+    }
+
+    #[test]
+    fn info_unsupported_without_export() {
+        let driver = stub_driver(Box::new(XrunLog(Default::default())));
+        let err = driver.info().unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[cfg(feature = "static-link")]
+    struct StubFactory;
+    #[cfg(feature = "static-link")]
+    impl DriverFactory for StubFactory {
+        unsafe extern "C" fn create(params: *const sys::oa_create_params, out: *mut *mut sys::oa_driver) -> i32 { stub_create(params, out) }
+        unsafe extern "C" fn destroy(drv: *mut sys::oa_driver) { stub_destroy(drv) }
+    }
+
+    #[cfg(feature = "static-link")]
+    #[test]
+    fn static_load_calls_the_factory() {
+        let driver = Driver::static_load::<StubFactory>(Box::new(XrunLog(Default::default())), StreamConfig::default(), true).unwrap();
+        assert_eq!(driver.abi_version(), Some(sys::oa_abi_split(sys::OA_ABI_VERSION)));
+        let err = driver.info().unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn status_unsupported_for_old_vtable() {
+        let vt = vtable(std::mem::offset_of!(sys::oa_driver_vtable, get_status));
+        let mut drv = sys::oa_driver { vt: &vt };
+        let err = unsafe { query_status(&vt, &mut drv) }.unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
+    }
+
+    #[test]
+    fn clock_sources_report_index_and_current() {
+        let vt = vtable(std::mem::size_of::<sys::oa_driver_vtable>());
+        let mut drv = sys::oa_driver { vt: &vt };
+        let sources = unsafe { query_clock_sources(&vt, &mut drv) }.unwrap();
+        assert_eq!(sources, [
+            ClockSource { index: 0, name: "Internal".into(), current: false },
+            ClockSource { index: 1, name: "S/PDIF".into(), current: true },
+        ]);
+    }
+
+    #[test]
+    fn clock_sources_fall_back_to_names_for_old_vtable() {
+        let vt = vtable(std::mem::offset_of!(sys::oa_driver_vtable, query_clock_sources));
+        let mut drv = sys::oa_driver { vt: &vt };
+        let sources = unsafe { query_clock_sources(&vt, &mut drv) }.unwrap();
+        assert_eq!(sources.iter().map(|s| (s.index, s.name.as_str(), s.current)).collect::<Vec<_>>(), [(0, "Internal", false), (1, "S/PDIF", false)]);
+    }
+
+    #[test]
+    fn channel_names_iterate_until_invalid_index() {
+        let vt = vtable(std::mem::size_of::<sys::oa_driver_vtable>());
+        let mut drv = sys::oa_driver { vt: &vt };
+        let inputs = unsafe { query_channel_names(&vt, &mut drv, sys::oa_direction::OA_DIR_INPUT) }.unwrap();
+        assert_eq!(inputs, CHANNELS);
+        let outputs = unsafe { query_channel_names(&vt, &mut drv, sys::oa_direction::OA_DIR_OUTPUT) }.unwrap();
+        assert!(outputs.is_empty());
+    }
+
+    #[test]
+    fn channel_name_truncates_to_tiny_buffers() {
+        let name = CHANNELS[1];
+        for len in 1..8 {
+            let mut buf = [0x55 as c_char; 8];
+            let rc = unsafe { fake_channel_name(std::ptr::null_mut(), sys::oa_direction::OA_DIR_INPUT, 1, buf.as_mut_ptr(), len) };
+            assert_eq!(rc as usize, name.len());
+            assert_eq!(buf[len - 1], 0, "len {len}");
+            let got = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_bytes();
+            assert_eq!(got, &name.as_bytes()[..len - 1]);
+            assert!(buf[len..].iter().all(|&b| b == 0x55), "wrote past len {len}");
+        }
+        let rc = unsafe { fake_channel_name(std::ptr::null_mut(), sys::oa_direction::OA_DIR_INPUT, 0, std::ptr::null_mut(), 0) };
+        assert_eq!(rc as usize, CHANNELS[0].len());
+    }
+
+    #[test]
+    fn device_info_unsupported_for_old_vtable() {
+        let vt = vtable(std::mem::offset_of!(sys::oa_driver_vtable, query_device_info));
+        let mut drv = sys::oa_driver { vt: &vt };
+        let err = unsafe { query_device_info(&vt, &mut drv, None) }.unwrap_err();
+        assert_eq!(err.downcast_ref::<DriverError>(), Some(&DriverError::Unsupported));
     }
-    pub fn start(&mut self) -> Result<()> { unsafe { let vt = &*(*self.drv.as_ptr()).vt; (vt.start.unwrap())(self.drv.as_ptr(), &(*self._host_thunk).cfg as *const _); Ok(()) } }
-    pub fn stop(&mut self) { unsafe { let vt = &*(*self.drv.as_ptr()).vt; let _=(vt.stop.unwrap())(self.drv.as_ptr()); } }
 }
-impl Drop for Driver { fn drop(&mut self) { unsafe { let vt=&*(*self.drv.as_ptr()).vt; let _=(vt.close_device.unwrap())(self.drv.as_ptr()); } } }
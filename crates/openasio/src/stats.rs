@@ -0,0 +1,72 @@
+//! Stream statistics the RT callback publishes for other threads.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+pub(crate) struct StatsInner {
+    underruns: AtomicU64,
+    overruns: AtomicU64,
+    near_xruns: AtomicU64,
+    slow_callbacks: AtomicU64,
+    callbacks_dispatched: AtomicU64,
+    max_callback_ns: AtomicU64,
+    last_callback_ns: AtomicU64,
+}
+
+/// Shared view of a driver's stream statistics, from [`Driver::stats_handle`](crate::Driver::stats_handle).
+///
+/// Cheap to clone and safe to read from any thread (e.g. a UI timer) while the stream runs. Values
+/// are updated independently, so a read of several fields is not a consistent snapshot.
+#[derive(Clone, Default)]
+pub struct Stats(pub(crate) Arc<StatsInner>);
+
+impl Stats {
+    /// Underruns the driver reported since the stream was loaded or [`Stats::reset`].
+    pub fn underruns(&self) -> u64 { self.0.underruns.load(Ordering::Relaxed) }
+    /// Overruns the driver reported since the stream was loaded or [`Stats::reset`].
+    pub fn overruns(&self) -> u64 { self.0.overruns.load(Ordering::Relaxed) }
+    /// Blocks the driver delivered with the device buffer nearly drained: a sign to raise the
+    /// buffer size before [`Stats::underruns`] starts counting. Zero for drivers that don't report it.
+    pub fn near_xruns(&self) -> u64 { self.0.near_xruns.load(Ordering::Relaxed) }
+    /// `process` calls the driver timed past its `callback_threshold_ns` option; zero for drivers
+    /// without one.
+    pub fn slow_callbacks(&self) -> u64 { self.0.slow_callbacks.load(Ordering::Relaxed) }
+    /// Calls into [`HostProcess::process`](crate::HostProcess::process).
+    pub fn callbacks_dispatched(&self) -> u64 { self.0.callbacks_dispatched.load(Ordering::Relaxed) }
+    /// Longest time spent in one `process` call, in nanoseconds.
+    pub fn max_callback_ns(&self) -> u64 { self.0.max_callback_ns.load(Ordering::Relaxed) }
+    /// Time spent in the most recent `process` call, in nanoseconds.
+    pub fn last_callback_ns(&self) -> u64 { self.0.last_callback_ns.load(Ordering::Relaxed) }
+    /// Zero every counter.
+    pub fn reset(&self) {
+        for field in [&self.0.underruns, &self.0.overruns, &self.0.near_xruns, &self.0.slow_callbacks, &self.0.callbacks_dispatched, &self.0.max_callback_ns, &self.0.last_callback_ns] {
+            field.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_underruns(&self, n: u32) { self.0.underruns.fetch_add(n.into(), Ordering::Relaxed); }
+    pub(crate) fn add_overruns(&self, n: u32) { self.0.overruns.fetch_add(n.into(), Ordering::Relaxed); }
+    pub(crate) fn add_near_xruns(&self, n: u32) { self.0.near_xruns.fetch_add(n.into(), Ordering::Relaxed); }
+    pub(crate) fn add_slow_callbacks(&self, n: u32) { self.0.slow_callbacks.fetch_add(n.into(), Ordering::Relaxed); }
+    pub(crate) fn record_callback(&self, took: Duration) {
+        let ns = took.as_nanos().min(u64::MAX as u128) as u64;
+        self.0.callbacks_dispatched.fetch_add(1, Ordering::Relaxed);
+        self.0.last_callback_ns.store(ns, Ordering::Relaxed);
+        self.0.max_callback_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Debug for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stats")
+            .field("underruns", &self.underruns())
+            .field("overruns", &self.overruns())
+            .field("near_xruns", &self.near_xruns())
+            .field("slow_callbacks", &self.slow_callbacks())
+            .field("callbacks_dispatched", &self.callbacks_dispatched())
+            .field("max_callback_ns", &self.max_callback_ns())
+            .field("last_callback_ns", &self.last_callback_ns())
+            .finish()
+    }
+}
@@ -0,0 +1,92 @@
+//! The queue behind [`Driver::xrun_receiver`](crate::Driver::xrun_receiver): a fixed ring the RT
+//! callback writes without locking or allocating, overwriting the oldest event once it is full.
+use crate::{XrunEvent, XrunKind};
+use std::cell::Cell;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// One event behind a sequence lock: `version` is odd while the writer is in the slot and
+/// `2 * (pos + 1)` once event `pos` is complete.
+#[derive(Default)]
+struct Slot {
+    version: AtomicU64,
+    kind: AtomicU64,
+    timestamp_ns: AtomicU64,
+    total_count: AtomicU64,
+}
+
+pub(crate) struct XrunQueue {
+    slots: Box<[Slot]>,
+    written: AtomicU64, // events pushed since the queue was created
+}
+
+fn kind_code(kind: XrunKind) -> u64 {
+    match kind { XrunKind::Underrun => 0, XrunKind::Overrun => 1, XrunKind::SlowCallback => 2 }
+}
+
+fn kind_of(code: u64) -> Option<XrunKind> {
+    match code { 0 => Some(XrunKind::Underrun), 1 => Some(XrunKind::Overrun), 2 => Some(XrunKind::SlowCallback), _ => None }
+}
+
+impl XrunQueue {
+    pub(crate) fn new(len: usize) -> Arc<Self> {
+        Arc::new(Self { slots: (0..len.max(1)).map(|_| Slot::default()).collect(), written: AtomicU64::new(0) })
+    }
+
+    /// Append `ev`, overwriting the oldest event if the reader has not kept up. Only one thread
+    /// (the RT callback) may push; it never waits for the reader.
+    pub(crate) fn force_push(&self, ev: XrunEvent) {
+        let pos = self.written.load(Ordering::Relaxed);
+        let slot = &self.slots[(pos % self.slots.len() as u64) as usize];
+        slot.version.store(2 * pos + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.kind.store(kind_code(ev.xrun_type), Ordering::Relaxed);
+        slot.timestamp_ns.store(ev.timestamp_ns, Ordering::Relaxed);
+        slot.total_count.store(ev.total_count, Ordering::Relaxed);
+        slot.version.store(2 * pos + 2, Ordering::Release);
+        self.written.store(pos + 1, Ordering::Release);
+    }
+
+    /// Event `pos`, or `None` if the writer has moved on to a later event in its slot.
+    fn read(&self, pos: u64) -> Option<XrunEvent> {
+        let slot = &self.slots[(pos % self.slots.len() as u64) as usize];
+        let done = 2 * pos + 2;
+        if slot.version.load(Ordering::Acquire) != done { return None; }
+        let kind = slot.kind.load(Ordering::Relaxed);
+        let timestamp_ns = slot.timestamp_ns.load(Ordering::Relaxed);
+        let total_count = slot.total_count.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if slot.version.load(Ordering::Relaxed) != done { return None; }
+        Some(XrunEvent { xrun_type: kind_of(kind)?, timestamp_ns, total_count })
+    }
+}
+
+/// Xruns as the driver reports them, from [`Driver::xrun_receiver`](crate::Driver::xrun_receiver).
+///
+/// Holds the latest [`XRUN_QUEUE_LEN`](crate::XRUN_QUEUE_LEN) events. When more arrive before
+/// they are read the oldest are dropped; the `total_count` of the ones kept still accounts for
+/// them.
+pub struct XrunReceiver {
+    queue: Arc<XrunQueue>,
+    next: Cell<u64>, // position of the next event to read
+}
+
+impl XrunReceiver {
+    pub(crate) fn new(queue: Arc<XrunQueue>) -> Self { Self { queue, next: Cell::new(0) } }
+
+    /// The oldest event still held, or `None` once every event has been read.
+    pub fn try_recv(&self) -> Option<XrunEvent> {
+        let written = self.queue.written.load(Ordering::Acquire);
+        let mut next = self.next.get().max(written.saturating_sub(self.queue.slots.len() as u64));
+        let mut ev = None;
+        while ev.is_none() && next < written {
+            ev = self.queue.read(next);
+            next += 1;
+        }
+        self.next.set(next);
+        ev
+    }
+
+    /// Every event held right now, oldest first.
+    pub fn try_iter(&self) -> impl Iterator<Item = XrunEvent> + '_ { std::iter::from_fn(|| self.try_recv()) }
+}
@@ -0,0 +1,49 @@
+//! A fresh driver per run, created through each driver's `DriverFactory` with no library loaded.
+use openasio_driver_alsa17h::Alsa17h;
+use openasio_driver_umc202hd::Umc202hd;
+use openasio_sys as sys;
+use std::os::raw::c_void;
+use std::ptr;
+
+unsafe extern "C" fn process(
+    _user: *mut c_void,
+    _in_ptr: *const c_void,
+    _out_ptr: *mut c_void,
+    _frames: u32,
+    _time: *const sys::oa_time_info,
+    _cfg: *const sys::oa_stream_config,
+) -> sys::oa_bool {
+    sys::OA_FALSE
+}
+
+static HOST: sys::oa_host_callbacks = sys::oa_host_callbacks {
+    process: Some(process),
+    ..sys::oa_host_callbacks::empty()
+};
+
+/// Discards driver logging, which would otherwise dominate each run.
+unsafe extern "C" fn quiet(_user: *mut c_void, _level: u32, _msg: *const std::os::raw::c_char) {}
+
+/// Run `f` on a new instance of every fuzzed driver, destroying each afterwards.
+pub fn each_driver(mut f: impl FnMut(&sys::oa_driver_vtable, *mut sys::oa_driver)) {
+    for_factory::<Alsa17h>(&mut f);
+    for_factory::<Umc202hd>(&mut f);
+}
+
+fn for_factory<F: sys::DriverFactory>(f: &mut impl FnMut(&sys::oa_driver_vtable, *mut sys::oa_driver)) {
+    let params = sys::oa_create_params {
+        log_cb: Some(quiet),
+        ..sys::oa_create_params::new(&HOST, ptr::null_mut())
+    };
+    let mut drv = ptr::null_mut();
+    unsafe {
+        assert_eq!(F::create(&params, &mut drv), sys::OA_OK);
+        f(&*(*drv).vt, drv);
+        F::destroy(drv);
+    }
+}
+
+/// Fail the run unless `rc` is success or one of the `OA_ERR_*` codes.
+pub fn assert_valid(call: &str, rc: sys::oa_result) {
+    assert_ne!(sys::oa_strerror(rc), "unknown error", "{call} returned {rc}");
+}
@@ -0,0 +1,20 @@
+//! `open_device` with arbitrary names: embedded NULs, invalid UTF-8, very long strings, or null.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use openasio_sys as sys;
+
+mod common;
+
+fuzz_target!(|data: &[u8]| {
+    // An empty input stands for a null name; otherwise the C string ends at the first NUL.
+    let mut name = data.to_vec();
+    name.push(0);
+    common::each_driver(|vt, drv| unsafe {
+        let ptr = if data.is_empty() { std::ptr::null() } else { name.as_ptr().cast() };
+        let rc = (vt.open_device.unwrap())(drv, ptr);
+        common::assert_valid("open_device", rc);
+        if rc == sys::OA_OK {
+            common::assert_valid("close_device", (vt.close_device.unwrap())(drv));
+        }
+    });
+});
@@ -0,0 +1,35 @@
+//! `query_devices` with any buffer length, from 0 to far past the buffer, and with a null buffer.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use openasio_sys as sys;
+
+mod common;
+
+/// Bytes really allocated. Longer lengths are passed as is: a driver writes only its list and the
+/// NUL, so anything past this is a driver bug the sanitizer reports.
+const BUF: usize = 64 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&null, rest)) = data.split_first() else {
+        return;
+    };
+    let mut raw = [0u8; 8];
+    let n = rest.len().min(8);
+    raw[..n].copy_from_slice(&rest[..n]);
+    let len = u64::from_le_bytes(raw) as usize;
+    common::each_driver(|vt, drv| unsafe {
+        let query = vt.query_devices.unwrap();
+        if null & 1 == 1 {
+            let rc = query(drv, std::ptr::null_mut(), len);
+            common::assert_valid("query_devices(NULL)", rc);
+            assert!(len == 0 || rc != sys::OA_OK, "null buffer of {len} accepted");
+            return;
+        }
+        let mut buf = vec![0xFFu8; BUF];
+        let rc = query(drv, buf.as_mut_ptr().cast(), len);
+        common::assert_valid("query_devices", rc);
+        if rc == sys::OA_OK && len > 0 {
+            assert!(buf[..len.min(BUF)].contains(&0), "list not terminated within {len}");
+        }
+    });
+});
@@ -1,21 +1,17 @@
 /*
  OpenASIO: permissive, ASIO-like realtime audio driver ABI.
- Version: 1.0.0 — NOT affiliated with Steinberg ASIO®.
+ NOT affiliated with Steinberg ASIO(R).
  License: MIT OR Apache-2.0
 */
+
 #ifndef OPENASIO_H
 #define OPENASIO_H
-#ifdef __cplusplus
-extern "C" {
-#endif
+
+/* Generated from openasio-sys by `cargo xtask header`. Do not edit by hand. */
 
 #include <stdint.h>
 #include <stddef.h>
 
-#define OA_VERSION_MAJOR 1
-#define OA_VERSION_MINOR 0
-#define OA_VERSION_PATCH 0
-
 #if defined(_WIN32) || defined(__CYGWIN__)
   #ifdef OA_BUILDING_DLL
     #define OA_API __declspec(dllexport)
@@ -26,117 +22,481 @@ extern "C" {
   #define OA_API __attribute__((visibility("default")))
 #endif
 
+// How get_last_error starts when an exclusive start failed because the device is shared.
+#define OA_EXCLUSIVE_UNAVAILABLE "exclusive access unavailable"
+
+// Bit for an oa_sample_format in an oa_supported_configs::formats mask.
+#define OA_FORMAT_BIT(fmt) (1u << (uint32_t)(fmt))
+
+// The vtable's entries take the driver they belong to, which may be declared after it.
+struct oa_driver;
+
+#define OA_VERSION_MAJOR 1
+
+#define OA_VERSION_MINOR 0
+
+#define OA_VERSION_PATCH 0
+
+// What `openasio_abi_version` returns: the major version in the high 16 bits, the minor in the
+// low 16. Hosts refuse drivers whose major differs from theirs; a newer minor only adds fields.
+#define OA_ABI_VERSION ((OA_VERSION_MAJOR << 16) | OA_VERSION_MINOR)
+
+// `oa_direction` values as `get_channel_names` and `set_channel_gain` take them.
+#define OA_DIR_INPUT 0
+
+#define OA_DIR_OUTPUT 1
+
+#define OA_CAP_OUTPUT (1 << 0)
+
+#define OA_CAP_INPUT (1 << 1)
+
+#define OA_CAP_FULL_DUPLEX (1 << 2)
+
+#define OA_CAP_SET_SAMPLERATE (1 << 3)
+
+#define OA_CAP_SET_BUFFRAMES (1 << 4)
+
+#define OA_CAP_CLOCK_SELECT (1 << 5)
+
+// Output is routed back to an input through shared memory rather than to hardware.
+#define OA_CAP_LOOPBACK (1 << 6)
+
+// `oa_time_info::sample_position` is filled in. Without it hosts must count frames themselves.
+#define OA_CAP_SAMPLE_POSITION (1 << 7)
+
+// `set_channel_mask` is honored: disabled channels are left out of the buffers `process` sees.
+#define OA_CAP_CHANNEL_MASK (1 << 8)
+
+// `oa_stream_config::exclusive` is honored: the driver can bypass any sound server or software mixer.
+#define OA_CAP_EXCLUSIVE (1 << 9)
+
+// The open device has a MIDI port: `send_midi` is implemented and `midi_in` may be called.
+#define OA_CAP_MIDI (1 << 10)
+
+// The driver can move samples through the device's DMA buffer directly, without a copy through
+// the kernel, once asked to with `set_option("access", "mmap")`.
+#define OA_CAP_MMAP (1 << 11)
+
+// Most channels a stream may have in either direction.
+#define OA_MAX_CHANNELS 64
+
+// Lowest sample rate a stream may run at.
+#define OA_MIN_SAMPLE_RATE 8000
+
+// Highest sample rate a stream may run at.
+#define OA_MAX_SAMPLE_RATE 768000
+
+// Largest linear gain `set_channel_gain` accepts (about +12 dB); larger values are clamped.
+#define OA_MAX_CHANNEL_GAIN 4.0
+
+#define OA_DEVICE_INFO_STR_LEN 256
+
+#define OA_DRIVER_INFO_STR_LEN 64
+
+#define OA_LOG_ERROR 1
+
+#define OA_LOG_WARN 2
+
+#define OA_LOG_INFO 3
+
+#define OA_LOG_DEBUG 4
+
+#define OA_LOG_TRACE 5
+
+// `oa_create_params::rt_priority`: let the driver choose.
+#define OA_RT_PRIORITY_DEFAULT -1
+
+// Largest `struct_size` a reader accepts. No ABI struct comes near it, so a larger value is
+// garbage, e.g. an uninitialised field, not a struct from a newer minor version.
+#define OA_MAX_STRUCT_SIZE 4096
+
+#define OA_CLOCK_SOURCE_NAME_LEN 64
+
+typedef int32_t oa_result;
+
+typedef int32_t oa_sample_format;
+
+typedef int32_t oa_buffer_layout;
+
 typedef int32_t oa_bool;
-enum { OA_FALSE = 0, OA_TRUE = 1 };
-
-typedef enum {
-  OA_OK              =  0,
-  OA_ERR_GENERIC     = -1,
-  OA_ERR_UNSUPPORTED = -2,
-  OA_ERR_INVALID_ARG = -3,
-  OA_ERR_DEVICE      = -4,
-  OA_ERR_BACKEND     = -5,
-  OA_ERR_STATE       = -6,
-} oa_result;
-
-typedef enum {
-  OA_SAMPLE_F32 = 1,   // native float32 [-1,+1]
-  OA_SAMPLE_I16 = 2,
-} oa_sample_format;
-
-typedef enum {
-  OA_BUF_INTERLEAVED    = 1, // frames*channels
-  OA_BUF_NONINTERLEAVED = 2, // array of channel pointers
-} oa_buffer_layout;
-
-// Capability bitfield (bitwise OR)
-typedef enum {
-  OA_CAP_OUTPUT         = 1<<0,
-  OA_CAP_INPUT          = 1<<1,
-  OA_CAP_FULL_DUPLEX    = 1<<2,
-  OA_CAP_SET_SAMPLERATE = 1<<3,
-  OA_CAP_SET_BUFFRAMES  = 1<<4,
-} oa_caps;
-
-typedef struct {
-  uint32_t sample_rate;     // Hz
-  uint32_t buffer_frames;   // frames per callback (target; driver may adjust)
-  uint16_t in_channels;     // inputs
-  uint16_t out_channels;    // outputs
-  oa_sample_format format;  // sample format
-  oa_buffer_layout layout;  // interleaved/non-interleaved
+
+typedef struct oa_stream_config {
+  uint32_t sample_rate;
+  uint32_t buffer_frames;
+  uint16_t in_channels;
+  uint16_t out_channels;
+  oa_sample_format format;
+  oa_buffer_layout layout;
+  // Nonzero to require exclusive access to the hardware. Drivers that report
+  // `OA_CAP_EXCLUSIVE` then refuse devices behind a mixer or converter with `OA_ERR_DEVICE`
+  // and a `get_last_error` text starting with [`OA_EXCLUSIVE_UNAVAILABLE`]. Present only if
+  // `oa_create_params::config_size` covers it; read through [`read_stream_config`].
+  oa_bool exclusive;
 } oa_stream_config;
 
-typedef struct {
-  uint64_t host_time_ns;    // host monotonic time
-  uint64_t device_time_ns;  // device clock (0 if unknown)
-  uint32_t underruns;       // since last callback
-  uint32_t overruns;        // since last callback
-} oa_time_info;
+// Filled by `query_supported_configs`. `sample_rates` points at driver-owned storage that stays
+// valid until the next call on the same driver or until it is destroyed.
+typedef struct oa_supported_configs {
+  const uint32_t *sample_rates;
+  uint32_t sample_rate_count;
+  uint32_t min_buffer_frames;
+  uint32_t max_buffer_frames;
+  uint32_t formats;
+} oa_supported_configs;
 
-struct oa_driver;
-typedef struct oa_driver oa_driver;
+// Filled by `query_device_info`. The host sets `struct_size`; drivers write only the fields it
+// covers (see [`write_device_info`]).
+typedef struct oa_device_info {
+  uint32_t struct_size;
+  char id[OA_DEVICE_INFO_STR_LEN];
+  char description[OA_DEVICE_INFO_STR_LEN];
+  uint32_t max_in_channels;
+  uint32_t max_out_channels;
+  uint32_t min_buffer_frames;
+  uint32_t max_buffer_frames;
+} oa_device_info;
+
+// Filled by `get_status`. The host sets `struct_size`; drivers write only the fields it covers
+// (see [`write_stream_status`]).
+typedef struct oa_stream_status {
+  uint32_t struct_size;
+  oa_bool running;
+  uint32_t actual_sample_rate;
+  uint32_t actual_buffer_frames;
+  uint64_t frames_processed;
+  uint32_t underruns;
+  uint32_t overruns;
+  uint32_t near_xrun_count;
+  // Captured frames waiting for `process`, for drivers that queue input between device
+  // callbacks (the CPAL driver); 0 for the others.
+  uint32_t input_queued_frames;
+  // Longest single `process` call since the last start, in nanoseconds; 0 from drivers that
+  // do not time it.
+  uint64_t max_callback_ns;
+} oa_stream_status;
+
+// One entry of `query_clock_sources`.
+typedef struct oa_clock_source {
+  uint32_t index;
+  char name[OA_CLOCK_SOURCE_NAME_LEN];
+  oa_bool current;
+} oa_clock_source;
+
+// Which side of the device a channel is on, for `get_channel_name`.
+typedef int32_t oa_direction;
+
+// One entry of `query_config_ranges`: every combination of the listed rates, channel counts,
+// formats and buffer sizes is accepted. Ranges are inclusive.
+typedef struct oa_config_range {
+  uint32_t min_rate;
+  uint32_t max_rate;
+  uint32_t min_channels_in;
+  uint32_t max_channels_in;
+  uint32_t min_channels_out;
+  uint32_t max_channels_out;
+  uint32_t formats_mask;
+  uint32_t min_buffer_frames;
+  uint32_t max_buffer_frames;
+} oa_config_range;
+
+// Device channels a stream carries, bit n for channel n; see `set_channel_mask`.
+typedef struct oa_channel_mask {
+  uint64_t in_mask;
+  uint64_t out_mask;
+} oa_channel_mask;
 
-// Host callbacks: invoked by the driver on its RT thread.
-typedef struct {
-  // In non-interleaved mode: `in` is const void** (one per input ch), `out` is void** (one per output ch).
-  // In interleaved mode:     `in` is const void* samples,          `out` is void* samples.
+// One short MIDI message: a status byte and its data bytes in `data[..size]`. `sample_offset`
+// places it in a block, in frames from the block's first. System exclusive is not carried.
+typedef struct oa_midi_event {
+  uint32_t sample_offset;
+  uint8_t size;
+  uint8_t data[3];
+} oa_midi_event;
+
+// Entries are only ever appended. `struct_size` is the size of the table the driver was built
+// with; hosts read an entry only if it lies wholly inside that size and treat null or missing
+// entries past the 1.0 table as unsupported.
+typedef struct oa_driver_vtable {
+  uint32_t struct_size;
+  uint32_t (*get_caps)(struct oa_driver*);
+  // List device names, one per line, NUL-terminated. Returns `OA_OK` when the list fits in
+  // `len` bytes; otherwise writes an empty string and returns the bytes needed, NUL included.
+  int32_t (*query_devices)(struct oa_driver*, char*, size_t);
+  int32_t (*open_device)(struct oa_driver*, const char*);
+  int32_t (*close_device)(struct oa_driver*);
+  int32_t (*get_default_config)(struct oa_driver*, struct oa_stream_config*);
+  int32_t (*start)(struct oa_driver*, const struct oa_stream_config*);
+  // Stop streaming once the audio already queued to the device has played out.
+  int32_t (*stop)(struct oa_driver*);
+  int32_t (*get_latency)(struct oa_driver*, uint32_t*, uint32_t*);
+  int32_t (*set_sample_rate)(struct oa_driver*, uint32_t);
+  int32_t (*set_buffer_frames)(struct oa_driver*, uint32_t);
+  int32_t (*query_supported_configs)(struct oa_driver*, struct oa_supported_configs*);
+  // Fill up to `count` pointers with channel names for `direction` (`OA_DIR_*`); returns the
+  // number written. Strings are driver-owned and valid until the next call or destroy.
+  int32_t (*get_channel_names)(struct oa_driver*, uint32_t, const char**, uint32_t);
+  // Copy the accepted sample rates into `buf[..len]`. Returns the number written, or the
+  // required count (without writing) when `len` is too small.
+  int32_t (*query_supported_rates)(struct oa_driver*, uint32_t*, size_t);
+  // Newline-separated clock source names into `buf` (NUL-terminated, truncated to `len`).
+  // Returns the number of sources, or `OA_ERR_UNSUPPORTED` without a selectable clock.
+  int32_t (*enumerate_clock_sources)(struct oa_driver*, char*, size_t);
+  // Switch to the named clock source from `enumerate_clock_sources`. Not while running.
+  int32_t (*select_clock_source)(struct oa_driver*, const char*);
+  // Describe device `name` (NULL or "" = the open or default device) without opening it for
+  // streaming. `OA_ERR_DEVICE` if no such device exists.
+  int32_t (*query_device_info)(struct oa_driver*, const char*, struct oa_device_info*);
+  // Like `start`, but the worker holds the device until `trigger_start`. The driver is
+  // `Running` afterwards, so `stop` cancels an armed stream.
+  int32_t (*arm_start)(struct oa_driver*, const struct oa_stream_config*);
+  // Start an armed stream once `CLOCK_REALTIME` reaches `start_ns`. `OA_ERR_STATE` if not armed.
+  int32_t (*trigger_start)(struct oa_driver*, uint64_t);
+  // Report whether the stream is running, its actual rate and period, and its frame and xrun
+  // counts. Valid in any phase; a stopped driver reports `running = OA_FALSE`.
+  int32_t (*get_status)(struct oa_driver*, struct oa_stream_status*);
+  // Copy the device's clock sources into `buf[..len]` like `query_supported_rates`: returns the
+  // number written, or the required count (without writing) when `len` is too small. Unlike
+  // `enumerate_clock_sources` every driver answers; one without external sync reports a
+  // single current "Internal" source.
+  int32_t (*query_clock_sources)(struct oa_driver*, struct oa_clock_source*, size_t);
+  // Sync to the source with `index` from `query_clock_sources`. Not while running.
+  int32_t (*set_clock_source)(struct oa_driver*, uint32_t);
+  // Hardware control: turn the capture DC blocker on or off. Allowed in any phase; takes
+  // effect from the next period. Null when the driver has no such filter.
+  int32_t (*enable_dc_filter)(struct oa_driver*, oa_bool);
+  // Copy the name of channel `index` into `buf` (NUL-terminated, truncated to `len`) and
+  // return its full length in bytes, so a return `>= len` means it was cut. Returns
+  // `OA_ERR_INVALID_ARG` for an index past the last channel.
+  int32_t (*get_channel_name)(struct oa_driver*, oa_direction, uint32_t, char*, size_t);
+  // Linear trim gain for one channel in `direction` (`OA_DIR_*`), clamped to
+  // [0.0, `OA_MAX_CHANNEL_GAIN`] and applied from the next period. Allowed in any phase.
+  int32_t (*set_channel_gain)(struct oa_driver*, uint32_t, uint32_t, float);
+  // Stop calling `process` but keep the device configured, so `resume` carries on without
+  // renegotiating it the way `stop`/`start` does. `OA_ERR_STATE` unless running and not
+  // already paused. The driver stays `Running`: `stop` is valid while paused.
+  int32_t (*pause)(struct oa_driver*);
+  // Resume calling `process` after `pause`; `sample_position` continues from where it stopped.
+  // `OA_ERR_STATE` if not paused.
+  int32_t (*resume)(struct oa_driver*);
+  // Drop all queued audio and stop as fast as possible, e.g. for a panic button. Leaves the
+  // driver `Configured` like `stop`, which instead waits for the queued audio to play out.
+  // `OA_ERR_STATE` unless running.
+  int32_t (*abort)(struct oa_driver*);
+  // Copy a description of the driver's most recent failure into `buf` (NUL-terminated,
+  // truncated to `len`) and return its full length, so a return `>= len` means it was cut.
+  // Returns 0 when nothing has failed yet.
+  int32_t (*get_last_error)(struct oa_driver*, char*, size_t);
+  // Copy the configurations the open device accepts into `buf[..len]` like
+  // `query_supported_rates`: returns the number written, or the required count (without
+  // writing) when `len` is too small. A device with gaps in its ranges reports several.
+  int32_t (*query_config_ranges)(struct oa_driver*, struct oa_config_range*, size_t);
+  // Carry only the device channels set in `mask` (null for all) from the next `start`. The
+  // stream config still counts device channels; `process` gets the enabled ones packed in
+  // channel order, and its config carries their counts. `OA_ERR_STATE` while running,
+  // `OA_ERR_UNSUPPORTED` for a direction the driver cannot mask. Drivers report
+  // `OA_CAP_CHANNEL_MASK`.
+  int32_t (*set_channel_mask)(struct oa_driver*, const struct oa_channel_mask*);
+  // Queue `events[..count]` for the device's MIDI output, each due `sample_offset` frames
+  // after the call at the running stream's rate (at once when stopped). Returns
+  // `OA_ERR_UNSUPPORTED` when the open device has no MIDI port, `OA_ERR_INVALID_ARG` for an
+  // event [`midi_message_len`] rejects. Drivers report `OA_CAP_MIDI`.
+  int32_t (*send_midi)(struct oa_driver*, const struct oa_midi_event*, uint32_t);
+  // Show the driver's own settings window, like an ASIO control panel. Null for drivers with
+  // nothing to show, which is most on Linux; they take settings through `set_option` instead.
+  int32_t (*show_control_panel)(struct oa_driver*);
+  // Set the driver-specific setting `key` to `value`, both NUL-terminated text. Allowed in any
+  // phase; settings that shape the stream take effect from the next `start`. Returns
+  // `OA_ERR_UNSUPPORTED` for a key or value the driver does not take, `OA_ERR_INVALID_ARG`
+  // for a value it cannot parse.
+  int32_t (*set_option)(struct oa_driver*, const char*, const char*);
+  // Copy the current value of `key` into `buf` (NUL-terminated, truncated to `len`) and return
+  // its full length, like `get_last_error`. `OA_ERR_UNSUPPORTED` for a key `set_option` would
+  // not take.
+  int32_t (*get_option)(struct oa_driver*, const char*, char*, size_t);
+} oa_driver_vtable;
+
+// Header of every driver instance: drivers put it first in their `#[repr(C)]` state and point
+// `vt` at a table that outlives the instance, typically a `static`.
+typedef struct oa_driver {
+  const struct oa_driver_vtable *vt;
+} oa_driver;
+
+// Which direction glitched, passed to `oa_host_callbacks::xrun`.
+typedef int32_t oa_xrun_kind;
+
+typedef struct oa_time_info {
+  uint64_t host_time_ns;
+  uint64_t device_time_ns;
+  uint32_t underruns;
+  uint32_t overruns;
+  // Blocks that reached the device with little of the previous one left to play, since the
+  // last start: a warning before `underruns` moves. Absent from 1.0 drivers; see [`time_info_has_near_xrun`].
+  uint32_t near_xrun;
+  // Frames passed to `process` since the last start, before this block: 0 in the first
+  // callback, advancing by exactly `frames` per call. Only drivers reporting
+  // `OA_CAP_SAMPLE_POSITION` have (and write) this field.
+  uint64_t sample_position;
+} oa_time_info;
+
+// Host entry points. Fields after `reset_request` exist only if `oa_create_params::host_size`
+// covers them; drivers read the struct through [`host_callbacks`].
+typedef struct oa_host_callbacks {
+  // Required; drivers refuse to be created without it. The other 1.0 callbacks,
+  // `latency_changed` and `reset_request`, are optional.
   oa_bool (*process)(void *user,
-                     const void *in,
-                     void *out,
+                     const void *in_ptr,
+                     void *out_ptr,
                      uint32_t frames,
-                     const oa_time_info *time,
-                     const oa_stream_config *cfg);
-  void (*latency_changed)(void *user, uint32_t input_latency, uint32_t output_latency); // optional
-  void (*reset_request)(void *user); // optional
+                     const struct oa_time_info *time,
+                     const struct oa_stream_config *cfg);
+  void (*latency_changed)(void *user, uint32_t in_latency, uint32_t out_latency);
+  void (*reset_request)(void *user);
+  // Optional. The set of devices `query_devices` would report has changed (hot-plug).
+  // Called from a driver thread, never the RT thread.
+  void (*device_list_changed)(void *user);
+  // Optional. An xrun happened; `count` is the new total for `kind`, the same counter
+  // `oa_time_info` reports. Called from the driver's RT thread as soon as it is detected.
+  void (*xrun)(void *user, oa_xrun_kind kind, uint32_t count);
+  // Optional. MIDI that arrived during the block about to be processed, in time order, each
+  // event's `sample_offset` counting frames into that block. Called from the RT thread right
+  // before `process`, only by drivers reporting `OA_CAP_MIDI`; `events` is valid for the call only.
+  void (*midi_in)(void *user, const struct oa_midi_event *events, uint32_t count);
 } oa_host_callbacks;
 
-// Creation parameters for a driver instance
-typedef struct {
-  uint32_t struct_size;      // set to sizeof(oa_create_params)
-  const oa_host_callbacks *host;
+// Receives driver log lines (`level` is one of `OA_LOG_*`, `msg` is NUL-terminated UTF-8).
+// May be called from any driver thread, including the RT thread. `None` (NULL) = no callback.
+typedef void (*oa_log_fn)(void *user, uint32_t level, const char *msg);
+
+typedef struct oa_create_params {
+  uint32_t struct_size;
+  const struct oa_host_callbacks *host;
   void *host_user;
+  oa_log_fn log_cb;
+  void *log_user;
+  // `sizeof(oa_host_callbacks)` as the host built it.
+  uint32_t host_size;
+  // Scheduling for the driver's audio thread: `OA_RT_PRIORITY_DEFAULT` leaves it to the driver,
+  // 0 asks for normal (non-real-time) scheduling and 1..=99 for `SCHED_FIFO` at that priority.
+  int32_t rt_priority;
+  // CPUs the audio thread may run on, bit n for CPU n; 0 leaves it unset.
+  uint64_t cpu_affinity_mask;
+  // `sizeof(oa_stream_config)` as the host built it, for the configs it passes and receives.
+  uint32_t config_size;
+  // Backend hints as comma-separated `key=value` text, e.g. `host=jack` or
+  // `device=hw:2,period_count=3`; null or empty for none. A value holding a comma is
+  // double-quoted, with `\"` and `\\` inside the quotes. Drivers ignore keys they do not
+  // know; parse with [`CreateOptions`].
+  const char *options;
 } oa_create_params;
 
-// Function table implemented by the driver
-typedef struct {
-  uint32_t struct_size; // sizeof(oa_driver_vtable)
+// Mandatory export `openasio_driver_create`: validate `params`, build a driver in the `Created`
+// phase and store it in `*out`. Returns `OA_OK`, or an error leaving `*out` untouched.
+typedef int32_t (*openasio_driver_create_fn)(const struct oa_create_params *params,
+                                             struct oa_driver **out);
 
-  // Capabilities bit mask (OR of oa_caps)
-  uint32_t (*get_caps)(oa_driver *self);
+// Mandatory export `openasio_driver_destroy`: stop and free a driver from `openasio_driver_create`.
+// Null is ignored. The host owns the driver and must destroy it exactly once, and must not use
+// `driver` afterwards; debug builds of the ALSA drivers log and ignore a second destroy.
+typedef void (*openasio_driver_destroy_fn)(struct oa_driver *driver);
 
-  // Optional device enumeration: newline-separated names into buf. Returns OA_OK or error.
-  oa_result (*query_devices)(oa_driver *self, char *buf, size_t buf_len);
+// Filled by a library's optional `openasio_driver_get_info` export, which hosts may call without
+// creating a driver (e.g. to show "Behringer UMC202HD 0.3.0 by OpenASIO project" in a driver
+// list). The host sets `struct_size`.
+typedef struct oa_driver_info {
+  uint32_t struct_size;
+  char name[OA_DRIVER_INFO_STR_LEN];
+  char vendor[OA_DRIVER_INFO_STR_LEN];
+  uint32_t version_major;
+  uint32_t version_minor;
+  uint32_t version_patch;
+  uint32_t api_major;
+  uint32_t api_minor;
+} oa_driver_info;
 
-  // Open by name (NULL or "" = default). Returns >=0 device_id or <0 error.
-  int32_t  (*open_device)(oa_driver *self, const char *name);
-  oa_result (*close_device)(oa_driver *self);
+// Optional export; see [`oa_driver_info`].
+typedef int32_t (*openasio_driver_get_info_fn)(struct oa_driver_info *out);
 
-  // Retrieve a default config for the currently open device.
-  oa_result (*get_default_config)(oa_driver *self, oa_stream_config *out);
+// Export every driver provides except those built against the first 1.0 SDK. See [`OA_ABI_VERSION`].
+typedef uint32_t (*openasio_abi_version_fn)(void);
 
-  // Start/stop streaming. On start, driver begins invoking host.process() on its RT thread.
-  oa_result (*start)(oa_driver *self, const oa_stream_config *cfg);
-  oa_result (*stop)(oa_driver *self);
+// A set of `OA_CAP_*` bits, as `get_caps` returns it.
+typedef uint32_t oa_caps;
 
-  // Latency reporting in frames (<=0 if unknown).
-  oa_result (*get_latency)(oa_driver *self, uint32_t *in_latency, uint32_t *out_latency);
+#define OA_FALSE 0
 
-  // Optional reconfiguration while stopped.
-  oa_result (*set_sample_rate)(oa_driver *self, uint32_t sr);
-  oa_result (*set_buffer_frames)(oa_driver *self, uint32_t frames);
-} oa_driver_vtable;
+#define OA_TRUE 1
+
+#define OA_OK 0
+
+#define OA_ERR_GENERIC -1
 
-// Opaque driver instance
-struct oa_driver {
-  const oa_driver_vtable *vt;
-};
+#define OA_ERR_UNSUPPORTED -2
 
-// Mandatory factory symbols (C ABI)
-typedef int32_t (*openasio_driver_create_fn)(const oa_create_params*, oa_driver**);
-typedef void    (*openasio_driver_destroy_fn)(oa_driver*);
+#define OA_ERR_INVALID_ARG -3
+
+#define OA_ERR_DEVICE -4
+
+#define OA_ERR_BACKEND -5
+
+#define OA_ERR_STATE -6
+
+// The device exists but another client holds it, e.g. a sound server; closing it may help.
+#define OA_ERR_BUSY -7
+
+// The driver gave up waiting, e.g. for its worker thread to exit.
+#define OA_ERR_TIMEOUT -8
+
+// The call needs an open device and none is: `start` before `open_device` or after `close_device`.
+#define OA_ERR_NOT_OPEN -9
+
+// Native float, full scale [-1.0, +1.0].
+#define OA_SAMPLE_F32 1
+
+// Signed 16-bit, full scale ±32768.
+#define OA_SAMPLE_I16 2
+
+// Signed 32-bit, full scale ±2^31. 24-bit hardware is MSB-aligned (low byte zero).
+#define OA_SAMPLE_I32 3
+
+// Signed 24-bit packed in 3 little-endian bytes, full scale ±2^23.
+#define OA_SAMPLE_I24 4
+
+// Native double, full scale [-1.0, +1.0]. Never a driver's default; hosts ask for it.
+#define OA_SAMPLE_F64 5
+
+#define OA_BUF_INTERLEAVED 1
+
+#define OA_BUF_NONINTERLEAVED 2
+
+#define OA_XRUN_UNDERRUN 1
+
+#define OA_XRUN_OVERRUN 2
+
+// One `process` call ran longer than the limit the host set with a driver option (the alsa17h
+// driver's `callback_threshold_ns`); drivers send it only to hosts that set one.
+#define OA_XRUN_CALLBACK 3
 
 #ifdef __cplusplus
-}
-#endif
-#endif // OPENASIO_H
+extern "C" {
+#endif // __cplusplus
+
+// C export of [`oa_strerror`]; the returned string is static and must not be freed.
+const char *oa_strerror_c(oa_result code);
+
+// Start `count` drivers so their devices begin within one sample period of each other: every
+// driver is armed with its entry in `cfgs` first, then all are triggered for one
+// `CLOCK_REALTIME` instant. `OA_ERR_UNSUPPORTED` if any driver lacks
+// `arm_start`/`trigger_start`; on failure the drivers already armed are stopped again.
+//
+// # Safety
+// Unless `count` is 0, `drivers` and `cfgs` must each point at `count` elements; every non-null
+// driver must be live.
+oa_result openasio_start_synchronized(struct oa_driver *const *drivers,
+                                      const struct oa_stream_config *cfgs,
+                                      size_t count);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OPENASIO_H */
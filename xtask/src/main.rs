@@ -0,0 +1,140 @@
+//! Repository maintenance tasks, run as `cargo xtask <task>`.
+//!
+//! - `header`: regenerate the SDK's `sdk/include/openasio/openasio.h` from the Rust definitions.
+//! - `header --check`: fail if the committed header is out of date.
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+fn sys_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../crates/openasio-sys")
+}
+
+/// Include directory C users put on their path, for `#include <openasio/openasio.h>`.
+fn sdk_include_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../sdk/include")
+}
+
+fn header_path() -> PathBuf {
+    sdk_include_dir().join("openasio/openasio.h")
+}
+
+/// The C header cbindgen produces for `openasio-sys` with its `cbindgen.toml`.
+fn generate_header() -> Result<String, String> {
+    let dir = sys_dir();
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml"))?;
+    // The crate is a single source file; parsing it directly avoids `cargo metadata`.
+    let bindings = cbindgen::Builder::new()
+        .with_src(dir.join("src/lib.rs"))
+        .with_config(config)
+        .generate()
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    bindings.write(&mut out);
+    String::from_utf8(out).map_err(|e| e.to_string())
+}
+
+fn header(check: bool) -> Result<(), String> {
+    let generated = generate_header()?;
+    let path = header_path();
+    if check {
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        if committed != generated {
+            return Err(format!(
+                "{} is stale; run `cargo xtask header`",
+                path.display()
+            ));
+        }
+        return Ok(());
+    }
+    std::fs::write(&path, generated).map_err(|e| format!("writing {}: {e}", path.display()))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["header"] => header(false),
+        ["header", "--check"] => header(true),
+        _ => Err("usage: cargo xtask header [--check]".into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("xtask: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn committed_header_is_current() {
+        header(true).unwrap();
+    }
+
+    /// `cbindgen.toml` spells out the string constant cbindgen cannot translate.
+    #[test]
+    fn header_carries_the_string_constants() {
+        let define = format!(
+            "#define OA_EXCLUSIVE_UNAVAILABLE \"{}\"",
+            openasio_sys::OA_EXCLUSIVE_UNAVAILABLE
+        );
+        assert!(generate_header().unwrap().contains(&define), "{define}");
+    }
+
+    /// Build a translation unit that touches every entry point typedef and the vtable, as C99
+    /// and as C++, with warnings as errors.
+    #[test]
+    fn header_compiles() {
+        let out = std::env::temp_dir().join(format!("openasio-header-{}", std::process::id()));
+        std::fs::create_dir_all(&out).unwrap();
+        let src = out.join("compile_test.c");
+        std::fs::write(
+            &src,
+            r#"#include <openasio/openasio.h>
+OA_API int32_t openasio_driver_create(const oa_create_params *params, oa_driver **out);
+static oa_result start(oa_driver *self, const oa_stream_config *cfg) { (void)self; (void)cfg; return OA_OK; }
+static oa_driver_vtable vt;
+int main(void) {
+    oa_caps caps = OA_CAP_OUTPUT | OA_CAP_INPUT;
+    uint32_t formats = OA_FORMAT_BIT(OA_SAMPLE_F32);
+    const char *busy = OA_EXCLUSIVE_UNAVAILABLE;
+    openasio_driver_create_fn create = openasio_driver_create;
+    openasio_driver_destroy_fn destroy = 0;
+    openasio_abi_version_fn abi = 0;
+    oa_stream_config cfg = {48000, 256, 2, 2, OA_SAMPLE_F32, OA_BUF_INTERLEAVED, OA_FALSE};
+    vt.struct_size = sizeof vt;
+    vt.start = start;
+    (void)create; (void)destroy; (void)abi; (void)caps; (void)formats; (void)busy;
+    return vt.start(0, &cfg) == OA_OK ? 0 : 1;
+}
+"#,
+        )
+        .unwrap();
+        let include = sdk_include_dir();
+        for (compiler, std) in [("cc", "-std=c99"), ("c++", "-std=c++11")] {
+            let lang: &[&str] = if compiler == "c++" {
+                &["-x", "c++"]
+            } else {
+                &[]
+            };
+            let status = Command::new(compiler)
+                .args(lang)
+                .args([std, "-Wall", "-Wextra", "-Werror", "-fsyntax-only", "-I"])
+                .arg(&include)
+                .arg(&src)
+                .status()
+                .unwrap_or_else(|e| panic!("running {compiler}: {e}"));
+            assert!(status.success(), "{compiler} rejected openasio.h");
+        }
+        let _ = std::fs::remove_dir_all(&out);
+    }
+}